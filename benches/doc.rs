@@ -0,0 +1,82 @@
+//! Benchmarks of document operations on generated documents, run with
+//! `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use chrono::NaiveDate;
+use sors::doc::Doc;
+use uuid::Uuid;
+
+const SIZES: &[usize] = &[10_000, 100_000];
+const SEED: u64 = 42;
+
+/// Task with the deepest nesting, the worst case for walks up the tree.
+fn deepest_task(doc: &Doc) -> Uuid {
+    doc.iter_subtree(&doc.root)
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, task)| task.id)
+        .unwrap()
+}
+
+fn find_parent(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_parent");
+    for &n in SIZES {
+        let doc = Doc::generate_random(SEED, n);
+        let task_id = deepest_task(&doc);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &doc, |b, doc| {
+            b.iter(|| doc.find_parent(black_box(&task_id)))
+        });
+        // Every change of the tree drops the cached parents.
+        group.bench_with_input(BenchmarkId::new("uncached", n), &doc, |b, doc| {
+            b.iter(|| {
+                doc.invalidate_cache();
+                doc.find_parent(black_box(&task_id))
+            })
+        });
+    }
+    group.finish();
+}
+
+fn day_clock(c: &mut Criterion) {
+    let mut group = c.benchmark_group("day_clock");
+    let date = NaiveDate::from_ymd(2020, 6, 15);
+    for &n in SIZES {
+        let doc = Doc::generate_random(SEED, n);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &doc, |b, doc| {
+            b.iter(|| doc.day_clock(black_box(date), doc.root))
+        });
+    }
+    group.finish();
+}
+
+fn subtree(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iter_subtree");
+    for &n in SIZES {
+        let doc = Doc::generate_random(SEED, n);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &doc, |b, doc| {
+            b.iter(|| doc.iter_subtree(black_box(&doc.root)).count())
+        });
+    }
+    group.finish();
+}
+
+fn save_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save_load");
+    group.sample_size(10);
+    for &n in SIZES {
+        let doc = Doc::generate_random(SEED, n);
+        for extension in &["json", "cbor"] {
+            let path = std::env::temp_dir().join(format!("sors-bench-{}.{}", Uuid::new_v4(), extension));
+            group.bench_with_input(BenchmarkId::new(format!("save_{}", extension), n), &doc, |b, doc| {
+                b.iter(|| doc.save(&path).unwrap())
+            });
+            group.bench_with_input(BenchmarkId::new(format!("load_{}", extension), n), &path, |b, path| {
+                b.iter(|| Doc::load(path).unwrap())
+            });
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, find_parent, day_clock, subtree, save_load);
+criterion_main!(benches);
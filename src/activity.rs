@@ -0,0 +1,48 @@
+//! Append-only log of what happened in a document.
+
+use uuid::Uuid;
+use serde::{Serialize, Deserialize};
+use chrono::prelude::*;
+use crate::tasks::Progress;
+use crate::doc::Doc;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ActivityEvent {
+    TaskCreated,
+    ProgressChanged { from: Option<Progress>, to: Option<Progress> },
+    ClockStarted { clock_id: Uuid },
+    ClockStopped { clock_id: Uuid },
+    /// Clock which was still running after the end of the day and was
+    /// stopped then, see `Doc::auto_clock_out`.
+    ClockAutoStopped { clock_id: Uuid },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Activity {
+    pub time: DateTime<Local>,
+    pub task_id: Option<Uuid>,
+    pub event: ActivityEvent,
+}
+
+impl Activity {
+    pub fn new(task_id: Option<Uuid>, event: ActivityEvent) -> Activity {
+        Activity {
+            time: Local::now(),
+            task_id,
+            event,
+        }
+    }
+}
+
+impl Doc {
+    /// Task of the entry, for clock events the task of the clock if the
+    /// entry has none.
+    pub fn activity_task(&self, entry: &Activity) -> Option<Uuid> {
+        match &entry.event {
+            ActivityEvent::ClockStarted { clock_id } | ActivityEvent::ClockStopped { clock_id }
+                    | ActivityEvent::ClockAutoStopped { clock_id } =>
+                entry.task_id.or_else(|| self.clock(clock_id).ok().and_then(|clock| clock.task_id)),
+            _ => entry.task_id,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Next actions and the agenda, see the `next` and `agenda` commands.
+//!
+//! Both only list open tasks.  Next actions are tasks in work or todo
+//! without open subtasks, the agenda lists the open tasks with a due date.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use chrono::prelude::*;
+use uuid::Uuid;
+
+/// Tasks which aren't done yet, cancelled tasks aren't open either.
+fn is_open(progress: Option<Progress>) -> bool {
+    progress.is_some_and(|progress| !progress.done() && progress.counts())
+}
+
+impl Doc {
+    /// Tasks in work or todo below the given task which have no open
+    /// subtasks, in tree order.
+    pub fn next_actions(&self, task_ref: &Uuid) -> Vec<Uuid> {
+        self.iter_subtree(task_ref).skip(1)
+            .map(|(_, task)| task)
+            .filter(|task| matches!(task.progress, Some(Progress::Work) | Some(Progress::Todo)))
+            .filter(|task| !task.children.iter()
+                .filter_map(|child_ref| self.get(child_ref).ok())
+                .any(|child| is_open(child.progress)))
+            .map(|task| task.id)
+            .collect()
+    }
+
+    /// Open tasks below the given task which are due before the end,
+    /// overdue ones included, the earliest first.
+    pub fn agenda(&self, task_ref: &Uuid, end: DateTime<Local>) -> Vec<Uuid> {
+        let mut tasks: Vec<_> = self.iter_subtree(task_ref).skip(1)
+            .map(|(_, task)| task)
+            .filter(|task| is_open(task.progress) && task.due.is_some_and(|due| due < end))
+            .collect();
+        tasks.sort_by_key(|task| task.due);
+        tasks.iter().map(|task| task.id).collect()
+    }
+}
@@ -0,0 +1,91 @@
+//! Age of open tasks since they were last changed or clocked.
+//!
+//! Thresholds in days mark tasks as aging or stale, `ls` and the `aging`
+//! report color them with the warning and error styles.
+
+use crate::doc::Doc;
+use crate::output::Style;
+use crate::tasks::*;
+use chrono::prelude::*;
+use uuid::Uuid;
+
+/// Days after which open tasks are colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AgingThresholds {
+    pub warning: i64,
+    pub stale: i64,
+}
+
+impl Default for AgingThresholds {
+    fn default() -> Self {
+        AgingThresholds { warning: 7, stale: 30 }
+    }
+}
+
+impl AgingThresholds {
+    /// Style for tasks of the age, None if it's below both thresholds.
+    pub fn style(&self, age: chrono::Duration) -> Option<Style> {
+        if age.num_days() >= self.stale {
+            Some(Style::Error)
+        } else if age.num_days() >= self.warning {
+            Some(Style::Warning)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgingItem {
+    pub task_id: Uuid,
+    pub progress: Progress,
+
+    /// Last change or clock, None if the task has no creation time.
+    pub touched: Option<DateTime<Local>>,
+    pub age: chrono::Duration,
+}
+
+/// Progress of tasks which age, tasks which wait or are blocked don't.
+pub fn ages(progress: Option<Progress>) -> bool {
+    matches!(progress, Some(Progress::Work) | Some(Progress::Todo))
+}
+
+impl Doc {
+    /// When the task was changed or clocked last, a running clock counts
+    /// as now.
+    pub fn last_touched(&self, task_ref: &Uuid) -> Option<DateTime<Local>> {
+        let clocked = self.task_clock(task_ref).iter()
+            .map(|clock| clock.end.unwrap_or_else(Utc::now).with_timezone(&Local))
+            .max();
+        self.last_modified(task_ref).max(clocked)
+    }
+
+    /// Age of the task at the given time, None if it doesn't age.
+    pub fn task_age(&self, task: &Task, now: DateTime<Local>) -> Option<chrono::Duration> {
+        if !ages(task.progress) || task.habit.is_some() {
+            return None;
+        }
+        self.last_touched(&task.id).map(|touched| now.signed_duration_since(touched))
+    }
+
+    /// Tasks in work or todo below the given task, the oldest first.
+    ///
+    /// Habits are left out.
+    pub fn aging(&self, task_ref: &Uuid, now: DateTime<Local>) -> Vec<AgingItem> {
+        let mut items: Vec<_> = self.iter_subtree(task_ref).skip(1)
+            .map(|(_, task)| task)
+            .filter(|task| ages(task.progress) && task.habit.is_none())
+            .map(|task| {
+                let touched = self.last_touched(&task.id);
+                AgingItem {
+                    task_id: task.id,
+                    progress: task.progress.unwrap_or(Progress::Todo),
+                    touched,
+                    age: touched.map_or_else(chrono::Duration::zero, |touched| now.signed_duration_since(touched)),
+                }
+            })
+            .collect();
+        items.sort_by_key(|item| std::cmp::Reverse(item.age));
+        items
+    }
+}
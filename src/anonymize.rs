@@ -0,0 +1,91 @@
+//! Copies of a document without its texts, to share it in bug reports.
+//!
+//! Titles, bodies, comments and other texts are replaced by placeholders
+//! like `task-3fa2c01b`.  Equal texts get equal placeholders, so duplicate
+//! titles or shared tags stay recognizable.  The hash is salted per export,
+//! short texts like client names can't be guessed from it.  Tree structure,
+//! progress, dates, durations and numbers the program interprets are kept.
+
+use crate::billing::RATE_PROPERTY;
+use crate::doc::Doc;
+use crate::sort::PRIORITY_PROPERTY;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Properties whose values are kept since they change the behavior.
+const KEPT_PROPERTIES: &[&str] = &[PRIORITY_PROPERTY, RATE_PROPERTY];
+
+struct Anonymizer {
+    salt: Uuid,
+}
+
+impl Anonymizer {
+    /// FNV-1a, which is stable between Rust versions unlike the std hasher.
+    fn hash(&self, text: &str) -> u32 {
+        self.salt.as_bytes().iter().chain(text.as_bytes())
+            .fold(0x811c_9dc5u32, |hash, byte| (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193))
+    }
+
+    /// Placeholder for the text, empty texts stay empty.
+    fn text(&self, kind: &str, text: &str) -> String {
+        if text.is_empty() {
+            String::new()
+        } else {
+            format!("{}-{:08x}", kind, self.hash(text))
+        }
+    }
+
+    fn texts(&self, kind: &str, texts: &[String]) -> Vec<String> {
+        texts.iter().map(|text| self.text(kind, text)).collect()
+    }
+}
+
+impl Doc {
+    /// Copy of the document with placeholders instead of texts.
+    pub fn anonymized(&self) -> Doc {
+        let anonymizer = Anonymizer { salt: Uuid::new_v4() };
+        let mut doc = self.clone();
+        for task in doc.map.values_mut() {
+            let task = Rc::make_mut(task);
+            task.title = anonymizer.text("task", &task.title);
+            task.body = anonymizer.text("body", &task.body);
+            task.external_ref = task.external_ref.as_ref().map(|external_ref| anonymizer.text("ref", external_ref));
+            task.tags = anonymizer.texts("tag", &task.tags);
+            task.attachments = anonymizer.texts("attachment", &task.attachments);
+            for note in task.notes.iter_mut() {
+                note.text = anonymizer.text("note", &note.text);
+            }
+            for (key, value) in task.properties.iter_mut() {
+                if !KEPT_PROPERTIES.contains(&key.as_str()) {
+                    *value = anonymizer.text("value", value);
+                }
+            }
+        }
+        for clock in doc.clocks.values_mut() {
+            let clock = Rc::make_mut(clock);
+            clock.comment = clock.comment.as_ref().map(|comment| anonymizer.text("comment", comment));
+            clock.category = clock.category.as_ref().map(|category| anonymizer.text("category", category));
+            clock.tags = anonymizer.texts("tag", &clock.tags);
+            clock.channel = clock.channel.as_ref().map(|channel| anonymizer.text("channel", channel));
+        }
+        doc.channel_clocks = doc.channel_clocks.iter()
+            .map(|(channel, clock_id)| (anonymizer.text("channel", channel), *clock_id))
+            .collect();
+        doc.bookmarks = doc.bookmarks.iter()
+            .map(|(name, task_id)| (anonymizer.text("bookmark", name), *task_id))
+            .collect();
+        // Keys are titles of top-level tasks and must match their placeholders.
+        doc.timesheet_projects = doc.timesheet_projects.iter()
+            .map(|(title, project)| (anonymizer.text("task", title), anonymizer.text("project", project)))
+            .collect();
+        if let Some(caldav) = doc.caldav.as_mut() {
+            caldav.url = anonymizer.text("url", &caldav.url);
+            caldav.user = caldav.user.as_ref().map(|user| anonymizer.text("user", user));
+            for item in caldav.items.values_mut() {
+                item.href = anonymizer.text("href", &item.href);
+            }
+        }
+        doc.invalidate_cache();
+        doc
+    }
+}
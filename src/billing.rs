@@ -0,0 +1,442 @@
+//! Hourly rates and earnings.
+//!
+//! The rate is the `rate` property of a task, like `85 EUR` or `$120`, and
+//! applies to all its subtasks which don't have their own.  Earnings are
+//! summed per project, the top-level task like in the timesheet, and
+//! currency.  Invoices list the tracked time of a client's tasks as line
+//! items between a header and footer template.  The `rounding` property
+//! like `15m` or `6m nearest` rounds the time of each line item.
+
+use crate::doc::Doc;
+use crate::duration::parse_duration;
+use crate::text::{display_width, pad_to_width};
+use crate::error::*;
+use crate::timesheet::NO_PROJECT;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Property with the hourly rate of a task and its subtasks.
+pub const RATE_PROPERTY: &str = "rate";
+
+/// Property with the rounding of invoiced time of a task and its subtasks.
+pub const ROUNDING_PROPERTY: &str = "rounding";
+
+/// Currency symbols and their codes.
+const SYMBOLS: &[(&str, &str)] = &[("€", "EUR"), ("$", "USD"), ("£", "GBP"), ("¥", "JPY")];
+
+/// Header of an invoice if none is configured.
+pub const DEFAULT_INVOICE_HEADER: &str = "# Invoice\n\n**{client}**\n\nPeriod: {from} - {to}  \nDate: {date}";
+
+/// Amount of money per hour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rate {
+    pub cents: i64,
+
+    /// Currency code like EUR, empty if the rate has none.
+    pub currency: String,
+}
+
+fn parse_cents(text: &str) -> Option<i64> {
+    let (units, fraction) = match text.split_once('.') {
+        Some((units, fraction)) => (units, fraction),
+        None => (text, ""),
+    };
+    let units = units.replace(',', "");
+    if units.is_empty() || fraction.len() > 2
+            || !units.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let fraction = format!("{:0<2}", fraction);
+    Some(units.parse::<i64>().ok()?.checked_mul(100)? + fraction.parse::<i64>().ok()?)
+}
+
+impl std::str::FromStr for Rate {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Rate> {
+        let invalid = || Error::InvalidRate { text: text.to_string() };
+        let mut amount = text.trim().trim_end_matches("/h").trim();
+        let mut currency = String::new();
+        for (symbol, code) in SYMBOLS {
+            if let Some(rest) = amount.strip_prefix(symbol).or_else(|| amount.strip_suffix(symbol)) {
+                amount = rest.trim();
+                currency = code.to_string();
+            }
+        }
+        if currency.is_empty() {
+            let code_start = amount.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(amount.len());
+            let (number, code) = if code_start == 0 {
+                let code_end = amount.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(amount.len());
+                (&amount[code_end..], &amount[..code_end])
+            } else {
+                (&amount[..code_start], &amount[code_start..])
+            };
+            if !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(invalid());
+            }
+            amount = number.trim();
+            currency = code.to_uppercase();
+        }
+        let cents = parse_cents(amount).ok_or_else(invalid)?;
+        Ok(Rate { cents, currency })
+    }
+}
+
+/// Amount with thousands separators, two decimals and the currency code.
+pub fn format_amount(cents: i64, currency: &str) -> String {
+    let digits: Vec<char> = (cents.abs() / 100).to_string().chars().collect();
+    let grouped = digits.rchunks(3).rev().map(|group| group.iter().collect::<String>()).collect::<Vec<_>>().join(",");
+    let sign = if cents < 0 { "-" } else { "" };
+    let amount = format!("{}{}.{:02}", sign, grouped, cents.abs() % 100);
+    if currency.is_empty() { amount } else { format!("{} {}", amount, currency) }
+}
+
+impl std::fmt::Display for Rate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", format_amount(self.cents, &self.currency))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    Up,
+    Nearest,
+    Down,
+}
+
+/// Step to which invoiced time is rounded, like `15m` or `6m nearest`.
+/// Without mode, the time is rounded up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rounding {
+    pub step: chrono::Duration,
+    pub mode: RoundingMode,
+}
+
+impl Rounding {
+    /// The duration rounded to a multiple of the step.
+    pub fn apply(&self, duration: chrono::Duration) -> chrono::Duration {
+        let step = self.step.num_seconds();
+        let seconds = duration.num_seconds();
+        let steps = match self.mode {
+            RoundingMode::Up => (seconds + step - 1).div_euclid(step),
+            RoundingMode::Nearest => (seconds + step / 2).div_euclid(step),
+            RoundingMode::Down => seconds.div_euclid(step),
+        };
+        chrono::Duration::seconds(steps * step)
+    }
+}
+
+impl std::str::FromStr for Rounding {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Rounding> {
+        let invalid = || Error::InvalidRounding { text: text.to_string() };
+        let text = text.trim();
+        let (step, mode) = match text.rsplit_once(' ') {
+            Some((step, "up")) => (step, RoundingMode::Up),
+            Some((step, "nearest")) => (step, RoundingMode::Nearest),
+            Some((step, "down")) => (step, RoundingMode::Down),
+            _ => (text, RoundingMode::Up),
+        };
+        let step = parse_duration(step).map_err(|_| invalid())?;
+        if step < chrono::Duration::seconds(1) {
+            return Err(invalid());
+        }
+        Ok(Rounding { step, mode })
+    }
+}
+
+/// Earned money of a project in one currency.
+#[derive(Debug, Clone)]
+pub struct EarningsRow {
+    pub project: String,
+    pub currency: String,
+    pub duration: chrono::Duration,
+    pub cents: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Earnings {
+    pub rows: Vec<EarningsRow>,
+
+    /// Tracked time of tasks without a rate.
+    pub unbilled: chrono::Duration,
+}
+
+fn hours(duration: chrono::Duration) -> String {
+    format!("{:.2}h", duration.num_minutes() as f64 / 60.0)
+}
+
+impl Earnings {
+    /// Sum of all projects per currency.
+    pub fn totals(&self) -> Vec<(String, i64)> {
+        let mut totals: Vec<(String, i64)> = Vec::new();
+        for row in self.rows.iter() {
+            match totals.iter_mut().find(|(currency, _)| *currency == row.currency) {
+                Some((_, cents)) => *cents += row.cents,
+                None => totals.push((row.currency.clone(), row.cents)),
+            }
+        }
+        totals.sort();
+        totals
+    }
+
+    /// Text table with hours and amount per project and the totals.
+    pub fn to_table(&self) -> String {
+        let width = self.rows.iter().map(|row| display_width(&row.project))
+            .chain(std::iter::once(8))
+            .max().unwrap_or(0);
+        let mut table = String::new();
+        for row in self.rows.iter() {
+            table.push_str(&format!("{} {:>8} {:>16}\n", pad_to_width(&row.project, width), hours(row.duration),
+                format_amount(row.cents, &row.currency)));
+        }
+        for (currency, cents) in self.totals() {
+            table.push_str(&format!("{:width$} {:>8} {:>16}\n", "Total", "", format_amount(cents, &currency), width = width));
+        }
+        if self.unbilled > chrono::Duration::zero() {
+            table.push_str(&format!("{:width$} {:>8}\n", "Unbilled", hours(self.unbilled), width = width));
+        }
+        table
+    }
+}
+
+impl Doc {
+    /// Rate of the task or of the closest parent which has one.
+    pub fn rate(&self, task_ref: &Uuid) -> Result<Option<Rate>> {
+        for task_id in self.path(task_ref) {
+            if let Some(rate) = self.get(&task_id)?.properties.get(RATE_PROPERTY) {
+                return rate.parse().map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Rounding of the task or of the closest parent which has one.
+    pub fn rounding(&self, task_ref: &Uuid) -> Result<Option<Rounding>> {
+        for task_id in self.path(task_ref) {
+            if let Some(rounding) = self.get(&task_id)?.properties.get(ROUNDING_PROPERTY) {
+                return rounding.parse().map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Tracked time multiplied by the rates between both dates (inclusive),
+    /// only of the given task and its subtasks if there is one.
+    pub fn earnings(&self, start: NaiveDate, end: NaiveDate, main_task: impl Into<Option<Uuid>>) -> Result<Earnings> {
+        // Cents times seconds, rounded to cents once per row.
+        let mut sums: Vec<(String, String, chrono::Duration, i64)> = Vec::new();
+        let mut unbilled = chrono::Duration::zero();
+        for clock in self.range_clock(start, end, main_task) {
+            let rate = match clock.task_id {
+                Some(task_id) => self.rate(&task_id)?,
+                None => None,
+            };
+            let rate = match rate {
+                Some(rate) => rate,
+                None => {
+                    unbilled = unbilled + clock.duration();
+                    continue;
+                },
+            };
+            let project = clock.task_id
+                .map(|task_id| self.path(&task_id))
+                .filter(|path| path.len() >= 2)
+                .and_then(|path| self.get(&path[path.len() - 2]).ok())
+                .map(|task| self.timesheet_project(&task.title))
+                .unwrap_or_else(|| NO_PROJECT.to_string());
+            let index = match sums.iter().position(|(name, currency, _, _)| *name == project && *currency == rate.currency) {
+                Some(index) => index,
+                None => {
+                    sums.push((project, rate.currency.clone(), chrono::Duration::zero(), 0));
+                    sums.len() - 1
+                },
+            };
+            let sum = &mut sums[index];
+            sum.2 = sum.2 + clock.duration();
+            sum.3 += rate.cents * clock.duration().num_seconds();
+        }
+        let mut rows: Vec<_> = sums.into_iter()
+            .map(|(project, currency, duration, cent_seconds)| EarningsRow {
+                project, currency, duration,
+                cents: (cent_seconds + 1800) / 3600,
+            })
+            .collect();
+        rows.sort_by(|a, b| (&a.project, &a.currency).cmp(&(&b.project, &b.currency)));
+        Ok(Earnings { rows, unbilled })
+    }
+}
+
+/// Markdown around the line items of an invoice.
+///
+/// The placeholders `{client}`, `{from}`, `{to}`, `{date}`, `{hours}` and
+/// `{total}` are replaced by the values of the invoice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceTemplate {
+    pub header: String,
+    pub footer: String,
+}
+
+impl Default for InvoiceTemplate {
+    fn default() -> Self {
+        InvoiceTemplate { header: DEFAULT_INVOICE_HEADER.to_string(), footer: String::new() }
+    }
+}
+
+/// Tracked time of one task on an invoice.
+#[derive(Debug, Clone)]
+pub struct InvoiceItem {
+    pub task_id: Uuid,
+
+    /// Path of the task below the client.
+    pub description: String,
+
+    /// Quantity in hundredths of an hour, as printed on the invoice.
+    pub centihours: i64,
+    pub rate: Rate,
+    pub cents: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    pub client: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub items: Vec<InvoiceItem>,
+
+    /// Tracked time of tasks without a rate, which isn't invoiced.
+    pub unbilled: chrono::Duration,
+}
+
+fn decimal_hours(centihours: i64) -> String {
+    format!("{}.{:02}", centihours / 100, centihours % 100)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl Invoice {
+    /// Sum of the items per currency.
+    pub fn totals(&self) -> Vec<(String, i64)> {
+        let mut totals: Vec<(String, i64)> = Vec::new();
+        for item in self.items.iter() {
+            match totals.iter_mut().find(|(currency, _)| *currency == item.rate.currency) {
+                Some((_, cents)) => *cents += item.cents,
+                None => totals.push((item.rate.currency.clone(), item.cents)),
+            }
+        }
+        totals.sort();
+        totals
+    }
+
+    pub fn centihours(&self) -> i64 {
+        self.items.iter().map(|item| item.centihours).sum()
+    }
+
+    fn fill(&self, template: &str, date: NaiveDate, date_format: &str) -> String {
+        let total = self.totals().iter()
+            .map(|(currency, cents)| format_amount(*cents, currency))
+            .collect::<Vec<_>>().join(", ");
+        template.replace("{client}", &self.client)
+            .replace("{from}", &self.start.format(date_format).to_string())
+            .replace("{to}", &self.end.format(date_format).to_string())
+            .replace("{date}", &date.format(date_format).to_string())
+            .replace("{hours}", &decimal_hours(self.centihours()))
+            .replace("{total}", &total)
+    }
+
+    /// Header, a table with the items and totals and the footer.
+    pub fn to_markdown(&self, template: &InvoiceTemplate, date: NaiveDate, date_format: &str) -> String {
+        let mut text = self.fill(&template.header, date, date_format);
+        text.push_str("\n\n| Item | Hours | Rate | Amount |\n| ---- | ----: | ---: | -----: |\n");
+        for item in self.items.iter() {
+            text.push_str(&format!("| {} | {} | {} | {} |\n", item.description.replace('|', "\\|"),
+                decimal_hours(item.centihours), item.rate, format_amount(item.cents, &item.rate.currency)));
+        }
+        for (currency, cents) in self.totals() {
+            text.push_str(&format!("| **Total** | | | **{}** |\n", format_amount(cents, &currency)));
+        }
+        let footer = self.fill(&template.footer, date, date_format);
+        if !footer.trim().is_empty() {
+            text.push_str(&format!("\n{}\n", footer));
+        }
+        text
+    }
+
+    /// Standalone HTML page, the templates are rendered as Markdown.
+    pub fn to_html(&self, template: &InvoiceTemplate, date: NaiveDate, date_format: &str) -> String {
+        let mut html = format!("<!doctype html><html><head><meta charset=\"utf-8\"><title>Invoice {}</title>", escape_html(&self.client));
+        html.push_str("<style>body { font-family: sans-serif; } table { border-collapse: collapse; } th, td { padding: 0.3em 0.8em; border-bottom: 1px solid #ccc; } .number { text-align: right; }</style></head><body>");
+        html.push_str(&crate::render::to_html(&self.fill(&template.header, date, date_format)));
+        html.push_str("<table><tr><th>Item</th><th class=\"number\">Hours</th><th class=\"number\">Rate</th><th class=\"number\">Amount</th></tr>");
+        for item in self.items.iter() {
+            html.push_str(&format!("<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>",
+                escape_html(&item.description), decimal_hours(item.centihours), escape_html(&item.rate.to_string()),
+                escape_html(&format_amount(item.cents, &item.rate.currency))));
+        }
+        for (currency, cents) in self.totals() {
+            html.push_str(&format!("<tr><th>Total</th><td></td><td></td><th class=\"number\">{}</th></tr>", escape_html(&format_amount(cents, &currency))));
+        }
+        html.push_str("</table>");
+        html.push_str(&crate::render::to_html(&self.fill(&template.footer, date, date_format)));
+        html.push_str("</body></html>\n");
+        html
+    }
+}
+
+impl Doc {
+    /// Line items of the tasks below the client with the time tracked
+    /// between both dates (inclusive), in the order of the tree.
+    ///
+    /// The time of each item is rounded by the rounding of the task if it
+    /// has one.  Quantities are rounded to hundredths of an hour and the
+    /// amounts are calculated from the rounded quantities, so they add up on
+    /// paper.
+    pub fn invoice(&self, client: &Uuid, start: NaiveDate, end: NaiveDate) -> Result<Invoice> {
+        let client_task = self.get(client)?;
+        let mut durations: HashMap<Uuid, chrono::Duration> = HashMap::new();
+        for clock in self.range_clock(start, end, *client) {
+            if let Some(task_id) = clock.task_id {
+                let duration = durations.entry(task_id).or_insert_with(chrono::Duration::zero);
+                *duration = *duration + clock.duration();
+            }
+        }
+        let mut items = Vec::new();
+        let mut unbilled = chrono::Duration::zero();
+        for (_, task) in self.iter_subtree(client) {
+            let duration = match durations.get(&task.id) {
+                Some(duration) => *duration,
+                None => continue,
+            };
+            let rate = match self.rate(&task.id)? {
+                Some(rate) => rate,
+                None => {
+                    unbilled = unbilled + duration;
+                    continue;
+                },
+            };
+            let path = self.path(&task.id);
+            let client_index = path.iter().position(|task_id| task_id == client).unwrap_or(0);
+            let description = path[..client_index.max(1)].iter().rev()
+                .filter_map(|task_id| self.get(task_id).ok())
+                .map(|task| task.title.clone())
+                .collect::<Vec<_>>().join(" -> ");
+            let duration = match self.rounding(&task.id)? {
+                Some(rounding) => rounding.apply(duration),
+                None => duration,
+            };
+            let centihours = (duration.num_seconds() + 18) / 36;
+            items.push(InvoiceItem {
+                task_id: task.id,
+                description,
+                centihours,
+                cents: (rate.cents * centihours + 50) / 100,
+                rate,
+            });
+        }
+        Ok(Invoice { client: client_task.title.clone(), start, end, items, unbilled })
+    }
+}
@@ -0,0 +1,86 @@
+//! Time budgets of tasks.
+
+use crate::doc::Doc;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Tracked time of a task with a budget.
+#[derive(Debug, Clone)]
+pub struct BudgetUsage {
+    pub task_id: Uuid,
+    pub budget: chrono::Duration,
+
+    /// Time of all clocks of the task and its subtasks.
+    pub tracked: chrono::Duration,
+}
+
+impl BudgetUsage {
+    pub fn exceeded(&self) -> bool {
+        self.tracked > self.budget
+    }
+}
+
+impl Doc {
+    /// Sum of all clocks of the task and its subtasks.
+    pub fn tracked_time(&self, task_ref: &Uuid) -> chrono::Duration {
+        self.subtree_duration(task_ref)
+    }
+
+    /// Sum of all clocks of the task and its subtasks, like `tracked_time`.
+    pub fn subtree_duration(&self, task_ref: &Uuid) -> chrono::Duration {
+        self.subtree_durations(task_ref).get(task_ref).cloned().unwrap_or_else(chrono::Duration::zero)
+    }
+
+    /// Tracked time of the subtree of every task below the given task,
+    /// including the task itself.
+    ///
+    /// The clocks are only visited once, so this is much faster than
+    /// calling `subtree_duration` for every task of a listing.
+    pub fn subtree_durations(&self, task_ref: &Uuid) -> HashMap<Uuid, chrono::Duration> {
+        let mut durations = (*self.cached_clock_durations()).clone();
+        for clock in self.clocks.values().filter(|clock| clock.end.is_none()) {
+            if let Some(task_id) = clock.task_id {
+                let duration = durations.entry(task_id).or_insert_with(chrono::Duration::zero);
+                *duration = *duration + clock.duration();
+            }
+        }
+        let tasks: Vec<_> = self.iter_subtree(task_ref).map(|(_, task)| task).collect();
+        let mut totals = HashMap::new();
+        for task in tasks.iter().rev() {
+            let own = durations.get(&task.id).cloned().unwrap_or_else(chrono::Duration::zero);
+            let total = task.children.iter()
+                .filter_map(|child| totals.get(child))
+                .fold(own, |acc, duration| acc + *duration);
+            totals.insert(task.id, total);
+        }
+        totals
+    }
+
+    /// Get the usage of all tasks which have a budget.
+    pub fn budget_usage(&self) -> Vec<BudgetUsage> {
+        self.map.values()
+            .filter(|task| self.is_in_hierarchy_of(&task.id, &self.root))
+            .filter_map(|task| task.budget.map(|budget| (task.id, budget)))
+            .map(|(task_id, budget)| BudgetUsage {
+                task_id,
+                budget: chrono::Duration::seconds(budget),
+                tracked: self.tracked_time(&task_id),
+            })
+            .collect()
+    }
+
+    /// Get the usage of the budgets which are exceeded and which contain the
+    /// given task.
+    pub fn exceeded_budgets(&self, task_ref: &Uuid) -> Vec<BudgetUsage> {
+        self.path(task_ref).iter()
+            .filter_map(|task_id| self.get(task_id).ok())
+            .filter_map(|task| task.budget.map(|budget| (task.id, budget)))
+            .map(|(task_id, budget)| BudgetUsage {
+                task_id,
+                budget: chrono::Duration::seconds(budget),
+                tracked: self.tracked_time(&task_id),
+            })
+            .filter(|usage| usage.exceeded())
+            .collect()
+    }
+}
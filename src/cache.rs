@@ -0,0 +1,100 @@
+//! Results of expensive lookups which are kept until the document changes.
+//!
+//! `Doc::upsert`, `Doc::remove_task` and `Doc::upsert_clock` invalidate the
+//! cache.  Code which changes `Doc::map` or `Doc::clocks` directly has to
+//! call `Doc::invalidate_cache`.
+
+use crate::doc::Doc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use uuid::Uuid;
+
+type Cached<T> = RefCell<Option<Rc<T>>>;
+type ProgressSummaries = HashMap<(Uuid, Option<usize>), (i32, i32)>;
+
+#[derive(Default)]
+pub struct Cache {
+    /// Parent of every task which is a child of another task.
+    parents: Cached<HashMap<Uuid, Uuid>>,
+
+    /// Duration of the finished clocks per task.  Running clocks change
+    /// with the time, so they are not cached.
+    clock_durations: Cached<HashMap<Uuid, chrono::Duration>>,
+
+    /// Progress summaries per task and depth.
+    progress_summaries: RefCell<ProgressSummaries>,
+}
+
+/// A copy of a document starts with an empty cache, since the copy is
+/// usually modified.
+impl Clone for Cache {
+    fn clone(&self) -> Cache {
+        Cache::default()
+    }
+}
+
+impl std::fmt::Debug for Cache {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Cache")
+    }
+}
+
+impl Cache {
+    pub fn invalidate_tasks(&self) {
+        self.parents.replace(None);
+        self.progress_summaries.borrow_mut().clear();
+    }
+
+    pub fn invalidate_clocks(&self) {
+        self.clock_durations.replace(None);
+    }
+}
+
+impl Doc {
+    /// Drop all cached results, needed after `map` or `clocks` were
+    /// changed directly.
+    pub fn invalidate_cache(&self) {
+        self.cache.invalidate_tasks();
+        self.cache.invalidate_clocks();
+    }
+
+    pub(crate) fn cached_parents(&self) -> Rc<HashMap<Uuid, Uuid>> {
+        if let Some(parents) = self.cache.parents.borrow().as_ref() {
+            return parents.clone();
+        }
+        let parents: HashMap<Uuid, Uuid> = self.map.values()
+            .flat_map(|task| task.children.iter().map(move |child_id| (*child_id, task.id)))
+            .collect();
+        let parents = Rc::new(parents);
+        self.cache.parents.replace(Some(parents.clone()));
+        parents
+    }
+
+    pub(crate) fn cached_clock_durations(&self) -> Rc<HashMap<Uuid, chrono::Duration>> {
+        if let Some(durations) = self.cache.clock_durations.borrow().as_ref() {
+            return durations.clone();
+        }
+        let mut durations: HashMap<Uuid, chrono::Duration> = HashMap::new();
+        for clock in self.clocks.values().filter(|clock| clock.end.is_some()) {
+            if let Some(task_id) = clock.task_id {
+                let duration = durations.entry(task_id).or_insert_with(chrono::Duration::zero);
+                *duration = *duration + clock.duration();
+            }
+        }
+        let durations = Rc::new(durations);
+        self.cache.clock_durations.replace(Some(durations.clone()));
+        durations
+    }
+
+    pub(crate) fn cached_progress_summary(&self, task_ref: &Uuid, max_depth: Option<usize>,
+            calculate: impl FnOnce() -> (i32, i32)) -> (i32, i32) {
+        let key = (*task_ref, max_depth);
+        if let Some(summary) = self.cache.progress_summaries.borrow().get(&key) {
+            return *summary;
+        }
+        let summary = calculate();
+        self.cache.progress_summaries.borrow_mut().insert(key, summary);
+        summary
+    }
+}
@@ -0,0 +1,448 @@
+//! Sync of tasks with a CalDAV calendar as VTODOs.
+//!
+//! The document remembers the remote copy of each synced task by the task
+//! id.  Tasks which changed on one side are copied to the other side, if
+//! both changed the newer change wins.  Todos which were created on the
+//! server, like on a phone, are added to the inbox.
+
+use crate::curl::SecretConfig;
+use crate::doc::Doc;
+use crate::error::*;
+use crate::ics::{escape, fold, unescape, utc_time};
+use crate::tasks::*;
+use chrono::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashSet};
+use uuid::Uuid;
+
+/// Environment variable with the password of the CalDAV account.
+pub const PASSWORD_VAR: &str = "SORS_CALDAV_PASSWORD";
+
+/// Remote copy of a task.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaldavItem {
+    /// Path of the calendar object on the server.
+    pub href: String,
+
+    /// UID of the VTODO, the task id unless it was created remotely.
+    pub uid: String,
+
+    /// ETag of the remote copy after the last sync.
+    pub etag: Option<String>,
+
+    /// `modified` of the task after the last sync.
+    pub modified: Option<DateTime<Local>>,
+
+    /// The remote copy was deleted, so the task isn't uploaded again.
+    #[serde(default)]
+    pub removed: bool,
+}
+
+/// Calendar of the sync and the remote copies of the tasks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaldavSync {
+    /// URL of the calendar collection.
+    pub url: String,
+    pub user: Option<String>,
+
+    #[serde(default)]
+    pub items: BTreeMap<Uuid, CaldavItem>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Tasks which were uploaded.
+    pub pushed: usize,
+
+    /// Tasks which were changed by their remote copy.
+    pub pulled: usize,
+
+    /// Tasks which were created from new remote todos.
+    pub created: usize,
+}
+
+/// A VTODO as it was read from the server.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RemoteTodo {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub status: Option<String>,
+    pub due: Option<DateTime<Utc>>,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Access to the calendar collection, `CurlTransport` by default.
+pub trait CaldavTransport {
+    /// Hrefs and ETags of the calendar objects.
+    fn list(&self) -> Result<Vec<(String, String)>>;
+
+    fn get(&self, href: &str) -> Result<String>;
+
+    /// Upload the calendar object if it still has the ETag, or with None
+    /// only if it doesn't exist yet.  Returns the new ETag if the server
+    /// sends it.
+    fn put(&self, href: &str, body: &str, etag: Option<&str>) -> Result<Option<String>>;
+}
+
+fn sync_error(msg: impl ToString) -> Error {
+    Error::CaldavError { msg: msg.to_string() }
+}
+
+/// Status of the progress, tasks which wait or are blocked still need
+/// an action.
+fn status(progress: Option<Progress>) -> &'static str {
+    match progress {
+        Some(Progress::Work) => "IN-PROCESS",
+        Some(Progress::Done) => "COMPLETED",
+        Some(Progress::Cancelled) => "CANCELLED",
+        _ => "NEEDS-ACTION",
+    }
+}
+
+/// Progress for the status, the current one if it has the same status.
+/// Synced tasks always have a progress.
+fn progress_for_status(status_name: &str, current: Option<Progress>) -> Option<Progress> {
+    if current.is_some() && status(current) == status_name {
+        return current;
+    }
+    Some(match status_name {
+        "IN-PROCESS" => Progress::Work,
+        "COMPLETED" => Progress::Done,
+        "CANCELLED" => Progress::Cancelled,
+        _ => Progress::Todo,
+    })
+}
+
+/// Path of the URL without scheme and host.
+fn url_path(url: &str) -> &str {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    without_scheme.find('/').map_or("/", |index| &without_scheme[index..])
+}
+
+/// URL of the href which is usually an absolute path on the server.
+pub fn resolve(url: &str, href: &str) -> String {
+    if href.contains("://") {
+        return href.to_string();
+    }
+    let path = url_path(url);
+    let origin = &url[..url.len() - path.len()];
+    if href.starts_with('/') {
+        format!("{}{}", origin, href)
+    } else {
+        format!("{}/{}", url.trim_end_matches('/'), href)
+    }
+}
+
+/// Contents of the XML elements with the name, regardless of the namespace
+/// prefix.
+fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = rest[..end].split_whitespace().next().unwrap_or_default();
+        let local_name = tag.rsplit(':').next().unwrap_or_default();
+        if tag.starts_with('/') || tag.ends_with('/') || local_name != name {
+            continue;
+        }
+        let content = &rest[end + 1..];
+        if let Some(close) = content.find(&format!("</{}>", tag)) {
+            elements.push(&content[..close]);
+        }
+    }
+    elements
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.trim()
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Hrefs and ETags of the calendar objects in a PROPFIND response.
+pub fn parse_multistatus(xml: &str) -> Vec<(String, String)> {
+    xml_elements(xml, "response").into_iter()
+        .filter_map(|response| {
+            let href = xml_unescape(xml_elements(response, "href").first()?);
+            let etag = xml_unescape(xml_elements(response, "getetag").first()?);
+            Some((href, etag)).filter(|(href, _)| href.ends_with(".ics"))
+        })
+        .collect()
+}
+
+fn parse_time(params: &str, value: &str, doc: &Doc) -> Option<DateTime<Utc>> {
+    if params.contains("VALUE=DATE") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return doc.timezone.to_utc(date.and_hms(0, 0, 0));
+    }
+    match value.strip_suffix('Z') {
+        Some(value) => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok().map(|time| Utc.from_utc_datetime(&time)),
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok().and_then(|time| doc.timezone.to_utc(time)),
+    }
+}
+
+impl Doc {
+    /// Read the first VTODO of the calendar, times without zone are in the
+    /// time zone of the document.
+    pub fn parse_vtodo(&self, ics: &str) -> Option<RemoteTodo> {
+        let mut lines: Vec<String> = Vec::new();
+        for line in ics.lines() {
+            match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+                (Some(continuation), Some(last)) => last.push_str(continuation),
+                _ => lines.push(line.to_string()),
+            }
+        }
+        let mut todo = RemoteTodo::default();
+        let mut in_todo = false;
+        for line in lines.iter() {
+            let (name, value) = match line.split_once(':') {
+                Some(property) => property,
+                None => continue,
+            };
+            let (name, params) = name.split_once(';').unwrap_or((name, ""));
+            match (name.to_uppercase().as_str(), in_todo) {
+                ("BEGIN", _) if value == "VTODO" => in_todo = true,
+                ("END", true) if value == "VTODO" => return Some(todo),
+                ("UID", true) => todo.uid = value.to_string(),
+                ("SUMMARY", true) => todo.summary = unescape(value),
+                ("DESCRIPTION", true) => todo.description = unescape(value),
+                ("STATUS", true) => todo.status = Some(value.to_uppercase()),
+                ("DUE", true) => todo.due = parse_time(params, value, self),
+                ("LAST-MODIFIED", true) => todo.last_modified = parse_time(params, value, self),
+                _ => {},
+            }
+        }
+        None
+    }
+
+    /// Calendar with the task as VTODO.
+    pub fn to_vtodo(&self, task_ref: &Uuid, uid: &str) -> Result<String> {
+        let task = self.get(task_ref)?;
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//sors//sors//EN".to_string(),
+            "BEGIN:VTODO".to_string(),
+            format!("UID:{}", uid),
+            format!("DTSTAMP:{}", utc_time(Utc::now())),
+        ];
+        if let Some(created) = task.created {
+            lines.push(format!("CREATED:{}", utc_time(created.with_timezone(&Utc))));
+        }
+        if let Some(modified) = task.modified.or(task.created) {
+            lines.push(format!("LAST-MODIFIED:{}", utc_time(modified.with_timezone(&Utc))));
+        }
+        lines.push(format!("SUMMARY:{}", escape(&task.title)));
+        if !task.body.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&task.body)));
+        }
+        if let Some(due) = task.due {
+            lines.push(self.time_property("DUE", due.with_timezone(&Utc)));
+        }
+        lines.push(format!("STATUS:{}", status(task.progress)));
+        if let (Some(Progress::Done), Some(finished)) = (task.progress, task.finished) {
+            lines.push(format!("COMPLETED:{}", utc_time(finished.with_timezone(&Utc))));
+        }
+        if !task.tags.is_empty() {
+            lines.push(format!("CATEGORIES:{}", task.tags.iter().map(|tag| escape(tag)).collect::<Vec<_>>().join(",")));
+        }
+        lines.push("END:VTODO".to_string());
+        lines.push("END:VCALENDAR".to_string());
+        Ok(lines.iter().map(|line| fold(line)).collect())
+    }
+
+    /// Copy title, description, due date and status of the todo to the task.
+    fn apply_todo(&mut self, task_ref: &Uuid, todo: &RemoteTodo) -> Result<()> {
+        let mut task = self.get(task_ref)?;
+        task.set_title(&todo.summary);
+        task.set_body(&todo.description);
+        task.set_due(todo.due.map(|due| due.with_timezone(&Local)));
+        let progress = progress_for_status(todo.status.as_deref().unwrap_or("NEEDS-ACTION"), task.progress);
+        if progress != task.progress {
+            if let Some(progress) = progress {
+                task.set_progress(progress);
+            }
+        }
+        self.upsert(task);
+        Ok(())
+    }
+
+    /// Sync the tasks with a progress below the given task with the
+    /// calendar of `caldav`.
+    ///
+    /// Finished tasks are only uploaded if they were synced before.  Tasks
+    /// which were removed locally are kept on the server.
+    ///
+    /// # Error
+    /// Fails if the sync isn't configured or the server fails, the changes
+    /// up to the error are kept.
+    pub fn caldav_sync(&mut self, main_task: &Uuid, transport: &dyn CaldavTransport) -> Result<SyncReport> {
+        let mut sync = self.caldav.clone().ok_or(Error::CaldavNotConfigured {})?;
+        let result = self.caldav_sync_items(&mut sync, main_task, transport);
+        self.caldav = Some(sync);
+        result
+    }
+
+    fn caldav_sync_items(&mut self, sync: &mut CaldavSync, main_task: &Uuid, transport: &dyn CaldavTransport) -> Result<SyncReport> {
+        let mut report = SyncReport::default();
+        let remote: BTreeMap<String, String> = transport.list()?.into_iter().collect();
+        let known: HashSet<String> = sync.items.values().map(|item| item.href.clone()).collect();
+
+        let synced: Vec<Uuid> = sync.items.iter()
+            .filter(|(task_id, item)| !item.removed && self.map.contains_key(task_id))
+            .map(|(task_id, _)| *task_id)
+            .collect();
+        for task_id in synced.iter() {
+            let item = sync.items.get_mut(task_id).expect("synced tasks have an item");
+            let remote_etag = match remote.get(&item.href) {
+                Some(etag) => etag,
+                None => {
+                    item.removed = true;
+                    continue;
+                },
+            };
+            if Some(remote_etag) != item.etag.as_ref() {
+                let todo = self.parse_vtodo(&transport.get(&item.href)?)
+                    .ok_or_else(|| sync_error(format!("No VTODO in {}", item.href)))?;
+                let local_modified = self.get(task_id)?.modified;
+                let remote_newer = local_modified == item.modified
+                    || todo.last_modified.is_some_and(|remote| local_modified.is_none_or(|local| remote >= local.with_timezone(&Utc)));
+                item.etag = Some(remote_etag.clone());
+                if remote_newer {
+                    self.apply_todo(task_id, &todo)?;
+                    item.modified = self.get(task_id)?.modified;
+                    report.pulled += 1;
+                    continue;
+                }
+            }
+            let task = self.get(task_id)?;
+            if task.modified != item.modified {
+                item.etag = transport.put(&item.href, &self.to_vtodo(task_id, &item.uid)?, item.etag.as_deref())?;
+                item.modified = task.modified;
+                report.pushed += 1;
+            }
+        }
+
+        for (href, etag) in remote.iter().filter(|(href, _)| !known.contains(*href)) {
+            let todo = match self.parse_vtodo(&transport.get(href)?) {
+                Some(todo) => todo,
+                None => continue,
+            };
+            // Uploaded before the mapping was stored, or a copy of it.
+            let existing = todo.uid.parse::<Uuid>().ok()
+                .filter(|task_id| self.map.contains_key(task_id) && !sync.items.contains_key(task_id));
+            let task_id = match existing {
+                Some(task_id) => task_id,
+                None => {
+                    let task_id = self.capture(&todo.summary)?;
+                    self.apply_todo(&task_id, &todo)?;
+                    report.created += 1;
+                    task_id
+                },
+            };
+            let modified = self.get(&task_id)?.modified;
+            sync.items.insert(task_id, CaldavItem { href: href.clone(), uid: todo.uid, etag: Some(etag.clone()), modified, removed: false });
+        }
+
+        let new_tasks: Vec<Uuid> = self.iter_subtree(main_task).skip(1)
+            .map(|(_, task)| task)
+            .filter(|task| task.habit.is_none() && !sync.items.contains_key(&task.id))
+            .filter(|task| task.progress.is_some_and(|progress| !progress.done() && progress.counts()))
+            .map(|task| task.id)
+            .collect();
+        for task_id in new_tasks {
+            let href = format!("{}/{}.ics", url_path(&sync.url).trim_end_matches('/'), task_id);
+            let uid = task_id.to_string();
+            let etag = transport.put(&href, &self.to_vtodo(&task_id, &uid)?, None)?;
+            let modified = self.get(&task_id)?.modified;
+            sync.items.insert(task_id, CaldavItem { href, uid, etag, modified, removed: false });
+            report.pushed += 1;
+        }
+        Ok(report)
+    }
+}
+
+/// Transport which calls `curl`.
+pub struct CurlTransport {
+    pub url: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Headers of the final response and the body of `curl -D -` output.
+fn split_response(output: &str) -> (&str, &str) {
+    let mut rest = output;
+    loop {
+        let (headers, body) = rest.split_once("\r\n\r\n").unwrap_or((rest, ""));
+        if headers.starts_with("HTTP/") && headers.contains(" 100 ") && body.starts_with("HTTP/") {
+            rest = body;
+        } else {
+            return (headers, body);
+        }
+    }
+}
+
+impl CurlTransport {
+    /// Transport for the configured sync with the password from
+    /// `SORS_CALDAV_PASSWORD`.
+    pub fn new(sync: &CaldavSync) -> CurlTransport {
+        CurlTransport { url: sync.url.clone(), user: sync.user.clone(), password: std::env::var(PASSWORD_VAR).ok() }
+    }
+
+    fn curl(&self, href: &str, args: &[&str], body: Option<&str>) -> Result<(String, String)> {
+        let mut exec = subprocess::Exec::cmd("curl").args(&["-sSf", "-D", "-"]).args(args);
+        let credentials = self.user.as_ref().map(|user| format!("{}:{}", user, self.password.as_deref().unwrap_or_default()));
+        let config = credentials.map(|credentials| SecretConfig::new(&[("user", &credentials)]))
+            .transpose().map_err(sync_error)?;
+        if let Some(config) = &config {
+            exec = exec.arg("-K").arg(config.path());
+        }
+        if let Some(body) = body {
+            exec = exec.args(&["--data-binary", "@-"]).stdin(body);
+        }
+        let capture = exec.arg(resolve(&self.url, href))
+            .stdout(subprocess::Redirection::Pipe)
+            .stderr(subprocess::Redirection::Pipe)
+            .capture()
+            .map_err(sync_error)?;
+        if !capture.success() {
+            return Err(sync_error(capture.stderr_str().trim()));
+        }
+        let output = capture.stdout_str();
+        let (headers, body) = split_response(&output);
+        Ok((headers.to_string(), body.to_string()))
+    }
+}
+
+impl CaldavTransport for CurlTransport {
+    fn list(&self) -> Result<Vec<(String, String)>> {
+        let request = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+            <d:propfind xmlns:d=\"DAV:\"><d:prop><d:getetag/></d:prop></d:propfind>";
+        let (_, body) = self.curl(&self.url, &["-X", "PROPFIND", "-H", "Depth: 1", "-H", "Content-Type: application/xml; charset=utf-8"], Some(request))?;
+        Ok(parse_multistatus(&body))
+    }
+
+    fn get(&self, href: &str) -> Result<String> {
+        Ok(self.curl(href, &[], None)?.1)
+    }
+
+    fn put(&self, href: &str, body: &str, etag: Option<&str>) -> Result<Option<String>> {
+        let condition = match etag {
+            Some(etag) => format!("If-Match: {}", etag),
+            None => "If-None-Match: *".to_string(),
+        };
+        let (headers, _) = self.curl(href, &["-X", "PUT", "-H", "Content-Type: text/calendar; charset=utf-8", "-H", &condition], Some(body))?;
+        Ok(headers.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("etag"))
+            .map(|(_, etag)| etag.trim().to_string()))
+    }
+}
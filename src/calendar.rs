@@ -0,0 +1,31 @@
+//! Weeks with a configurable first day.
+//!
+//! Weeks like `2024-W23` are ISO weeks, but they start on the configured
+//! first day on or before the ISO Monday, so with Sunday as first day the
+//! week starts one day earlier.
+
+use chrono::prelude::*;
+
+/// First day of the week which contains the date.
+pub fn start_of_week(date: NaiveDate, week_start: Weekday) -> NaiveDate {
+    let days = (7 + date.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    date - chrono::Duration::days(i64::from(days))
+}
+
+/// First and last day of the week which contains the date.
+pub fn week_of(date: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+    let start = start_of_week(date, week_start);
+    (start, start + chrono::Duration::days(6))
+}
+
+/// First and last day of a week like `2024-W23`.
+pub fn parse_week(string: &str, week_start: Weekday) -> Option<(NaiveDate, NaiveDate)> {
+    let (year, week) = string.split_once("-W")?;
+    let monday = NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon)?;
+    Some(week_of(monday, week_start))
+}
+
+/// Parse a weekday like `mon` or `Monday`.
+pub fn parse_weekday(string: &str) -> Option<Weekday> {
+    string.parse().ok()
+}
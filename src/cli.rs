@@ -1,11 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use crate::error::*;
+use crate::output::{Style, Theme};
+use crate::config::{default_opener, Config, DEFAULT_DATE_FORMAT};
+use crate::aging::AgingThresholds;
+use crate::billing::InvoiceTemplate;
 
 
 pub type Result<T, E=Box<dyn std::error::Error>> = std::result::Result<T, E>;
 
 pub type Func<T, C> = Box<Fn(&mut T, &str, &mut C) -> Result<()>>;
 
+/// Description of a command which is shown by `help`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandHelp {
+    pub category: String,
+    pub usage: String,
+    pub summary: String,
+    pub examples: Vec<String>,
+}
+
+impl CommandHelp {
+    pub fn new(category: impl ToString, usage: impl ToString, summary: impl ToString) -> Self {
+        CommandHelp {
+            category: category.to_string(),
+            usage: usage.to_string(),
+            summary: summary.to_string(),
+            examples: Vec::new(),
+        }
+    }
+
+    pub fn example(mut self, example: impl ToString) -> Self {
+        self.examples.push(example.to_string());
+        self
+    }
+}
+
 pub enum CliInputResult {
     Value(String),
     Termination,
@@ -14,6 +43,11 @@ pub enum CliInputResult {
 pub trait CliStateCallback<T> {
     fn pre_exec(&mut self, _state: &mut T, _command: &str) {}
     fn post_exec(&mut self, _state: &mut T, _command: &str) {}
+
+    /// Prompt shown by `run_loop`, by default the prompt passed to it.
+    fn prompt(&mut self, _state: &T, default: &str) -> String {
+        default.to_string()
+    }
 }
 
 pub trait CliCallbacks<T> : CliStateCallback<T> {
@@ -23,10 +57,389 @@ pub trait CliCallbacks<T> : CliStateCallback<T> {
     }
 
     fn read_line(&mut self, prompt: &str) -> CliInputResult;
-    fn edit_string(&mut self, text: String) -> String;
+
+    /// Let the user change the text in an editor, an error cancels the
+    /// edit.
+    fn edit_string(&mut self, text: String) -> crate::error::Result<String>;
+
+    /// Open the URL or file with the configured opener.
+    fn open_url(&mut self, url: &str) -> crate::error::Result<()> {
+        let opener = self.config().map_or_else(default_opener, |config| config.opener.clone());
+        let mut opener = opener.split_whitespace();
+        let program = opener.next().unwrap_or("xdg-open").to_string();
+        let error = |msg: String| crate::error::Error::OpenFailed { program: program.clone(), msg };
+        // Browsers print a lot, which would end up in the prompt.
+        let status = subprocess::Exec::cmd(&program).args(&opener.collect::<Vec<_>>()).arg(url)
+            .stdout(subprocess::NullFile).stderr(subprocess::NullFile)
+            .join()
+            .map_err(|err| error(err.to_string()))?;
+        if !status.success() {
+            return Err(error(format!("{:?}", status)));
+        }
+        Ok(())
+    }
+
+    /// Ask a yes/no question, only `y` and `yes` confirm.
+    fn confirm(&mut self, prompt: &str) -> bool {
+        match self.read_line(&format!("{} [y/N] ", prompt)) {
+            CliInputResult::Value(answer) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+            CliInputResult::Termination => false,
+        }
+    }
 
     fn exit(&mut self);
     fn is_exit(&self) -> bool;
+
+    /// Theme used by `styled`, None for plain text.
+    fn theme(&self) -> Option<&Theme> {
+        None
+    }
+    fn theme_mut(&mut self) -> Option<&mut Theme> {
+        None
+    }
+
+    /// User settings of the frontend, None if it has none.
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+    fn config_mut(&mut self) -> Option<&mut Config> {
+        None
+    }
+
+    /// chrono format of dates in listings.
+    fn date_format(&self) -> &str {
+        self.config().map_or(DEFAULT_DATE_FORMAT, |config| config.date_format.as_str())
+    }
+
+    /// First day of the week in weekly reports.
+    fn week_start(&self) -> chrono::Weekday {
+        self.config().map_or(chrono::Weekday::Mon, |config| config.first_weekday())
+    }
+
+    /// Ask for a comment when clocking out of a clock without one.
+    fn clock_out_comment(&self) -> bool {
+        self.config().is_some_and(|config| config.clock_out_comment)
+    }
+
+    /// End of the day when running clocks are stopped automatically.
+    fn auto_clock_out(&self) -> Option<chrono::NaiveTime> {
+        self.config().and_then(|config| config.auto_clock_out_time())
+    }
+
+    /// Breaks required by the worked time of a day.
+    fn break_rules(&self) -> Vec<crate::worktime::BreakRule> {
+        self.config().map_or_else(Vec::new, |config| config.break_rules())
+    }
+
+    /// Days after which open tasks are shown as aging or stale.
+    fn aging_thresholds(&self) -> AgingThresholds {
+        self.config().map_or_else(AgingThresholds::default, |config| config.aging_thresholds())
+    }
+
+    /// Header and footer of invoices.
+    fn invoice_template(&self) -> InvoiceTemplate {
+        self.config().map_or_else(InvoiceTemplate::default, |config| config.invoice_template())
+    }
+
+    /// Format the text in the given style.
+    fn styled(&self, style: Style, text: &str) -> String {
+        match self.theme() {
+            Some(theme) => theme.paint(style, text),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Answer of `Dialog::choose`, selected by its key or its label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice<V> {
+    pub key: char,
+    pub label: String,
+    pub value: V,
+    /// Selected by an empty answer.
+    pub is_default: bool,
+}
+
+impl<V> Choice<V> {
+    pub fn new(key: char, label: impl ToString, value: V) -> Self {
+        Choice { key, label: label.to_string(), value, is_default: false }
+    }
+
+    pub fn as_default(mut self) -> Self {
+        self.is_default = true;
+        self
+    }
+
+    /// Label with the key in brackets like `[d]one`, upper case for the
+    /// default.
+    fn prompt(&self) -> String {
+        let key = if self.is_default { self.key.to_ascii_uppercase() } else { self.key };
+        match self.label.strip_prefix(self.key) {
+            Some(rest) => format!("[{}]{}", key, rest),
+            None => format!("{} [{}]", self.label, key),
+        }
+    }
+
+    fn matches(&self, answer: &str) -> bool {
+        let answer = answer.to_lowercase();
+        answer == self.label.to_lowercase() || answer.chars().eq(self.key.to_lowercase())
+    }
+}
+
+/// Questions to the user on top of `CliCallbacks::read_line`, for
+/// confirmations and multi-step wizards.
+///
+/// Invalid answers are asked again.  Every question returns None if the
+/// user cancels with the end of the input or Ctrl-C, so the command can
+/// stop without changing more.
+pub struct Dialog<'a, T> {
+    callbacks: &'a mut dyn CliCallbacks<T>,
+}
+
+impl<'a, T> Dialog<'a, T> {
+    pub fn new(callbacks: &'a mut dyn CliCallbacks<T>) -> Self {
+        Dialog { callbacks }
+    }
+
+    pub fn println(&mut self, text: &str) {
+        self.callbacks.println(text);
+    }
+
+    /// Ask for text, an empty answer gives the default if there is one.
+    pub fn ask(&mut self, question: &str, default: Option<&str>) -> Option<String> {
+        let prompt = match default {
+            Some(default) => format!("{} [{}]: ", question, default),
+            None => format!("{}: ", question),
+        };
+        match self.callbacks.read_line(&prompt) {
+            CliInputResult::Value(answer) if answer.trim().is_empty() => Some(default.unwrap_or_default().to_string()),
+            CliInputResult::Value(answer) => Some(answer.trim().to_string()),
+            CliInputResult::Termination => None,
+        }
+    }
+
+    /// Ask until `parse` accepts the answer, its errors are shown.
+    pub fn ask_parsed<V, E: std::fmt::Display>(&mut self, question: &str, default: Option<&str>, mut parse: impl FnMut(&str) -> std::result::Result<V, E>) -> Option<V> {
+        loop {
+            let answer = self.ask(question, default)?;
+            match parse(&answer) {
+                Ok(value) => return Some(value),
+                Err(err) => {
+                    let message = self.callbacks.styled(Style::Error, &err.to_string());
+                    self.callbacks.println(&message);
+                },
+            }
+        }
+    }
+
+    /// Ask a yes/no question, an empty answer gives the default.
+    pub fn confirm(&mut self, question: &str, default: bool) -> Option<bool> {
+        let prompt = format!("{} [{}]", question, if default { "Y/n" } else { "y/N" });
+        loop {
+            match self.callbacks.read_line(&format!("{} ", prompt)) {
+                CliInputResult::Value(answer) => match answer.trim().to_lowercase().as_str() {
+                    "" => return Some(default),
+                    "y" | "yes" => return Some(true),
+                    "n" | "no" => return Some(false),
+                    _ => {},
+                },
+                CliInputResult::Termination => return None,
+            }
+        }
+    }
+
+    /// Ask for one of the choices like `[d]one, [s]kip or [q]uit?`.
+    pub fn choose<V: Clone>(&mut self, question: &str, choices: &[Choice<V>]) -> Option<V> {
+        let mut labels: Vec<String> = choices.iter().map(|choice| choice.prompt()).collect();
+        let last = labels.pop().unwrap_or_default();
+        let options = if labels.is_empty() { last } else { format!("{} or {}", labels.join(", "), last) };
+        let prompt = if question.is_empty() { format!("{}? ", options) } else { format!("{} {}? ", question, options) };
+        loop {
+            let answer = match self.callbacks.read_line(&prompt) {
+                CliInputResult::Value(answer) => answer.trim().to_string(),
+                CliInputResult::Termination => return None,
+            };
+            let choice = if answer.is_empty() {
+                choices.iter().find(|choice| choice.is_default)
+            } else {
+                choices.iter().find(|choice| choice.matches(&answer))
+            };
+            if let Some(choice) = choice {
+                return Some(choice.value.clone());
+            }
+        }
+    }
+}
+
+/// Check if the terminal supports line editing.
+///
+/// Without a terminal on stdin, with `TERM=dumb` or inside an Emacs shell,
+/// lines are read plainly from stdin instead.
+pub fn line_editing_supported() -> bool {
+    use std::io::IsTerminal;
+    std::io::stdin().is_terminal()
+        && std::env::var("TERM").map_or(true, |term| term != "dumb")
+        && std::env::var_os("INSIDE_EMACS").is_none()
+}
+
+/// Print the prompt and read a line without the line ending.
+pub fn read_plain_line(input: &mut impl std::io::BufRead, output: &mut impl std::io::Write, prompt: &str) -> CliInputResult {
+    if write!(output, "{}", prompt).and_then(|_| output.flush()).is_err() {
+        return CliInputResult::Termination;
+    }
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => CliInputResult::Termination,
+        Ok(_) => CliInputResult::Value(line.trim_end_matches(&['\n', '\r'][..]).to_string()),
+    }
+}
+
+/// Callbacks which read lines from `input` and write to `output` without
+/// line editing, for scripts, pipes and embedding.
+///
+/// There is no editor, so `edit_string` returns the text unchanged.
+pub struct PlainCallbacks<R, W> {
+    input: R,
+    output: W,
+    exit: bool,
+}
+
+impl<R: std::io::BufRead, W: std::io::Write> PlainCallbacks<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        PlainCallbacks { input, output, exit: false }
+    }
+
+    pub fn into_output(self) -> W {
+        self.output
+    }
+}
+
+impl PlainCallbacks<std::io::StdinLock<'static>, std::io::Stdout> {
+    pub fn stdio() -> Self {
+        PlainCallbacks::new(std::io::stdin().lock(), std::io::stdout())
+    }
+}
+
+impl<T, R, W> CliStateCallback<T> for PlainCallbacks<R, W> {}
+impl<T, R: std::io::BufRead, W: std::io::Write> CliCallbacks<T> for PlainCallbacks<R, W> {
+    fn print(&mut self, text: &str) {
+        let _ = self.output.write_all(text.as_bytes());
+    }
+
+    fn read_line(&mut self, prompt: &str) -> CliInputResult {
+        read_plain_line(&mut self.input, &mut self.output, prompt)
+    }
+    fn edit_string(&mut self, text: String) -> crate::error::Result<String> {
+        Ok(text)
+    }
+
+    fn exit(&mut self) {
+        self.exit = true;
+    }
+    fn is_exit(&self) -> bool {
+        self.exit
+    }
+}
+
+/// Callbacks which answer prompts from a script and keep everything
+/// printed, to test commands without a terminal.
+///
+/// The editor returns the queued edits in order and the text unchanged when
+/// none is left.  Prompts and the texts opened in the editor are recorded.
+#[derive(Default)]
+pub struct ScriptedCallbacks {
+    pub output: String,
+
+    /// Answers of `read_line`, the input ends when it's empty.
+    pub input: VecDeque<String>,
+
+    /// Texts the editor returns, `None` fails like an editor which
+    /// couldn't start.
+    pub edits: VecDeque<Option<String>>,
+
+    /// Texts which were opened in the editor.
+    pub edited: Vec<String>,
+
+    /// Links passed to `open_url`, nothing is launched.
+    pub opened: Vec<String>,
+    pub prompts: Vec<String>,
+    pub exit: bool,
+    pub config: Option<Config>,
+}
+
+impl ScriptedCallbacks {
+    pub fn new() -> Self {
+        ScriptedCallbacks::default()
+    }
+
+    /// Queue an answer for the next prompt.
+    pub fn input(mut self, line: impl ToString) -> Self {
+        self.input.push_back(line.to_string());
+        self
+    }
+
+    /// Queue the result of the next editor call.
+    pub fn edit(mut self, text: impl ToString) -> Self {
+        self.edits.push_back(Some(text.to_string()));
+        self
+    }
+
+    /// Let the next editor call fail.
+    pub fn failed_edit(mut self) -> Self {
+        self.edits.push_back(None);
+        self
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Output since the last call.
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl<T> CliStateCallback<T> for ScriptedCallbacks {}
+impl<T> CliCallbacks<T> for ScriptedCallbacks {
+    fn print(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn read_line(&mut self, prompt: &str) -> CliInputResult {
+        self.prompts.push(prompt.to_string());
+        match self.input.pop_front() {
+            Some(line) => CliInputResult::Value(line),
+            None => CliInputResult::Termination,
+        }
+    }
+    fn edit_string(&mut self, text: String) -> crate::error::Result<String> {
+        let result = self.edits.pop_front().unwrap_or_else(|| Some(text.clone()));
+        self.edited.push(text);
+        result.ok_or_else(|| crate::error::Error::EditorFailed {
+            program: "script".to_string(),
+            msg: "no edit".to_string(),
+        })
+    }
+    fn open_url(&mut self, url: &str) -> crate::error::Result<()> {
+        self.opened.push(url.to_string());
+        Ok(())
+    }
+
+    fn exit(&mut self) {
+        self.exit = true;
+    }
+    fn is_exit(&self) -> bool {
+        self.exit
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.config.as_ref()
+    }
+    fn config_mut(&mut self) -> Option<&mut Config> {
+        self.config.as_mut()
+    }
 }
 
 pub struct CliCallbackHolder<'a, T, T2, C2: CliStateCallback<T2>> {
@@ -52,7 +465,9 @@ pub fn new_cli_with_callbacks<T: Sized, C: CliCallbacks<T>, T2: Sized, C2: CliSt
     Cli {
         state: initial_state,
         commands: HashMap::new(),
+        help: HashMap::new(),
         callbacks: CliCallbackHolder::new(callbacks, state_callbacks),
+        history: Vec::new(),
     }
 }
 
@@ -63,6 +478,9 @@ impl<'a, T, T2, C2: CliStateCallback<T2>> CliStateCallback<T2> for CliCallbackHo
     fn post_exec(&mut self, state: &mut T2, command: &str) {
         self.state_callbacks.post_exec(state, command)
     }
+    fn prompt(&mut self, state: &T2, default: &str) -> String {
+        self.state_callbacks.prompt(state, default)
+    }
 }
 impl<'a, T, T2, C2: CliStateCallback<T2>> CliCallbacks<T2> for CliCallbackHolder<'a, T, T2, C2> {
     fn print(&mut self, text: &str) {
@@ -75,9 +493,12 @@ impl<'a, T, T2, C2: CliStateCallback<T2>> CliCallbacks<T2> for CliCallbackHolder
     fn read_line(&mut self, prompt: &str) -> CliInputResult {
         self.callbacks.read_line(prompt)
     }
-    fn edit_string(&mut self, text: String) -> String {
+    fn edit_string(&mut self, text: String) -> crate::error::Result<String> {
         self.callbacks.edit_string(text)
     }
+    fn open_url(&mut self, url: &str) -> crate::error::Result<()> {
+        self.callbacks.open_url(url)
+    }
 
     fn exit(&mut self) {
         self.exit = true;
@@ -86,12 +507,30 @@ impl<'a, T, T2, C2: CliStateCallback<T2>> CliCallbacks<T2> for CliCallbackHolder
     fn is_exit(&self) -> bool {
         self.exit
     }
+
+    fn theme(&self) -> Option<&Theme> {
+        self.callbacks.theme()
+    }
+    fn theme_mut(&mut self) -> Option<&mut Theme> {
+        self.callbacks.theme_mut()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.callbacks.config()
+    }
+    fn config_mut(&mut self) -> Option<&mut Config> {
+        self.callbacks.config_mut()
+    }
 }
 
 pub struct Cli<T: Sized, C: CliCallbacks<T>> {
     pub state: T,
     pub commands: HashMap<String, Func<T, C>>,
+    pub help: HashMap<String, CommandHelp>,
     pub callbacks: C,
+
+    /// Entered command lines, the oldest first, for `history` and `!!`.
+    pub history: Vec<String>,
 }
 
 impl<T: Sized, C: CliCallbacks<T>> Cli<T, C> {
@@ -99,7 +538,9 @@ impl<T: Sized, C: CliCallbacks<T>> Cli<T, C> {
         Cli {
             state: initial_state,
             commands: HashMap::new(),
+            help: HashMap::new(),
             callbacks,
+            history: Vec::new(),
         }
     }
 
@@ -107,13 +548,20 @@ impl<T: Sized, C: CliCallbacks<T>> Cli<T, C> {
         Cli {
             state: initial_state,
             commands: HashMap::new(),
+            help: HashMap::new(),
             callbacks: CliCallbackHolder::new(&mut self.callbacks, state_callbacks),
+            history: Vec::new(),
         }
     }
 
     pub fn run_command(&mut self, line: &str) -> Result<()> {
         if let Some(command) = line.trim().split(' ').next() {
-            if let Some(func) = self.commands.get(command) {
+            if command == "help" && !self.commands.contains_key(command) {
+                let topic = line.trim().split(' ').nth(1).filter(|topic| !topic.is_empty());
+                self.print_help(topic)
+            } else if command == "history" && !self.commands.contains_key(command) {
+                self.print_history(line.trim().split(' ').nth(1).filter(|n| !n.is_empty()))
+            } else if let Some(func) = self.commands.get(command) {
                 func(&mut self.state, line.trim(), &mut self.callbacks)
             } else {
                 Err(Box::new(CliError::CommandNotFound { command: command.to_string() }))
@@ -123,14 +571,52 @@ impl<T: Sized, C: CliCallbacks<T>> Cli<T, C> {
         }
     }
 
+    /// Run the commands of a script like a startup file, one per line.
+    ///
+    /// Empty lines and lines starting with `#` are skipped.  Failing lines
+    /// are reported with their number and the script goes on, the number
+    /// of failures is returned.
+    pub fn run_script(&mut self, script: &str) -> usize {
+        let mut failures = 0;
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.callbacks.pre_exec(&mut self.state, line);
+            if let Err(err) = self.run_command(line) {
+                let message = self.callbacks.styled(Style::Error, &format!("Error in line {}: {}", i + 1, err));
+                self.callbacks.println(&message);
+                failures += 1;
+            }
+            self.callbacks.post_exec(&mut self.state, line);
+        }
+        failures
+    }
+
     pub fn run_loop(&mut self, prompt: &str) {
         while !self.callbacks.is_exit() {
-            match self.callbacks.read_line(prompt) {
+            let prompt = self.callbacks.prompt(&self.state, prompt);
+            match self.callbacks.read_line(&prompt) {
                 CliInputResult::Value(input) => {
+                    let input = match self.expand_history(&input) {
+                        Ok(input) => input,
+                        Err(err) => {
+                            let message = self.callbacks.styled(Style::Error, &format!("Error: {}", err));
+                            self.callbacks.println(&message);
+                            continue;
+                        },
+                    };
+                    if !input.trim().is_empty() {
+                        self.history.push(input.trim().to_string());
+                    }
                     self.callbacks.pre_exec(&mut self.state, &input);
                     match self.run_command(&input) {
                         Ok(()) => {},
-                        Err(err) => self.callbacks.println(&format!("Error: {}", err))
+                        Err(err) => {
+                            let message = self.callbacks.styled(Style::Error, &format!("Error: {}", err));
+                            self.callbacks.println(&message)
+                        },
                     }
                     self.callbacks.post_exec(&mut self.state, &input);
                 },
@@ -139,11 +625,97 @@ impl<T: Sized, C: CliCallbacks<T>> Cli<T, C> {
         }
     }
 
-    pub fn register_command(&mut self, command: impl ToString, func: Func<T, C>) {
+    /// Replace a history reference at the start of the line by the command
+    /// line it refers to: `!!` the last one, `!n` the nth one and `!prefix`
+    /// the last one starting with the prefix.  Further arguments are
+    /// appended and the expanded line is printed.
+    pub fn expand_history(&mut self, line: &str) -> CliResult<String> {
+        let reference = match line.trim().strip_prefix('!') {
+            Some(reference) => reference,
+            None => return Ok(line.to_string()),
+        };
+        let (word, rest) = reference.split_once(' ').unwrap_or((reference, ""));
+        let entry = if word == "!" {
+            self.history.last()
+        } else if let Ok(n) = word.parse::<usize>() {
+            n.checked_sub(1).and_then(|i| self.history.get(i))
+        } else if !word.is_empty() {
+            self.history.iter().rev().find(|entry| entry.starts_with(word))
+        } else {
+            None
+        };
+        let entry = entry.ok_or_else(|| CliError::ParseError { msg: format!("No command in history: !{}", word) })?;
+        let expanded = if rest.trim().is_empty() { entry.clone() } else { format!("{} {}", entry, rest.trim()) };
+        self.callbacks.println(&expanded);
+        Ok(expanded)
+    }
+
+    /// Print the last n command lines, 20 by default, numbered for `!n`.
+    pub fn print_history(&mut self, n: Option<&str>) -> Result<()> {
+        let n = match n {
+            Some(n) => n.parse()?,
+            None => 20,
+        };
+        let start = self.history.len().saturating_sub(n);
+        for (i, entry) in self.history.iter().enumerate().skip(start) {
+            self.callbacks.println(&format!("{:>4}  {}", i + 1, entry));
+        }
+        Ok(())
+    }
+
+    /// Print the usage of one command or all commands grouped by category.
+    pub fn print_help(&mut self, command: Option<&str>) -> Result<()> {
+        if let Some(command) = command {
+            if !self.commands.contains_key(command) {
+                return Err(Box::new(CliError::CommandNotFound { command: command.to_string() }));
+            }
+            match self.help.get(command) {
+                Some(help) => {
+                    self.callbacks.println(&format!("Usage: {}", help.usage));
+                    self.callbacks.println(&help.summary);
+                    if !help.examples.is_empty() {
+                        self.callbacks.println("Examples:");
+                        for example in help.examples.iter() {
+                            self.callbacks.println(&format!("  {}", example));
+                        }
+                    }
+                },
+                None => self.callbacks.println(&format!("No help for {}", command)),
+            }
+            return Ok(());
+        }
+        let mut categories: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+        for command in self.commands.keys() {
+            let (category, summary) = match self.help.get(command) {
+                Some(help) => (help.category.as_str(), help.summary.as_str()),
+                None => ("Other", ""),
+            };
+            categories.entry(category).or_default().push((command, summary));
+        }
+        let width = self.commands.keys().map(|command| command.len()).max().unwrap_or(0);
+        let mut lines = Vec::new();
+        for (category, mut commands) in categories {
+            commands.sort();
+            lines.push(format!("{}:", category));
+            for (command, summary) in commands {
+                lines.push(format!("  {:width$}  {}", command, summary, width = width));
+            }
+        }
+        lines.push("Type `help <command>` for the usage of a command.".to_string());
+        lines.push("`history [n]` shows the last commands, `!!`, `!n` and `!prefix` run one again.".to_string());
+        for line in lines {
+            self.callbacks.println(&line);
+        }
+        Ok(())
+    }
+
+    pub fn register_command(&mut self, command: impl ToString, help: CommandHelp, func: Func<T, C>) {
+        self.help.insert(command.to_string(), help);
         self.commands.insert(command.to_string(), func);
     }
 
     pub fn remove_command(&mut self, command: &str) -> Option<Func<T, C>> {
+        self.help.remove(command);
         self.commands.remove(command)
     }
 }
\ No newline at end of file
@@ -4,13 +4,30 @@ use chrono::prelude::*;
 use serde::{Serialize, Deserialize};
 
 
+/// Name of the channel of clocks without one.
+pub const DEFAULT_CHANNEL: &str = "default";
+
+/// Tracked time, see the `timezone` module for the time zone of the
+/// start and end.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Clock {
     pub id: Uuid,
-    pub start: DateTime<Local>,
-    pub end: Option<DateTime<Local>>,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
     pub comment: Option<String>,
-    pub task_id: Option<Uuid>
+    pub task_id: Option<Uuid>,
+    #[serde(default)]
+    pub category: Option<String>,
+
+    /// Cross-cutting labels like `travel` or `on-call`, independent of
+    /// the task.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Named channel the clock runs in, like `oncall`, `None` for the
+    /// default channel.
+    #[serde(default)]
+    pub channel: Option<String>,
 }
 
 impl std::cmp::PartialEq for Clock {
@@ -32,23 +49,44 @@ impl std::cmp::Ord for Clock {
 
 impl Clock {
     pub fn duration(&self) -> chrono::Duration {
-        self.end.unwrap_or_else(Local::now) - self.start
+        self.end.unwrap_or_else(Utc::now) - self.start
+    }
+
+    /// The category of the clock or the prefix of its comment in brackets
+    /// like `[meeting] Weekly sync`.
+    pub fn group(&self) -> Option<String> {
+        if self.category.is_some() {
+            return self.category.clone();
+        }
+        let comment = self.comment.as_deref()?.trim_start().strip_prefix('[')?;
+        let end = comment.find(']')?;
+        Some(comment[..end].trim().to_string()).filter(|group| !group.is_empty())
+    }
+
+    pub fn channel_name(&self) -> &str {
+        self.channel.as_deref().unwrap_or(DEFAULT_CHANNEL)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|clock_tag| clock_tag == tag)
     }
 }
 
 pub trait ClockMod {
-    fn set_start(&mut self, start: DateTime<Local>) -> &mut Self;
-    fn set_end(&mut self, end: DateTime<Local>) -> &mut Self;
+    fn set_start(&mut self, start: DateTime<Utc>) -> &mut Self;
+    fn set_end(&mut self, end: DateTime<Utc>) -> &mut Self;
     fn set_comment(&mut self, comment: String) -> &mut Self;
     fn set_task_id(&mut self, task_id: Uuid) -> &mut Self;
+    fn set_category(&mut self, category: Option<String>) -> &mut Self;
+    fn set_tags(&mut self, tags: Vec<String>) -> &mut Self;
 }
 
 impl ClockMod for Rc<Clock> {
-    fn set_start(&mut self, start: DateTime<Local>) -> &mut Self {
+    fn set_start(&mut self, start: DateTime<Utc>) -> &mut Self {
         Rc::make_mut(self).start = start;
         self
     }
-    fn set_end(&mut self, end: DateTime<Local>) -> &mut Self {
+    fn set_end(&mut self, end: DateTime<Utc>) -> &mut Self {
         Rc::make_mut(self).end = Some(end);
         self
     }
@@ -60,4 +98,12 @@ impl ClockMod for Rc<Clock> {
         Rc::make_mut(self).task_id = Some(task_id);
         self
     }
+    fn set_category(&mut self, category: Option<String>) -> &mut Self {
+        Rc::make_mut(self).category = category;
+        self
+    }
+    fn set_tags(&mut self, tags: Vec<String>) -> &mut Self {
+        Rc::make_mut(self).tags = tags;
+        self
+    }
 }
\ No newline at end of file
@@ -2,12 +2,16 @@ use crate::clock::Clock;
 use crate::clock::ClockMod;
 use crate::doc::Doc;
 use crate::error::*;
+use crate::timezone::DisplayZone;
 use chrono::prelude::*;
 use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct ClockEdit {
-    pub clocks: Vec<Rc<Clock>>
+    pub clocks: Vec<Rc<Clock>>,
+
+    /// Time zone of the edited times and dates.
+    pub timezone: DisplayZone,
 }
 
 impl ClockEdit {
@@ -37,37 +41,40 @@ impl ClockEdit {
         })
     }
 
-    pub fn set_start(&mut self, i: usize, start: DateTime<Local>) -> Result<()> {
+    pub fn set_start(&mut self, i: usize, start: DateTime<Utc>) -> Result<()> {
         self.modify_clock(i, move |clock: &mut Rc<Clock>| {
             clock.set_start(start);
         })
     }
     pub fn set_start_time(&mut self, i: usize, start: NaiveTime) -> Result<()> {
+        let timezone = self.timezone;
         self.modify_clock(i, move |clock: &mut Rc<Clock>| {
-            if let Some(new_start) = clock.start.date().and_time(start) {
+            if let Some(new_start) = timezone.to_utc(timezone.date(clock.start).and_time(start)) {
                 clock.set_start(new_start);
             }
         })
     }
 
-    pub fn set_end(&mut self, i: usize, end: DateTime<Local>) -> Result<()> {
+    pub fn set_end(&mut self, i: usize, end: DateTime<Utc>) -> Result<()> {
         self.modify_clock(i, move | clock: &mut Rc<Clock>| {
             clock.set_end(end);
         })
     }
     pub fn set_end_time(&mut self, i: usize, start: NaiveTime) -> Result<()> {
+        let timezone = self.timezone;
         self.modify_clock(i, move |clock: &mut Rc<Clock>| {
             if let Some(end) = clock.end {
-                if let Some(new_start) = end.date().and_time(start) {
+                if let Some(new_start) = timezone.to_utc(timezone.date(end).and_time(start)) {
                     clock.set_end(new_start);
                 }
             }
         })
     }
-    pub fn set_end_date(&mut self, i: usize, new_end: Date<Local>) -> Result<()> {
+    pub fn set_end_date(&mut self, i: usize, new_end: NaiveDate) -> Result<()> {
+        let timezone = self.timezone;
         self.modify_clock(i, move |clock: &mut Rc<Clock>| {
             if let Some(end) = clock.end {
-                if let Some(new_end) = new_end.and_time(end.time()) {
+                if let Some(new_end) = timezone.to_utc(new_end.and_time(timezone.localize(end).time())) {
                     clock.set_end(new_end);
                 }
             }
@@ -76,14 +83,15 @@ impl ClockEdit {
 }
 
 impl Doc {
-    pub fn create_clock_edit(&self, date: Date<Local>) -> ClockEdit {
+    pub fn create_clock_edit(&self, date: NaiveDate) -> ClockEdit {
         let mut clocks: Vec<Rc<Clock>> = self.clocks.values()
-            .filter(|clock| clock.start.date() == date)
+            .filter(|clock| self.clock_date(clock) == date)
             .cloned()
             .collect();
         clocks.sort();
         ClockEdit {
-            clocks
+            clocks,
+            timezone: self.timezone,
         }
     }
 
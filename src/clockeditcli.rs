@@ -2,7 +2,7 @@ use crate::clockedit::*;
 use crate::error::*;
 use crate::doc::*;
 use crate::helper::*;
-use crate::cli::{Cli, CliCallbacks, CliStateCallback};
+use crate::cli::{Cli, CliCallbacks, CliStateCallback, CommandHelp};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExitAction {
@@ -22,11 +22,12 @@ impl<'a> CliStateCallback<ClockEditCli<'a>> for ClockCallbacks {}
 
 impl<'a> ClockEditCli<'a> {
     pub fn apply_commands<C: CliCallbacks<ClockEditCli<'a>>>(terminal: &mut Cli<ClockEditCli<'a>, C>) {
-        terminal.register_command("cancel", Box::new(|_, _, callbacks| {
+        terminal.register_command("cancel", CommandHelp::new("Clock edit", "cancel", "Leave without applying the changes"), Box::new(|_, _, callbacks| {
             callbacks.exit();
             Ok(())
         }));
-        terminal.register_command("start", Box::new(|state: &mut ClockEditCli, line: &str, _| {
+        terminal.register_command("start", CommandHelp::new("Clock edit", "start <n> <time>", "Change the start time of the n-th clock")
+                .example("start 1 09:15"), Box::new(|state: &mut ClockEditCli, line: &str, _| {
             let mut splitted_line = line.split(' ');
             splitted_line.next();
             let i = if let Some(index) = splitted_line.next() {
@@ -40,7 +41,8 @@ impl<'a> ClockEditCli<'a> {
             }
             Ok(())
         }));
-        terminal.register_command("end", Box::new(|state: &mut ClockEditCli, line: &str, _| {
+        terminal.register_command("end", CommandHelp::new("Clock edit", "end <n> <time>", "Change the end time of the n-th clock")
+                .example("end 1 10:00"), Box::new(|state: &mut ClockEditCli, line: &str, _| {
             let mut splitted_line = line.split(' ');
             splitted_line.next();
             let i = if let Some(index) = splitted_line.next() {
@@ -54,7 +56,22 @@ impl<'a> ClockEditCli<'a> {
             }
             Ok(())
         }));
-        terminal.register_command("enddate", Box::new(|state: &mut ClockEditCli, line: &str, _| {
+        terminal.register_command("duration", CommandHelp::new("Clock edit", "duration <n> <duration>", "Change the end of the n-th clock to start plus duration")
+                .example("duration 1 1h30m"), Box::new(|state: &mut ClockEditCli, line: &str, _| {
+            let mut splitted_line = line.splitn(3, ' ');
+            splitted_line.next();
+            let i = if let Some(index) = splitted_line.next() {
+                index.parse::<usize>()?
+            } else {
+                return Err(Box::new(Error::UnsufficientInput {}));
+            };
+            if let Some(duration_str) = splitted_line.next() {
+                let duration = parse_duration(duration_str)?;
+                state.clockedit.set_duration(i.checked_sub(1).ok_or(Error::ClockOutOfIndex {})?, duration)?;
+            }
+            Ok(())
+        }));
+        terminal.register_command("enddate", CommandHelp::new("Clock edit", "enddate <n> <date>", "Change the end date of the n-th clock"), Box::new(|state: &mut ClockEditCli, line: &str, callbacks| {
             let mut splitted_line = line.split(' ');
             splitted_line.next();
             let i = if let Some(index) = splitted_line.next() {
@@ -63,20 +80,20 @@ impl<'a> ClockEditCli<'a> {
                 return Err(Box::new(Error::UnsufficientInput {}));
             };
             if let Some(end_str) = splitted_line.next() {
-                let date = parse_date(end_str)?;
+                let date = parse_date(end_str, &DateContext::new(state.doc, callbacks))?;
                 state.clockedit.set_end_date(i - 1, date)?;
             }
             Ok(())
         }));
-        terminal.register_command("apply", Box::new(|state: &mut ClockEditCli, _, callbacks| {
+        terminal.register_command("apply", CommandHelp::new("Clock edit", "apply", "Apply the changes and leave"), Box::new(|state: &mut ClockEditCli, _, callbacks| {
             state.apply_result = ExitAction::Apply;
             callbacks.exit();
             Ok(())
         }));
-        terminal.register_command("ls", Box::new(|state: &mut ClockEditCli, _, callbacks| {
+        terminal.register_command("ls", CommandHelp::new("Clock edit", "ls", "List the clocks"), Box::new(|state: &mut ClockEditCli, _, callbacks| {
             for (clock, i) in state.clockedit.clocks.iter().zip(1..) {
-                let start = &clock.start;
-                let end = clock.end.map(|end| format!("{}", end)).unwrap_or_else(|| "(none)".to_string());
+                let start = state.clockedit.timezone.localize(clock.start);
+                let end = clock.end.map(|end| format!("{}", state.clockedit.timezone.localize(end))).unwrap_or_else(|| "(none)".to_string());
                 let comment = clock.comment.clone().map(|comment| comment).unwrap_or_else(|| "(none)".to_string());
                 let task_str = if let Some(task_id) = clock.task_id {
                     let path = state.doc.path(&task_id);
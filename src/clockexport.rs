@@ -0,0 +1,115 @@
+//! Clocks without their tasks, to exchange time data with other documents
+//! or people.
+//!
+//! Each clock carries the titles of its task path, so the task can be found
+//! in a document which doesn't share the task UUIDs.  Clocks are identified
+//! by their UUID, importing the same file twice doesn't add them again.
+
+use crate::clock::Clock;
+use crate::doc::Doc;
+use crate::error::*;
+use serde::{Serialize, Deserialize};
+use snafu::ResultExt;
+use std::path::Path;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Version of the file format.
+pub const CLOCK_EXPORT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedClock {
+    #[serde(flatten)]
+    pub clock: Clock,
+
+    /// Titles of the task and its parents, starting below the root.
+    #[serde(default)]
+    pub task_path: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClockExport {
+    pub version: u32,
+    pub clocks: Vec<ExportedClock>,
+}
+
+impl ClockExport {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context(SerdeSerializationError)?;
+        std::fs::write(path, json).context(IO)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<ClockExport> {
+        let json = std::fs::read_to_string(path).context(IO)?;
+        Ok(serde_json::from_str(&json).context(SerdeSerializationError)?)
+    }
+}
+
+/// Result of `Doc::import_clocks`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClockImportReport {
+    pub imported: usize,
+
+    /// Clocks which already exist in the document.
+    pub skipped: usize,
+
+    /// Imported clocks whose task wasn't found.
+    pub without_task: usize,
+}
+
+impl Doc {
+    /// Export the given clocks, running clocks are left out.
+    pub fn export_clocks(&self, clocks: &[Rc<Clock>]) -> ClockExport {
+        let mut clocks: Vec<_> = clocks.iter()
+            .filter(|clock| clock.end.is_some())
+            .map(|clock| ExportedClock {
+                clock: (**clock).clone(),
+                task_path: clock.task_id.map(|task_id| self.title_path(&task_id)).unwrap_or_default(),
+            })
+            .collect();
+        clocks.sort_by_key(|exported| exported.clock.start);
+        ClockExport { version: CLOCK_EXPORT_VERSION, clocks }
+    }
+
+    /// Add the clocks which aren't in the document yet.  Their task is found
+    /// by its UUID or else by the titles of its path.
+    pub fn import_clocks(&mut self, export: &ClockExport) -> ClockImportReport {
+        let mut report = ClockImportReport::default();
+        for exported in export.clocks.iter() {
+            if self.clocks.contains_key(&exported.clock.id) {
+                report.skipped += 1;
+                continue;
+            }
+            let mut clock = exported.clock.clone();
+            clock.task_id = clock.task_id
+                .filter(|task_id| self.map.contains_key(task_id))
+                .or_else(|| self.find_title_path(&exported.task_path));
+            if clock.task_id.is_none() && !exported.task_path.is_empty() {
+                report.without_task += 1;
+            }
+            self.upsert_clock(Rc::new(clock));
+            report.imported += 1;
+        }
+        report
+    }
+
+    fn title_path(&self, task_id: &Uuid) -> Vec<String> {
+        self.path(task_id).iter().rev()
+            .filter(|task_id| **task_id != self.root)
+            .filter_map(|task_id| self.get(task_id).ok())
+            .map(|task| task.title.clone())
+            .collect()
+    }
+
+    fn find_title_path(&self, titles: &[String]) -> Option<Uuid> {
+        if titles.is_empty() {
+            return None;
+        }
+        titles.iter().try_fold(self.root, |parent, title| {
+            self.get(&parent).ok()?.children.iter()
+                .find(|child| self.get(child).is_ok_and(|task| task.title == *title))
+                .cloned()
+        })
+    }
+}
@@ -0,0 +1,2276 @@
+//! Commands of the task manager which can be registered on a `Cli`.
+
+use crate::error::{self, *};
+use crate::{billing, external, import, ics, info, render};
+use crate::tasks::*;
+use crate::doc::*;
+use crate::workflow::*;
+use crate::worktime::*;
+use crate::state::*;
+use crate::output::{Style, Theme};
+use crate::hooks::HookEvent;
+use crate::config::Config;
+use crate::snapshot::*;
+use crate::diff::Change;
+use crate::context::context_name;
+use crate::sort::SortKey;
+use crate::habit::{Habit, RATE_WEEKS};
+use crate::caldav::{CaldavSync, CurlTransport};
+use crate::clock::ClockMod;
+use crate::clockexport::ClockExport;
+use crate::clockeditcli::*;
+use crate::helper::*;
+use crate::cli::*;
+use crate::statics::*;
+use crate::text::*;
+use crate::links::short_id;
+use crate::split::{checklist_items, parse_item_list};
+use chrono::Local;
+use std::env::var;
+use std::path::Path;
+use std::rc::Rc;
+
+fn pin_command<C: CliCallbacks<State>>(pinned: bool) -> Func<State, C> {
+    Box::new(move |state: &mut State, cmd: &str, _| {
+        let task_ids = match positional_args(cmd).first() {
+            Some(selector) => resolve_selector(state, selector)?,
+            None => vec![state.wt],
+        };
+        for task_id in task_ids {
+            let mut task = state.doc.get(&task_id)?;
+            task.set_pinned(pinned);
+            state.doc.upsert(task);
+        }
+        Ok(())
+    })
+}
+
+fn progress_command<C: CliCallbacks<State>>(progress: Progress) -> Func<State, C> {
+    Box::new(move |state: &mut State, cmd: &str, _| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let task_ids = if let Some(selector) = split.next() {
+            resolve_selector(state, selector)?
+        } else {
+            vec![state.wt]
+        };
+        for task_id in task_ids {
+            state.doc.set_progress(&task_id, progress)?;
+        }
+        Ok(())
+    })
+}
+
+/// Stop forgotten clocks at the end of the day before the command runs,
+/// so reports don't count the night, see `apply_auto_clock_out`.
+pub fn with_auto_clock_out<C: CliCallbacks<State> + 'static>(func: Func<State, C>) -> Func<State, C> {
+    Box::new(move |state: &mut State, cmd: &str, callbacks| {
+        if !state.read_only {
+            apply_auto_clock_out(&mut state.doc, callbacks);
+        }
+        func(state, cmd, callbacks)
+    })
+}
+
+/// Run the command as a transaction, if it fails its changes are rolled
+/// back.
+///
+/// This way a command which stops midway, like a `mv` which removed the
+/// task from its parent but couldn't attach it, leaves no half done change
+/// behind.  If the document is read-only, every command which changes the
+/// document fails, also if only some of its subcommands change something.
+/// In debug builds, commands which break one of the `invariants` fail too.
+pub fn transaction<C: CliCallbacks<State> + 'static>(func: Func<State, C>) -> Func<State, C> {
+    Box::new(move |state: &mut State, cmd: &str, callbacks| {
+        let snapshot = state.doc.snapshot();
+        let navigation = (state.wt, state.previous_wt, state.dir_stack.clone());
+        let mut result = func(state, cmd, callbacks);
+        #[cfg(debug_assertions)]
+        {
+            let violations = if result.is_ok() { state.doc.check_invariants() } else { Vec::new() };
+            if !violations.is_empty() {
+                let before = snapshot.doc().check_invariants();
+                if let Some(violation) = violations.into_iter().find(|violation| !before.contains(violation)) {
+                    result = Err(Box::new(Error::InvariantViolated { msg: violation.to_string() }));
+                }
+            }
+        }
+        if state.read_only {
+            state.doc.read_only = true;
+            if state.update_dirty() {
+                result = Err(Box::new(Error::ReadOnly {}));
+            }
+        }
+        if result.is_err() && state.doc.rollback(snapshot) {
+            (state.wt, state.previous_wt, state.dir_stack) = navigation;
+        }
+        if result.is_err() {
+            state.update_dirty();
+        }
+        result
+    })
+}
+
+/// Register all commands of the task manager.
+pub fn register_default_commands<C: CliCallbacks<State> + 'static>(terminal: &mut Cli<State, C>) {
+    terminal.register_command("exit", CommandHelp::new("General", "exit [--yes]", "Exit, asks before unsaved changes are lost"), Box::new(|state: &mut State, cmd: &str, response| {
+        if !state.dirty || confirmed(cmd, "Exit without saving the changes?", response) {
+            response.exit();
+        }
+        Ok(())
+    }));
+    terminal.register_command("discard", CommandHelp::new("General", "discard [--yes]", "Throw away all unsaved changes"), Box::new(|state: &mut State, cmd: &str, response| {
+        if !state.dirty || !confirmed(cmd, "Discard all unsaved changes?", response) {
+            return Ok(());
+        }
+        let doc = state.load_doc()?;
+        state.replace_doc(doc);
+        state.mark_saved();
+        state.refresh_disk_state();
+        Ok(())
+    }));
+    terminal.register_command("debug", CommandHelp::new("General", "debug", "Print the internal state"), Box::new(|state, _, response| { 
+        response.println(&format!("{:?}", state));
+        Ok(())
+    }));
+    terminal.register_command("set", CommandHelp::new("General", "set [output json|text | <property> <value>]",
+            "Show or change settings or set a property of the working task").example("set output json").example("set client ACME Corp").example("set rate 85 EUR").example("set rounding 15m"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        match positional_args(cmd).as_slice() {
+            ["output", "json"] => state.output = OutputFormat::Json,
+            ["output", "text"] => state.output = OutputFormat::Text,
+            ["output", ..] => return Err(Box::new(CliError::ParseError { msg: "Usage: set output json|text".to_string() })),
+            [] => response.println(&format!("output: {:?}", state.output)),
+            [_] => return Err(Box::new(Error::UnsufficientInput {})),
+            [key, ..] => {
+                let value = cmd.splitn(3, ' ').nth(2).unwrap_or_default().trim();
+                if *key == billing::RATE_PROPERTY {
+                    value.parse::<billing::Rate>()?;
+                }
+                if *key == billing::ROUNDING_PROPERTY {
+                    value.parse::<billing::Rounding>()?;
+                }
+                let mut task = state.doc.get(&state.wt)?;
+                task.set_property(*key, value);
+                state.doc.upsert(task);
+            },
+        }
+        Ok(())
+    }));
+    terminal.register_command("unset", CommandHelp::new("Tasks", "unset <property>...", "Remove properties from the working task").example("unset client"), Box::new(|state: &mut State, cmd: &str, _| {
+        let mut task = state.doc.get(&state.wt)?;
+        for key in positional_args(cmd) {
+            task.remove_property(key);
+        }
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("props", CommandHelp::new("Tasks", "props [--json]", "List the properties of the working task"), Box::new(|state: &mut State, cmd: &str, response| {
+        let task = state.doc.get(&state.wt)?;
+        if json_output(state, cmd) {
+            print_json(&serde_json::to_value(&task.properties)?, response);
+            return Ok(());
+        }
+        for (key, value) in task.properties.iter() {
+            response.println(&format!("{}: {}", key, value));
+        }
+        Ok(())
+    }));
+    terminal.register_command("theme", CommandHelp::new("General", "theme [color on|off | reset | <style> <ansi code>]", "Show or change the colors of the output")
+            .example("theme work 1;34").example("theme color off"), Box::new(|_: &mut State, cmd: &str, response| {
+        let theme = response.theme_mut().ok_or(CliError::ParseError { msg: "No theme support".to_string() })?;
+        match positional_args(cmd).as_slice() {
+            [] => {
+                let theme = theme.clone();
+                response.println(&format!("color: {}", if theme.color { "on" } else { "off" }));
+                for style in Style::all() {
+                    let code = theme.styles.get(style).cloned().unwrap_or_default();
+                    response.println(&format!("{:?}: {}", style, response.styled(*style, &code)));
+                }
+                return Ok(());
+            },
+            ["color", "on"] => theme.color = true,
+            ["color", "off"] => theme.color = false,
+            ["reset"] => *theme = Theme::default(),
+            [style, code] => {
+                theme.styles.insert(style.parse::<Style>()?, code.to_string());
+            },
+            _ => return Err(Box::new(CliError::ParseError { msg: "Usage: theme [color on|off | reset | <style> <ansi code>]".to_string() })),
+        }
+        theme.save(&*THEME_FILE)?;
+        Ok(())
+    }));
+    terminal.register_command("config", CommandHelp::new("General", "config [<key> [value]]", "Show or change the settings of the config file")
+            .example("config date_format %d.%m.%Y"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.splitn(3, ' ');
+        split.next();
+        let config = response.config_mut().ok_or(CliError::ParseError { msg: "No config support".to_string() })?;
+        match (split.next(), split.next().map(|value| value.trim())) {
+            (None, _) => {
+                let config = config.clone();
+                for key in Config::KEYS {
+                    response.println(&format!("{} = {}", key, config.get(key).unwrap_or_default()));
+                }
+            },
+            (Some(key), None) => {
+                let value = config.get(key).ok_or_else(|| Error::ConfigError { msg: format!("Unknown key: {}", key) })?;
+                response.println(&value);
+            },
+            (Some(key), Some(value)) => {
+                config.set(key, value)?;
+                if key == "autosave" {
+                    state.autosave = if config.autosave { Autosave::OnCommand } else { Autosave::ManualOnly };
+                }
+                if key.starts_with("s3_") {
+                    crate::storage::set_s3_config(config.s3_config());
+                }
+                config.save(&*CONFIG_FILE)?;
+            },
+        }
+        Ok(())
+    }));
+    terminal.register_command("hook", CommandHelp::new("General", "hook [list | add <event> <shell command> | rm <event> <n>]",
+            "Manage shell commands which run on task-done, clock-in, clock-out and save")
+            .example("hook add clock-in notify-send \"Working on $SORS_TASK_TITLE\""), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.splitn(4, ' ');
+        split.next();
+        let mut hooks = state.hooks.clone();
+        match (split.next(), split.next(), split.next()) {
+            (None, _, _) | (Some("list"), None, _) => {
+                for event in HookEvent::all() {
+                    for (command, i) in hooks.for_event(*event).iter().zip(1..) {
+                        response.println(&format!("{} {}: {}", event.name(), i, command));
+                    }
+                }
+                return Ok(());
+            },
+            (Some("add"), Some(event), Some(command)) if !command.trim().is_empty() => {
+                hooks.add(event.parse()?, command.trim());
+            },
+            (Some("rm"), Some(event), Some(index)) => {
+                let index: usize = index.trim().parse()?;
+                hooks.remove(event.parse()?, index.checked_sub(1).ok_or(Error::ChildOutOfIndex {})?)
+                    .ok_or(Error::ChildOutOfIndex {})?;
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        // The hooks only change if they could be saved.
+        hooks.save(&*HOOKS_FILE)?;
+        state.hooks = hooks;
+        Ok(())
+    }));
+    terminal.register_command("ls", CommandHelp::new("Tasks", "ls [--time] [--json]", "Show the working task and its children, with --time the tracked time of their subtrees"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        if json_output(state, cmd) {
+            print_json(&state.doc.task_json(&state.wt)?, response);
+            return Ok(());
+        }
+        let task = state.doc.get(&state.wt)?;
+        let mut breadcrumb_item_opn = Some(state.wt);
+        let mut breadcrumb_data = Vec::new();
+        while let Some(breadcrumb_item) = breadcrumb_item_opn {
+            breadcrumb_data.push(breadcrumb_item);
+            breadcrumb_item_opn = state.doc.find_parent(&breadcrumb_item);
+        }
+        breadcrumb_data.iter().rev().zip(1..).for_each(|(breadcrumb_ref, i)| {
+            if let Ok(task) = state.doc.get(breadcrumb_ref) {
+                if i > 1 {
+                    response.print(" -> ");
+                }
+                response.print(&task.title);
+            }
+        });
+        let (done, all_subtasks) = state.doc.progress_summary_recursive(&task.id, None)?;
+        response.println(&format!("  [{}/{}]", done, all_subtasks));
+        if let Some(external_ref) = &task.external_ref {
+            response.println(&format!("Link: {}", external_ref));
+        }
+        if !task.tags.is_empty() {
+            response.println(&format!("Tags: {}", join_strings(task.tags.iter().cloned(), ", ")));
+        }
+        if !task.properties.is_empty() {
+            response.println(&format!("Properties: {}", join_strings(task.properties.iter().map(|(key, value)| format!("{}={}", key, value)), ", ")));
+        }
+        response.println("");
+        response.println(&task.body);
+        response.println(&response.styled(Style::Heading, "--- Children: "));
+        let now = Local::now();
+        let thresholds = response.aging_thresholds();
+        let durations = if has_flag(cmd, "--time") { Some(state.doc.subtree_durations(&task.id)) } else { None };
+        let mut children = Vec::new();
+        for (child_id, i) in task.children.iter().zip(1..) {
+            if state.in_context(child_id) {
+                children.push((i, state.doc.get(child_id)?));
+            }
+        }
+        let hidden = task.children.len() - children.len();
+        // Styles add escape codes, so the columns are padded outside of them.
+        let index_width = children.last().map(|(i, _)| i.to_string().len()).unwrap_or(0);
+        let label_width = children.iter().map(|(_, child)| display_width(&child.progress_label())).max().unwrap_or(0);
+        let title_width = column_width(children.iter().map(|(_, child)| child.title.as_str()), TITLE_COLUMN_WIDTH);
+        for (i, child) in children.iter() {
+            let plain_label = child.progress_label();
+            let label = match child.progress {
+                Some(progress) => response.styled(Style::for_progress(progress), &plain_label),
+                None => plain_label.clone(),
+            };
+            let label = format!("{}{}", label, " ".repeat(label_width - display_width(&plain_label)));
+            let duration = durations.as_ref().and_then(|durations| durations.get(&child.id));
+            let plain_title = match duration {
+                Some(_) => truncate_to_width(&child.title, title_width),
+                None => child.title.clone(),
+            };
+            let title = match state.doc.task_age(child, now).and_then(|age| thresholds.style(age)) {
+                _ if child.is_overdue(now) => response.styled(Style::Overdue, &plain_title),
+                Some(style) => response.styled(style, &plain_title),
+                None => plain_title.clone(),
+            };
+            match duration {
+                Some(duration) => response.println(&format!("{:>index_width$}: {} {}{} ({})", i, label, title,
+                    " ".repeat(title_width - display_width(&plain_title)), format_duration(*duration), index_width = index_width)),
+                None => response.println(&format!("{:>index_width$}: {} {}", i, label, title, index_width = index_width)),
+            }
+        }
+        if hidden > 0 {
+            response.println(&format!("({} hidden by the context)", hidden));
+        }
+        Ok(())
+    }));
+    terminal.register_command("ed", CommandHelp::new("Tasks", "ed", "Edit the working task in the editor"), Box::new(|state: &mut State, _, callbacks| {
+        let task = vim_edit_task(state.doc.get(&state.wt)?, callbacks)?;
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("view", CommandHelp::new("Tasks", "view [selector]", "Show the body and notes of tasks with their Markdown formatted")
+            .example("view 2"), Box::new(|state: &mut State, cmd: &str, response| {
+        let task_ids = match positional_args(cmd).first() {
+            Some(selector) => resolve_selector(state, selector)?,
+            None => vec![state.wt],
+        };
+        for (task_id, i) in task_ids.iter().zip(0..) {
+            let task = state.doc.get(task_id)?;
+            if i > 0 {
+                response.println("");
+            }
+            response.println(&response.styled(Style::Heading, &task.title));
+            if !task.body.trim().is_empty() {
+                let body = state.doc.expand_links(&task.body, short_id);
+                let body = render::to_terminal(&body, &|style, text| response.styled(style, text));
+                response.println("");
+                response.println(&body);
+            }
+            if !task.notes.is_empty() {
+                response.println("");
+                response.println(&response.styled(Style::Heading, "--- Notes: "));
+                for note in task.notes.iter() {
+                    let text = state.doc.expand_links(&note.text, short_id);
+                    let text = render::to_terminal(&text, &|style, text| response.styled(style, text));
+                    response.println(&format!("{} {}", note.time.format(&format!("{} %H:%M", response.date_format())), text));
+                }
+            }
+        }
+        Ok(())
+    }));
+    terminal.register_command("add", CommandHelp::new("Tasks", "add [-e] [title]", "Add a child to the working task, opens the editor without title or with -e")
+            .example("add Buy milk").example("add -e Write report"), Box::new(|state: &mut State, cmd: &str, callbacks| {
+        let mut split = cmd.splitn(2, ' ');
+        split.next();
+        let args = split.next().unwrap_or("").trim();
+        let (use_editor, title) = if args == "-e" {
+            (true, "")
+        } else if let Some(title) = args.strip_prefix("-e ") {
+            (true, title.trim())
+        } else {
+            (args.is_empty(), args)
+        };
+        let mut task = Rc::new(Task::new());
+        task.set_title(title);
+        if use_editor {
+            task = vim_edit_task(task, callbacks)?;
+        }
+        state.doc.add_subtask(task, &state.wt)?;
+        Ok(())
+    }));
+    terminal.register_command("split", CommandHelp::new("Tasks", "split [checklist [items] | children <selector>]",
+            "Break the working task apart: turn checklist lines of the body into subtasks or move children up behind the task, asks for both without arguments")
+            .example("split").example("split checklist 1-3").example("split children 2,4"), Box::new(|state: &mut State, cmd: &str, response| {
+        let item_error = || CliError::ParseError { msg: "Use item numbers like 1,3-5, all or none".to_string() };
+        let checklist = checklist_items(&state.doc.get(&state.wt)?.body);
+        match positional_args(cmd).as_slice() {
+            ["checklist"] => {
+                let items: Vec<usize> = (0..checklist.len()).collect();
+                let task_ids = state.doc.checklist_to_subtasks(&state.wt, &items)?;
+                response.println(&format!("Added {} subtasks", task_ids.len()));
+            },
+            ["checklist", items] => {
+                let items = parse_item_list(items, checklist.len()).ok_or_else(item_error)?;
+                let task_ids = state.doc.checklist_to_subtasks(&state.wt, &items)?;
+                response.println(&format!("Added {} subtasks", task_ids.len()));
+            },
+            ["children", selector] => {
+                let children = resolve_selector(state, selector)?;
+                state.doc.promote_children(&state.wt, &children)?;
+                response.println(&format!("Moved {} children up", children.len()));
+            },
+            [] => {
+                let mut dialog = Dialog::new(response);
+                let mut added = 0;
+                if !checklist.is_empty() {
+                    dialog.println("Checklist:");
+                    for (item, i) in checklist.iter().zip(1..) {
+                        dialog.println(&format!("  {}: [{}] {}", i, if item.done { "x" } else { " " }, item.title));
+                    }
+                    let items = match dialog.ask_parsed("Items to turn into subtasks", Some("all"), |answer| parse_item_list(answer, checklist.len()).ok_or_else(item_error)) {
+                        Some(items) => items,
+                        None => return Ok(()),
+                    };
+                    added = state.doc.checklist_to_subtasks(&state.wt, &items)?.len();
+                }
+                let task = state.doc.get(&state.wt)?;
+                let mut moved = 0;
+                if !task.children.is_empty() && state.doc.find_parent(&state.wt).is_some() {
+                    dialog.println("Children:");
+                    for (child_id, i) in task.children.iter().zip(1..) {
+                        dialog.println(&format!("  {}: {}", i, state.doc.get(child_id)?.title));
+                    }
+                    let items = dialog.ask_parsed("Children to move up behind the task", Some("none"), |answer| parse_item_list(answer, task.children.len()).ok_or_else(item_error));
+                    if let Some(items) = items {
+                        let children: Vec<_> = items.iter().map(|i| task.children[*i]).collect();
+                        state.doc.promote_children(&state.wt, &children)?;
+                        moved = children.len();
+                    }
+                }
+                if checklist.is_empty() && task.children.is_empty() {
+                    dialog.println("Nothing to split, the task has no checklist and no children");
+                } else {
+                    dialog.println(&format!("Added {} subtasks, moved {} children up", added, moved));
+                }
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+    terminal.register_command("demote", CommandHelp::new("Tasks", "demote [selector]",
+            "Turn tasks without subtasks into checklist lines of their parent, their clocks go to the parent and the tasks to the trash")
+            .example("demote 3"), Box::new(|state: &mut State, cmd: &str, response| {
+        let task_ids = match positional_args(cmd).first() {
+            Some(selector) => resolve_selector(state, selector)?,
+            None => vec![state.wt],
+        };
+        for task_id in task_ids.iter() {
+            let parent = state.doc.find_parent(task_id);
+            state.doc.task_to_checklist(task_id)?;
+            if let Some(parent) = parent.filter(|_| *task_id == state.wt) {
+                state.change_wt(parent);
+            }
+        }
+        response.println(&format!("Turned {} tasks into checklist lines", task_ids.len()));
+        Ok(())
+    }));
+    terminal.register_command("promote", CommandHelp::new("Tasks", "promote [items]", "Turn checklist lines of the working task into subtasks, lists the checklist without items")
+            .example("promote 2").example("promote all"), Box::new(|state: &mut State, cmd: &str, response| {
+        let checklist = checklist_items(&state.doc.get(&state.wt)?.body);
+        let items = match positional_args(cmd).first() {
+            Some(items) => parse_item_list(items, checklist.len())
+                .ok_or_else(|| CliError::ParseError { msg: "Use item numbers like 1,3-5 or all".to_string() })?,
+            None => {
+                if checklist.is_empty() {
+                    response.println("The task has no checklist");
+                }
+                for (item, i) in checklist.iter().zip(1..) {
+                    response.println(&format!("{}: [{}] {}", i, if item.done { "x" } else { " " }, item.title));
+                }
+                return Ok(());
+            },
+        };
+        let task_ids = state.doc.checklist_to_subtasks(&state.wt, &items)?;
+        response.println(&format!("Added {} subtasks", task_ids.len()));
+        Ok(())
+    }));
+    terminal.register_command("capture", CommandHelp::new("Tasks", "capture <text>", "Add a task to the inbox without changing the working task, the inbox is @inbox")
+            .example("capture Call the dentist"), Box::new(|state: &mut State, cmd: &str, response| {
+        let text = cmd.split_once(' ').map(|(_, text)| text.trim()).unwrap_or_default();
+        if text.is_empty() {
+            return Err(Box::new(Error::UnsufficientInput {}));
+        }
+        let task_id = state.doc.capture(text)?;
+        response.println(&format!("Captured: {}", task_path_string(&state.doc, &task_id)));
+        Ok(())
+    }));
+    terminal.register_command("addmany", CommandHelp::new("Tasks", "addmany", "Add one child per line at the prompt, an empty line ends"), Box::new(|state: &mut State, _, callbacks| {
+        while let CliInputResult::Value(title) = callbacks.read_line("title> ") {
+            let title = title.trim();
+            if title.is_empty() {
+                break;
+            }
+            let mut task = Rc::new(Task::new());
+            task.set_title(title);
+            state.doc.add_subtask(task, &state.wt)?;
+        }
+        Ok(())
+    }));
+    terminal.register_command("save", CommandHelp::new("Files", "save [file]", "Save the document"), Box::new(|state: &mut State, cmd: &str, callbacks| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let filename = if let Some(filename) = split.next() {
+            filename.to_string()
+        } else {
+            state.path.clone()
+        };
+        save_doc(state, &filename, false, callbacks)?;
+        Ok(())
+    }));
+    terminal.register_command("export", CommandHelp::new("Files", "export <file> [--anonymize]",
+            "Write a copy of the document, with --anonymize texts are replaced by placeholders to share it in bug reports")
+            .example("export bug-report.json --anonymize"), Box::new(|state: &mut State, cmd: &str, response| {
+        let filename = positional_args(cmd).first().map(|filename| filename.to_string()).ok_or(Error::UnsufficientInput {})?;
+        let mut doc = if has_flag(cmd, "--anonymize") {
+            state.doc.anonymized()
+        } else {
+            state.doc.clone()
+        };
+        doc.read_only = false;
+        doc.save(&filename)?;
+        response.println(&format!("Exported {} tasks and {} clocks to {}", doc.map.len(), doc.clocks.len(), filename));
+        Ok(())
+    }));
+    terminal.register_command("load", CommandHelp::new("Files", "load [file] [--yes] [--dry-run]", "Load a document and replace the current one"), Box::new(|state: &mut State, cmd: &str, response| {
+        let filename = if let Some(filename) = positional_args(cmd).first() {
+            filename.to_string()
+        } else {
+            state.path.clone()
+        };
+        let filename = filename.as_str();
+        if has_flag(cmd, "--dry-run") {
+            let doc = Doc::load(filename)?;
+            response.println(&format!("Would replace the document by {} with {} tasks and {} clocks{}",
+                filename, doc.map.len(), doc.clocks.len(),
+                if state.dirty { ", discarding unsaved changes" } else { "" }));
+            return Ok(());
+        }
+        if state.dirty && !confirmed(cmd, "Discard unsaved changes?", response) {
+            return Ok(());
+        }
+        let doc = if filename == state.path {
+            state.load_doc()?
+        } else {
+            let doc = Doc::load(filename)?;
+            state.mounts.clear();
+            doc
+        };
+        let new_root = doc.root;
+        state.doc = doc;
+        state.wt = new_root;
+        state.dir_stack.clear();
+        state.previous_wt = None;
+        if filename == state.path {
+            state.mark_saved();
+            state.refresh_disk_state();
+        }
+        Ok(())
+    }));
+    terminal.register_command("cd", CommandHelp::new("Navigation", "cd [path | -]", "Change the working task")
+            .example("cd project/api").example("cd ..").example("cd @inbox").example("cd -"), Box::new(|state: &mut State, cmd: &str, _| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let task_id = match split.next() {
+            Some("-") => state.previous_wt
+                .ok_or(CliError::ParseError { msg: "No previous task".to_string() })?,
+            Some(path) => state.uuid_for_path(path)
+                .ok_or(CliError::ParseError { msg: "Couldn't resolve path".to_string() })?,
+            None => state.root(),
+        };
+        state.change_wt(task_id);
+        Ok(())
+    }));
+    terminal.register_command("pushd", CommandHelp::new("Navigation", "pushd [path]", "Remember the working task and change to another one"), Box::new(|state: &mut State, cmd: &str, _| {
+        let task_id = match positional_args(cmd).first() {
+            Some(path) => state.uuid_for_path(path)
+                .ok_or(CliError::ParseError { msg: "Couldn't resolve path".to_string() })?,
+            None => state.previous_wt
+                .ok_or(CliError::ParseError { msg: "No previous task".to_string() })?,
+        };
+        state.pushd(task_id);
+        Ok(())
+    }));
+    terminal.register_command("popd", CommandHelp::new("Navigation", "popd", "Change back to the last remembered task"), Box::new(|state: &mut State, _, _| {
+        if !state.popd() {
+            return Err(Box::new(CliError::OtherError { msg: "Directory stack is empty".to_string() }));
+        }
+        Ok(())
+    }));
+    #[cfg(feature = "tui")]
+    terminal.register_command("tui", CommandHelp::new("Navigation", "tui", "Browse the tree in a full screen view, q returns to the prompt"), Box::new(|state: &mut State, _, _| {
+        crate::tui::run(state)?;
+        Ok(())
+    }));
+    terminal.register_command("focus", CommandHelp::new("Navigation", "focus [path]", "Let the working or given task act as root for paths, listings and reports")
+            .example("focus project"), Box::new(|state: &mut State, cmd: &str, _| {
+        let task_id = match positional_args(cmd).first() {
+            Some(path) => state.uuid_for_path(path)
+                .ok_or(CliError::ParseError { msg: "Couldn't resolve path".to_string() })?,
+            None => state.wt,
+        };
+        state.focus(Some(task_id).filter(|task_id| *task_id != state.doc.root));
+        Ok(())
+    }));
+    terminal.register_command("unfocus", CommandHelp::new("Navigation", "unfocus", "Show the whole document again"), Box::new(|state: &mut State, _, _| {
+        state.focus(None);
+        Ok(())
+    }));
+    terminal.register_command("dirs", CommandHelp::new("Navigation", "dirs", "List the remembered tasks"), Box::new(|state: &mut State, _, response| {
+        for task_id in std::iter::once(&state.wt).chain(state.dir_stack.iter().rev()) {
+            let path = task_path_string(&state.doc, task_id);
+            response.println(if path.is_empty() { "/" } else { &path });
+        }
+        Ok(())
+    }));
+    terminal.register_command("bookmark", CommandHelp::new("Navigation", "bookmark [list | set <name> [path] | rm <name>]", "Manage bookmarks which are used as @name in paths")
+            .example("bookmark set inbox /1"), Box::new(|state: &mut State, cmd: &str, response| {
+        let args = positional_args(cmd);
+        match args.as_slice() {
+            ["set", name] | ["set", name, _] => {
+                let task_id = match args.get(2) {
+                    Some(path) => state.uuid_for_path(path)
+                        .ok_or(CliError::ParseError { msg: "Couldn't resolve path".to_string() })?,
+                    None => state.wt,
+                };
+                state.doc.bookmarks.insert(name.to_string(), task_id);
+            },
+            ["rm", name] => {
+                state.doc.bookmarks.remove(*name);
+            },
+            [] | ["list"] => {
+                for (name, task_id) in state.doc.bookmarks.iter() {
+                    response.println(&format!("@{}: {}", name, task_path_string(&state.doc, task_id)));
+                }
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+    terminal.register_command("mark", CommandHelp::new("Navigation", "mark <letter> [path]", "Mark the working or given task for this session, used as 'letter in paths")
+            .example("mark a").example("mv 3 'a"), Box::new(|state: &mut State, cmd: &str, _| {
+        let args = positional_args(cmd);
+        let mark = match args.first().map(|mark| (mark.chars().next(), mark.chars().count())) {
+            Some((Some(mark), 1)) if mark.is_ascii_alphanumeric() => mark,
+            Some(_) => return Err(Box::new(CliError::ParseError { msg: "Marks are a single letter or digit".to_string() })),
+            None => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let task_id = match args.get(1) {
+            Some(path) => state.uuid_for_path(path)
+                .ok_or(CliError::ParseError { msg: "Couldn't resolve path".to_string() })?,
+            None => state.wt,
+        };
+        state.marks.insert(mark, task_id);
+        Ok(())
+    }));
+    terminal.register_command("marks", CommandHelp::new("Navigation", "marks", "List the marked tasks"), Box::new(|state: &mut State, _, response| {
+        let doc = &state.doc;
+        state.marks.retain(|_, task_id| doc.get(task_id).is_ok());
+        for (mark, task_id) in state.marks.iter() {
+            response.println(&format!("'{}: {}", mark, task_path_string(&state.doc, task_id)));
+        }
+        Ok(())
+    }));
+    terminal.register_command("todo", CommandHelp::new("Tasks", "todo [selector]", "Mark tasks as todo").example("todo 1-3"), progress_command(Progress::Todo));
+    terminal.register_command("work", CommandHelp::new("Tasks", "work [selector]", "Mark tasks as in progress"), progress_command(Progress::Work));
+    terminal.register_command("done", CommandHelp::new("Tasks", "done [selector]", "Mark tasks as done").example("done 2,4"), progress_command(Progress::Done));
+    terminal.register_command("waiting", CommandHelp::new("Tasks", "waiting [selector]", "Mark tasks as waiting"), progress_command(Progress::Waiting));
+    terminal.register_command("blocked", CommandHelp::new("Tasks", "blocked [selector]", "Mark tasks as blocked"), progress_command(Progress::Blocked));
+    terminal.register_command("cancel", CommandHelp::new("Tasks", "cancel [selector]", "Mark tasks as cancelled"), progress_command(Progress::Cancelled));
+    terminal.register_command("id", CommandHelp::new("Tasks", "id", "Print the id of the working task"), Box::new(|state: &mut State, _, response| {
+        let task = state.doc.get(&state.wt)?;
+        response.println(&format!("Task ID: {}", task.id));
+        response.println(&format!("Link: [[{}]]", short_id(&task.id)));
+        Ok(())
+    }));
+    terminal.register_command("open", CommandHelp::new("Tasks", "open [n] [--list]",
+            "Open the n-th link of the working task in the browser, the first by default, links are URLs in title, body and notes and the attachments")
+            .example("open 2").example("open --list"), Box::new(|state: &mut State, cmd: &str, response| {
+        let links = state.doc.get(&state.wt)?.links();
+        if has_flag(cmd, "--list") {
+            if links.is_empty() {
+                response.println("The task has no links");
+            }
+            for (link, i) in links.iter().zip(1..) {
+                response.println(&format!("{}: {}", i, link));
+            }
+            return Ok(());
+        }
+        let index = match positional_args(cmd).first() {
+            Some(index) => index.parse::<usize>().map_err(|_| CliError::ParseError { msg: format!("Not a link number: {}", index) })?,
+            None => 1,
+        };
+        let link = index.checked_sub(1).and_then(|i| links.get(i))
+            .ok_or(Error::LinkNotFound { index, count: links.len() })?;
+        response.open_url(link)?;
+        response.println(&format!("Opened {}", link));
+        Ok(())
+    }));
+    terminal.register_command("backlinks", CommandHelp::new("Tasks", "backlinks [--json]", "List the tasks which link to the working task with [[id]] or [[title]] in their body or notes"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let backlinks = state.doc.backlinks(&state.wt);
+        if json_output(state, cmd) {
+            let backlinks: Vec<_> = backlinks.iter()
+                .map(|task_id| serde_json::json!({ "id": task_id, "path": state.doc.path_titles(task_id) }))
+                .collect();
+            print_json(&serde_json::Value::Array(backlinks), response);
+            return Ok(());
+        }
+        if backlinks.is_empty() {
+            response.println("No task links to this task");
+        }
+        for task_id in backlinks {
+            response.println(&format!("[[{}]] {}", short_id(&task_id), task_path_string(&state.doc, &task_id)));
+        }
+        Ok(())
+    }));
+    terminal.register_command("parent", CommandHelp::new("Tasks", "parent", "Print the id of the parent task"), Box::new(|state: &mut State, _, response| {
+        let task = state.doc.get(&state.wt)?;
+        if let Some(parent)  = state.doc.find_parent(&task.id) {
+            response.println(&format!("Parent Task ID: {}", parent));
+        }
+        Ok(())
+    }));
+    terminal.register_command("rm", CommandHelp::new("Tasks", "rm <selector> [--yes] [--dry-run]", "Move tasks to the trash")
+            .example("rm 2").example("rm *done* --dry-run"), Box::new(|state: &mut State, cmd: &str, response| {
+        if let Some(selector) = positional_args(cmd).first() {
+            let task_ids: Vec<_> = resolve_selector(state, selector)?.into_iter()
+                .filter(|task_id| state.doc.find_parent(task_id).is_some())
+                .collect();
+            if has_flag(cmd, "--dry-run") {
+                for task_id in task_ids.iter() {
+                    response.println(&format!("Would move to trash: {}", task_path_string(&state.doc, task_id)));
+                }
+                return Ok(());
+            }
+            if task_ids.is_empty() || !confirmed(cmd, &format!("Move {} tasks to the trash?", task_ids.len()), response) {
+                return Ok(());
+            }
+            for task_id in task_ids {
+                state.doc.move_to_trash(&task_id)?;
+            }
+        }
+        Ok(())
+    }));
+    terminal.register_command("archive", CommandHelp::new("Tasks", "archive <selector> [--yes] [--dry-run]", "Move tasks to the archive at the end of the root")
+            .example("archive *done*"), Box::new(|state: &mut State, cmd: &str, response| {
+        let selector = positional_args(cmd).first().map(|selector| selector.to_string()).ok_or(Error::UnsufficientInput {})?;
+        let task_ids: Vec<_> = resolve_selector(state, &selector)?.into_iter()
+            .filter(|task_id| state.doc.find_parent(task_id).is_some() && !state.doc.is_archived(task_id))
+            .collect();
+        if has_flag(cmd, "--dry-run") {
+            for task_id in task_ids.iter() {
+                response.println(&format!("Would archive: {}", task_path_string(&state.doc, task_id)));
+            }
+            return Ok(());
+        }
+        if task_ids.is_empty() || !confirmed(cmd, &format!("Archive {} tasks?", task_ids.len()), response) {
+            return Ok(());
+        }
+        for task_id in task_ids {
+            state.doc.archive(&task_id)?;
+        }
+        Ok(())
+    }));
+    terminal.register_command("trash", CommandHelp::new("Tasks", "trash [list | empty [--older-than <duration>] [--dry-run]]", "List or empty the trash")
+            .example("trash empty --older-than 30d"), Box::new(|state: &mut State, cmd: &str, response| {
+        let args: Vec<&str> = cmd.split(' ').skip(1).filter(|arg| !arg.is_empty()).collect();
+        match args.as_slice() {
+            [] | ["list"] => {
+                for (entry, i) in state.doc.trash_entries.iter().zip(1..) {
+                    let title = state.doc.get(&entry.task_id).map(|task| task.title.clone()).unwrap_or_default();
+                    let parent = task_path_string(&state.doc, &entry.parent_id);
+                    response.println(&format!("{}: {} {} (from {})",
+                        i, entry.deleted.format(&format!("{} %H:%M", response.date_format())), title, if parent.is_empty() { "/" } else { &parent }));
+                }
+            },
+            ["empty", rest @ ..] => {
+                let older_than = match rest.iter().position(|arg| *arg == "--older-than") {
+                    Some(i) => Some(parse_duration(rest.get(i + 1).ok_or(Error::UnsufficientInput {})?)?),
+                    None => None,
+                };
+                let expired = state.doc.expired_trash_entries(older_than);
+                if has_flag(cmd, "--dry-run") {
+                    for entry in expired.iter() {
+                        let title = state.doc.get(&entry.task_id).map(|task| task.title.clone()).unwrap_or_default();
+                        response.println(&format!("Would delete: {}", title));
+                    }
+                    return Ok(());
+                }
+                if expired.is_empty() || !confirmed(cmd, &format!("Delete {} tasks permanently?", expired.len()), response) {
+                    return Ok(());
+                }
+                let count = state.doc.empty_trash(older_than)?;
+                response.println(&format!("Deleted {} tasks", count));
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+    terminal.register_command("restore", CommandHelp::new("Tasks", "restore <n>", "Restore the n-th entry of the trash"), Box::new(|state: &mut State, cmd: &str, response| {
+        let index: usize = match positional_args(cmd).first() {
+            Some(index) => index.parse()?,
+            None => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let task_id = state.doc.restore(index.checked_sub(1).ok_or(Error::ChildOutOfIndex {})?)?;
+        response.println(&format!("Restored to {}", task_path_string(&state.doc, &task_id)));
+        Ok(())
+    }));
+    terminal.register_command("mv", CommandHelp::new("Tasks", "mv <selector> <path> [position]", "Move tasks to another parent, to the end or to the given position")
+            .example("mv 3 ../inbox").example("mv 3 ../inbox 1"), Box::new(|state: &mut State, cmd: &str, _response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let dest_ids = {
+            let selector = split.next().ok_or(CliError::ParseError{ msg: "First path contains errors".to_string() })?;
+            resolve_selector(state, selector)?
+        };
+        let to_id = {
+            let path = split.next().ok_or(CliError::ParseError{ msg: "First path contains errors".to_string() })?;
+            state.uuid_for_path(path).ok_or_else(|| Box::new(CliError::ParseError{ msg: "First path contains errors".to_string() }))?
+        };
+        let position = match split.next() {
+            Some(position) => Some(position.parse::<usize>()?.checked_sub(1).ok_or(Error::ChildOutOfIndex {})?),
+            None => None,
+        };
+        for (dest_id, i) in dest_ids.into_iter().zip(0..) {
+            state.doc.move_task(&dest_id, &to_id, position.map(|position| position + i))?;
+        }
+        Ok(())
+    }));
+    terminal.register_command("outline", CommandHelp::new("Tasks", "outline [depth] [--time] [--json]", "Print the tree below the working task, with --time the tracked time of each subtree")
+            .example("outline 2"), Box::new(|state: &mut State, cmd: &str, response| {
+        let max_depth = if let Some(depth_str) = positional_args(cmd).first() {
+            if let Ok(max_depth) = depth_str.parse() {
+                max_depth
+            } else {
+                1000
+            }
+        } else {
+            1000
+        };
+        if json_output(state, cmd) {
+            print_json(&state.doc.outline_json(&state.wt, max_depth)?, response);
+            return Ok(());
+        }
+        let durations = if has_flag(cmd, "--time") { Some(state.doc.subtree_durations(&state.wt)) } else { None };
+        state.doc.get(&state.wt)?;
+        let tasks = state.doc.iter_subtree_filtered(&state.wt, |task| task.id == state.wt || state.in_context(&task.id))
+            .max_depth(max_depth);
+        rec_print(tasks, durations.as_ref(), response);
+        Ok(())
+    }));
+    terminal.register_command("html", CommandHelp::new("Files", "html [directory] [--copy-attachments]", "Export the working task as HTML pages into the directory, html by default")
+            .example("html /tmp/export"), Box::new(|state: &mut State, cmd: &str, response| {
+        let copy_attachments = cmd.split(' ').any(|arg| arg == "--copy-attachments");
+        let directory = positional_args(cmd).first().copied().unwrap_or("html");
+        let export = dump_html(&state.doc, Path::new(directory), &state.wt, copy_attachments)?;
+        for page in export.pages.iter() {
+            response.println(&page.to_string_lossy());
+        }
+        for (attachment, err) in export.failed_attachments.iter() {
+            response.println(&format!("Couldn't copy {}: {}", attachment, err));
+        }
+        Ok(())
+    }));
+    terminal.register_command("reorder", CommandHelp::new("Tasks", "reorder <from> <to>", "Move a child to another position").example("reorder 4 1"), Box::new(|state: &mut State, cmd: &str, _| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let idx_string: &str = split.next().ok_or(Error::UnsufficientInput {})?;
+        let idx_from: usize = idx_string.parse()?;
+        let idx_string: &str = split.next().ok_or(Error::UnsufficientInput {})?;
+        let idx_to: usize = idx_string.parse()?;
+        let from_id = idx_from.checked_sub(1)
+            .and_then(|index| state.doc.task_child(&state.wt, index))
+            .ok_or(Error::ChildOutOfIndex {})?;
+        state.doc.move_task(&from_id, &state.wt, Some(idx_to.checked_sub(1).ok_or(Error::ChildOutOfIndex {})?))?;
+        Ok(())
+    }));
+    terminal.register_command("sort", CommandHelp::new("Tasks", "sort <title|due|prio|progress>", "Sort the children of the working task, prio is the priority property")
+            .example("sort due").example("sort prio"), Box::new(|state: &mut State, cmd: &str, _| {
+        let key: SortKey = positional_args(cmd).first().ok_or(Error::UnsufficientInput {})?.parse()?;
+        state.doc.sort_children(&state.wt, key)?;
+        Ok(())
+    }));
+    terminal.register_command("habit", CommandHelp::new("Tasks", "habit <daily|weekly|<n>/day|<n>/week|clear>", "Make the working task a habit which is open again once it's done")
+            .example("habit daily").example("habit 3/week"), Box::new(|state: &mut State, cmd: &str, _| {
+        let habit = match *positional_args(cmd).first().ok_or(Error::UnsufficientInput {})? {
+            "clear" => None,
+            habit => Some(habit.parse::<Habit>()?),
+        };
+        let mut task = state.doc.get(&state.wt)?;
+        task.set_habit(habit);
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("cli", CommandHelp::new("Clocks", "cli [path] [--channel <name>]", "Clock in on the working or given task, in a channel the clocks of other channels keep running")
+            .example("cli --channel oncall"), Box::new(|state: &mut State, cmd: &str, _| {
+        let (channel, cmd) = take_channel(cmd);
+        let task_id = match positional_args(&cmd).first() {
+            Some(path) => state.uuid_for_path(path)
+                .ok_or_else(|| CliError::ParseError { msg: format!("Couldn't resolve path: {}", path) })?,
+            None => state.wt,
+        };
+        state.doc.clock_new_in(doc_channel(&channel))?;
+        state.doc.modify_running_clock(doc_channel(&channel), |clock| { clock.set_task_id(task_id); })?;
+        Ok(())
+    }));
+    terminal.register_command("clr", CommandHelp::new("Clocks", "clr [--channel <name>]", "Clock in on the last clocked task"), Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, _) = take_channel(cmd);
+        if let Some(task_id) = state.doc.last_clocked_task() {
+            state.doc.clock_new_in(doc_channel(&channel))?;
+            state.doc.modify_running_clock(doc_channel(&channel), |clock| { clock.set_task_id(task_id); })?;
+            response.println(&format!("Clocked in: {}", task_path_string(&state.doc, &task_id)));
+        } else {
+            response.println("No clock with a task found");
+        }
+        Ok(())
+    }));
+    terminal.register_command("cln", CommandHelp::new("Clocks", "cln [--channel <name>]", "Clock in without a task"), Box::new(|state: &mut State, cmd: &str, _| {
+        let (channel, _) = take_channel(cmd);
+        state.doc.clock_new_in(doc_channel(&channel))?;
+        Ok(())
+    }));
+    terminal.register_command("cla", CommandHelp::new("Clocks", "cla [--channel <name>]", "Assign the running clock to the working task"), Box::new(|state: &mut State, cmd: &str, _| {
+        let (channel, _) = take_channel(cmd);
+        let task_id = state.wt;
+        state.doc.modify_running_clock(doc_channel(&channel), |clock| { clock.set_task_id(task_id); })?;
+        Ok(())
+    }));
+    terminal.register_command("clo", CommandHelp::new("Clocks", "clo [--channel <name>]", "Clock out of the default or given channel, with the clock_out_comment setting ask for a missing comment"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, _) = take_channel(cmd);
+        let channel = doc_channel(&channel);
+        let without_comment = state.doc.running_clock_in(channel)
+            .is_some_and(|clock| clock.comment.as_deref().unwrap_or_default().trim().is_empty());
+        if without_comment && response.clock_out_comment() {
+            if let CliInputResult::Value(comment) = response.read_line("Clock comment> ") {
+                if !comment.trim().is_empty() {
+                    state.doc.modify_running_clock(channel, |clock| { clock.set_comment(comment.trim().to_string()); })?;
+                }
+            }
+        }
+        state.doc.clock_out_of(channel)?;
+        Ok(())
+    }));
+    terminal.register_command("clc", CommandHelp::new("Clocks", "clc [--channel <name>]", "Set the comment of the running clock"), Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, _) = take_channel(cmd);
+        let comment = match response.read_line("Clock comment> ") {
+            CliInputResult::Value(comment) => comment,
+            CliInputResult::Termination => return Ok(()),
+        };
+        state.doc.modify_running_clock(doc_channel(&channel), |clock| { clock.set_comment(comment.trim().to_string()); })?;
+        Ok(())
+    }));
+    terminal.register_command("break", CommandHelp::new("Clocks", "break [end]", "Clock out and start a break, the next cli, cln or break end ends it"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        match positional_args(cmd).as_slice() {
+            [] => state.doc.start_break(chrono::Utc::now())?,
+            ["end"] => {
+                if !state.doc.end_break(chrono::Utc::now()) {
+                    response.println("No break running");
+                }
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+
+    terminal.register_command("workhours", CommandHelp::new("Reports", "workhours [clear | <weekday> <duration> | <7 durations>]", "Show or set the daily working time targets")
+            .example("workhours mon 8h"), Box::new(|state: &mut State, cmd: &str, response| {
+        let args = positional_args(cmd);
+        match args.as_slice() {
+            [] => {
+                if let Some(work_hours) = &state.doc.work_hours {
+                    let mut weekday = chrono::Weekday::Mon;
+                    for _ in 0..7 {
+                        response.println(&format!("{:?}: {}", weekday, short_duration(work_hours.target(weekday))));
+                        weekday = weekday.succ();
+                    }
+                } else {
+                    response.println("No work hours configured");
+                }
+            },
+            ["clear"] => state.doc.work_hours = None,
+            [weekday, target] => {
+                let weekday: chrono::Weekday = weekday.parse()
+                    .map_err(|_| CliError::ParseError { msg: format!("Couldn't parse weekday: {}", weekday) })?;
+                let target = parse_duration(target)?;
+                state.doc.work_hours.get_or_insert_with(WorkHours::default).set_target(weekday, target);
+            },
+            targets if targets.len() == 7 => {
+                let mut work_hours = WorkHours::default();
+                let mut weekday = chrono::Weekday::Mon;
+                for target in targets.iter() {
+                    work_hours.set_target(weekday, parse_duration(target)?);
+                    weekday = weekday.succ();
+                }
+                state.doc.work_hours = Some(work_hours);
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+    terminal.register_command("flexitime", CommandHelp::new("Reports", "flexitime <from>", "Show the overtime balance since the given day"), Box::new(|state: &mut State, cmd: &str, response| {
+        let start = match positional_args(cmd).first() {
+            Some(date) => parse_date(date, &DateContext::new(&state.doc, response))?,
+            None => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let days = state.doc.work_days(start, state.doc.timezone.today());
+        let worked = days.iter().fold(chrono::Duration::zero(), |acc, day| acc + day.worked);
+        let target = days.iter().fold(chrono::Duration::zero(), |acc, day| acc + day.target);
+        let balance = days.last().map(|day| day.balance).unwrap_or_else(chrono::Duration::zero);
+        response.println(&format!("Worked: {}", short_duration(worked)));
+        response.println(&format!("Target: {}", short_duration(target)));
+        response.println(&format!("Balance since {}: {}", start.format(response.date_format()), signed_duration(balance)));
+        Ok(())
+    }));
+    terminal.register_command("timesheet", CommandHelp::new("Reports", "timesheet [<week> | <from> <to>] [--csv] | timesheet map [<path> <project>] | timesheet unmap <path>",
+            "Print a timesheet per project and day")
+            .example("timesheet 2019-W22 --csv").example("timesheet last-mon"), Box::new(|state: &mut State, cmd: &str, response| {
+        let args = positional_args(cmd);
+        let resolve = |path: &str| state.uuid_for_path(path)
+            .and_then(|task_id| state.doc.get(&task_id).ok())
+            .ok_or_else(|| CliError::ParseError { msg: format!("Couldn't resolve path: {}", path) });
+        let (start, end) = match args.as_slice() {
+            ["map", path, name @ ..] if !name.is_empty() => {
+                let task = resolve(path)?;
+                state.doc.timesheet_projects.insert(task.title.clone(), name.join(" "));
+                return Ok(());
+            },
+            ["unmap", path] => {
+                let task = resolve(path)?;
+                state.doc.timesheet_projects.remove(&task.title);
+                return Ok(());
+            },
+            ["map"] => {
+                for (title, name) in state.doc.timesheet_projects.iter() {
+                    response.println(&format!("{} -> {}", title, name));
+                }
+                return Ok(());
+            },
+            [week] => parse_week_range(week, &DateContext::new(&state.doc, response))?,
+            [start, end] => {
+                let context = DateContext::new(&state.doc, response);
+                (parse_date(start, &context)?, parse_date(end, &context)?)
+            },
+            [] => DateContext::new(&state.doc, response).this_week(),
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let timesheet = state.doc.timesheet(start, end, state.focus);
+        if cmd.split(' ').any(|arg| arg == "--csv") {
+            response.print(&timesheet.to_csv());
+        } else {
+            response.print(&timesheet.to_table());
+        }
+        Ok(())
+    }));
+    terminal.register_command("earnings", CommandHelp::new("Reports", "earnings [<week> | <from> <to>] [--json]", "Print the tracked time multiplied by the rate property per project")
+            .example("earnings 2019-06-01 2019-06-30").example("earnings last-mon"), Box::new(|state: &mut State, cmd: &str, response| {
+        let context = DateContext::new(&state.doc, response);
+        let (start, end) = match positional_args(cmd).as_slice() {
+            [week] => parse_week_range(week, &context)?,
+            [start, end] => (parse_date(start, &context)?, parse_date(end, &context)?),
+            [] => context.this_week(),
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let earnings = state.doc.earnings(start, end, state.focus)?;
+        if json_output(state, cmd) {
+            print_json(&serde_json::json!({
+                "projects": earnings.rows.iter().map(|row| serde_json::json!({
+                    "project": row.project,
+                    "currency": row.currency,
+                    "tracked_seconds": row.duration.num_seconds(),
+                    "cents": row.cents,
+                })).collect::<Vec<_>>(),
+                "totals": earnings.totals().into_iter().map(|(currency, cents)| serde_json::json!({ "currency": currency, "cents": cents })).collect::<Vec<_>>(),
+                "unbilled_seconds": earnings.unbilled.num_seconds(),
+            }), response);
+            return Ok(());
+        }
+        response.print(&earnings.to_table());
+        Ok(())
+    }));
+    terminal.register_command("invoice", CommandHelp::new("Reports", "invoice <client-path> <from> <to> [file] [--html]",
+            "Print or write an invoice with the tracked time of the client's tasks, as HTML with --html or a .html file")
+            .example("invoice /ACME 2019-06-01 2019-06-30 acme-june.html"), Box::new(|state: &mut State, cmd: &str, response| {
+        let context = DateContext::new(&state.doc, response);
+        let (client, start, end, file) = match positional_args(cmd).as_slice() {
+            [client, start, end, file @ ..] if file.len() <= 1 =>
+                (*client, parse_date(start, &context)?, parse_date(end, &context)?, file.first().copied()),
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let client = state.uuid_for_path(client)
+            .ok_or_else(|| CliError::ParseError { msg: format!("Couldn't resolve path: {}", client) })?;
+        let invoice = state.doc.invoice(&client, start, end)?;
+        if invoice.items.is_empty() {
+            return Err(Box::new(CliError::OtherError { msg: "No tracked time with a rate in this period".to_string() }));
+        }
+        let html = has_flag(cmd, "--html")
+            || file.is_some_and(|file| file.ends_with(".html") || file.ends_with(".htm"));
+        let template = response.invoice_template();
+        let text = if html {
+            invoice.to_html(&template, context.today(), response.date_format())
+        } else {
+            invoice.to_markdown(&template, context.today(), response.date_format())
+        };
+        match file {
+            Some(file) => {
+                std::fs::write(file, text)?;
+                response.println(&format!("Invoice written to {}", file));
+            },
+            None => response.print(&text),
+        }
+        if invoice.unbilled > chrono::Duration::zero() {
+            response.println(&format!("Not invoiced without a rate: {}", short_duration(invoice.unbilled)));
+        }
+        Ok(())
+    }));
+    terminal.register_command("planner", CommandHelp::new("Reports", "planner [week] [--md]", "Print a week plan with due tasks, tasks in work and empty time slots, with --md as Markdown")
+            .example("planner next-mon --md"), Box::new(|state: &mut State, cmd: &str, response| {
+        let context = DateContext::new(&state.doc, response);
+        let (start, end) = match positional_args(cmd).first() {
+            Some(week) => parse_week_range(week, &context)?,
+            None => context.this_week(),
+        };
+        let planner = state.doc.planner(start, end, &state.root());
+        if has_flag(cmd, "--md") {
+            response.print(&planner.to_markdown(response.date_format()));
+        } else {
+            response.print(&planner.to_text(response.date_format()));
+        }
+        Ok(())
+    }));
+    terminal.register_command("note", CommandHelp::new("Tasks", "note <text>", "Add a timestamped note to the working task")
+            .example("note Called the customer"), Box::new(|state: &mut State, cmd: &str, _| {
+        let text = cmd.split_once(' ').map(|(_, text)| text.trim()).unwrap_or_default();
+        if text.is_empty() {
+            return Err(Box::new(Error::UnsufficientInput {}));
+        }
+        let mut task = state.doc.get(&state.wt)?;
+        task.add_note(text);
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("notes", CommandHelp::new("Tasks", "notes [--json]", "List the notes of the working task"), Box::new(|state: &mut State, cmd: &str, response| {
+        let task = state.doc.get(&state.wt)?;
+        if json_output(state, cmd) {
+            print_json(&serde_json::to_value(&task.notes)?, response);
+            return Ok(());
+        }
+        for note in task.notes.iter() {
+            response.println(&format!("{} {}", note.time.format(&format!("{} %H:%M", response.date_format())), note.text));
+        }
+        Ok(())
+    }));
+    terminal.register_command("attach", CommandHelp::new("Tasks", "attach <url or path>", "Attach a URL or file to the working task"), Box::new(|state: &mut State, cmd: &str, _| {
+        let attachment = cmd.split_once(' ').map(|(_, attachment)| attachment.trim()).unwrap_or_default();
+        if attachment.is_empty() {
+            return Err(Box::new(Error::UnsufficientInput {}));
+        }
+        let attachment = if external::is_url(attachment) {
+            attachment.to_string()
+        } else {
+            std::fs::canonicalize(attachment)?.to_string_lossy().to_string()
+        };
+        let mut task = state.doc.get(&state.wt)?;
+        task.add_attachment(attachment);
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("detach", CommandHelp::new("Tasks", "detach <n>", "Remove the n-th attachment"), Box::new(|state: &mut State, cmd: &str, _| {
+        let index: usize = match positional_args(cmd).first() {
+            Some(index) => index.parse()?,
+            None => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let mut task = state.doc.get(&state.wt)?;
+        task.remove_attachment(index.checked_sub(1).ok_or(Error::UnsufficientInput {})?);
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("attachments", CommandHelp::new("Tasks", "attachments", "List the attachments of the working task"), Box::new(|state: &mut State, _, response| {
+        let task = state.doc.get(&state.wt)?;
+        for (attachment, i) in task.attachments.iter().zip(1..) {
+            let missing = !external::is_url(attachment) && !Path::new(attachment).exists();
+            response.println(&format!("{}: {}{}", i, attachment, if missing { " (missing)" } else { "" }));
+        }
+        Ok(())
+    }));
+    terminal.register_command("pin", CommandHelp::new("Tasks", "pin [selector]", "Pin tasks"), pin_command(true));
+    terminal.register_command("unpin", CommandHelp::new("Tasks", "unpin [selector]", "Unpin tasks"), pin_command(false));
+    terminal.register_command("find", CommandHelp::new("Tasks", "find <query> [--json]", "List the tasks below the working task which match the query")
+            .example("find progress=todo and tag=urgent and due<2024-07-01")
+            .example("find title~review or (state=review and not pinned=true)"), Box::new(|state: &mut State, cmd: &str, response| {
+        // Only a trailing `--json` is the flag, the query may contain it in
+        // quotes.
+        let query = cmd.split_once(' ').map(|(_, query)| query.trim_end()).unwrap_or_default();
+        let (query, json_flag) = match query.strip_suffix("--json").filter(|rest| rest.is_empty() || rest.ends_with(' ')) {
+            Some(query) => (query, true),
+            None => (query, false),
+        };
+        let mut tasks = state.doc.query_subtree(&state.wt, query)?;
+        tasks.retain(|task| state.in_context(&task.id));
+        if json_flag || state.output == OutputFormat::Json {
+            let tasks: Vec<_> = tasks.iter()
+                .map(|task| serde_json::json!({ "id": task.id, "path": state.doc.path_titles(&task.id), "label": task.progress_label() }))
+                .collect();
+            print_json(&serde_json::Value::Array(tasks), response);
+            return Ok(());
+        }
+        for task in tasks.iter() {
+            response.println(format!("{} {} ({})", task.progress_label(), task_path_string(&state.doc, &task.id), task.id).trim_start());
+        }
+        Ok(())
+    }));
+    terminal.register_command("pinned", CommandHelp::new("Tasks", "pinned [--json]", "List the pinned tasks"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut pinned: Vec<(String, uuid::Uuid)> = state.doc.pinned_tasks().iter()
+            .filter(|task| state.in_focus(&task.id) && state.in_context(&task.id))
+            .map(|task| (task_path_string(&state.doc, &task.id), task.id))
+            .collect();
+        pinned.sort();
+        if json_output(state, cmd) {
+            let pinned: Vec<_> = pinned.iter()
+                .map(|(_, task_id)| serde_json::json!({ "id": task_id, "path": state.doc.path_titles(task_id) }))
+                .collect();
+            print_json(&serde_json::Value::Array(pinned), response);
+            return Ok(());
+        }
+        for (path, task_id) in pinned.iter() {
+            response.println(&format!("{} ({})", path, task_id));
+        }
+        Ok(())
+    }));
+    terminal.register_command("next", CommandHelp::new("Tasks", "next [--json]", "List the next actions below the working task, tasks in work or todo without open subtasks"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let tasks: Vec<_> = state.doc.next_actions(&state.wt).into_iter()
+            .filter(|task_id| state.in_context(task_id))
+            .collect();
+        if json_output(state, cmd) {
+            let tasks = tasks.iter()
+                .map(|task_id| state.doc.task_json(task_id))
+                .collect::<error::Result<Vec<_>>>()?;
+            print_json(&serde_json::Value::Array(tasks), response);
+            return Ok(());
+        }
+        if tasks.is_empty() {
+            response.println("No next actions");
+        }
+        for task_id in tasks.iter() {
+            let task = state.doc.get(task_id)?;
+            response.println(&format!("{} {}", task.progress_label(), task_path_string(&state.doc, task_id)));
+        }
+        Ok(())
+    }));
+    terminal.register_command("context", CommandHelp::new("Tasks", "context [<name> | clear]",
+            "Only list tasks of the context in ls, outline, find, pinned, next and agenda, without name show the contexts")
+            .example("context home").example("context clear"), Box::new(|state: &mut State, cmd: &str, response| {
+        match positional_args(cmd).as_slice() {
+            [] => {
+                match &state.context {
+                    Some(context) => response.println(&format!("Active context: {}", context)),
+                    None => response.println("No active context"),
+                }
+                for context in state.doc.contexts() {
+                    response.println(&context);
+                }
+            },
+            ["clear"] => state.context = None,
+            [name] => state.context = Some(context_name(name)),
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+    terminal.register_command("budget", CommandHelp::new("Reports", "budget [<path> <duration>|clear]", "Show or set time budgets")
+            .example("budget . 40h"), Box::new(|state: &mut State, cmd: &str, response| {
+        let args = positional_args(cmd);
+        match (args.first(), args.get(1)) {
+            (Some(path), Some(budget)) => {
+                let task_id = state.uuid_for_path(path)
+                    .ok_or_else(|| CliError::ParseError { msg: format!("Couldn't resolve path: {}", path) })?;
+                let budget = if *budget == "clear" { None } else { Some(parse_duration(budget)?) };
+                let mut task = state.doc.get(&task_id)?;
+                task.set_budget(budget);
+                state.doc.upsert(task);
+            },
+            (None, None) => {
+                for usage in state.doc.budget_usage().iter().filter(|usage| state.in_focus(&usage.task_id)) {
+                    response.println(&format!("{}: {} of {}{}",
+                        task_path_string(&state.doc, &usage.task_id),
+                        short_duration(usage.tracked), short_duration(usage.budget),
+                        if usage.exceeded() { " (exceeded)" } else { "" }));
+                }
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+    terminal.register_command("status", CommandHelp::new("Clocks", "status [--json]", "Show the running clocks"), Box::new(|state: &mut State, cmd: &str, response| {
+        if json_output(state, cmd) {
+            let clock = match state.doc.running_clock() {
+                Some(clock) => state.doc.clock_json(&clock)?,
+                None => serde_json::Value::Null,
+            };
+            let mut channels = serde_json::Map::new();
+            for (channel, clock_id) in state.doc.channel_clocks.iter() {
+                channels.insert(channel.clone(), state.doc.clock_json(&*state.doc.clock(clock_id)?)?);
+            }
+            print_json(&serde_json::json!({ "clock": clock, "channels": channels }), response);
+            return Ok(());
+        }
+        display_status(&state.doc, response);
+        Ok(())
+    }));
+    terminal.register_command("clockadd", CommandHelp::new("Clocks", "clockadd <start> <end | duration> [path] [comment]", "Add a clock for the working task afterwards")
+            .example("clockadd 09:00 10:30 . Review").example("clockadd yesterday@14:00 1h30m"), Box::new(|state: &mut State, cmd: &str, response| {
+        let args: Vec<&str> = cmd.split(' ').skip(1).filter(|arg| !arg.is_empty()).collect();
+        if args.len() < 2 {
+            return Err(Box::new(Error::UnsufficientInput {}));
+        }
+        let context = DateContext::new(&state.doc, response);
+        let start = parse_point_in_time(args[0], &context)?;
+        let end = match parse_point_in_time(args[1], &context) {
+            Ok(end) => end,
+            Err(_) => start + parse_duration(args[1])?,
+        };
+        let task_id = match args.get(2) {
+            Some(path) => state.uuid_for_path(path)
+                .ok_or_else(|| CliError::ParseError { msg: format!("Couldn't resolve path: {}", path) })?,
+            None => state.wt,
+        };
+        let comment = Some(args[args.len().min(3)..].join(" ")).filter(|comment| !comment.is_empty());
+        state.doc.add_clock_manual(start, end, Some(task_id), comment)?;
+        Ok(())
+    }));
+    terminal.register_command("clcat", CommandHelp::new("Clocks", "clcat <category> | clcat clear [--channel <name>]", "Set the category of the running clock")
+            .example("clcat meetings"), Box::new(|state: &mut State, cmd: &str, _| {
+        let (channel, cmd) = take_channel(cmd);
+        let category = match positional_args(&cmd).first() {
+            Some(&"clear") => None,
+            Some(category) => Some(category.to_string()),
+            None => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        state.doc.modify_running_clock(doc_channel(&channel), |clock| { clock.set_category(category); })?;
+        Ok(())
+    }));
+    terminal.register_command("clt", CommandHelp::new("Clocks", "clt [<tag>... | rm <tag>... | clear] [--channel <name>]", "Show or add tags of the running clock")
+            .example("clt travel on-call"), Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, cmd) = take_channel(cmd);
+        let clock = state.doc.running_clock_in(doc_channel(&channel)).ok_or_else(|| CliError::OtherError { msg: "No clock running".to_string() })?;
+        let mut tags = clock.tags.clone();
+        match positional_args(&cmd).as_slice() {
+            [] => {
+                response.println(&tags.join(", "));
+                return Ok(());
+            },
+            ["clear"] => tags.clear(),
+            ["rm", remove @ ..] => tags.retain(|tag| !remove.contains(&tag.as_str())),
+            add => {
+                for tag in add {
+                    if !tags.iter().any(|existing| existing == tag) {
+                        tags.push(tag.to_string());
+                    }
+                }
+            },
+        }
+        state.doc.modify_running_clock(doc_channel(&channel), |clock| { clock.set_tags(tags); })?;
+        Ok(())
+    }));
+
+    terminal.register_command("timezone", CommandHelp::new("Clocks", "timezone [local | utc | <offset>]", "Show or set the time zone of clock listings and reports")
+            .example("timezone +02:00"), Box::new(|state: &mut State, cmd: &str, response| {
+        match positional_args(cmd).as_slice() {
+            [] => response.println(&state.doc.timezone.to_string()),
+            [timezone] => state.doc.timezone = timezone.parse()?,
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        }
+        Ok(())
+    }));
+
+    terminal.register_command("taskclock", CommandHelp::new("Reports", "taskclock [--tag=<tag>] [--channel <name>] [--json]", "List the clocks of the working task"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, cmd) = take_channel(cmd);
+        let cmd = cmd.as_str();
+        let mut clocks = filter_clocks_by_channel(filter_clocks_by_tag(state.doc.task_clock(&state.wt), cmd), &channel);
+        clocks.sort();
+        if json_output(state, cmd) {
+            print_clocks_json(&clocks, &state.doc, response)?;
+        } else if let Some(mode) = chart_mode(cmd) {
+            display_clock_chart(&clocks, &state.doc, mode, response);
+        } else {
+            display_clocks(&clocks, &state.doc, response);
+        }
+        Ok(())
+    }));
+    terminal.register_command("dayclock", CommandHelp::new("Reports", "dayclock [date] [--tag=<tag>] [--channel <name>] [--chart[=day|task|group|tag|channel]] [--json]", "List the clocks of a day")
+            .example("dayclock 2019-05-31").example("dayclock last-fri"), Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, cmd) = take_channel(cmd);
+        let cmd = cmd.as_str();
+        let args = positional_args(cmd);
+        let date = if let Some(param) = args.first() {
+            parse_date(param, &DateContext::new(&state.doc, response))?
+        } else {
+            state.doc.timezone.today()
+        };
+        let mut clocks = filter_clocks_by_channel(filter_clocks_by_tag(state.doc.day_clock(date, state.wt), cmd), &channel);
+        clocks.sort();
+        if json_output(state, cmd) {
+            return Ok(print_clocks_json(&clocks, &state.doc, response)?);
+        }
+        if let Some(mode) = chart_mode(cmd) {
+            display_clock_chart(&clocks, &state.doc, mode, response);
+        } else {
+            display_clocks(&clocks, &state.doc, response);
+        }
+        display_work_days(&state.doc, date, date, response);
+        display_breaks(&state.doc, date, date, response);
+        Ok(())
+    }));
+    terminal.register_command("remind", CommandHelp::new("Tasks", "remind <date> [time] | remind clear", "Set or clear the due date of the working task")
+            .example("remind 2019-06-01 14:00"), Box::new(|state: &mut State, cmd: &str, response| {
+        let args = positional_args(cmd);
+        let due = match args.first() {
+            Some(&"clear") => None,
+            Some(date_str) => Some(parse_date_time(date_str, args.get(1).cloned(), &DateContext::new(&state.doc, response))?.with_timezone(&Local)),
+            None => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let mut task = state.doc.get(&state.wt)?;
+        task.set_due(due);
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("reminders", CommandHelp::new("Tasks", "reminders [--json]", "List the tasks which are due"), Box::new(|state: &mut State, cmd: &str, response| {
+        if json_output(state, cmd) {
+            let tasks = due_tasks(state).iter()
+                .map(|task| state.doc.task_json(&task.id))
+                .collect::<error::Result<Vec<_>>>()?;
+            print_json(&serde_json::Value::Array(tasks), response);
+            return Ok(());
+        }
+        display_reminders(state, response);
+        Ok(())
+    }));
+    terminal.register_command("agenda", CommandHelp::new("Reports", "agenda [days] [--json]", "List the open tasks which are due in the next days, 7 by default, and the overdue ones by day")
+            .example("agenda 14"), Box::new(|state: &mut State, cmd: &str, response| {
+        let days: i64 = match positional_args(cmd).first() {
+            Some(days) => days.parse()?,
+            None => 7,
+        };
+        let timezone = state.doc.timezone;
+        let end = timezone.to_utc((timezone.today() + chrono::Duration::days(days + 1)).and_hms(0, 0, 0))
+            .ok_or_else(|| CliError::ParseError { msg: "Invalid end of the agenda".to_string() })?;
+        let tasks: Vec<_> = state.doc.agenda(&state.wt, end.with_timezone(&Local)).into_iter()
+            .filter(|task_id| state.in_context(task_id))
+            .filter_map(|task_id| state.doc.get(&task_id).ok())
+            .collect();
+        if json_output(state, cmd) {
+            let tasks = tasks.iter()
+                .map(|task| state.doc.task_json(&task.id))
+                .collect::<error::Result<Vec<_>>>()?;
+            print_json(&serde_json::Value::Array(tasks), response);
+            return Ok(());
+        }
+        if tasks.is_empty() {
+            response.println(&format!("Nothing due in the next {} days", days));
+        }
+        let now = Local::now();
+        let mut day = None;
+        for task in tasks.iter() {
+            let due = match task.due {
+                Some(due) => timezone.localize(due.with_timezone(&chrono::Utc)),
+                None => continue,
+            };
+            if day != Some(due.naive_local().date()) {
+                day = Some(due.naive_local().date());
+                response.println(&format!("--- {}: ", due.format(response.date_format())));
+            }
+            let line = format!("{} {} {}", due.format("%H:%M"), task.progress_label(), task_path_string(&state.doc, &task.id));
+            let line = if task.is_overdue(now) { response.styled(Style::Overdue, &line) } else { line };
+            response.println(&line);
+        }
+        Ok(())
+    }));
+    terminal.register_command("review", CommandHelp::new("Tasks", "review [days]",
+            "Go through the open tasks which weren't changed for some days, 7 by default, to mark them as done, reschedule or archive them")
+            .example("review 14"), Box::new(|state: &mut State, cmd: &str, response| {
+        let days: i64 = match positional_args(cmd).first() {
+            Some(days) => days.parse()?,
+            None => 7,
+        };
+        let tasks: Vec<_> = state.doc.stale_tasks(&state.root(), Local::now() - chrono::Duration::days(days)).into_iter()
+            .filter(|task_id| state.in_context(task_id))
+            .collect();
+        if tasks.is_empty() {
+            response.println(&format!("No tasks unchanged for {} days", days));
+            return Ok(());
+        }
+        #[derive(Clone, Copy)]
+        enum Action { Done, Reschedule, Archive, Skip, Quit }
+        let choices = [
+            Choice::new('d', "done", Action::Done),
+            Choice::new('r', "reschedule", Action::Reschedule),
+            Choice::new('a', "archive", Action::Archive),
+            Choice::new('s', "skip", Action::Skip).as_default(),
+            Choice::new('q', "quit", Action::Quit),
+        ];
+        let context = DateContext::new(&state.doc, response);
+        let (mut done, mut rescheduled, mut archived) = (0, 0, 0);
+        for (i, task_id) in tasks.iter().enumerate() {
+            let task = state.doc.get(task_id)?;
+            response.println(&format!("[{}/{}]{} ({})", i + 1, tasks.len(), task_path_string(&state.doc, task_id), task.progress_label()));
+            if let Some(modified) = state.doc.last_modified(task_id) {
+                response.println(&format!("  Last changed: {}", modified.format(response.date_format())));
+            }
+            let mut dialog = Dialog::new(response);
+            match dialog.choose("", &choices) {
+                Some(Action::Done) => {
+                    state.doc.set_progress(task_id, Progress::Done)?;
+                    done += 1;
+                },
+                Some(Action::Reschedule) => {
+                    let due = dialog.ask_parsed("Due date", None, |date| {
+                        let args: Vec<&str> = date.split_whitespace().collect();
+                        args.first().map(|date_str| parse_date_time(date_str, args.get(1).cloned(), &context)).transpose()
+                    });
+                    match due {
+                        Some(Some(due)) => {
+                            let mut task = state.doc.get(task_id)?;
+                            task.set_due(Some(due.with_timezone(&Local)));
+                            state.doc.upsert(task);
+                            rescheduled += 1;
+                        },
+                        Some(None) => {},
+                        None => break,
+                    }
+                },
+                Some(Action::Archive) => {
+                    state.doc.archive(task_id)?;
+                    archived += 1;
+                },
+                Some(Action::Skip) => {},
+                Some(Action::Quit) | None => break,
+            }
+        }
+        response.println(&format!("Done: {}, rescheduled: {}, archived: {}", done, rescheduled, archived));
+        Ok(())
+    }));
+    terminal.register_command("log", CommandHelp::new("Reports", "log [n] [--json]", "Show the last activity entries").example("log 20"), Box::new(|state: &mut State, cmd: &str, response| {
+        let n = if let Some(n_str) = positional_args(cmd).first() {
+            n_str.parse()?
+        } else {
+            20
+        };
+        let activity: Vec<_> = state.doc.activity.iter()
+            .filter(|entry| state.focus.is_none() || state.doc.activity_task(entry).is_some_and(|task_id| state.in_focus(&task_id)))
+            .cloned()
+            .collect();
+        let start = activity.len().saturating_sub(n);
+        if json_output(state, cmd) {
+            let activity = activity[start..].iter()
+                .map(|entry| state.doc.activity_json(entry))
+                .collect::<error::Result<Vec<_>>>()?;
+            print_json(&serde_json::Value::Array(activity), response);
+            return Ok(());
+        }
+        display_activity(&activity[start..], &state.doc, response);
+        Ok(())
+    }));
+    terminal.register_command("import", CommandHelp::new("Files", "import <format> <file>", "Import tasks as children of the working task")
+            .example("import todoist export.csv"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let (format, filename) = match (split.next(), split.next()) {
+            (Some(format), Some(filename)) => (format, filename),
+            _ => {
+                let formats = join_strings(import::adapters().iter().map(|adapter| adapter.name().to_string()), ", ");
+                response.println(&format!("Usage: import <format> <file>, formats: {}", formats));
+                return Ok(());
+            }
+        };
+        let adapter = import::adapter(format)
+            .ok_or_else(|| CliError::ParseError { msg: format!("Unknown import format: {}", format) })?;
+        let input = std::fs::read_to_string(filename)?;
+        let tasks = adapter.import(&input)?;
+        let count = state.doc.import_tasks(&tasks, &state.wt)?;
+        response.println(&format!("Imported {} tasks", count));
+        Ok(())
+    }));
+    terminal.register_command("import-github", CommandHelp::new("Files", "import-github <owner/repository>", "Import the open issues of a GitHub repository")
+            .example("import-github neosam/sors"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let repository = split.next().ok_or(Error::UnsufficientInput {})?;
+        let token = var("GITHUB_TOKEN").ok();
+        let issues: Vec<_> = import::fetch_github_issues(repository, token.as_deref())?
+            .into_iter()
+            .filter(|issue| !state.doc.map.values().any(|task| task.external_ref.is_some() && task.external_ref == issue.external_ref))
+            .collect();
+        let count = state.doc.import_tasks(&issues, &state.wt)?;
+        response.println(&format!("Imported {} issues", count));
+        Ok(())
+    }));
+    terminal.register_command("clockexport", CommandHelp::new("Files", "clockexport <file> [<week> | <from> <to>]",
+            "Write the finished clocks of the working task and its subtasks to a JSON file, without the tasks")
+            .example("clockexport acme-june.json 2019-06-01 2019-06-30"), Box::new(|state: &mut State, cmd: &str, response| {
+        let context = DateContext::new(&state.doc, response);
+        let (file, start, end) = match positional_args(cmd).as_slice() {
+            [file] => (file.to_string(), chrono::naive::MIN_DATE, chrono::naive::MAX_DATE),
+            [file, week] => {
+                let (start, end) = parse_week_range(week, &context)?;
+                (file.to_string(), start, end)
+            },
+            [file, start, end] => (file.to_string(), parse_date(start, &context)?, parse_date(end, &context)?),
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let export = state.doc.export_clocks(&state.doc.range_clock(start, end, state.wt));
+        export.save(&file)?;
+        response.println(&format!("Exported {} clocks", export.clocks.len()));
+        Ok(())
+    }));
+    terminal.register_command("clockimport", CommandHelp::new("Files", "clockimport <file>", "Add the clocks of a clockexport file, clocks which exist already are skipped")
+            .example("clockimport acme-june.json"), Box::new(|state: &mut State, cmd: &str, response| {
+        let file = positional_args(cmd).first().cloned().ok_or(Error::UnsufficientInput {})?;
+        let report = state.doc.import_clocks(&ClockExport::load(file)?);
+        response.println(&format!("Imported {} clocks, skipped {} existing", report.imported, report.skipped));
+        if report.without_task > 0 {
+            response.println(&response.styled(Style::Warning, &format!("{} clocks have no task, their task wasn't found", report.without_task)));
+        }
+        Ok(())
+    }));
+    terminal.register_command("caldav", CommandHelp::new("Files", "caldav [<url> [user] | clear]", "Show or set the CalDAV calendar for caldav-sync, the password is read from SORS_CALDAV_PASSWORD")
+            .example("caldav https://example.com/dav/calendars/me/tasks/ me"), Box::new(|state: &mut State, cmd: &str, response| {
+        match positional_args(cmd).as_slice() {
+            [] => match &state.doc.caldav {
+                Some(sync) => response.println(&format!("{} ({} synced tasks)", sync.url, sync.items.values().filter(|item| !item.removed).count())),
+                None => response.println("No CalDAV calendar"),
+            },
+            ["clear"] => state.doc.caldav = None,
+            [url, rest @ ..] => {
+                let items = state.doc.caldav.take().filter(|sync| sync.url == *url).map(|sync| sync.items).unwrap_or_default();
+                state.doc.caldav = Some(CaldavSync { url: url.to_string(), user: rest.first().map(|user| user.to_string()), items });
+            },
+        }
+        Ok(())
+    }));
+    terminal.register_command("caldav-sync", CommandHelp::new("Files", "caldav-sync", "Upload the open tasks as todos to the CalDAV calendar and apply the changes made there"),
+            Box::new(|state: &mut State, _, response| {
+        let transport = CurlTransport::new(state.doc.caldav.as_ref().ok_or(Error::CaldavNotConfigured {})?);
+        let root = state.root();
+        // The tasks which were synced before an error are remembered, so
+        // the error doesn't roll back the command.
+        match state.doc.caldav_sync(&root, &transport) {
+            Ok(report) => response.println(&format!("Uploaded: {}, updated: {}, new: {}", report.pushed, report.pulled, report.created)),
+            Err(err) => response.println(&response.styled(Style::Error, &err.to_string())),
+        }
+        Ok(())
+    }));
+    terminal.register_command("tag", CommandHelp::new("Tasks", "tag <tag>...", "Add tags to the working task").example("tag home urgent"), Box::new(|state: &mut State, cmd: &str, _| {
+        let mut task = state.doc.get(&state.wt)?;
+        for tag in positional_args(cmd) {
+            task.add_tag(tag);
+        }
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("untag", CommandHelp::new("Tasks", "untag <tag>...", "Remove tags from the working task"), Box::new(|state: &mut State, cmd: &str, _| {
+        let mut task = state.doc.get(&state.wt)?;
+        for tag in positional_args(cmd) {
+            task.remove_tag(tag);
+        }
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("link", CommandHelp::new("Tasks", "link [<issue key or URL> | clear]", "Show or set the external reference of the working task")
+            .example("link PROJ-123"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let mut task = state.doc.get(&state.wt)?;
+        match split.next() {
+            Some("clear") => task.set_external_ref(None),
+            Some(external_ref) => task.set_external_ref(Some(external_ref.to_string())),
+            None => {
+                response.println(task.external_ref.as_deref().unwrap_or("(none)"));
+                return Ok(());
+            }
+        };
+        state.doc.upsert(task);
+        Ok(())
+    }));
+    terminal.register_command("sync-status", CommandHelp::new("Tasks", "sync-status", "Update the progress from the linked external issues"), Box::new(|state: &mut State, _, response| {
+        let providers = external::status_providers();
+        if providers.is_empty() {
+            response.println("No issue status provider available");
+            return Ok(());
+        }
+        let count = state.doc.sync_external_status(&providers)?;
+        response.println(&format!("Updated {} tasks", count));
+        Ok(())
+    }));
+    terminal.register_command("standup", CommandHelp::new("Reports", "standup [date]", "Show what was done since the given day"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let date = if let Some(param) = split.next() {
+            parse_date(param, &DateContext::new(&state.doc, response))?
+        } else {
+            state.doc.timezone.today() - chrono::Duration::days(1)
+        };
+        for line in standup_summary(&state.doc, date, state.wt) {
+            response.println(&line);
+        }
+        Ok(())
+    }));
+    terminal.register_command("stats", CommandHelp::new("Reports", "stats [days]", "Show statistics of completed tasks"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let days = if let Some(days_str) = split.next() {
+            days_str.parse()?
+        } else {
+            28
+        };
+        let stats = state.doc.throughput_stats(&state.wt, days, response.week_start())?;
+        response.println("--- Completed per week: ");
+        for (week, count) in stats.completed_per_week.iter() {
+            response.println(&format!("Week of {}: {}", week.format(response.date_format()), count));
+        }
+        response.println("");
+        let cycle_time = stats.average_cycle_time
+            .map(format_duration)
+            .unwrap_or_else(|| "(none)".to_string());
+        response.println(&format!("Average cycle time: {}", cycle_time));
+        response.println(&format!("Work in progress: {}", stats.wip));
+        response.println("");
+        response.println("--- Burndown (open tasks): ");
+        let max = stats.burndown.iter().map(|(_, open)| *open).max().unwrap_or(0);
+        for (day, open) in stats.burndown.iter() {
+            response.println(&format!("{} {:>4} {}", day.format(response.date_format()), open, bar(*open as i64, max as i64, 40)));
+        }
+        Ok(())
+    }));
+    terminal.register_command("count", CommandHelp::new("Reports", "count [--json]", "Count the subtasks of the working task by progress and show their tracked time"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let stats = state.doc.subtree_stats(&state.wt)?;
+        if json_output(state, cmd) {
+            print_json(&serde_json::json!({
+                "descendants": stats.descendants,
+                "by_progress": stats.by_progress,
+                "without_progress": stats.without_progress,
+                "depth": stats.depth,
+                "tracked_seconds": stats.tracked.num_seconds(),
+            }), response);
+            return Ok(());
+        }
+        response.println(&format!("Subtasks: {} in {} levels", stats.descendants, stats.depth));
+        for (label, count) in stats.by_progress.iter() {
+            response.println(&format!("  {}: {}", label, count));
+        }
+        if stats.without_progress > 0 {
+            response.println(&format!("  No progress: {}", stats.without_progress));
+        }
+        response.println(&format!("Tracked: {}", format_duration(stats.tracked)));
+        Ok(())
+    }));
+    terminal.register_command("aging", CommandHelp::new("Reports", "aging [--json]", "List the tasks in work or todo by the time since they were changed or clocked, the oldest first"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let items: Vec<_> = state.doc.aging(&state.root(), Local::now()).into_iter()
+            .filter(|item| state.in_context(&item.task_id))
+            .collect();
+        if json_output(state, cmd) {
+            print_json(&serde_json::Value::Array(items.iter().map(|item| serde_json::json!({
+                "id": item.task_id.to_string(),
+                "path": task_path_string(&state.doc, &item.task_id),
+                "progress": item.progress.to_string(),
+                "touched": item.touched.map(|touched| touched.to_rfc3339()),
+                "age_days": item.age.num_days(),
+            })).collect()), response);
+            return Ok(());
+        }
+        if items.is_empty() {
+            response.println("No tasks in work or todo");
+        }
+        let thresholds = response.aging_thresholds();
+        for item in items.iter() {
+            let line = format!("{:>4}d {} {}", item.age.num_days(), item.progress.to_string(), task_path_string(&state.doc, &item.task_id));
+            match thresholds.style(item.age) {
+                Some(style) => response.println(&response.styled(style, &line)),
+                None => response.println(&line),
+            }
+        }
+        Ok(())
+    }));
+    terminal.register_command("habits", CommandHelp::new("Reports", "habits [--json]", "Show the streaks and completion rates of the habits"), Box::new(|state: &mut State, cmd: &str, response| {
+        let context = DateContext::new(&state.doc, response);
+        let stats = state.doc.habits(&state.root()).iter()
+            .map(|task_id| state.doc.habit_stats(task_id, context.today(), context.week_start))
+            .collect::<error::Result<Vec<_>>>()?;
+        if json_output(state, cmd) {
+            print_json(&serde_json::Value::Array(stats.iter().map(|stats| serde_json::json!({
+                "id": stats.task_id.to_string(),
+                "path": task_path_string(&state.doc, &stats.task_id),
+                "habit": stats.habit.to_string(),
+                "current": stats.current,
+                "streak": stats.streak,
+                "rate": stats.rate,
+            })).collect()), response);
+            return Ok(());
+        }
+        if stats.is_empty() {
+            response.println("No habits");
+        }
+        for stats in stats.iter() {
+            let rate = stats.rate.map_or("-".to_string(), |rate| format!("{:.0}%", rate * 100.0));
+            response.println(&format!("{} ({}): {}/{} this {}, streak {}, {} of the last {} weeks",
+                task_path_string(&state.doc, &stats.task_id), stats.habit, stats.current, stats.habit.target,
+                stats.habit.period,
+                stats.streak, rate, RATE_WEEKS));
+        }
+        Ok(())
+    }));
+    terminal.register_command("serve-ics", CommandHelp::new("Files", "serve-ics [port] [--clocks] | serve-ics stop",
+            "Serve the due tasks as iCalendar feed for calendar apps in the background, with --clocks the clocks as well")
+            .example("serve-ics 8090 --clocks").example("serve-ics stop"), Box::new(|state: &mut State, cmd: &str, response| {
+        // A running server is stopped first, also to free its port.
+        let stopped = state.ics_server.take();
+        if let Some(server) = &stopped {
+            for line in server.take_log() {
+                response.println(&line);
+            }
+        }
+        if positional_args(cmd).first() == Some(&"stop") {
+            match stopped {
+                Some(server) => response.println(&format!("Stopped serving on port {}", server.port)),
+                None => response.println("No feed is served"),
+            }
+            return Ok(());
+        }
+        drop(stopped);
+        let port = match positional_args(cmd).first() {
+            Some(port) => port.parse()?,
+            None => ics::DEFAULT_PORT,
+        };
+        let with_clocks = has_flag(cmd, "--clocks");
+        let server = ics::IcsServer::start(port, with_clocks, state.doc.to_ics(&state.root(), with_clocks))?;
+        response.println(&format!("Serving http://127.0.0.1:{}/sors.ics, stop with serve-ics stop", server.port));
+        state.ics_server = Some(server);
+        Ok(())
+    }));
+    terminal.register_command("mount", CommandHelp::new("Files", "mount [file]", "List the open documents or open another one below a common root")
+            .example("mount ~/work.json"), Box::new(|state: &mut State, cmd: &str, response| {
+        match cmd.split_once(' ').map(|(_, path)| path.trim()).filter(|path| !path.is_empty()) {
+            Some(path) => {
+                let path = crate::config::expand_home(path);
+                if state.mounts.iter().any(|mount| mount.path == path) || (state.mounts.is_empty() && state.path == path) {
+                    return Err(Box::new(CliError::ParseError { msg: format!("Already open: {}", path) }));
+                }
+                state.mount(&path)?;
+            },
+            None if state.mounts.is_empty() => response.println(&state.path),
+            None => for mount in state.mounts.iter() {
+                response.println(&format!("{}: {}", task_path_string(&state.doc, &mount.root), mount.path));
+            },
+        }
+        Ok(())
+    }));
+    terminal.register_command("snapshot", CommandHelp::new("Files", "snapshot [name]", "Store a copy of the document to compare it later, named by the time by default")
+            .example("snapshot sprint-12"), Box::new(|state: &mut State, cmd: &str, response| {
+        let name = match positional_args(cmd).first() {
+            Some(name) => name.to_string(),
+            None => Local::now().format("%Y-%m-%d-%H%M%S").to_string(),
+        };
+        state.doc.save_snapshot(&state.path, &name)?;
+        response.println(&format!("Stored snapshot {}", name));
+        Ok(())
+    }));
+    terminal.register_command("snapshots", CommandHelp::new("Files", "snapshots", "List the snapshots of the document"), Box::new(|state: &mut State, _, response| {
+        for name in list_snapshots(&state.path)? {
+            response.println(&name);
+        }
+        Ok(())
+    }));
+    terminal.register_command("info", CommandHelp::new("Files", "info [--json]",
+            "Show the size of the document, counts, nesting, largest subtrees, broken clocks and how long loading and saving takes"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let info = state.doc.info();
+        let paths: Vec<String> = if state.mounts.is_empty() {
+            vec![state.path.clone()]
+        } else {
+            state.mounts.iter().map(|mount| mount.path.clone()).collect()
+        };
+        let mut files = Vec::new();
+        for path in paths.iter().filter(|path| crate::storage::storage(path).exists()) {
+            let start = std::time::Instant::now();
+            Doc::load(path)?;
+            let size = crate::storage::storage(path).local_path().and_then(|local_path| std::fs::metadata(local_path).ok()).map(|metadata| metadata.len());
+            files.push((path, start.elapsed(), size));
+        }
+        let start = std::time::Instant::now();
+        let encoded = state.doc.encode(Format::from_path(Path::new(&state.path)))?;
+        let encode_time = start.elapsed();
+        let title = |task_id: &uuid::Uuid| task_path_string(&state.doc, task_id);
+        if json_output(state, cmd) {
+            print_json(&serde_json::json!({
+                "files": files.iter().map(|(path, load_time, size)| serde_json::json!({
+                    "path": path,
+                    "bytes": size,
+                    "load_ms": load_time.as_millis() as u64,
+                })).collect::<Vec<_>>(),
+                "encoded_bytes": encoded.len(),
+                "encode_ms": encode_time.as_millis() as u64,
+                "tasks": info.tasks,
+                "trashed_tasks": info.trashed_tasks,
+                "unreachable_tasks": info.unreachable_tasks,
+                "clocks": info.clocks,
+                "running_clocks": info.running_clocks,
+                "clocks_without_task": info.clocks_without_task,
+                "unreferenced_clocks": info.unreferenced_clocks,
+                "activity": info.activity,
+                "notes": info.notes,
+                "attachments": info.attachments,
+                "max_depth": info.max_depth,
+                "deepest_task": info.deepest_task.to_string(),
+                "largest_subtrees": info.largest_subtrees.iter().map(|(task_id, size)| serde_json::json!({
+                    "id": task_id.to_string(),
+                    "title": state.doc.get(task_id).map(|task| task.title.clone()).unwrap_or_default(),
+                    "tasks": size,
+                })).collect::<Vec<_>>(),
+            }), response);
+            return Ok(());
+        }
+        for (path, load_time, size) in files.iter() {
+            let size = size.map(|size| format!(", {}", info::format_size(size))).unwrap_or_default();
+            response.println(&format!("File: {} (load {} ms{})", path, load_time.as_millis(), size));
+        }
+        response.println(&format!("Serialized: {} in {} ms", info::format_size(encoded.len() as u64), encode_time.as_millis()));
+        response.println(&format!("Tasks: {}, in the trash: {}, unreachable: {}", info.tasks, info.trashed_tasks, info.unreachable_tasks));
+        response.println(&format!("Clocks: {}, running: {}, without task: {}, unreferenced: {}", info.clocks, info.running_clocks, info.clocks_without_task, info.unreferenced_clocks));
+        response.println(&format!("Activity entries: {}, notes: {}, attachments: {}", info.activity, info.notes, info.attachments));
+        response.println(&format!("Deepest nesting: {} ({})", info.max_depth, title(&info.deepest_task)));
+        if !info.largest_subtrees.is_empty() {
+            response.println("--- Largest subtrees: ");
+            for (task_id, size) in info.largest_subtrees.iter() {
+                response.println(&format!("{:>6} {}", size, title(task_id)));
+            }
+        }
+        if info.unreachable_tasks > 0 || info.unreferenced_clocks > 0 {
+            response.println(&response.styled(Style::Warning, "The document has unreachable tasks or clocks of deleted tasks"));
+        }
+        Ok(())
+    }));
+    terminal.register_command("doctor", CommandHelp::new("Files", "doctor [--json]",
+            "Check the document for broken rules, orphaned tasks and clocks, its size, clocks, journal and backups and suggest maintenance"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let info = state.doc.info();
+        let violations = state.doc.check_invariants();
+        let mut suggestions = state.doc.suggestions(&info, &violations);
+        let storage = crate::storage::storage(&state.path);
+        let local_path = storage.local_path();
+        let size = local_path.and_then(|local_path| std::fs::metadata(local_path).ok()).map(|metadata| metadata.len());
+        // Number of entries and size of the journal.
+        let journal = local_path.map(crate::journal::journal_path)
+            .and_then(|journal_path| Some((std::fs::read_to_string(&journal_path).ok()?.lines().count(), std::fs::metadata(&journal_path).ok()?.len())));
+        if let Some(size) = size {
+            suggestions.extend(info::file_suggestions(Format::from_path(Path::new(&state.path)), size, journal));
+        }
+        let snapshot = last_snapshot(&state.path)?;
+        if snapshot.as_ref().is_none_or(|(_, time)| Local::now() - *time > chrono::Duration::days(info::SNAPSHOT_MAX_AGE_DAYS)) {
+            suggestions.push(info::Suggestion::Snapshot);
+        }
+        let title = |task_id: &uuid::Uuid| task_path_string(&state.doc, task_id);
+        if json_output(state, cmd) {
+            print_json(&serde_json::json!({
+                "path": state.path,
+                "bytes": size,
+                "violations": violations.iter().map(|violation| violation.to_string()).collect::<Vec<_>>(),
+                "unreachable_tasks": info.unreachable_tasks,
+                "unreferenced_clocks": info.unreferenced_clocks,
+                "clocks_without_task": info.clocks_without_task,
+                "clocks": info.clocks,
+                "running_clocks": info.running_clocks,
+                "clocked_tasks": info.clocked_tasks,
+                "first_clock": info.clock_range.map(|(first, _)| first.to_rfc3339()),
+                "last_clock": info.clock_range.map(|(_, last)| last.to_rfc3339()),
+                "journal_entries": journal.map(|(entries, _)| entries),
+                "journal_bytes": journal.map(|(_, bytes)| bytes),
+                "trashed_tasks": info.trashed_tasks,
+                "last_snapshot": snapshot.as_ref().map(|(name, time)| serde_json::json!({
+                    "name": name,
+                    "time": time.to_rfc3339(),
+                })),
+                "largest_subtrees": info.largest_subtrees.iter().map(|(task_id, size)| serde_json::json!({
+                    "id": task_id.to_string(),
+                    "title": state.doc.get(task_id).map(|task| task.title.clone()).unwrap_or_default(),
+                    "tasks": size,
+                })).collect::<Vec<_>>(),
+                "suggestions": suggestions.iter().map(|suggestion| suggestion.to_string()).collect::<Vec<_>>(),
+            }), response);
+            return Ok(());
+        }
+        let size = size.map(|size| format!(" ({})", info::format_size(size))).unwrap_or_default();
+        response.println(&format!("File: {}{}", state.path, size));
+        response.println(&format!("Orphans: {} unreachable tasks, {} clocks of deleted tasks, {} clocks without task", info.unreachable_tasks, info.unreferenced_clocks, info.clocks_without_task));
+        let clock_range = info.clock_range
+            .map(|(first, last)| format!(", from {} to {}", state.doc.timezone.date(first).format(response.date_format()), state.doc.timezone.date(last).format(response.date_format())))
+            .unwrap_or_default();
+        response.println(&format!("Clocks: {} on {} tasks, running: {}{}", info.clocks, info.clocked_tasks, info.running_clocks, clock_range));
+        match journal {
+            Some((entries, bytes)) => response.println(&format!("Journal: {} entries ({})", entries, info::format_size(bytes))),
+            None => response.println("Journal: none"),
+        }
+        response.println(&format!("Trash: {} tasks", info.trashed_tasks));
+        match snapshot {
+            Some((name, time)) => response.println(&format!("Last snapshot: {} ({})", name, time.format(response.date_format()))),
+            None => response.println("Last snapshot: none"),
+        }
+        if !violations.is_empty() {
+            response.println("--- Broken rules: ");
+            for violation in violations.iter() {
+                response.println(&response.styled(Style::Warning, &violation.to_string()));
+            }
+        }
+        if !info.largest_subtrees.is_empty() {
+            response.println("--- Largest subtrees: ");
+            for (task_id, size) in info.largest_subtrees.iter() {
+                response.println(&format!("{:>6} {}", size, title(task_id)));
+            }
+        }
+        if suggestions.is_empty() {
+            response.println("Nothing to do");
+        } else {
+            response.println("--- Suggestions: ");
+            for suggestion in suggestions.iter() {
+                response.println(&format!(" - {}", suggestion));
+            }
+        }
+        Ok(())
+    }));
+    terminal.register_command("diff", CommandHelp::new("Reports", "diff <snapshot> [--json]", "Show added, removed, retitled and moved tasks and progress changes since the snapshot")
+            .example("diff sprint-12"), Box::new(|state: &mut State, cmd: &str, response| {
+        let name = positional_args(cmd).first().map(|name| name.to_string()).ok_or(Error::UnsufficientInput {})?;
+        let snapshot = load_snapshot(&state.path, &name)?;
+        let changes = state.doc.diff(&snapshot);
+        if json_output(state, cmd) {
+            print_json(&serde_json::to_value(&changes)?, response);
+            return Ok(());
+        }
+        let path_string = |path: &[String]| match path.split_first() {
+            Some((_, path)) if !path.is_empty() => path.join(" -> "),
+            _ => "/".to_string(),
+        };
+        let label = |doc: &Doc, task_id: &uuid::Uuid| match doc.get(task_id).map(|task| task.progress_label()) {
+            Ok(label) if !label.is_empty() => label,
+            _ => "-".to_string(),
+        };
+        let (mut retitled, mut progress, mut moved, mut clocks) = (0, 0, 0, 0);
+        for change in changes.iter() {
+            match change {
+                Change::TaskAdded { path, .. } => response.println(&format!("+ {}", path_string(path))),
+                Change::TaskRemoved { path, .. } => response.println(&format!("- {}", path_string(path))),
+                Change::TaskModified { task_id, path, .. } => {
+                    if let Some(title) = change.field("title") {
+                        retitled += 1;
+                        response.println(&format!("~ {} (was {})", path_string(path), title.from.as_str().unwrap_or_default()));
+                    }
+                    let (from, to) = (label(&snapshot, task_id), label(&state.doc, task_id));
+                    if from != to {
+                        progress += 1;
+                        response.println(&format!("* {}: {} -> {}", path_string(path), from, to));
+                    }
+                    if change.field("parent").is_some() {
+                        moved += 1;
+                        response.println(&format!("> {}", path_string(path)));
+                    }
+                },
+                Change::ClockAdded { .. } | Change::ClockRemoved { .. } | Change::ClockModified { .. } => clocks += 1,
+            }
+        }
+        let count = |f: fn(&Change) -> bool| changes.iter().filter(|change| f(change)).count();
+        response.println(&format!("{} added, {} removed, {} retitled, {} progress changes, {} moved, {} clock changes",
+            count(|change| matches!(change, Change::TaskAdded { .. })),
+            count(|change| matches!(change, Change::TaskRemoved { .. })),
+            retitled, progress, moved, clocks));
+        Ok(())
+    }));
+    terminal.register_command("autosave", CommandHelp::new("Files", "autosave", "Save after every command"), Box::new(|state: &mut State, _, _| {
+        state.autosave = Autosave::OnCommand;
+        Ok(())
+    }));
+    terminal.register_command("noautosave", CommandHelp::new("Files", "noautosave", "Only save with the save command"), Box::new(|state: &mut State, _, _| {
+        state.autosave = Autosave::ManualOnly;
+        Ok(())
+    }));
+    terminal.register_command("state", CommandHelp::new("Workflow", "state <name> [selector]", "Set the workflow state of tasks")
+            .example("state review 2"), Box::new(|state: &mut State, cmd: &str, _| {
+        let mut split = cmd.split(' ');
+        split.next();
+        let name = split.next().ok_or(Error::UnsufficientInput {})?;
+        let task_ids = if let Some(selector) = split.next() {
+            resolve_selector(state, selector)?
+        } else {
+            vec![state.wt]
+        };
+        for task_id in task_ids {
+            state.doc.set_state(&task_id, name)?;
+        }
+        Ok(())
+    }));
+    terminal.register_command("states", CommandHelp::new("Workflow", "states [add <name> <progress> | rm <name> | allow <from> <to> | disallow <from> <to>]", "Show or change the workflow states")
+            .example("states add review work"), Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        match split.next() {
+            Some("add") => {
+                let name = split.next().ok_or(Error::UnsufficientInput {})?;
+                let progress: Progress = split.next().ok_or(Error::UnsufficientInput {})?.parse()?;
+                state.doc.workflow.get_or_insert_with(Workflow::default).add_state(name, progress);
+            },
+            Some("rm") => {
+                let name = split.next().ok_or(Error::UnsufficientInput {})?;
+                if let Some(workflow) = &mut state.doc.workflow {
+                    workflow.remove_state(name);
+                }
+            },
+            Some(sub) if sub == "allow" || sub == "disallow" => {
+                let allow = sub == "allow";
+                let from = split.next().ok_or(Error::UnsufficientInput {})?;
+                let to = split.next().ok_or(Error::UnsufficientInput {})?;
+                let workflow = state.doc.workflow.as_mut()
+                    .ok_or_else(|| Error::UnknownWorkflowState { name: from.to_string() })?;
+                for name in &[from, to] {
+                    if workflow.state(name).is_none() {
+                        return Err(Box::new(Error::UnknownWorkflowState { name: name.to_string() }));
+                    }
+                }
+                if allow {
+                    workflow.allow(from, to);
+                } else {
+                    workflow.disallow(from, to);
+                }
+            },
+            Some("clear") => state.doc.workflow = None,
+            Some(arg) => return Err(Box::new(CliError::ParseError { msg: format!("Unknown argument: {}", arg) })),
+            None => {
+                if let Some(workflow) = &state.doc.workflow {
+                    response.println("--- States: ");
+                    for workflow_state in workflow.states.iter() {
+                        response.println(&format!("{} ({})", workflow_state.name, workflow_state.progress.to_string()));
+                    }
+                    response.println("--- Transitions: ");
+                    if workflow.transitions.is_empty() {
+                        response.println("(all allowed)");
+                    }
+                    for (from, to) in workflow.transitions.iter() {
+                        response.println(&format!("{} -> {}", from, to));
+                    }
+                } else {
+                    response.println("No workflow defined");
+                }
+            },
+        }
+        Ok(())
+    }));
+    terminal.register_command("rollup", CommandHelp::new("Workflow", "rollup [on | off | skip]", "Configure the progress roll-up, skip toggles it for the working task"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let mut split = cmd.split(' ');
+        split.next();
+        match split.next() {
+            Some("on") => state.doc.progress_rollup = true,
+            Some("off") => state.doc.progress_rollup = false,
+            Some("skip") => {
+                let mut task = state.doc.get(&state.wt)?;
+                let no_rollup = !task.no_rollup;
+                task.set_no_rollup(no_rollup);
+                state.doc.upsert(task);
+                response.println(&format!("Skip roll-up for this task: {}", no_rollup));
+            },
+            Some(arg) => return Err(Box::new(CliError::ParseError { msg: format!("Unknown argument: {}", arg) })),
+            None => response.println(&format!("Progress roll-up: {}", if state.doc.progress_rollup { "on" } else { "off" })),
+        }
+        Ok(())
+    }));
+    terminal.register_command("cle", CommandHelp::new("Clocks", "cle [date]", "Edit the clocks of a day"), Box::new(|state: &mut State, cmd: &str, callbacks| {
+        let mut cmd_split = cmd.split(' ');
+        cmd_split.next();
+        let date = if let Some(param) = cmd_split.next() {
+            parse_date(param, &DateContext::new(&state.doc, callbacks))?
+        } else {
+            state.doc.timezone.today()
+        };
+        let clockedit_state = {
+            let clockedit_state = ClockEditCli {
+                clockedit: state.doc.create_clock_edit(date),
+                apply_result: ExitAction::Cancel,
+                doc: &state.doc,
+            };
+            let mut clockedit_cli = new_cli_with_callbacks(callbacks, clockedit_state, ClockCallbacks);
+            ClockEditCli::apply_commands(&mut clockedit_cli);
+            clockedit_cli.run_loop("clockedit> ");
+            clockedit_cli.state
+        };
+        if clockedit_state.apply_result == ExitAction::Apply {
+            for clock in clockedit_state.clockedit.clocks.iter().cloned() {
+                state.doc.upsert_clock(clock);
+            }
+        }
+        Ok(())
+    }));
+    terminal.register_command("rangeclock", CommandHelp::new("Reports", "rangeclock <days> [--tag=<tag>] [--channel <name>] [--chart[=day|task|group|tag|channel]] [--json]",
+            "List the clocks of the last days")
+            .example("rangeclock 30"), Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, cmd) = take_channel(cmd);
+        let cmd = cmd.as_str();
+        let args = positional_args(cmd);
+        if let Some(index_str) = args.first() {
+            if let Ok(i) = index_str.parse() {
+                let end = state.doc.timezone.today();
+                let duration = chrono::Duration::days(i);
+                let start = end - duration;
+                let mut clocks = filter_clocks_by_channel(filter_clocks_by_tag(state.doc.range_clock(start, end, state.wt), cmd), &channel);
+                clocks.sort();
+                if json_output(state, cmd) {
+                    return Ok(print_clocks_json(&clocks, &state.doc, response)?);
+                }
+                if let Some(mode) = chart_mode(cmd) {
+                    display_clock_chart(&clocks, &state.doc, mode, response);
+                } else {
+                    display_clocks(&clocks, &state.doc, response);
+                }
+                display_work_days(&state.doc, start, end, response);
+                display_breaks(&state.doc, start, end, response);
+            }
+        }
+        Ok(())
+    }));
+    terminal.register_command("clockgroup", CommandHelp::new("Reports", "clockgroup <from> <to> [--tags | --channels] [--tag=<tag>] [--channel <name>]",
+            "Sum the clocks in a date range per group, or per tag or channel"),
+            Box::new(|state: &mut State, cmd: &str, response| {
+        let (channel, cmd) = take_channel(cmd);
+        let cmd = cmd.as_str();
+        let args = positional_args(cmd);
+        let (start, end) = match (args.first(), args.get(1)) {
+            (Some(start), Some(end)) => {
+                let context = DateContext::new(&state.doc, response);
+                (parse_date(start, &context)?, parse_date(end, &context)?)
+            },
+            _ => return Err(Box::new(Error::UnsufficientInput {})),
+        };
+        let clocks = filter_clocks_by_channel(filter_clocks_by_tag(state.doc.range_clock(start, end, state.wt), cmd), &channel);
+        let mode = if has_flag(cmd, "--tags") {
+            ChartMode::Tag
+        } else if has_flag(cmd, "--channels") {
+            ChartMode::Channel
+        } else {
+            ChartMode::Group
+        };
+        display_clock_chart(&clocks, &state.doc, mode, response);
+        let overall = clocks.iter().fold(chrono::Duration::zero(), |acc, clock| acc + clock.duration());
+        response.println(&format!("Overall duration in time range: {}", format_duration(overall)));
+        Ok(())
+    }));
+    let names: Vec<String> = terminal.commands.keys().cloned().collect();
+    for name in names {
+        if let Some(func) = terminal.commands.remove(&name) {
+            let is_report = terminal.help.get(&name).is_some_and(|help| help.category == "Reports");
+            let func = if is_report { with_auto_clock_out(transaction(func)) } else { transaction(func) };
+            terminal.commands.insert(name, func);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! User settings which are read from a TOML file at startup.
+
+use crate::error::*;
+use crate::statics::*;
+use crate::calendar::parse_weekday;
+use crate::aging::AgingThresholds;
+use crate::billing::{InvoiceTemplate, DEFAULT_INVOICE_HEADER};
+use crate::worktime::{parse_break_rules, BreakRule};
+use crate::storage::{is_remote, S3Config};
+use chrono::{NaiveTime, Weekday};
+use serde::{Serialize, Deserialize};
+use snafu::ResultExt;
+use std::env::var;
+use std::path::{Path, PathBuf};
+
+/// Date format of listings if nothing else is configured.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Document which is opened at startup.
+    pub file: String,
+
+    /// Temporary file to edit tasks in the editor.
+    pub edit_file: String,
+
+    /// Command history of the prompt.
+    pub history_file: String,
+
+    /// Save after every command instead of only with `save`.
+    pub autosave: bool,
+
+    /// Ask for a comment at `clo` if the running clock has none.
+    pub clock_out_comment: bool,
+
+    /// End of the day like `22:00` when clocks which are still running are
+    /// stopped, at startup and before reports.  Empty to keep them running.
+    pub auto_clock_out: String,
+
+    /// Breaks required by worked time per day like `6h=30m, 9h=45m`, day
+    /// reports warn if they're missing.
+    pub break_rules: String,
+
+    /// Command which opens the editor, `$EDITOR` or `vi` by default.
+    pub editor: String,
+
+    /// Command which opens links and attachments with `open`, `$BROWSER`
+    /// or the opener of the system by default.
+    pub opener: String,
+
+    /// chrono format of dates in listings, times are appended.  Dates in
+    /// this format are accepted as input as well.
+    pub date_format: String,
+
+    /// First day of the week like `mon` or `sun` for weekly reports.
+    pub week_start: String,
+
+    /// Days after which open tasks which weren't changed or clocked are
+    /// shown as aging.
+    pub aging_warning_days: i64,
+
+    /// Days after which they are shown as stale.
+    pub aging_stale_days: i64,
+
+    /// Object store for `s3://` documents, AWS if empty.
+    pub s3_endpoint: String,
+
+    pub s3_region: String,
+
+    pub s3_access_key: String,
+
+    pub s3_secret_key: String,
+
+    /// Markdown above and below the items of `invoice`, see
+    /// `InvoiceTemplate` for the placeholders.
+    pub invoice_header: String,
+
+    pub invoice_footer: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file: format!("{}/tasks.json", *DATA_DIR),
+            edit_file: format!("{}/task.md", *DATA_DIR),
+            history_file: format!("{}/history", *DATA_DIR),
+            autosave: false,
+            clock_out_comment: false,
+            auto_clock_out: String::new(),
+            break_rules: String::new(),
+            editor: var("EDITOR").unwrap_or_else(|_| "vi".to_string()),
+            opener: default_opener(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            week_start: "mon".to_string(),
+            aging_warning_days: AgingThresholds::default().warning,
+            aging_stale_days: AgingThresholds::default().stale,
+            s3_endpoint: String::new(),
+            s3_region: "us-east-1".to_string(),
+            s3_access_key: String::new(),
+            s3_secret_key: String::new(),
+            invoice_header: DEFAULT_INVOICE_HEADER.to_string(),
+            invoice_footer: String::new(),
+        }
+    }
+}
+
+/// `$BROWSER`, `open` on macOS and `xdg-open` everywhere else.
+pub fn default_opener() -> String {
+    var("BROWSER").unwrap_or_else(|_| if cfg!(target_os = "macos") { "open" } else { "xdg-open" }.to_string())
+}
+
+/// Replace a leading `~` by the home directory.
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home, rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Formatting dates with an invalid format panics, so check it first.
+fn check_date_format(format: &str) -> Result<()> {
+    if chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+        return Err(Error::ConfigError { msg: format!("Invalid date format: {}", format) });
+    }
+    Ok(())
+}
+
+fn check_week_start(week_start: &str) -> Result<()> {
+    if parse_weekday(week_start).is_none() {
+        return Err(Error::ConfigError { msg: format!("Invalid weekday: {}", week_start) });
+    }
+    Ok(())
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    value.parse().map_err(|_| Error::ConfigError { msg: format!("Not a boolean: {}", value) })
+}
+
+fn parse_day_end(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|_| Error::ConfigError { msg: format!("Not a time like 22:00: {}", value) })
+}
+
+fn parse_days(value: &str) -> Result<i64> {
+    value.parse().ok()
+        .filter(|days| *days >= 0)
+        .ok_or_else(|| Error::ConfigError { msg: format!("Not a number of days: {}", value) })
+}
+
+/// Document of a project relative to the project directory.
+pub const PROJECT_FILE: &str = ".sors/tasks.json";
+
+/// Find the project document in the directory or its parents, like git
+/// finds the repository.
+pub fn find_project_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|file| file.is_file())
+}
+
+/// Move a file and create the directory of the destination.
+fn move_file(from: &str, to: &str) -> std::io::Result<()> {
+    if let Some(dir) = Path::new(to).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+impl Config {
+    pub const KEYS: &'static [&'static str] = &["file", "edit_file", "history_file", "autosave", "clock_out_comment", "auto_clock_out", "break_rules", "editor", "opener", "date_format", "week_start", "aging_warning_days", "aging_stale_days", "s3_endpoint", "s3_region", "s3_access_key", "s3_secret_key", "invoice_header", "invoice_footer"];
+
+    /// Load the config, missing files and settings use the defaults.
+    pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+        if !path.as_ref().exists() {
+            return Ok(Config::default());
+        }
+        let content = std::fs::read_to_string(path).context(IO)?;
+        let config: Config = toml::from_str(&content).map_err(|err| Error::ConfigError { msg: err.to_string() })?;
+        check_date_format(&config.date_format)?;
+        check_week_start(&config.week_start)?;
+        Ok(config)
+    }
+
+    /// Save the config and create the directory if needed.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let content = toml::to_string(self).map_err(|err| Error::ConfigError { msg: err.to_string() })?;
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).context(IO)?;
+        }
+        std::fs::write(path, content).context(IO)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        Some(match key {
+            "file" => self.file.clone(),
+            "edit_file" => self.edit_file.clone(),
+            "history_file" => self.history_file.clone(),
+            "autosave" => self.autosave.to_string(),
+            "clock_out_comment" => self.clock_out_comment.to_string(),
+            "auto_clock_out" => self.auto_clock_out.clone(),
+            "break_rules" => self.break_rules.clone(),
+            "editor" => self.editor.clone(),
+            "opener" => self.opener.clone(),
+            "date_format" => self.date_format.clone(),
+            "week_start" => self.week_start.clone(),
+            "aging_warning_days" => self.aging_warning_days.to_string(),
+            "aging_stale_days" => self.aging_stale_days.to_string(),
+            "s3_endpoint" => self.s3_endpoint.clone(),
+            "s3_region" => self.s3_region.clone(),
+            "s3_access_key" => self.s3_access_key.clone(),
+            // Don't show the secret on screen.
+            "s3_secret_key" if self.s3_secret_key.is_empty() => String::new(),
+            "s3_secret_key" => "********".to_string(),
+            "invoice_header" => self.invoice_header.clone(),
+            "invoice_footer" => self.invoice_footer.clone(),
+            _ => return None,
+        })
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.to_string();
+        match key {
+            "file" => self.file = value,
+            "edit_file" => self.edit_file = value,
+            "history_file" => self.history_file = value,
+            "autosave" => self.autosave = parse_bool(&value)?,
+            "clock_out_comment" => self.clock_out_comment = parse_bool(&value)?,
+            "auto_clock_out" => {
+                if !value.is_empty() {
+                    parse_day_end(&value)?;
+                }
+                self.auto_clock_out = value
+            },
+            "break_rules" => {
+                parse_break_rules(&value)?;
+                self.break_rules = value
+            },
+            "editor" => self.editor = value,
+            "opener" => self.opener = value,
+            "date_format" => {
+                check_date_format(&value)?;
+                self.date_format = value
+            },
+            "week_start" => {
+                check_week_start(&value)?;
+                self.week_start = value
+            },
+            "aging_warning_days" => self.aging_warning_days = parse_days(&value)?,
+            "aging_stale_days" => self.aging_stale_days = parse_days(&value)?,
+            "s3_endpoint" => self.s3_endpoint = value,
+            "s3_region" => self.s3_region = value,
+            "s3_access_key" => self.s3_access_key = value,
+            "s3_secret_key" => self.s3_secret_key = value,
+            // Line breaks can be written as \n in the prompt.
+            "invoice_header" => self.invoice_header = value.replace("\\n", "\n"),
+            "invoice_footer" => self.invoice_footer = value.replace("\\n", "\n"),
+            _ => return Err(Error::ConfigError { msg: format!("Unknown key: {}", key) }),
+        }
+        Ok(())
+    }
+
+    pub fn s3_config(&self) -> S3Config {
+        S3Config {
+            endpoint: self.s3_endpoint.clone(),
+            region: self.s3_region.clone(),
+            access_key: self.s3_access_key.clone(),
+            secret_key: self.s3_secret_key.clone(),
+        }
+    }
+
+    /// End of the day for `Doc::auto_clock_out`, if it's set.
+    pub fn auto_clock_out_time(&self) -> Option<NaiveTime> {
+        parse_day_end(&self.auto_clock_out).ok()
+    }
+
+    /// Required breaks, invalid rules were rejected by `set`.
+    pub fn break_rules(&self) -> Vec<BreakRule> {
+        parse_break_rules(&self.break_rules).unwrap_or_default()
+    }
+
+    pub fn invoice_template(&self) -> InvoiceTemplate {
+        InvoiceTemplate { header: self.invoice_header.clone(), footer: self.invoice_footer.clone() }
+    }
+
+    pub fn aging_thresholds(&self) -> AgingThresholds {
+        AgingThresholds { warning: self.aging_warning_days, stale: self.aging_stale_days }
+    }
+
+    /// First day of the week, Monday if the setting is invalid.
+    pub fn first_weekday(&self) -> Weekday {
+        parse_weekday(&self.week_start).unwrap_or(Weekday::Mon)
+    }
+
+    pub fn file_path(&self) -> String {
+        expand_home(&self.file)
+    }
+
+    pub fn edit_file_path(&self) -> String {
+        expand_home(&self.edit_file)
+    }
+
+    pub fn history_file_path(&self) -> String {
+        expand_home(&self.history_file)
+    }
+
+    /// Command history for the document, so every workspace has its own.
+    ///
+    /// The configured document uses `history_file`, all others a file in
+    /// the `histories` data directory named after their path.
+    pub fn history_file_for(&self, doc_path: &str) -> String {
+        if doc_path == self.file_path() {
+            return self.history_file_path();
+        }
+        let name: String = doc_path.chars()
+            .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '%' })
+            .collect();
+        format!("{}/histories/{}", *DATA_DIR, name)
+    }
+
+    /// Files in the home directory which were used before the XDG base
+    /// directories, with their new location.
+    pub fn legacy_files(&self) -> Vec<(String, String)> {
+        let file = self.file_path();
+        vec![
+            (expand_home("~/.tasks.json"), file.clone()),
+            (expand_home("~/.tasks.json.journal"), format!("{}.journal", file)),
+            (expand_home("~/.taskhistory"), self.history_file_path()),
+            (expand_home("~/.taskhooks.json"), HOOKS_FILE.clone()),
+            (expand_home("~/.tasktheme.json"), THEME_FILE.clone()),
+            (expand_home("~/.sorsrc"), RC_FILE.clone()),
+        ]
+    }
+
+    /// Move the legacy files to their new location unless there is already
+    /// a file or the location is remote.
+    ///
+    /// Returns the moved files.
+    pub fn migrate_legacy_files(&self) -> Result<Vec<(String, String)>> {
+        let mut moved = Vec::new();
+        for (from, to) in self.legacy_files() {
+            if from != to && !is_remote(&to) && Path::new(&from).exists() && !Path::new(&to).exists() {
+                move_file(&from, &to).context(IO)?;
+                moved.push((from, to));
+            }
+        }
+        Ok(moved)
+    }
+}
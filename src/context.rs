@@ -0,0 +1,50 @@
+//! GTD contexts like `@home` or `@office`.
+//!
+//! A context is a tag which starts with `@`.  Tasks without a context of
+//! their own get the contexts of their closest parent which has some, and
+//! tasks without any context can be done everywhere.
+
+use crate::doc::Doc;
+use crate::tasks::Task;
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+/// Name of the context with the leading `@`.
+pub fn context_name(name: &str) -> String {
+    if name.starts_with('@') {
+        name.to_string()
+    } else {
+        format!("@{}", name)
+    }
+}
+
+impl Task {
+    /// Contexts set directly on the task.
+    pub fn contexts(&self) -> impl Iterator<Item = &String> {
+        self.tags.iter().filter(|tag| tag.starts_with('@'))
+    }
+}
+
+impl Doc {
+    /// Contexts of the task or of its closest parent with contexts.
+    pub fn task_contexts(&self, task_ref: &Uuid) -> Vec<String> {
+        self.path(task_ref).iter()
+            .filter_map(|task_id| self.get(task_id).ok())
+            .map(|task| task.contexts().cloned().collect::<Vec<_>>())
+            .find(|contexts| !contexts.is_empty())
+            .unwrap_or_default()
+    }
+
+    /// Check if the task can be done in the context.
+    pub fn in_context(&self, task_ref: &Uuid, context: &str) -> bool {
+        let contexts = self.task_contexts(task_ref);
+        contexts.is_empty() || contexts.iter().any(|name| *name == context_name(context))
+    }
+
+    /// All contexts which are used below the root.
+    pub fn contexts(&self) -> BTreeSet<String> {
+        self.iter_subtree(&self.root)
+            .flat_map(|(_, task)| task.contexts().cloned().collect::<Vec<_>>())
+            .collect()
+    }
+}
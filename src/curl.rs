@@ -0,0 +1,63 @@
+//! Secrets for `curl` like passwords and tokens.
+//!
+//! The command line of a process can be read by every user of the machine,
+//! so secrets are written to a config file which only the user can read and
+//! passed with `-K`.  Standard input stays free for request bodies.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Value in a curl config file, quoted and escaped.
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Temporary curl config file with options like `user` or `header`, which
+/// is removed when it's dropped.
+pub struct SecretConfig {
+    path: PathBuf,
+}
+
+impl SecretConfig {
+    pub fn new(options: &[(&str, &str)]) -> std::io::Result<SecretConfig> {
+        let path = std::env::temp_dir().join(format!("sors-curl-{}", Uuid::new_v4()));
+        let mut open_options = OpenOptions::new();
+        open_options.write(true).create_new(true);
+        // Only the user can read it, elsewhere the temporary directory of
+        // the user is private already.
+        #[cfg(unix)]
+        open_options.mode(0o600);
+        let mut file = open_options.open(&path)?;
+        let config = SecretConfig { path };
+        for (option, value) in options {
+            writeln!(file, "{} = {}", option, quote(value))?;
+        }
+        Ok(config)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SecretConfig {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,129 @@
+//! Structured differences between two versions of a document.
+//!
+//! Tasks and clocks are matched by their id.  Modified tasks and clocks list
+//! the changed fields with their old and new values as JSON, so the changes
+//! can be shown or processed without knowing every field.
+
+use crate::doc::Doc;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// A field which has a different value in the newer document.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+/// Difference of a task or clock between two documents.
+///
+/// Task paths are the titles from the root, in the newer document or in the
+/// older one if the task was removed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "kebab-case")]
+pub enum Change {
+    TaskAdded { task_id: Uuid, path: Vec<String> },
+    TaskRemoved { task_id: Uuid, path: Vec<String> },
+    TaskModified { task_id: Uuid, path: Vec<String>, fields: Vec<FieldChange> },
+    ClockAdded { clock_id: Uuid },
+    ClockRemoved { clock_id: Uuid },
+    ClockModified { clock_id: Uuid, fields: Vec<FieldChange> },
+}
+
+impl Change {
+    /// Change of the field if it's a modification which contains it.
+    pub fn field(&self, name: &str) -> Option<&FieldChange> {
+        match self {
+            Change::TaskModified { fields, .. } | Change::ClockModified { fields, .. } =>
+                fields.iter().find(|field| field.field == name),
+            _ => None,
+        }
+    }
+}
+
+/// Fields of the serialized values which differ, except the ignored ones.
+fn field_changes(old: Value, new: Value, ignored: &[&str]) -> Vec<FieldChange> {
+    let (old, new) = match (old, new) {
+        (Value::Object(old), Value::Object(new)) => (old, new),
+        _ => return Vec::new(),
+    };
+    let mut fields: Vec<&String> = old.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    fields.into_iter()
+        .filter(|field| !ignored.contains(&field.as_str()))
+        .filter_map(|field| {
+            let from = old.get(field).cloned().unwrap_or(Value::Null);
+            let to = new.get(field).cloned().unwrap_or(Value::Null);
+            if from == to {
+                None
+            } else {
+                Some(FieldChange { field: field.clone(), from, to })
+            }
+        })
+        .collect()
+}
+
+impl Doc {
+    /// Changes since the older version of the document.
+    ///
+    /// Tasks come first in tree order followed by the removed tasks, then
+    /// the clocks by their start.  Changed children are not listed as field,
+    /// but moved tasks get a `parent` field.  Tasks in the trash count as
+    /// removed.
+    pub fn diff(&self, old: &Doc) -> Vec<Change> {
+        let ids: Vec<Uuid> = self.iter_subtree(&self.root).map(|(_, task)| task.id).collect();
+        let old_ids: Vec<Uuid> = old.iter_subtree(&old.root).map(|(_, task)| task.id).collect();
+        let current: HashSet<Uuid> = ids.iter().cloned().collect();
+        let previous: HashSet<Uuid> = old_ids.iter().cloned().collect();
+
+        let mut changes = Vec::new();
+        for task_id in ids.iter() {
+            let (task, old_task) = match (self.get(task_id), old.get(task_id)) {
+                (Ok(task), Ok(old_task)) if previous.contains(task_id) => (task, old_task),
+                _ => {
+                    changes.push(Change::TaskAdded { task_id: *task_id, path: self.path_titles(task_id) });
+                    continue;
+                },
+            };
+            let mut fields = field_changes(
+                serde_json::to_value(&*old_task).unwrap_or(Value::Null),
+                serde_json::to_value(&*task).unwrap_or(Value::Null),
+                &["id", "children", "modified"]);
+            let (parent, old_parent) = (self.find_parent(task_id), old.find_parent(task_id));
+            if parent != old_parent {
+                fields.push(FieldChange { field: "parent".to_string(), from: serde_json::json!(old_parent), to: serde_json::json!(parent) });
+            }
+            if !fields.is_empty() {
+                changes.push(Change::TaskModified { task_id: *task_id, path: self.path_titles(task_id), fields });
+            }
+        }
+        changes.extend(old_ids.iter()
+            .filter(|task_id| !current.contains(task_id))
+            .map(|task_id| Change::TaskRemoved { task_id: *task_id, path: old.path_titles(task_id) }));
+
+        let mut clocks: Vec<_> = self.clocks.values().chain(old.clocks.values()).collect();
+        clocks.sort_by_key(|clock| (clock.start, clock.id));
+        let mut seen = HashSet::new();
+        for clock in clocks.into_iter().filter(|clock| seen.insert(clock.id)) {
+            match (self.clocks.get(&clock.id), old.clocks.get(&clock.id)) {
+                (Some(_), None) => changes.push(Change::ClockAdded { clock_id: clock.id }),
+                (None, Some(_)) => changes.push(Change::ClockRemoved { clock_id: clock.id }),
+                (Some(new_clock), Some(old_clock)) => {
+                    let fields = field_changes(
+                        serde_json::to_value(&**old_clock).unwrap_or(Value::Null),
+                        serde_json::to_value(&**new_clock).unwrap_or(Value::Null),
+                        &["id"]);
+                    if !fields.is_empty() {
+                        changes.push(Change::ClockModified { clock_id: clock.id, fields });
+                    }
+                },
+                (None, None) => {},
+            }
+        }
+        changes
+    }
+}
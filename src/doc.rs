@@ -5,14 +5,25 @@ use serde::{Serialize, Deserialize};
 use super::tasks::*;
 use super::clock::*;
 use super::error::*;
+use super::workflow::*;
+use super::activity::*;
+use super::journal::*;
+use super::worktime::*;
+use super::trash::*;
+use super::timezone::DisplayZone;
+use super::caldav::CaldavSync;
+use super::storage::storage;
+#[cfg(feature = "sqlite")]
+use super::sqlite::SqliteDb;
+use super::cache::Cache;
 use std::io::Write;
 use std::fs::File;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::rc::Rc;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use snafu::ResultExt;
 use chrono::prelude::*;
-use crate::cli::CliCallbacks;
 
 /// Holding data which are serialized and stored to disk.
 /// 
@@ -114,7 +125,130 @@ pub struct Doc {
     #[serde(default)]
     pub clocks: HashMap<Uuid, Rc<Clock>>,
     pub current_clock: Option<Uuid>,
-    pub root: Uuid
+
+    /// Running clocks of the named channels, which run in parallel to the
+    /// `current_clock` of the default channel.
+    #[serde(default)]
+    pub channel_clocks: BTreeMap<String, Uuid>,
+    pub root: Uuid,
+
+    /// Recalculate the progress of the parents if a child changes.
+    #[serde(default)]
+    pub progress_rollup: bool,
+
+    /// Custom progress states of this document.
+    #[serde(default)]
+    pub workflow: Option<Workflow>,
+
+    /// Log of task and clock changes, the oldest first.
+    #[serde(default)]
+    pub activity: Vec<Activity>,
+
+    /// Top-level task titles and their project names in timesheets.
+    #[serde(default)]
+    pub timesheet_projects: BTreeMap<String, String>,
+
+    /// Expected working hours per weekday.
+    #[serde(default)]
+    pub work_hours: Option<WorkHours>,
+
+    /// Breaks, oldest first, see `Doc::start_break`.
+    #[serde(default)]
+    pub breaks: Vec<Break>,
+
+    /// Time zone of clock listings and reports.
+    #[serde(default)]
+    pub timezone: DisplayZone,
+
+    /// Named tasks which can be used in paths like `@inbox`.
+    #[serde(default)]
+    pub bookmarks: BTreeMap<String, Uuid>,
+
+    /// Hidden task which contains the removed tasks.
+    #[serde(default)]
+    pub trash: Option<Uuid>,
+
+    /// Tasks in the trash, the oldest first.
+    #[serde(default)]
+    pub trash_entries: Vec<TrashEntry>,
+
+    /// Calendar which the tasks are synced with.
+    #[serde(default)]
+    pub caldav: Option<CaldavSync>,
+
+    /// Changes which are not written to disk yet.
+    #[serde(skip)]
+    pub journal: Journal,
+
+    /// Refuse to write the document to disk.
+    #[serde(skip)]
+    pub read_only: bool,
+
+    /// SQLite database the document was loaded from, asked by reports and
+    /// queries while there are no unsaved changes.
+    #[cfg(feature = "sqlite")]
+    #[serde(skip)]
+    pub database: Option<SqliteDb>,
+
+    /// Identifies the document across clones, see `Doc::rollback`.
+    #[serde(skip, default = "new_origin")]
+    origin: u64,
+
+    /// Activity before it was reordered since the last `Doc::snapshot`.
+    #[serde(skip)]
+    activity_backup: Option<Vec<Activity>>,
+
+    #[serde(skip)]
+    pub(crate) cache: Cache,
+}
+
+fn new_origin() -> u64 {
+    static NEXT_ORIGIN: AtomicU64 = AtomicU64::new(0);
+    NEXT_ORIGIN.fetch_add(1, Ordering::Relaxed)
+}
+
+/// State of a document to go back to, see `Doc::snapshot`.
+#[derive(Debug)]
+pub struct Snapshot {
+    doc: Doc,
+    activity_len: usize,
+}
+
+impl Snapshot {
+    /// The document without its activity.
+    pub fn doc(&self) -> &Doc {
+        &self.doc
+    }
+}
+
+/// File format of a stored document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Default format, human readable.
+    Json,
+    /// Compact binary format for large documents, used for `.cbor` files.
+    Cbor,
+    /// Database with incremental saves for very large histories, used for
+    /// `.db`, `.sqlite` and `.sqlite3` files, see `sqlite`.  Loading and
+    /// saving them fails without the `sqlite` feature.
+    Sqlite,
+}
+
+/// Error for SQLite documents if the `sqlite` feature is off.
+#[cfg(not(feature = "sqlite"))]
+pub(crate) fn sqlite_disabled() -> Error {
+    Error::StorageError { msg: "SQLite documents need the sqlite feature".to_string() }
+}
+
+impl Format {
+    /// Select the format by the file extension.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("cbor") => Format::Cbor,
+            Some("db") | Some("sqlite") | Some("sqlite3") => Format::Sqlite,
+            _ => Format::Json,
+        }
+    }
 }
 
 impl Default for Doc {
@@ -144,29 +278,198 @@ impl Doc {
             map,
             clocks: HashMap::default(),
             current_clock: None,
-            root: root_id
+            channel_clocks: BTreeMap::new(),
+            root: root_id,
+            progress_rollup: false,
+            workflow: None,
+            activity: Vec::new(),
+            timesheet_projects: BTreeMap::new(),
+            work_hours: None,
+            breaks: Vec::new(),
+            timezone: DisplayZone::Local,
+            bookmarks: BTreeMap::new(),
+            trash: None,
+            trash_entries: Vec::new(),
+            caldav: None,
+            journal: Journal::default(),
+            read_only: false,
+            #[cfg(feature = "sqlite")]
+            database: None,
+            origin: new_origin(),
+            activity_backup: None,
+            cache: Cache::default(),
         }
     }
 
     /// Write the content to into the specified file.
+    /// 
+    /// The file format is selected by the file extension, see `Format`, and
+    /// the file can be on another machine, see `storage`.
+    /// A journal next to the file is removed since it's part of the file now.
     pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
-        serde_json::to_writer(
-            File::create(path).context(IO)?, self)
-            .context(SerdeSerializationError)?;
+        if self.read_only {
+            return Err(Error::ReadOnly {});
+        }
+        let format = Format::from_path(path.as_ref());
+        if format == Format::Sqlite {
+            #[cfg(not(feature = "sqlite"))]
+            return Err(sqlite_disabled());
+            #[cfg(feature = "sqlite")]
+            {
+                SqliteDb::open(path.as_ref())?.save(self)?;
+                self.journal.reset();
+                return Ok(());
+            }
+        }
+        let content = self.encode(format)?;
+        let storage = storage(path.as_ref());
+        storage.write(&content)?;
+        if let Some(local_path) = storage.local_path() {
+            if let Err(err) = std::fs::remove_file(journal_path(local_path)) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    Err(err).context(IO)?;
+                }
+            }
+        }
+        self.journal.reset();
         Ok(())
     }
 
+    /// Serialize the whole document in the file format.
+    ///
+    /// SQLite databases are written row by row, JSON is used for them.
+    pub fn encode(&self, format: Format) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+        match format {
+            Format::Json | Format::Sqlite => serde_json::to_writer(&mut content, self)
+                .context(SerdeSerializationError)?,
+            Format::Cbor => ciborium::ser::into_writer(self, &mut content)
+                .context(CborSerializationError)?,
+        }
+        Ok(content)
+    }
+
     /// Load the document of hte given path and return a new doc.
     /// 
+    /// The file format is selected by the file extension, see `Format`, and
+    /// the file can be on another machine, see `storage`.
+    /// If there is a journal next to a local file, it's applied.
+    /// 
     /// # Error
     /// Produces an error if there are IO issues or if the file format
     /// couldn't be parsed.
     pub fn load(path: impl AsRef<Path>) -> Result<Doc> {
-        Ok(
-            serde_json::from_reader(
-                File::open(path).context(IO)?
-            ).context(SerdeSerializationError)?
-        )
+        let format = Format::from_path(path.as_ref());
+        if format == Format::Sqlite {
+            #[cfg(not(feature = "sqlite"))]
+            return Err(sqlite_disabled());
+            #[cfg(feature = "sqlite")]
+            return SqliteDb::open(path.as_ref())?.load();
+        }
+        let storage = storage(path.as_ref());
+        let content = storage.read()?;
+        let mut doc: Doc = match format {
+            Format::Json => serde_json::from_slice(&content)
+                .context(SerdeSerializationError)?,
+            Format::Cbor => ciborium::de::from_reader(&content[..])
+                .context(CborDeserializationError)?,
+            Format::Sqlite => unreachable!(),
+        };
+        if let Some(local_path) = storage.local_path() {
+            doc.replay_journal(local_path)?;
+        }
+        Ok(doc)
+    }
+
+    /// Merge another version of this document into this one.
+    /// 
+    /// Tasks and clocks which only exist in the other document are added
+    /// and children of tasks existing in both are combined.  For everything
+    /// else, this document wins, also for the parent of a task which was
+    /// moved in one of the documents.
+    pub fn merge(&mut self, other: &Doc) {
+        let mut parents: HashMap<Uuid, Uuid> = self.map.values()
+            .flat_map(|task| task.children.iter().map(move |child| (*child, task.id)))
+            .collect();
+        let mut changed = HashSet::new();
+        for (task_id, other_task) in other.map.iter() {
+            if !self.map.contains_key(task_id) {
+                let mut task = other_task.clone();
+                Rc::make_mut(&mut task).children.clear();
+                self.map.insert(*task_id, task);
+                changed.insert(*task_id);
+            }
+        }
+        // Tasks of the other document have one parent each, so the order
+        // doesn't matter.
+        for (task_id, other_task) in other.map.iter() {
+            let missing: Vec<Uuid> = other_task.children.iter()
+                .filter(|child| !parents.contains_key(child))
+                .cloned()
+                .collect();
+            if missing.is_empty() {
+                continue;
+            }
+            for child in missing.iter() {
+                parents.insert(*child, *task_id);
+            }
+            if let Some(task) = self.map.get_mut(task_id) {
+                Rc::make_mut(task).children.extend(missing);
+                changed.insert(*task_id);
+            }
+        }
+        for task_id in changed {
+            self.journal.record(JournalEntry::Task(self.map[&task_id].clone()));
+        }
+        self.cache.invalidate_tasks();
+        for (clock_id, clock) in other.clocks.iter() {
+            if !self.clocks.contains_key(clock_id) {
+                self.upsert_clock(clock.clone());
+            }
+        }
+        let known: HashSet<&Activity> = self.activity.iter().collect();
+        let missing: Vec<Activity> = other.activity.iter()
+            .filter(|entry| !known.contains(entry))
+            .cloned()
+            .collect();
+        let last_time = self.activity.last().map(|entry| entry.time);
+        if self.activity_backup.is_none() && missing.iter().any(|entry| Some(entry.time) < last_time) {
+            self.activity_backup = Some(self.activity.clone());
+        }
+        for entry in missing {
+            self.journal.record(JournalEntry::Activity(entry.clone()));
+            self.activity.push(entry);
+        }
+        self.activity.sort_by_key(|entry| entry.time);
+    }
+
+    /// Remember the current state to go back to with `rollback`.
+    ///
+    /// Only the length of the activity is kept since entries are only
+    /// added, `merge` keeps a copy if it has to reorder them.
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.activity_backup = None;
+        let activity = std::mem::take(&mut self.activity);
+        let doc = self.clone();
+        self.activity = activity;
+        Snapshot { doc, activity_len: self.activity.len() }
+    }
+
+    /// Go back to the snapshot, activity entries added since then are
+    /// removed.
+    ///
+    /// A document which was replaced by another one, like a freshly loaded
+    /// one, is kept and false returned.
+    pub fn rollback(&mut self, snapshot: Snapshot) -> bool {
+        let Snapshot { mut doc, activity_len } = snapshot;
+        if doc.origin != self.origin {
+            return false;
+        }
+        let mut activity = self.activity_backup.take().unwrap_or_else(|| std::mem::take(&mut self.activity));
+        activity.truncate(activity_len);
+        doc.activity = activity;
+        *self = doc;
+        true
     }
 
     /// Load task which contains the given id.
@@ -184,9 +487,144 @@ impl Doc {
 
     /// Adds or replaces the given task.
     /// 
-    /// The task is identified by its id.
-    pub fn upsert(&mut self, task: Rc<Task>) {
-        self.map.insert(task.id, task);
+    /// The task is identified by its id.  New tasks and progress changes are
+    /// recorded in the activity log.  Habits which are marked as done are
+    /// logged as completed and stored as todo again.  Changed tasks get the
+    /// current time as `modified`.  If `progress_rollup` is enabled, the
+    /// progress of the parents is updated as well.
+    pub fn upsert(&mut self, mut task: Rc<Task>) {
+        let task_id = task.id;
+        let new_progress = task.progress;
+        let completed_habit = task.habit.is_some() && new_progress.is_some_and(|progress| progress.done());
+        if completed_habit {
+            let habit = Rc::make_mut(&mut task);
+            habit.progress = Some(Progress::Todo);
+            habit.state = None;
+            habit.finished = None;
+        }
+        if self.map.get(&task_id).is_some_and(|old_task| **old_task != *task) {
+            Rc::make_mut(&mut task).modified = Some(Local::now());
+        }
+        self.journal.record(JournalEntry::Task(task.clone()));
+        self.cache.invalidate_tasks();
+        match self.map.insert(task_id, task) {
+            None => self.log_activity(Some(task_id), ActivityEvent::TaskCreated),
+            Some(old_task) if completed_habit || old_task.progress != new_progress =>
+                self.log_activity(Some(task_id), ActivityEvent::ProgressChanged {
+                    from: old_task.progress,
+                    to: new_progress,
+                }),
+            Some(_) => {},
+        }
+        if self.progress_rollup {
+            self.rollup_progress(&task_id);
+        }
+    }
+
+    /// Append an entry to the activity log.
+    pub fn log_activity(&mut self, task_id: Option<Uuid>, event: ActivityEvent) {
+        let entry = Activity::new(task_id, event);
+        self.journal.record(JournalEntry::Activity(entry.clone()));
+        self.activity.push(entry);
+    }
+
+    /// Set the progress of a task.
+    /// 
+    /// If the document has a workflow, the first workflow state which maps
+    /// to the progress is used instead.
+    /// 
+    /// # Error
+    /// Returns an error if the task doesn't exist, the workflow has no state
+    /// for the progress or if the transition is not allowed.
+    pub fn set_progress(&mut self, task_ref: &Uuid, progress: Progress) -> Result<()> {
+        if let Some(workflow) = &self.workflow {
+            let state = workflow.state_for_progress(progress)
+                .ok_or_else(|| Error::UnknownWorkflowState { name: progress.to_string() })?
+                .name.clone();
+            self.set_state(task_ref, &state)
+        } else {
+            let mut task = self.get(task_ref)?;
+            task.set_progress(progress);
+            self.upsert(task);
+            Ok(())
+        }
+    }
+
+    /// Move a task into the given workflow state.
+    /// 
+    /// # Error
+    /// Returns an error if the task doesn't exist, the document has no
+    /// workflow, the state is unknown or if the transition is not allowed.
+    pub fn set_state(&mut self, task_ref: &Uuid, name: &str) -> Result<()> {
+        let mut task = self.get(task_ref)?;
+        let workflow = self.workflow.as_ref()
+            .ok_or_else(|| Error::UnknownWorkflowState { name: name.to_string() })?;
+        let state = workflow.state(name)
+            .ok_or_else(|| Error::UnknownWorkflowState { name: name.to_string() })?;
+        if !workflow.allows(task.state.as_deref(), &state.name) {
+            return Err(Error::TransitionNotAllowed {
+                from: task.state.clone().unwrap_or_default(),
+                to: state.name.clone(),
+            });
+        }
+        task.set_state(&state.name, state.progress);
+        self.upsert(task);
+        Ok(())
+    }
+
+    /// Recalculate the progress of the parent of the given task.
+    /// 
+    /// If all children are done, the parent is done.  If any child is in work,
+    /// the parent is in work.  A done parent with unfinished children becomes
+    /// todo again.  Cancelled children are ignored.  Parents without progress
+    /// or with `no_rollup` are left alone.  A parent in a workflow state keeps
+    /// it if it maps to the new progress, otherwise it gets the first state
+    /// which does.
+    pub fn rollup_progress(&mut self, task_ref: &Uuid) {
+        let parent_id = match self.find_parent(task_ref) {
+            Some(parent_id) => parent_id,
+            None => return,
+        };
+        let mut parent = match self.get(&parent_id) {
+            Ok(parent) => parent,
+            Err(_) => return,
+        };
+        let parent_progress = match parent.progress {
+            Some(progress) if !parent.no_rollup => progress,
+            _ => return,
+        };
+        let child_progress: Vec<Progress> = parent.children.iter()
+            .filter_map(|child_ref| self.get(child_ref).ok())
+            .filter_map(|child| child.progress)
+            .filter(|progress| progress.counts())
+            .collect();
+        if child_progress.is_empty() {
+            return;
+        }
+        let new_progress = if child_progress.iter().all(|progress| progress.done()) {
+            Progress::Done
+        } else if child_progress.contains(&Progress::Work) {
+            Progress::Work
+        } else if parent_progress.done() {
+            Progress::Todo
+        } else {
+            parent_progress
+        };
+        if new_progress == parent_progress {
+            return;
+        }
+        let workflow_state = match (&self.workflow, &parent.state) {
+            (Some(workflow), Some(name)) => workflow.state(name)
+                .filter(|state| state.progress == new_progress)
+                .or_else(|| workflow.state_for_progress(new_progress))
+                .map(|state| state.name.clone()),
+            _ => None,
+        };
+        match workflow_state {
+            Some(name) => parent.set_state(name, new_progress),
+            None => parent.set_progress(new_progress),
+        };
+        self.upsert(parent);
     }
 
     /// Modify the task with a function or closure
@@ -202,6 +640,48 @@ impl Doc {
         Ok(())
     }
 
+    /// Check if there are changes of tasks, clocks or activity which are not
+    /// saved yet.
+    pub fn has_unsaved_changes(&self) -> bool {
+        !self.journal.is_empty()
+    }
+
+    /// Delete the task and its subtasks from the document and from the
+    /// children of its parent.  Their clocks are kept without a task, so
+    /// the tracked time isn't lost.  Bookmarks of the tasks are removed.
+    ///
+    /// Unlike `move_to_trash`, this can't be restored.
+    ///
+    /// # Error
+    /// Returns an error if the task has no parent, like the root.
+    pub fn remove_task(&mut self, task_ref: &Uuid) -> Result<()> {
+        let parent_id = self.find_parent(task_ref).ok_or(Error::TaskUuidNotFound {})?;
+        let task_ids: HashSet<Uuid> = self.iter_subtree(task_ref).map(|(_, task)| task.id).collect();
+        self.modify_task(&parent_id, |parent| { parent.remove_child(task_ref); Ok(()) })?;
+        for task_id in task_ids.iter() {
+            self.forget_task(task_id);
+        }
+        let clocks: Vec<Rc<Clock>> = self.clocks.values()
+            .filter(|clock| clock.task_id.is_some_and(|task_id| task_ids.contains(&task_id)))
+            .cloned().collect();
+        for mut clock in clocks {
+            Rc::make_mut(&mut clock).task_id = None;
+            self.upsert_clock(clock);
+        }
+        self.bookmarks.retain(|_, task_id| !task_ids.contains(task_id));
+        Ok(())
+    }
+
+    /// Remove the task from the map.
+    ///
+    /// References to it like children of other tasks are not updated.
+    fn forget_task(&mut self, task_ref: &Uuid) {
+        if self.map.remove(task_ref).is_some() {
+            self.journal.record(JournalEntry::TaskRemoved(*task_ref));
+            self.cache.invalidate_tasks();
+        }
+    }
+
     /// Add a new task as child of the given parent id.
     /// 
     /// # Panic
@@ -212,11 +692,44 @@ impl Doc {
         Ok(())
     }
 
+    /// Move the task with its subtasks to another parent, at the given
+    /// index of its children or at the end.
+    ///
+    /// The index counts the children without the task, so a task can be
+    /// moved within its parent too.
+    ///
+    /// # Error
+    /// Fails without changes if the task has no parent, like the root, if
+    /// the new parent doesn't exist, if it is the task itself or one of its
+    /// subtasks or if the index is behind the last child.
+    pub fn move_task(&mut self, task_ref: &Uuid, new_parent_ref: &Uuid, index: Option<usize>) -> Result<()> {
+        let parent_id = self.find_parent(task_ref).ok_or(Error::TaskUuidNotFound {})?;
+        let new_parent = self.get(new_parent_ref)?;
+        if self.is_in_hierarchy_of(new_parent_ref, task_ref) {
+            return Err(Error::MoveIntoSubtree { title: self.get(task_ref)?.title.clone() });
+        }
+        let len = new_parent.children.iter().filter(|child| *child != task_ref).count();
+        let index = index.unwrap_or(len);
+        if index > len {
+            return Err(Error::ChildOutOfIndex {});
+        }
+        self.modify_task(&parent_id, |parent| { parent.remove_child(task_ref); Ok(()) })?;
+        self.modify_task(new_parent_ref, |parent| { parent.insert_child(*task_ref, index); Ok(()) })?;
+        Ok(())
+    }
+
+    /// Parent of the task and the index of the task in its children.
+    pub fn position(&self, task_ref: &Uuid) -> Option<(Uuid, usize)> {
+        let parent_id = self.find_parent(task_ref)?;
+        let index = self.get(&parent_id).ok()?.children.iter().position(|child| child == task_ref)?;
+        Some((parent_id, index))
+    }
+
     /// Return the parent of the given task.
     /// 
     /// It will be None, if not found.
     pub fn find_parent(&self, task_ref: &Uuid) -> Option<Uuid> {
-        self.map.values().find(|task| task.children.iter().any(|child_id| child_id == task_ref)).map(|task| task.id)
+        self.cached_parents().get(task_ref).cloned()
     }
 
     /// Checks if the first given task is a child or the second task or if it's
@@ -286,6 +799,12 @@ impl Doc {
     /// # Panic
     /// Panics if the task id is not found.
     pub fn to_html(&self, task_ref: &Uuid) -> Result<String> {
+        self.to_html_with_attachments(task_ref, None)
+    }
+
+    /// Like `to_html` but local attachments are linked relative to the given
+    /// directory, as `dump_html` copies them there.
+    pub fn to_html_with_attachments(&self, task_ref: &Uuid, attachment_dir: Option<&str>) -> Result<String> {
         let mut html = String::new();
         let task = self.get(task_ref)?;
         html.push_str("<!doctype html><html><head><link rel=\"stylesheet\" href=\"https://stackpath.bootstrapcdn.com/bootstrap/4.3.1/css/bootstrap.min.css\" integrity=\"sha384-ggOyR0iXCbMQv3Xipma34MD+dH/1fQ784/j6cY/iJTQUOhcWr7x9JvoRxT2MZw1T\" crossorigin=\"anonymous\"></head><body><div class=\"container\">");
@@ -305,10 +824,49 @@ impl Doc {
             }
         });
 
-        let (done, all_subtasks) = self.progress_summary(task_ref)?;
+        let (done, all_subtasks) = self.progress_summary_recursive(task_ref, None)?;
         html.push_str(&format!("[{}/{}]", done, all_subtasks));
 
-        html.push_str(&markdown::to_html(&task.body));
+        if let Some(external_ref) = &task.external_ref {
+            if crate::external::is_url(external_ref) {
+                html.push_str(&format!("<p><a href=\"{0}\">{0}</a></p>", external_ref));
+            } else {
+                html.push_str(&format!("<p>{}</p>", external_ref));
+            }
+        }
+
+        if !task.properties.is_empty() {
+            html.push_str("<dl>");
+            for (key, value) in task.properties.iter() {
+                html.push_str(&format!("<dt>{}</dt><dd>{}</dd>", key, value));
+            }
+            html.push_str("</dl>");
+        }
+
+        let page = |task_id: &Uuid| format!("{}.html", task_id);
+        html.push_str(&crate::render::to_html(&self.expand_links(&task.body, page)));
+        if !task.notes.is_empty() {
+            html.push_str("<h5>Notes</h5><ul>");
+            for note in task.notes.iter() {
+                html.push_str(&format!("<li><small>{}</small>{}</li>",
+                    note.time.format("%Y-%m-%d %H:%M"), crate::render::to_html(&self.expand_links(&note.text, page))));
+            }
+            html.push_str("</ul>");
+        }
+        if !task.attachments.is_empty() {
+            html.push_str("<h5>Attachments</h5><ul>");
+            for attachment in task.attachments.iter() {
+                let href = if crate::external::is_url(attachment) {
+                    attachment.clone()
+                } else if let Some(attachment_dir) = attachment_dir {
+                    format!("{}/{}", attachment_dir, attachment_file_name(task_ref, attachment))
+                } else {
+                    format!("file://{}", attachment)
+                };
+                html.push_str(&format!("<li><a href=\"{}\">{}</a></li>", href, attachment));
+            }
+            html.push_str("</ul>");
+        }
         html.push_str("<hr/>");
         html.push_str("<ul>");
         for child in task.children.iter() {
@@ -316,11 +874,7 @@ impl Doc {
             html.push_str("<li><a href=\"");
             html.push_str(&child.to_string());
             html.push_str(".html\">");
-            html.push_str(&if let Some(ref progress) = child_task.progress { 
-                progress.to_string()
-            } else {
-                String::new()
-            });
+            html.push_str(&child_task.progress_label());
             html.push_str(" ");
             html.push_str(&child_task.title);
             html.push_str("</a></li>");
@@ -335,16 +889,30 @@ impl Doc {
     /// It counts the children which have a progress assigned which indicates that
     /// the task is not done in the first tuple entry and the count of children
     /// which contain any progress field.  Actually, this is the current progress
-    /// state of the task: todo/all.
+    /// state of the task: todo/all.  Cancelled children are not counted.
     pub fn progress_summary(&self, task_ref: &Uuid) -> Result<(i32, i32)> {
-        Ok(self.get(task_ref)?
-            .children.iter()
-            .filter_map(|child_ref| self.get(child_ref).ok())
-            .filter_map(|child| child.progress)
-            .fold((0, 0), |(acc_done, acc_sum), progress| (
-                acc_done + if progress.done() { 1 } else { 0 },
-                acc_sum + 1
-            )))
+        self.progress_summary_recursive(task_ref, Some(1))
+    }
+
+    /// Like `progress_summary`, but counts all tasks of the subtree up to
+    /// `max_depth` levels below the task, or all levels for `None`.
+    pub fn progress_summary_recursive(&self, task_ref: &Uuid, max_depth: Option<usize>) -> Result<(i32, i32)> {
+        self.get(task_ref)?;
+        Ok(self.cached_progress_summary(task_ref, max_depth, || {
+            let subtree = self.iter_subtree(task_ref);
+            let subtree = match max_depth {
+                Some(max_depth) => subtree.max_depth(max_depth + 1),
+                None => subtree,
+            };
+            subtree.skip(1)
+                .filter(|(_, task)| task.habit.is_none())
+                .filter_map(|(_, task)| task.progress)
+                .filter(|progress| progress.counts())
+                .fold((0, 0), |(acc_done, acc_sum), progress| (
+                    acc_done + if progress.done() { 1 } else { 0 },
+                    acc_sum + 1
+                ))
+        }))
     }
 
     /// Get the clock which is under the name.
@@ -355,8 +923,51 @@ impl Doc {
         self.clocks.get(clock_ref).cloned().ok_or(Error::ClockNotFound {})
     }
 
+    /// Get the clock which is currently running.
+    pub fn running_clock(&self) -> Option<Rc<Clock>> {
+        self.running_clock_in(None)
+    }
+
+    /// Get the clock which is running in the channel, `None` is the
+    /// default channel.
+    pub fn running_clock_in(&self, channel: Option<&str>) -> Option<Rc<Clock>> {
+        self.channel_clock_ref(channel).and_then(|clock_ref| self.clock(&clock_ref).ok())
+    }
+
+    /// Running clocks of all channels, the default channel first.
+    pub fn running_clocks(&self) -> Vec<Rc<Clock>> {
+        self.current_clock.iter().chain(self.channel_clocks.values())
+            .filter_map(|clock_ref| self.clock(clock_ref).ok())
+            .collect()
+    }
+
+    fn channel_clock_ref(&self, channel: Option<&str>) -> Option<Uuid> {
+        match channel {
+            None => self.current_clock,
+            Some(channel) => self.channel_clocks.get(channel).cloned(),
+        }
+    }
+
+    fn set_channel_clock_ref(&mut self, channel: Option<&str>, clock_ref: Option<Uuid>) {
+        match (channel, clock_ref) {
+            (None, _) => self.current_clock = clock_ref,
+            (Some(channel), Some(clock_ref)) => { self.channel_clocks.insert(channel.to_string(), clock_ref); },
+            (Some(channel), None) => { self.channel_clocks.remove(channel); },
+        }
+    }
+
+    /// Get the task of the most recent clock which has a task assigned.
+    pub fn last_clocked_task(&self) -> Option<Uuid> {
+        self.clocks.values()
+            .filter(|clock| clock.task_id.is_some())
+            .max()
+            .and_then(|clock| clock.task_id)
+    }
+
     /// Insert or replace the clock.
     pub fn upsert_clock(&mut self, clock: Rc<Clock>) {
+        self.journal.record(JournalEntry::Clock(clock.clone()));
+        self.cache.invalidate_clocks();
         self.clocks.insert(clock.id, clock);
     }
 
@@ -367,11 +978,22 @@ impl Doc {
     /// references to a clock which doesn't exist, it will return
     /// an error.
     pub fn clock_out(&mut self) -> Result<bool> {
-        if let Some(ref clock_ref) = self.current_clock {
+        self.clock_out_of(None)
+    }
+
+    /// Stops the clock of the channel, the clocks of the other channels
+    /// keep running.
+    /// 
+    /// # Error
+    /// Like `clock_out`.
+    pub fn clock_out_of(&mut self, channel: Option<&str>) -> Result<bool> {
+        if let Some(ref clock_ref) = self.channel_clock_ref(channel) {
             let mut clock = self.clock(clock_ref)?;
-            clock.set_end(Local::now());
+            clock.set_end(Utc::now());
+            let (clock_id, task_id) = (clock.id, clock.task_id);
             self.upsert_clock(clock);
-            self.current_clock = None;
+            self.set_channel_clock_ref(channel, None);
+            self.log_activity(task_id, ActivityEvent::ClockStopped { clock_id });
             Ok(true)
         } else {
             Ok(false)
@@ -384,30 +1006,111 @@ impl Doc {
     /// Return an error on an internal error if the clock out doesn't
     /// work.
     pub fn clock_new(&mut self) -> Result<Rc<Clock>> {
-        self.clock_out()?;
+        self.clock_new_in(None)
+    }
+
+    /// Generate a new clock in the channel, which replaces only the
+    /// running clock of this channel.
+    /// 
+    /// # Error
+    /// Like `clock_new`.
+    pub fn clock_new_in(&mut self, channel: Option<&str>) -> Result<Rc<Clock>> {
+        self.clock_out_of(channel)?;
+        if channel.is_none() {
+            self.end_break(Utc::now());
+        }
         let clock = Rc::new(Clock {
             id: Uuid::new_v4(),
-            start: Local::now(),
+            start: Utc::now(),
             end: None,
             comment: None,
-            task_id: None
+            task_id: None,
+            category: None,
+            tags: Vec::new(),
+            channel: channel.map(|channel| channel.to_string()),
         });
         self.upsert_clock(clock.clone());
-        self.current_clock = Some(clock.id);
+        self.set_channel_clock_ref(channel, Some(clock.id));
+        self.log_activity(None, ActivityEvent::ClockStarted { clock_id: clock.id });
         Ok(clock)
     }
 
-    /// Assign the given task to the active clock.
+    /// Add a finished clock for the given time range.
+    /// 
+    /// # Error
+    /// Returns an error if the end is before the start or if the task doesn't
+    /// exist.
+    pub fn add_clock_manual(&mut self, start: DateTime<Utc>, end: DateTime<Utc>,
+                            task_id: Option<Uuid>, comment: Option<String>) -> Result<Rc<Clock>> {
+        if end < start {
+            return Err(Error::InvalidClockRange {});
+        }
+        if let Some(task_id) = task_id {
+            self.get(&task_id)?;
+        }
+        let clock = Rc::new(Clock {
+            id: Uuid::new_v4(),
+            start,
+            end: Some(end),
+            comment,
+            task_id,
+            category: None,
+            tags: Vec::new(),
+            channel: None,
+        });
+        self.upsert_clock(clock.clone());
+        Ok(clock)
+    }
+
+    /// Change the running clock of the channel, returns false if no clock
+    /// runs in it.
     /// 
     /// # Error
     /// It will return an error if the internal state is wrong and the current
     /// clock id cannot be found.
-    pub fn clock_assign(&mut self, task_ref: Uuid) -> Result<()> {
-        if let Some(ref clock_ref) = self.current_clock {
-            let mut clock = self.clock(clock_ref)?;
-            clock.set_task_id(task_ref);
-            self.upsert_clock(clock);
+    pub fn modify_running_clock(&mut self, channel: Option<&str>, modify: impl FnOnce(&mut Rc<Clock>)) -> Result<bool> {
+        match self.channel_clock_ref(channel) {
+            Some(clock_ref) => {
+                let mut clock = self.clock(&clock_ref)?;
+                modify(&mut clock);
+                self.upsert_clock(clock);
+                Ok(true)
+            },
+            None => Ok(false),
         }
+    }
+
+    /// Stop the running clocks which are still running after the first
+    /// end of the day since their start, at the end of that day.
+    ///
+    /// Catches clocks which were forgotten over night before they skew the
+    /// reports.  The end of the day is in the time zone of the document.
+    pub fn auto_clock_out(&mut self, end_of_day: NaiveTime, now: DateTime<Utc>) -> Vec<Rc<Clock>> {
+        let mut stopped = Vec::new();
+        for mut clock in self.running_clocks() {
+            let start = self.timezone.localize(clock.start).naive_local();
+            let day = if start.time() < end_of_day { start.date() } else { start.date().succ() };
+            let end = match self.timezone.to_utc(day.and_time(end_of_day)) {
+                Some(end) if end < now => end,
+                _ => continue,
+            };
+            clock.set_end(end);
+            let (clock_id, task_id, channel) = (clock.id, clock.task_id, clock.channel.clone());
+            self.upsert_clock(clock.clone());
+            self.set_channel_clock_ref(channel.as_deref(), None);
+            self.log_activity(task_id, ActivityEvent::ClockAutoStopped { clock_id });
+            stopped.push(clock);
+        }
+        stopped
+    }
+
+    /// Change the running clock of the channel, returns false if no clock
+    /// 
+    /// # Error
+    /// It will return an error if the internal state is wrong and the current
+    /// clock id cannot be found.
+    pub fn clock_assign(&mut self, task_ref: Uuid) -> Result<()> {
+        self.modify_running_clock(None, |clock| { clock.set_task_id(task_ref); })?;
         Ok(())
     }
 
@@ -417,14 +1120,48 @@ impl Doc {
     /// It will return an error if the internal state is wrong and the current
     /// clock id cannot be found.
     pub fn clock_comment(&mut self, comment: impl ToString) -> Result<()> {
-        if let Some(ref clock_ref) = self.current_clock {
-            let mut clock = self.clock(clock_ref)?;
-            clock.set_comment(comment.to_string());
-            self.upsert_clock(clock);
-        }
+        self.modify_running_clock(None, |clock| { clock.set_comment(comment.to_string()); })?;
+        Ok(())
+    }
+
+    /// Set or remove the category of the active clock.
+    /// 
+    /// # Error
+    /// It will return an error if the internal state is wrong and the current
+    /// clock id cannot be found.
+    pub fn clock_category(&mut self, category: Option<String>) -> Result<()> {
+        self.modify_running_clock(None, |clock| { clock.set_category(category); })?;
+        Ok(())
+    }
+
+    /// Replace the tags of the active clock.
+    /// 
+    /// # Error
+    /// It will return an error if the internal state is wrong and the current
+    /// clock id cannot be found.
+    pub fn clock_tags(&mut self, tags: Vec<String>) -> Result<()> {
+        self.modify_running_clock(None, |clock| { clock.set_tags(tags); })?;
         Ok(())
     }
 
+    /// Get all pinned tasks which are part of the tree.
+    pub fn pinned_tasks(&self) -> Vec<Rc<Task>> {
+        self.map.values()
+            .filter(|task| task.pinned)
+            .filter(|task| self.is_in_hierarchy_of(&task.id, &self.root))
+            .cloned().collect()
+    }
+
+    /// Get all unfinished tasks which are due at the given time, the oldest first.
+    pub fn due_tasks(&self, now: DateTime<Local>) -> Vec<Rc<Task>> {
+        let mut tasks: Vec<Rc<Task>> = self.map.values()
+            .filter(|task| task.is_overdue(now))
+            .filter(|task| self.is_in_hierarchy_of(&task.id, &self.root))
+            .cloned().collect();
+        tasks.sort_by_key(|task| task.due);
+        tasks
+    }
+
     /// Get the clocks assigned to the given task.
     pub fn task_clock(&self, task_ref: &Uuid) -> Vec<Rc<Clock>> {
         self.clocks.values()
@@ -432,95 +1169,109 @@ impl Doc {
             .cloned().collect()
     }
     
-    /// Get the clocks for the given date.
-    pub fn day_clock(&self, date: Date<Local>, main_task: impl Into<Option<Uuid>>) -> Vec<Rc<Clock>> {
-        let main_task = main_task.into();
-        self.clocks.values()
-            .filter(|clock| clock.start.date() == date)
-            .filter(|clock|
-                if let Some(clock_task) = clock.task_id {
-                    if let Some(main_task) = main_task {
-                        self.is_in_hierarchy_of(&clock_task, &main_task)
-                    } else { true }
-                } else { true })
-            .cloned().collect()
+    /// Get the clocks which start at the given date in the time zone of
+    /// the document.
+    pub fn day_clock(&self, date: NaiveDate, main_task: impl Into<Option<Uuid>>) -> Vec<Rc<Clock>> {
+        self.range_clock(date, date, main_task)
+    }
+
+    /// Day of the clock start in the time zone of the document.
+    pub fn clock_date(&self, clock: &Clock) -> NaiveDate {
+        self.timezone.date(clock.start)
+    }
+
+    /// Clocks of the database if it has all changes, see `database`.
+    ///
+    /// The time range is a day longer on both ends since the days of the
+    /// time zone don't start at midnight in UTC.
+    #[cfg(feature = "sqlite")]
+    fn database_clocks(&self, start: NaiveDate, end: NaiveDate) -> Option<Vec<Rc<Clock>>> {
+        if !self.journal.is_empty() {
+            return None;
+        }
+        let database = self.database.as_ref()?;
+        let start = Utc.from_utc_date(&start.pred()).and_hms(0, 0, 0);
+        let end = Utc.from_utc_date(&end.succ().succ()).and_hms(0, 0, 0);
+        database.clocks_between(start, end).ok()
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn database_clocks(&self, _start: NaiveDate, _end: NaiveDate) -> Option<Vec<Rc<Clock>>> {
+        None
     }
 
-    /// Get the clocks of the given date.
-    pub fn range_clock(&self, start: Date<Local>, end: Date<Local>, main_task: impl Into<Option<Uuid>>) -> Vec<Rc<Clock>> {
+    /// Get the clocks which start between both dates (inclusive).
+    pub fn range_clock(&self, start: NaiveDate, end: NaiveDate, main_task: impl Into<Option<Uuid>>) -> Vec<Rc<Clock>> {
         let main_task = main_task.into();
-        self.clocks.values()
-            .filter(|clock| clock.start.date() >= start && clock.start.date() <= end)
+        let clocks = self.database_clocks(start, end)
+            .unwrap_or_else(|| self.clocks.values().cloned().collect());
+        clocks.into_iter()
+            .filter(|clock| (start..=end).contains(&self.clock_date(clock)))
             .filter(|clock|
                 if let Some(clock_task) = clock.task_id {
                     if let Some(main_task) = main_task {
                         self.is_in_hierarchy_of(&clock_task, &main_task)
                     } else { true }
                 } else { true })
-            .cloned().collect()
+            .collect()
     }
 }
 
+/// File name of a local attachment when it's copied into the HTML export.
+pub fn attachment_file_name(task_ref: &Uuid, attachment: &str) -> String {
+    let file_name = Path::new(attachment).file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("{}-{}", task_ref, file_name)
+}
 
+/// Result of an HTML export.
+#[derive(Clone, Debug, Default)]
+pub struct HtmlExport {
+    /// Written pages, parents before their children.
+    pub pages: Vec<PathBuf>,
 
-
-pub fn rec_print<T>(doc: &mut Doc, task_id: &Uuid, level: usize, max_depth: usize, callbacks: &mut CliCallbacks<T>) -> Result<()> {
-    if level >= max_depth {
-        return Ok(());
-    }
-    let task = doc.get(task_id)?;
-    for _ in 0..level {
-        callbacks.print(" ");
-    }
-    callbacks.print("* ");
-    callbacks.println(&format!("{} {}", task.id, task.title));
-    for child_id in task.children.iter() {
-        rec_print(doc, child_id, level + 1, max_depth, callbacks)?;
-    }
-    Ok(())
+    /// Local attachments which couldn't be copied with the reason.
+    pub failed_attachments: Vec<(String, String)>,
 }
 
-pub fn dump_html_rec<T>(doc: &Doc, dir: &Path, task_ref: &Uuid, callbacks: &mut CliCallbacks<T>) -> Result<()> {
-    let task = doc.get(task_ref)?;
-    for child in task.children.iter() {
-        dump_html_rec(doc, dir, child, callbacks)?;
-    }
-    let task_html = doc.to_html(task_ref)?;
+fn dump_html_task(doc: &Doc, dir: &Path, task: &Task, copy_attachments: bool, export: &mut HtmlExport) -> Result<()> {
+    let task_ref = &task.id;
+    let task_html = if copy_attachments {
+        let attachment_dir = dir.join("attachments");
+        for attachment in task.attachments.iter().filter(|attachment| !crate::external::is_url(attachment)) {
+            std::fs::create_dir_all(&attachment_dir).context(IO)?;
+            let target = attachment_dir.join(attachment_file_name(task_ref, attachment));
+            if let Err(err) = std::fs::copy(attachment, &target) {
+                export.failed_attachments.push((attachment.clone(), err.to_string()));
+            }
+        }
+        doc.to_html_with_attachments(task_ref, Some("attachments"))?
+    } else {
+        doc.to_html(task_ref)?
+    };
     let filename = dir.join(format!("{}.html", task_ref));
-    callbacks.println(filename.to_str().unwrap_or("N/A"));
-    let mut html_file = File::create(filename).context(IO)?;
+    let mut html_file = File::create(&filename).context(IO)?;
     html_file.write_all(task_html.as_bytes()).context(IO)?;
+    export.pages.push(filename);
     Ok(())
 }
 
-pub fn dump_html<T>(doc: &Doc, dir: &Path, task_ref: &Uuid, callbacks: &mut CliCallbacks<T>) -> Result<()> {
+/// Write the task and its subtasks as HTML files into the directory.
+///
+/// With `copy_attachments`, local attachments are copied into the
+/// `attachments` directory of the export.
+pub fn dump_html(doc: &Doc, dir: &Path, task_ref: &Uuid, copy_attachments: bool) -> Result<HtmlExport> {
     std::fs::create_dir_all(dir).context(IO)?;
-    dump_html_rec(doc, dir, task_ref, callbacks)?;
+    let mut export = HtmlExport::default();
+    doc.get(task_ref)?;
+    for (_, task) in doc.iter_subtree(task_ref) {
+        dump_html_task(doc, dir, &task, copy_attachments, &mut export)?;
+    }
     let filename = dir.join("index.html");
     let mut index_file = File::create(filename).context(IO)?;
     index_file.write_all(b"<!doctype html><html><head></head><body><a href=\"").context(IO)?;
     index_file.write_all(task_ref.to_string().as_bytes()).context(IO)?;
     index_file.write_all(b".html\">Index</a></body></html>").context(IO)?;
-    Ok(())
+    Ok(export)
 }
-
-pub fn vim_edit_task<T, C: CliCallbacks<T>>(mut task: Rc<Task>, callbacks: &mut C) -> Result<Rc<Task>> {
-    let serialized_task = {   
-        let mut serialized_task = String::new();
-        serialized_task.push_str(&task.title);
-        serialized_task.push_str("\n\n");
-        serialized_task.push_str(&task.body);
-        serialized_task
-    };
-    let content = callbacks.edit_string(serialized_task);
-    let mut lines = content.lines();
-    let title = if let Some(title) = lines.next() {
-        title
-    } else {
-        return Err(Error::TaskSerializeError { msg: "Couldn't find a title".to_string() })
-    };
-    let body = lines.fold(String::new(), |mut acc: String, item| { acc.push_str(&item); acc.push('\n'); acc});
-    task.set_title(title).set_body(body.trim());
-    Ok(task)
-}
-
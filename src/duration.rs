@@ -0,0 +1,105 @@
+//! Parsing and formatting of durations like `1h30m`, `90m` or `2d 4h`.
+//!
+//! `format_duration` writes durations in a form which `parse_duration`
+//! reads back, so they can be copied from listings into commands.
+
+use crate::error::*;
+
+/// Seconds of one unit like `h` or `hours`.
+fn unit_seconds(unit: &str) -> Option<i64> {
+    Some(match unit {
+        "w" | "week" | "weeks" => 7 * 24 * 3600,
+        "d" | "day" | "days" => 24 * 3600,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        _ => return None,
+    })
+}
+
+/// Parse a duration like `40h`, `1h30m`, `2d 4h`, `90 minutes`, `-15m`
+/// or `1:30`.
+pub fn parse_duration(string: &str) -> Result<chrono::Duration> {
+    let error = || Error::InvalidDuration { text: string.to_string() };
+    let trimmed = string.trim();
+    let (negative, rest) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, trimmed),
+    };
+    let seconds = if let Some((hours, minutes)) = rest.split_once(':') {
+        let hours: i64 = hours.parse().map_err(|_| error())?;
+        let minutes: i64 = minutes.parse().map_err(|_| error())?;
+        hours * 3600 + minutes * 60
+    } else {
+        let mut seconds: i64 = 0;
+        let mut chars = rest.chars().peekable();
+        let mut parts = 0;
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                number.push(c);
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let mut unit = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_alphabetic()) {
+                unit.extend(c.to_lowercase());
+            }
+            let value: i64 = number.parse().map_err(|_| error())?;
+            let part = unit_seconds(&unit).and_then(|unit| unit.checked_mul(value)).ok_or_else(error)?;
+            seconds = seconds.checked_add(part).ok_or_else(error)?;
+            parts += 1;
+        }
+        if parts == 0 {
+            return Err(error());
+        }
+        seconds
+    };
+    let duration = chrono::Duration::seconds(seconds);
+    Ok(if negative { -duration } else { duration })
+}
+
+/// Format a duration like `1d 2h 30m 5s` without the zero parts.
+pub fn format_duration(duration: chrono::Duration) -> String {
+    if duration < chrono::Duration::zero() {
+        return format!("-{}", format_duration(-duration));
+    }
+    let parts = [
+        (duration.num_days(), "d"),
+        (duration.num_hours() % 24, "h"),
+        (duration.num_minutes() % 60, "m"),
+        (duration.num_seconds() % 60, "s"),
+    ];
+    let parts: Vec<String> = parts.iter()
+        .filter(|(value, _)| *value != 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect();
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Format a duration compact like `2h 15m`, days are counted as hours.
+pub fn short_duration(duration: chrono::Duration) -> String {
+    let hours = duration.num_hours();
+    let minutes = duration.num_minutes() % 60;
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Format a duration with its sign like `+1h 5m` or `-0m`.
+pub fn signed_duration(duration: chrono::Duration) -> String {
+    if duration < chrono::Duration::zero() {
+        format!("-{}", short_duration(-duration))
+    } else {
+        format!("+{}", short_duration(duration))
+    }
+}
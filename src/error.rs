@@ -10,9 +10,18 @@ pub enum Error {
     #[snafu(display("Serde Serialize Error: {}", source))]
     SerdeSerializationError { source: serde_json::error::Error },
 
+    #[snafu(display("CBOR Serialize Error: {}", source))]
+    CborSerializationError { source: ciborium::ser::Error<std::io::Error> },
+
+    #[snafu(display("CBOR Deserialize Error: {}", source))]
+    CborDeserializationError { source: ciborium::de::Error<std::io::Error> },
+
     #[snafu(display("Task UUID not found"))]
     TaskUuidNotFound {  },
 
+    #[snafu(display("Can't move a task into its own subtree: {}", title))]
+    MoveIntoSubtree { title: String },
+
      #[snafu(display("Child out of index"))]
     ChildOutOfIndex {  },
 
@@ -36,6 +45,105 @@ pub enum Error {
 
     #[snafu(display("{}",msg))]
     TaskSerializeError { msg: String },
+
+    #[snafu(display("Unknown progress: {}", name))]
+    UnknownProgress { name: String },
+
+    #[snafu(display("Unknown workflow state: {}", name))]
+    UnknownWorkflowState { name: String },
+
+    #[snafu(display("Transition from {} to {} is not allowed", from, to))]
+    TransitionNotAllowed { from: String, to: String },
+
+    #[snafu(display("Import failed: {}", msg))]
+    ImportError { msg: String },
+
+    #[snafu(display("Saving cancelled, the file changed on disk"))]
+    SaveCancelled {  },
+
+    #[snafu(display("Clock ends before it starts"))]
+    InvalidClockRange {  },
+
+    #[snafu(display("Invalid time zone, use local, utc or an offset like +02:00: {}", name))]
+    InvalidTimeZone { name: String },
+
+    #[snafu(display("Invalid duration, use something like 1h30m or 90m: {}", text))]
+    InvalidDuration { text: String },
+
+    #[snafu(display("Unknown style: {}", name))]
+    UnknownStyle { name: String },
+
+    #[snafu(display("The document is read-only"))]
+    ReadOnly {  },
+
+    #[snafu(display("Invalid query: {}", msg))]
+    QueryError { msg: String },
+
+    #[snafu(display("Unknown sort key, use title, due, prio or progress: {}", name))]
+    UnknownSortKey { name: String },
+
+    #[snafu(display("Invalid habit, use daily, weekly, <n>/day or <n>/week: {}", text))]
+    InvalidHabit { text: String },
+
+    #[snafu(display("Task is not a habit: {}", title))]
+    NotAHabit { title: String },
+
+    #[snafu(display("No CalDAV calendar configured, set it with caldav <url> [user]"))]
+    CaldavNotConfigured {},
+
+    #[snafu(display("CalDAV sync failed: {}", msg))]
+    CaldavError { msg: String },
+
+    #[snafu(display("Remote storage failed: {}", msg))]
+    StorageError { msg: String },
+
+    #[snafu(display("Unknown snapshot: {}", name))]
+    UnknownSnapshot { name: String },
+
+    #[snafu(display("Invalid snapshot name: {}", name))]
+    InvalidSnapshotName { name: String },
+
+    #[snafu(display("Config error: {}", msg))]
+    ConfigError { msg: String },
+
+    #[snafu(display("Unknown hook event: {}", name))]
+    UnknownHookEvent { name: String },
+
+    #[snafu(display("Hook `{}` failed: {}", command, msg))]
+    HookFailed { command: String, msg: String },
+
+    #[snafu(display("Invalid rate, use <amount> <currency> like 85 EUR or $120: {}", text))]
+    InvalidRate { text: String },
+
+    #[snafu(display("Invalid rounding, use a duration and optionally up, nearest or down like 15m or 6m nearest: {}", text))]
+    InvalidRounding { text: String },
+
+    #[snafu(display("The command broke the document and was undone: {}", msg))]
+    InvariantViolated { msg: String },
+
+    #[snafu(display("Couldn't use the edit file {}: {}", path, source))]
+    EditFile { path: String, source: std::io::Error },
+
+    #[snafu(display("The editor {} failed, the edit is cancelled: {}", program, msg))]
+    EditorFailed { program: String, msg: String },
+
+    #[snafu(display("The edited text is empty, nothing was changed"))]
+    EmptyEdit {},
+
+    #[snafu(display("Link {} doesn't exist, the task has {} links", index, count))]
+    LinkNotFound { index: usize, count: usize },
+
+    #[snafu(display("Couldn't open the link with {}: {}", program, msg))]
+    OpenFailed { program: String, msg: String },
+
+    #[snafu(display("The task has no parent: {}", title))]
+    NoParent { title: String },
+
+    #[snafu(display("Not a child of the task: {}", title))]
+    NotAChild { title: String },
+
+    #[snafu(display("The task has subtasks and can't become a checklist line: {}", title))]
+    HasSubtasks { title: String },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
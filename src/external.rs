@@ -0,0 +1,50 @@
+//! References from tasks to external issue trackers.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use crate::error::*;
+
+/// Looks up the state of an issue in an external tracker.
+///
+/// This is the hook point for `sync-status`.  Implementations decide which
+/// references they understand, like GitHub URLs or Jira keys.
+pub trait ExternalStatusProvider {
+    /// Return the progress of the referenced issue or None if the reference
+    /// is not handled by this provider.
+    fn status(&self, external_ref: &str) -> Result<Option<Progress>>;
+}
+
+/// All available status providers.
+pub fn status_providers() -> Vec<Box<dyn ExternalStatusProvider>> {
+    Vec::new()
+}
+
+/// Check if the external reference is a URL which can be linked to.
+pub fn is_url(external_ref: &str) -> bool {
+    external_ref.starts_with("http://") || external_ref.starts_with("https://")
+}
+
+impl Doc {
+    /// Update the progress of all tasks with an external reference.
+    ///
+    /// The first provider which knows the reference wins.  Returns the
+    /// number of updated tasks.
+    pub fn sync_external_status(&mut self, providers: &[Box<dyn ExternalStatusProvider>]) -> Result<usize> {
+        let linked: Vec<(uuid::Uuid, String)> = self.map.values()
+            .filter_map(|task| task.external_ref.clone().map(|external_ref| (task.id, external_ref)))
+            .collect();
+        let mut count = 0;
+        for (task_id, external_ref) in linked {
+            for provider in providers.iter() {
+                if let Some(progress) = provider.status(&external_ref)? {
+                    if self.get(&task_id)?.progress != Some(progress) {
+                        self.set_progress(&task_id, progress)?;
+                        count += 1;
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,94 @@
+//! Generated documents of any size for benchmarks and tests.
+//!
+//! The same seed always gives the same document, including the UUIDs.
+
+use crate::clock::Clock;
+use crate::doc::Doc;
+use crate::tasks::{Progress, Task};
+use crate::timezone::DisplayZone;
+use chrono::prelude::*;
+use std::rc::Rc;
+use uuid::{Builder, Uuid, Variant, Version};
+
+/// Start of the year in which the generated clocks start, as Unix time
+/// (2020-01-01 UTC).
+const GENERATED_EPOCH: i64 = 1_577_836_800;
+
+const PROGRESS: &[Option<Progress>] = &[None, Some(Progress::Todo), Some(Progress::Work), Some(Progress::Done), Some(Progress::Waiting)];
+
+/// SplitMix64, good enough for test data and without a dependency.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn uuid(&mut self) -> Uuid {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.next().to_le_bytes());
+        bytes[8..].copy_from_slice(&self.next().to_le_bytes());
+        Builder::from_bytes(bytes).set_variant(Variant::RFC4122).set_version(Version::Random).build()
+    }
+}
+
+impl Doc {
+    /// Document with the root and `n` more tasks at random places in the
+    /// tree and one finished clock for every second task, spread over 2020.
+    ///
+    /// Tasks and clocks are inserted directly, so there is no activity and
+    /// the journal is empty.  Days are in UTC, so `day_clock` gives the same
+    /// clocks on every machine.
+    pub fn generate_random(seed: u64, n: usize) -> Doc {
+        let mut rng = SplitMix64(seed);
+        let mut doc = Doc::new();
+        doc.timezone = DisplayZone::Utc;
+        let mut tasks = Vec::with_capacity(n + 1);
+        let mut root = Task::new();
+        root.id = rng.uuid();
+        root.created = None;
+        doc.root = root.id;
+        tasks.push(root);
+        for i in 1..=n {
+            let parent = rng.below(i as u64) as usize;
+            let mut task = Task::new();
+            task.id = rng.uuid();
+            task.title = format!("Task {}", i);
+            if rng.below(4) == 0 {
+                task.body = format!("Notes about task {}", i);
+            }
+            task.progress = PROGRESS[rng.below(PROGRESS.len() as u64) as usize];
+            task.created = Some(Local.timestamp(GENERATED_EPOCH + rng.below(365 * 86400) as i64, 0));
+            tasks[parent].children.push(task.id);
+            tasks.push(task);
+        }
+        for _ in 0..n / 2 {
+            let start = Utc.timestamp(GENERATED_EPOCH + rng.below(365 * 86400) as i64, 0);
+            let minutes = 15 + rng.below(240) as i64;
+            let task_id = tasks[1 + rng.below(n as u64) as usize].id;
+            let clock = Clock {
+                id: rng.uuid(),
+                start,
+                end: Some(start + chrono::Duration::minutes(minutes)),
+                comment: None,
+                task_id: Some(task_id),
+                category: None,
+                tags: Vec::new(),
+                channel: None,
+            };
+            doc.clocks.insert(clock.id, Rc::new(clock));
+        }
+        doc.map = tasks.into_iter().map(|task| (task.id, Rc::new(task))).collect();
+        doc.invalidate_cache();
+        doc
+    }
+}
@@ -0,0 +1,180 @@
+//! Habits, small tasks which recur every day or week.
+//!
+//! A habit is open again right after it's marked as done, the completions
+//! are read from the activity log.  Habits are left out of progress
+//! summaries, throughput statistics and the planner.
+
+use crate::calendar::start_of_week;
+use crate::doc::Doc;
+use crate::activity::ActivityEvent;
+use crate::error::*;
+use chrono::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+/// Number of weeks for the completion rate.
+pub const RATE_WEEKS: i64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HabitPeriod {
+    Day,
+    Week,
+}
+
+impl std::fmt::Display for HabitPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            HabitPeriod::Day => "day",
+            HabitPeriod::Week => "week",
+        })
+    }
+}
+
+/// How often a habit should be done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Habit {
+    pub period: HabitPeriod,
+
+    /// Completions per period.
+    pub target: u32,
+}
+
+impl std::fmt::Display for Habit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.target, self.period) {
+            (1, HabitPeriod::Day) => f.write_str("daily"),
+            (1, HabitPeriod::Week) => f.write_str("weekly"),
+            (target, period) => write!(f, "{}/{}", target, period),
+        }
+    }
+}
+
+impl std::str::FromStr for Habit {
+    type Err = Error;
+
+    /// Parse `daily`, `weekly` or a target like `3/week` or `2/day`.
+    fn from_str(string: &str) -> Result<Habit> {
+        let error = || Error::InvalidHabit { text: string.to_string() };
+        let (target, period) = match string.to_lowercase().as_str() {
+            "daily" => (1, HabitPeriod::Day),
+            "weekly" => (1, HabitPeriod::Week),
+            other => {
+                let (target, period) = other.split_once('/').ok_or_else(error)?;
+                let period = match period {
+                    "day" | "d" => HabitPeriod::Day,
+                    "week" | "w" => HabitPeriod::Week,
+                    _ => return Err(error()),
+                };
+                (target.parse().map_err(|_| error())?, period)
+            },
+        };
+        if target == 0 {
+            return Err(error());
+        }
+        Ok(Habit { period, target })
+    }
+}
+
+impl From<Habit> for String {
+    fn from(habit: Habit) -> String {
+        habit.to_string()
+    }
+}
+
+impl TryFrom<String> for Habit {
+    type Error = Error;
+
+    fn try_from(string: String) -> Result<Habit> {
+        string.parse()
+    }
+}
+
+impl Habit {
+    /// First day of the period which contains the date.
+    pub fn period_start(&self, date: NaiveDate, week_start: Weekday) -> NaiveDate {
+        match self.period {
+            HabitPeriod::Day => date,
+            HabitPeriod::Week => start_of_week(date, week_start),
+        }
+    }
+
+    fn period_length(&self) -> chrono::Duration {
+        match self.period {
+            HabitPeriod::Day => chrono::Duration::days(1),
+            HabitPeriod::Week => chrono::Duration::weeks(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HabitStats {
+    pub task_id: Uuid,
+    pub habit: Habit,
+
+    /// Completions in the current period.
+    pub current: u32,
+
+    /// Periods in a row which reached the target, the current one only
+    /// counts once it reached the target.
+    pub streak: u32,
+
+    /// Share of the periods of the last `RATE_WEEKS` weeks before the
+    /// current one which reached the target, None without such periods.
+    pub rate: Option<f64>,
+}
+
+impl Doc {
+    /// Days on which the task was marked as done, oldest first.
+    pub fn completion_dates(&self, task_ref: &Uuid) -> Vec<NaiveDate> {
+        self.activity.iter()
+            .filter(|entry| entry.task_id == Some(*task_ref))
+            .filter(|entry| matches!(entry.event, ActivityEvent::ProgressChanged { to: Some(to), .. } if to.done()))
+            .map(|entry| self.timezone.date(entry.time.with_timezone(&Utc)))
+            .collect()
+    }
+
+    /// Habits in the subtree of the task.
+    pub fn habits(&self, task_ref: &Uuid) -> Vec<Uuid> {
+        self.iter_subtree(task_ref)
+            .filter(|(_, task)| task.habit.is_some())
+            .map(|(_, task)| task.id)
+            .collect()
+    }
+
+    /// Streak and completion rate of the habit up to the given day.
+    ///
+    /// # Error
+    /// Fails if the task doesn't exist or isn't a habit.
+    pub fn habit_stats(&self, task_ref: &Uuid, today: NaiveDate, week_start: Weekday) -> Result<HabitStats> {
+        let task = self.get(task_ref)?;
+        let habit = task.habit.ok_or_else(|| Error::NotAHabit { title: task.title.clone() })?;
+        let completions = self.completion_dates(task_ref);
+        let count = |start: NaiveDate| completions.iter()
+            .filter(|date| habit.period_start(**date, week_start) == start)
+            .count() as u32;
+
+        let current_start = habit.period_start(today, week_start);
+        let current = count(current_start);
+        let mut streak = if current >= habit.target { 1 } else { 0 };
+        let first = completions.first().map(|date| habit.period_start(*date, week_start));
+        let mut start = current_start - habit.period_length();
+        while first.is_some_and(|first| start >= first) && count(start) >= habit.target {
+            streak += 1;
+            start -= habit.period_length();
+        }
+
+        let created = task.created.map(|created| habit.period_start(self.timezone.date(created.with_timezone(&Utc)), week_start));
+        let rate_start = current_start - chrono::Duration::weeks(RATE_WEEKS);
+        let periods: Vec<NaiveDate> = std::iter::successors(Some(current_start - habit.period_length()), |start| Some(*start - habit.period_length()))
+            .take_while(|start| *start >= rate_start && created.is_none_or(|created| *start >= created))
+            .collect();
+        let rate = if periods.is_empty() {
+            None
+        } else {
+            Some(periods.iter().filter(|start| count(**start) >= habit.target).count() as f64 / periods.len() as f64)
+        };
+        Ok(HabitStats { task_id: *task_ref, habit, current, streak, rate })
+    }
+}
@@ -1,12 +1,23 @@
 use crate::doc::*;
 use crate::clock::*;
+use crate::tasks::*;
+use crate::tree::Subtree;
+use crate::state::{State, OutputFormat};
+use crate::activity::*;
+use crate::statics::LONG_CLOCK_HOURS;
 use crate::error::*;
-use crate::DurationPrint;
-use crate::cli::CliCallbacks;
+use crate::cli::{CliCallbacks, Choice, Dialog};
+use crate::output::Style;
+use crate::hooks::{HookCall, HookEvent};
 use std::rc::Rc;
-use chrono::Local;
-use chrono::TimeZone;
-use chrono::Date;
+use std::collections::HashMap;
+use crate::timezone::DisplayZone;
+use crate::calendar::*;
+use crate::worktime::required_break;
+use crate::text::*;
+pub use crate::duration::*;
+use chrono::{Local, NaiveDate, Utc, Weekday};
+use uuid::Uuid;
 
 pub fn fold_strings<'a>(sep: &'a str) -> impl FnMut(String, (String, usize)) -> String + 'a {
     move | mut acc, (item, i) | {
@@ -24,6 +35,16 @@ pub fn join_strings(strings: impl Iterator<Item=String>, sep: &str) -> String {
         .fold(String::new(), fold_strings(sep))
 }
 
+/// Render a horizontal bar whose length is proportional to value/max.
+pub fn bar(value: i64, max: i64, width: usize) -> String {
+    let len = if max > 0 {
+        (value.max(0) as usize * width + max as usize / 2) / max as usize
+    } else {
+        0
+    };
+    "#".repeat(len.min(width))
+}
+
 pub fn parse_time(string: &str) -> chrono::ParseResult<chrono::NaiveTime> {
     let time = if let Ok(time) = chrono::NaiveTime::parse_from_str(string, "%H:%M:%S") {
         time
@@ -33,6 +54,50 @@ pub fn parse_time(string: &str) -> chrono::ParseResult<chrono::NaiveTime> {
     Ok(time)
 }
 
+/// Print the tasks as indented list with their ids and optionally the
+/// durations of their subtrees.
+pub fn rec_print<T>(tasks: Subtree, durations: Option<&HashMap<Uuid, chrono::Duration>>, callbacks: &mut dyn CliCallbacks<T>) {
+    let durations = match durations {
+        Some(durations) => durations,
+        None => {
+            for (depth, task) in tasks {
+                callbacks.println(&format!("{}* {} {}", " ".repeat(depth), task.id, task.title));
+            }
+            return;
+        }
+    };
+    // The durations start in one column, behind the indented titles.
+    let tasks: Vec<_> = tasks.collect();
+    let width = tasks.iter().map(|(depth, task)| depth + display_width(&task.title)).max().unwrap_or(0)
+        .min(TITLE_COLUMN_WIDTH);
+    for (depth, task) in tasks.iter() {
+        match durations.get(&task.id) {
+            Some(duration) => callbacks.println(&format!("{}* {} {} ({})", " ".repeat(*depth), task.id,
+                fit_to_width(&task.title, width.saturating_sub(*depth).max(1)), format_duration(*duration))),
+            None => callbacks.println(&format!("{}* {} {}", " ".repeat(*depth), task.id, task.title)),
+        }
+    }
+}
+
+/// Let the user edit title and body of the task in the editor.
+pub fn vim_edit_task<T, C: CliCallbacks<T>>(mut task: Rc<Task>, callbacks: &mut C) -> Result<Rc<Task>> {
+    let content = callbacks.edit_string(task.edit_text())?;
+    let (title, body) = Task::parse_edit_text(&content);
+    if title.is_empty() && body.is_empty() {
+        return Err(Error::EmptyEdit {});
+    }
+    task.set_title(title).set_body(body);
+    Ok(task)
+}
+
+/// Titles of the task and its parents like ` -> project -> api`.
+pub fn task_path_string(doc: &Doc, task_id: &Uuid) -> String {
+    join_strings(doc.path(task_id).iter().rev()
+        .map(|task_id| doc.get(task_id))
+        .filter_map(|task| task.ok())
+        .map(|task| task.title.clone()), " -> ")
+}
+
 pub fn display_clocks<T>(clocks: &[Rc<Clock>], doc: &Doc, callbacks: &mut CliCallbacks<T>) {
     let overall_duration = clocks.iter()
         .map(|clock| clock.duration())
@@ -42,26 +107,25 @@ pub fn display_clocks<T>(clocks: &[Rc<Clock>], doc: &Doc, callbacks: &mut CliCal
     let mut current_day = None;
     let mut day_duration = chrono::Duration::zero();
     for clock in clocks.iter() {
-        let start = &clock.start;
-        let end = clock.end.map(|end| format!("{}", end)).unwrap_or_else(|| "(none)".to_string());
+        let start = doc.timezone.localize(clock.start);
+        let end = clock.end.map(|end| format!("{}", doc.timezone.localize(end))).unwrap_or_else(|| "(none)".to_string());
         let comment = clock.comment.clone().map(|comment| comment).unwrap_or_else(|| "(none)".to_string());
         let task_str = if let Some(task_id) = clock.task_id {
-            let path = doc.path(&task_id);
-            join_strings(path.iter().rev()
-                .map(|task_id| doc.get(task_id))
-                .filter_map(|task| task.ok())
-                .map(|task| task.title.clone()), " -> ")
+            task_path_string(doc, &task_id)
         } else {
             "(none)".to_string()
         };
-        let day = start.date();
+        let day = doc.clock_date(clock);
         if Some(day) != current_day {
             callbacks.println(&format!("--- {} ---", day));
         }
         callbacks.println(&format!("{} - {}:\n Task: {}\n Comment: {}", start, end, task_str, comment));
+        if !clock.tags.is_empty() {
+            callbacks.println(&format!(" Tags: {}", clock.tags.join(", ")));
+        }
         if Some(day) != current_day {
             if current_day.is_some() {
-                callbacks.println(&format!("Day duration: {}", day_duration.print()));
+                callbacks.println(&format!("Day duration: {}", format_duration(day_duration)));
                 callbacks.println("");
             }
             day_duration = chrono::Duration::zero();
@@ -69,24 +133,503 @@ pub fn display_clocks<T>(clocks: &[Rc<Clock>], doc: &Doc, callbacks: &mut CliCal
         }
         day_duration = day_duration + clock.duration();
     }
-    callbacks.println(&format!("Day duration: {}", day_duration.print()));
+    callbacks.println(&format!("Day duration: {}", format_duration(day_duration)));
     callbacks.println("");
-    callbacks.println(&format!("Overall duration in time range: {}", overall_duration.print()));
+    callbacks.println(&format!("Overall duration in time range: {}", format_duration(overall_duration)));
+    display_budget_warnings(&clocks, doc, callbacks);
 }
 
-pub fn parse_date(date_str: &str) -> CliResult<Date<Local>> {
-    Ok(if date_str.starts_with('-') {
-        match (&date_str[1..]).parse::<i64>() {
-            Ok(offset) => Local::today() - chrono::Duration::days(offset),
-            Err(err) => return Err(CliError::ParseError { msg: format!("{}", err) }),
-        }
-    } else if let Ok(naive_date) = chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
-        if let Some(date) = Local.from_local_date(&naive_date).earliest() {
-            date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartMode {
+    Day,
+    Task,
+    /// Clock category or comment prefix, see `Clock::group`.
+    Group,
+    /// Clock tags, clocks with several tags count for each of them.
+    Tag,
+    Channel,
+}
+
+/// Find a `--chart`, `--chart=task`, `--chart=group`, `--chart=tag` or
+/// `--chart=channel` flag in the command line.
+pub fn chart_mode(cmd: &str) -> Option<ChartMode> {
+    cmd.split(' ').rev().find_map(|arg| match arg {
+        "--chart" | "--chart=day" => Some(ChartMode::Day),
+        "--chart=task" => Some(ChartMode::Task),
+        "--chart=group" => Some(ChartMode::Group),
+        "--chart=tag" => Some(ChartMode::Tag),
+        "--chart=channel" => Some(ChartMode::Channel),
+        _ => None,
+    })
+}
+
+/// Keep only the clocks with the tag of a `--tag=<tag>` flag, if the
+/// command line has one.
+pub fn filter_clocks_by_tag(clocks: Vec<Rc<Clock>>, cmd: &str) -> Vec<Rc<Clock>> {
+    match cmd.split(' ').find_map(|arg| arg.strip_prefix("--tag=")) {
+        Some(tag) => clocks.into_iter().filter(|clock| clock.has_tag(tag)).collect(),
+        None => clocks,
+    }
+}
+
+/// Take a `--channel <name>` or `--channel=<name>` option out of the
+/// command line.
+pub fn take_channel(cmd: &str) -> (Option<String>, String) {
+    let mut channel = None;
+    let mut rest = Vec::new();
+    let mut args = cmd.split(' ');
+    while let Some(arg) = args.next() {
+        if arg == "--channel" {
+            channel = args.next().map(|name| name.to_string());
+        } else if let Some(name) = arg.strip_prefix("--channel=") {
+            channel = Some(name.to_string());
         } else {
-            return Err(CliError::ParseError { msg: "Couldn't apply timezone".to_string() })
+            rest.push(arg);
+        }
+    }
+    (channel, rest.join(" "))
+}
+
+/// Channel for the clock functions of `Doc`, `None` for the default
+/// channel.
+pub fn doc_channel(channel: &Option<String>) -> Option<&str> {
+    channel.as_deref().filter(|channel| *channel != DEFAULT_CHANNEL)
+}
+
+/// Keep only the clocks of the channel, if there is one.
+pub fn filter_clocks_by_channel(clocks: Vec<Rc<Clock>>, channel: &Option<String>) -> Vec<Rc<Clock>> {
+    match channel {
+        Some(channel) => clocks.into_iter().filter(|clock| clock.channel_name() == channel).collect(),
+        None => clocks,
+    }
+}
+
+/// Check if the command line contains the flag like `--dry-run`.
+pub fn has_flag(cmd: &str, flag: &str) -> bool {
+    cmd.split(' ').any(|arg| arg == flag)
+}
+
+/// Ask for confirmation unless the command line contains `--yes`.
+pub fn confirmed<T>(cmd: &str, prompt: &str, callbacks: &mut dyn CliCallbacks<T>) -> bool {
+    has_flag(cmd, "--yes") || callbacks.confirm(prompt)
+}
+
+/// Check if the output should be JSON, either set globally or by the
+/// `--json` flag.
+pub fn json_output(state: &State, cmd: &str) -> bool {
+    state.output == OutputFormat::Json || has_flag(cmd, "--json")
+}
+
+/// Print the value as pretty JSON.
+pub fn print_json<T>(value: &serde_json::Value, callbacks: &mut dyn CliCallbacks<T>) {
+    callbacks.println(&serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+/// Print the clocks as a JSON array.
+pub fn print_clocks_json<T>(clocks: &[Rc<Clock>], doc: &Doc, callbacks: &mut dyn CliCallbacks<T>) -> Result<()> {
+    let clocks = clocks.iter()
+        .map(|clock| doc.clock_json(clock))
+        .collect::<Result<Vec<_>>>()?;
+    print_json(&serde_json::Value::Array(clocks), callbacks);
+    Ok(())
+}
+
+/// Arguments of a command line without the command itself and without flags.
+pub fn positional_args(cmd: &str) -> Vec<&str> {
+    cmd.split(' ').skip(1).filter(|arg| !arg.starts_with("--") && !arg.is_empty()).collect()
+}
+
+/// Print the durations of the clocks per day or per task as ASCII bars.
+pub fn display_clock_chart<T>(clocks: &[Rc<Clock>], doc: &Doc, mode: ChartMode, callbacks: &mut dyn CliCallbacks<T>) {
+    let mut clocks = clocks.to_vec();
+    clocks.sort();
+    let mut rows: Vec<(String, chrono::Duration)> = Vec::new();
+    for clock in clocks.iter() {
+        let labels = match mode {
+            ChartMode::Day => vec![doc.clock_date(clock).format(&format!("{} %a", callbacks.date_format())).to_string()],
+            ChartMode::Task => vec![clock.task_id
+                .and_then(|task_id| doc.get(&task_id).ok())
+                .map(|task| task.title.clone())
+                .unwrap_or_else(|| "(none)".to_string())],
+            ChartMode::Group => vec![clock.group().unwrap_or_else(|| "(none)".to_string())],
+            ChartMode::Tag if clock.tags.is_empty() => vec!["(none)".to_string()],
+            ChartMode::Tag => clock.tags.clone(),
+            ChartMode::Channel => vec![clock.channel_name().to_string()],
+        };
+        for label in labels {
+            if let Some(row) = rows.iter_mut().find(|(row_label, _)| *row_label == label) {
+                row.1 = row.1 + clock.duration();
+            } else {
+                rows.push((label, clock.duration()));
+            }
+        }
+    }
+    if mode != ChartMode::Day {
+        rows.sort_by(|(_, a), (_, b)| b.cmp(a));
+    }
+    let max = rows.iter().map(|(_, duration)| duration.num_seconds()).max().unwrap_or(0);
+    let label_width = column_width(rows.iter().map(|(label, _)| label.as_str()), TITLE_COLUMN_WIDTH);
+    for (label, duration) in rows.iter() {
+        callbacks.println(&format!("{} {:>14} {}",
+            fit_to_width(label, label_width), format_duration(*duration), bar(duration.num_seconds(), max, 40)));
+    }
+    display_budget_warnings(&clocks, doc, callbacks);
+}
+
+/// Tasks in the focus which are due.
+pub fn due_tasks(state: &State) -> Vec<Rc<Task>> {
+    let mut tasks = state.doc.due_tasks(Local::now());
+    tasks.retain(|task| state.in_focus(&task.id));
+    tasks
+}
+
+/// Print due tasks and warn about a clock which runs for too long.
+pub fn display_reminders<T>(state: &State, callbacks: &mut dyn CliCallbacks<T>) {
+    let doc = &state.doc;
+    for task in due_tasks(state) {
+        let due = task.due.map(|due| due.format(&format!("{} %H:%M", callbacks.date_format())).to_string()).unwrap_or_default();
+        let reminder = callbacks.styled(Style::Overdue, &format!("Reminder: {} (due {})", task.title, due));
+        callbacks.println(&reminder);
+    }
+    for clock in doc.running_clocks() {
+        if clock.duration() > chrono::Duration::hours(LONG_CLOCK_HOURS) {
+            let text = match &clock.channel {
+                Some(channel) => format!("Warning: The clock in channel {} is running for {}", channel, format_duration(clock.duration())),
+                None => format!("Warning: The current clock is running for {}", format_duration(clock.duration())),
+            };
+            let warning = callbacks.styled(Style::Warning, &text);
+            callbacks.println(&warning);
         }
+    }
+}
+
+/// Parse a date with an optional time like `2019-05-31 14:00` in the
+/// time zone of the context.
+///
+/// Without time, the start of the day is used.
+pub fn parse_date_time(date_str: &str, time_str: Option<&str>, context: &DateContext) -> CliResult<chrono::DateTime<Utc>> {
+    let timezone = context.timezone;
+    let date = parse_date(date_str, context)?;
+    let time = if let Some(time_str) = time_str {
+        parse_time(time_str).map_err(|err| CliError::ParseError { msg: format!("{}", err) })?
     } else {
-        return Err(CliError::ParseError { msg: "Couldn't parse argument format".to_string() })
+        chrono::NaiveTime::from_hms(0, 0, 0)
+    };
+    timezone.to_utc(date.and_time(time)).ok_or(CliError::ParseError { msg: "Couldn't apply timezone".to_string() })
+}
+
+/// Print the entries of the activity log.
+pub fn display_activity<T>(activity: &[Activity], doc: &Doc, callbacks: &mut dyn CliCallbacks<T>) {
+    let progress_str = |progress: Option<crate::tasks::Progress>|
+        progress.map(|progress| progress.to_string()).unwrap_or_else(|| "(none)".to_string());
+    for entry in activity.iter() {
+        let title = doc.activity_task(entry)
+            .and_then(|task_id| doc.get(&task_id).ok())
+            .map(|task| task.title.clone())
+            .unwrap_or_else(|| "(none)".to_string());
+        let description = match &entry.event {
+            ActivityEvent::TaskCreated => "Created".to_string(),
+            ActivityEvent::ProgressChanged { from, to } =>
+                format!("{} -> {}", progress_str(*from), progress_str(*to)),
+            ActivityEvent::ClockStarted { .. } => "Clock started".to_string(),
+            ActivityEvent::ClockStopped { .. } => "Clock stopped".to_string(),
+            ActivityEvent::ClockAutoStopped { clock_id } => match doc.clock(clock_id).ok().and_then(|clock| clock.end) {
+                Some(end) => format!("Clock stopped at the end of the day {}", doc.timezone.localize(end).format("%H:%M")),
+                None => "Clock stopped at the end of the day".to_string(),
+            },
+        };
+        callbacks.println(&format!("{} {}: {}", entry.time.format(&format!("{} %H:%M", callbacks.date_format())), description, title));
+    }
+}
+
+/// Warn about exceeded budgets of the tasks of the clocks.
+pub fn display_budget_warnings<T>(clocks: &[Rc<Clock>], doc: &Doc, callbacks: &mut dyn CliCallbacks<T>) {
+    let mut warned: Vec<Uuid> = Vec::new();
+    for task_id in clocks.iter().filter_map(|clock| clock.task_id) {
+        for usage in doc.exceeded_budgets(&task_id) {
+            if !warned.contains(&usage.task_id) {
+                let warning = callbacks.styled(Style::Warning, &format!("Warning: budget of {} exceeded: {} of {}",
+                    task_path_string(doc, &usage.task_id), short_duration(usage.tracked), short_duration(usage.budget)));
+                callbacks.println(&warning);
+                warned.push(usage.task_id);
+            }
+        }
+    }
+}
+
+/// Compact description of the running clock like `[⏱ 1h 12m api]`.
+pub fn clock_indicator(doc: &Doc) -> Option<String> {
+    let clock = doc.running_clock()?;
+    let title = clock.task_id
+        .and_then(|task_id| doc.get(&task_id).ok())
+        .map(|task| task.title.chars().take(20).collect::<String>())
+        .filter(|title| !title.is_empty());
+    Some(match title {
+        Some(title) => format!("[⏱ {} {}]", short_duration(clock.duration()), title),
+        None => format!("[⏱ {}]", short_duration(clock.duration())),
     })
 }
+
+/// Stop clocks which run past the configured end of the day and tell
+/// the user about it.
+pub fn apply_auto_clock_out<T>(doc: &mut Doc, callbacks: &mut dyn CliCallbacks<T>) {
+    let end_of_day = match callbacks.auto_clock_out() {
+        Some(end_of_day) => end_of_day,
+        None => return,
+    };
+    for clock in doc.auto_clock_out(end_of_day, Utc::now()) {
+        let task_str = clock.task_id
+            .map(|task_id| task_path_string(doc, &task_id))
+            .unwrap_or_else(|| "(none)".to_string());
+        let end = clock.end.map(|end| doc.timezone.localize(end).format(&format!("{} %H:%M", callbacks.date_format())).to_string()).unwrap_or_default();
+        let warning = callbacks.styled(Style::Warning, &format!("Clock stopped at the end of the day {}: {}", end, task_str));
+        callbacks.println(&warning);
+    }
+}
+
+/// Print the running clocks of all channels.
+pub fn display_status<T>(doc: &Doc, callbacks: &mut dyn CliCallbacks<T>) {
+    let clocks = doc.running_clocks();
+    if clocks.is_empty() {
+        callbacks.println("No clock running");
+    }
+    for clock in clocks {
+        let task_str = clock.task_id
+            .map(|task_id| task_path_string(doc, &task_id))
+            .unwrap_or_else(|| "(none)".to_string());
+        let channel = clock.channel.as_ref().map(|channel| format!(" in channel {}", channel)).unwrap_or_default();
+        callbacks.println(&format!("Clock running{} since {} ({})", channel, doc.timezone.localize(clock.start).format(&format!("{} %H:%M", callbacks.date_format())), short_duration(clock.duration())));
+        callbacks.println(&format!(" Task: {}", task_str));
+        callbacks.println(&format!(" Comment: {}", clock.comment.as_deref().unwrap_or("(none)")));
+        if let Some(group) = clock.group() {
+            callbacks.println(&format!(" Category: {}", group));
+        }
+        if !clock.tags.is_empty() {
+            callbacks.println(&format!(" Tags: {}", clock.tags.join(", ")));
+        }
+        display_budget_warnings(&[clock], doc, callbacks);
+    }
+    if let Some(pause) = doc.running_break() {
+        callbacks.println(&format!("Break since {} ({})", doc.timezone.localize(pause.start).format(&format!("{} %H:%M", callbacks.date_format())), short_duration(pause.duration())));
+    }
+}
+
+/// Print gross, break and net time per day with breaks or work and warn
+/// about breaks which are shorter than the rules require.
+pub fn display_breaks<T>(doc: &Doc, start: NaiveDate, end: NaiveDate, callbacks: &mut dyn CliCallbacks<T>) {
+    let rules = callbacks.break_rules();
+    let days: Vec<_> = doc.work_days(start, end).into_iter()
+        .filter(|day| day.breaks > chrono::Duration::zero() || (!rules.is_empty() && day.worked > chrono::Duration::zero()))
+        .collect();
+    if days.is_empty() {
+        return;
+    }
+    callbacks.println("--- Breaks: ");
+    for day in days {
+        callbacks.println(&format!("{} gross {:>8} breaks {:>8} net {:>8}",
+            day.date.format(&format!("{} %a", callbacks.date_format())), short_duration(day.gross()), short_duration(day.breaks), short_duration(day.worked)));
+        let required = required_break(&rules, day.worked);
+        if day.breaks < required {
+            let warning = callbacks.styled(Style::Warning, &format!("Breaks are {} short of the required {}", short_duration(required - day.breaks), short_duration(required)));
+            callbacks.println(&warning);
+        }
+    }
+}
+
+/// Print worked time, target and overtime balance per day if work hours
+/// are configured.
+pub fn display_work_days<T>(doc: &Doc, start: NaiveDate, end: NaiveDate, callbacks: &mut dyn CliCallbacks<T>) {
+    if doc.work_hours.is_none() {
+        return;
+    }
+    callbacks.println("--- Work time: ");
+    for day in doc.work_days(start, end) {
+        callbacks.println(&format!("{} worked {:>8} target {:>8} delta {:>9} balance {:>9}",
+            day.date.format(&format!("{} %a", callbacks.date_format())), short_duration(day.worked), short_duration(day.target),
+            signed_duration(day.delta()), signed_duration(day.balance)));
+    }
+}
+
+/// Summary of the given day for a standup meeting, ready to paste into a chat.
+pub fn standup_summary(doc: &Doc, date: NaiveDate, main_task: Uuid) -> Vec<String> {
+    let title = |task_id: &Uuid| doc.get(task_id).map(|task| task.title.clone()).unwrap_or_else(|_| "(none)".to_string());
+    let mut worked_on: Vec<(Option<Uuid>, chrono::Duration)> = Vec::new();
+    for clock in doc.day_clock(date, main_task) {
+        if let Some(entry) = worked_on.iter_mut().find(|(task_id, _)| *task_id == clock.task_id) {
+            entry.1 = entry.1 + clock.duration();
+        } else {
+            worked_on.push((clock.task_id, clock.duration()));
+        }
+    }
+    worked_on.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let worked_on = join_strings(worked_on.iter().map(|(task_id, duration)| {
+        let task_title = task_id.map(|task_id| title(&task_id)).unwrap_or_else(|| "other things".to_string());
+        format!("{} ({})", task_title, short_duration(*duration))
+    }), ", ");
+
+    let mut completed: Vec<Uuid> = Vec::new();
+    for entry in doc.activity.iter().filter(|entry| doc.timezone.date(entry.time.with_timezone(&Utc)) == date) {
+        if let (ActivityEvent::ProgressChanged { to: Some(progress), .. }, Some(task_id)) = (&entry.event, entry.task_id) {
+            if progress.done() && doc.is_in_hierarchy_of(&task_id, &main_task) && !completed.contains(&task_id) {
+                completed.push(task_id);
+            }
+        }
+    }
+    let completed = join_strings(completed.iter().map(title), ", ");
+
+    let mut in_work: Vec<String> = doc.map.values()
+        .filter(|task| task.progress == Some(crate::tasks::Progress::Work))
+        .filter(|task| doc.is_in_hierarchy_of(&task.id, &main_task))
+        .map(|task| task.title.clone())
+        .collect();
+    in_work.sort();
+    let in_work = join_strings(in_work.into_iter(), ", ");
+
+    let none = |text: String| if text.is_empty() { "nothing".to_string() } else { text };
+    vec![
+        format!("On {} I worked on {}.", date.format("%A, %Y-%m-%d"), none(worked_on)),
+        format!("Completed: {}.", none(completed)),
+        format!("Today's WORK items are: {}.", none(in_work)),
+    ]
+}
+
+/// Settings to resolve dates which are entered by the user.
+#[derive(Clone, Debug)]
+pub struct DateContext {
+    pub timezone: DisplayZone,
+
+    /// First day of the week, used for weekdays and weeks.
+    pub week_start: Weekday,
+
+    /// Date format of the user which is accepted besides `%Y-%m-%d`.
+    pub date_format: String,
+}
+
+impl DateContext {
+    /// Time zone of the document with the settings of the user.
+    pub fn new<T>(doc: &Doc, callbacks: &dyn CliCallbacks<T>) -> DateContext {
+        DateContext {
+            timezone: doc.timezone,
+            week_start: callbacks.week_start(),
+            date_format: callbacks.date_format().to_string(),
+        }
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.timezone.today()
+    }
+
+    /// First and last day of the current week.
+    pub fn this_week(&self) -> (NaiveDate, NaiveDate) {
+        week_of(self.today(), self.week_start)
+    }
+}
+
+/// Parse a date like `2019-05-31`, `today`, `yesterday`, `tomorrow`, `-1`
+/// for one day ago, a weekday of this week like `mon`, a weekday of the
+/// last or next week like `last-tue` or the first day of a week like
+/// `2024-W23`.
+///
+/// Dates in the configured date format like `31.05.2019` work as well.
+pub fn parse_date(date_str: &str, context: &DateContext) -> CliResult<NaiveDate> {
+    let error = || CliError::ParseError { msg: format!("Couldn't parse date: {}", date_str) };
+    let today = context.today();
+    let weekday = |name: &str, weeks: i64| parse_weekday(name)
+        .map(|weekday| start_of_week(today, context.week_start) + chrono::Duration::weeks(weeks)
+            + chrono::Duration::days(i64::from((7 + weekday.num_days_from_monday() - context.week_start.num_days_from_monday()) % 7)));
+    let lowercase = date_str.to_lowercase();
+    if let Some(offset) = lowercase.strip_prefix('-') {
+        return offset.parse::<i64>()
+            .map(|offset| today - chrono::Duration::days(offset))
+            .map_err(|_| error());
+    }
+    let date = match lowercase.as_str() {
+        "today" => Some(today),
+        "yesterday" => Some(today.pred()),
+        "tomorrow" => Some(today.succ()),
+        _ if lowercase.starts_with("last-") => weekday(&lowercase[5..], -1),
+        _ if lowercase.starts_with("next-") => weekday(&lowercase[5..], 1),
+        _ => weekday(&lowercase, 0)
+            .or_else(|| parse_week(date_str, context.week_start).map(|(start, _)| start))
+            .or_else(|| NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok())
+            .or_else(|| NaiveDate::parse_from_str(date_str, &context.date_format).ok()),
+    };
+    date.ok_or_else(error)
+}
+
+/// Parse a point in time like `14:30` (today), `2024-03-01T14:30` or
+/// `yesterday@14:30` in the time zone of the context.
+pub fn parse_point_in_time(string: &str, context: &DateContext) -> CliResult<chrono::DateTime<Utc>> {
+    match string.split_once('@').or_else(|| string.split_once('T')) {
+        Some((date_str, time_str)) => parse_date_time(date_str, Some(time_str), context),
+        None => parse_date_time("today", Some(string), context),
+    }
+}
+
+/// Parse a week like `2024-W23` or any date of the week into its first and
+/// last day.
+pub fn parse_week_range(string: &str, context: &DateContext) -> CliResult<(NaiveDate, NaiveDate)> {
+    match parse_week(string, context.week_start) {
+        Some(week) => Ok(week),
+        None => Ok(week_of(parse_date(string, context)?, context.week_start)),
+    }
+}
+
+/// Save the document.
+///
+/// If it's saved to its own file and the file content changed since it was
+/// loaded or saved, ask whether to overwrite it, merge it or cancel.
+/// With `journal`, only the changes are appended to the journal of the file.
+pub fn save_doc<T>(state: &mut State, filename: &str, journal: bool, callbacks: &mut dyn CliCallbacks<T>) -> Result<()> {
+    let own_file = filename == state.path;
+    if own_file && state.content_changed_on_disk() {
+        #[derive(Clone, Copy)]
+        enum Answer { Overwrite, Merge, Cancel }
+        let choices = [
+            Choice::new('o', "overwrite", Answer::Overwrite),
+            Choice::new('m', "merge", Answer::Merge),
+            Choice::new('c', "cancel", Answer::Cancel),
+        ];
+        match Dialog::new(callbacks).choose("The file changed on disk.", &choices) {
+            Some(Answer::Overwrite) => {},
+            Some(Answer::Merge) => {
+                let disk_doc = Doc::load(filename)?;
+                state.doc.merge(&disk_doc);
+            },
+            Some(Answer::Cancel) | None => return Err(Error::SaveCancelled {}),
+        }
+    }
+    if own_file && !state.mounts.is_empty() {
+        state.doc.save_mounts(&state.mounts)?;
+    } else if journal {
+        state.doc.save_journal(filename)?;
+    } else {
+        state.doc.save(filename)?;
+    }
+    if own_file {
+        state.mark_saved();
+        state.refresh_disk_state();
+    }
+    run_hook(state, &HookCall::new(HookEvent::Save).var("SORS_FILE", filename), callbacks);
+    Ok(())
+}
+
+/// Run the hooks of the event and print the failures.
+pub fn run_hook<T>(state: &State, call: &HookCall, callbacks: &mut dyn CliCallbacks<T>) {
+    for err in state.hooks.run(call) {
+        let warning = callbacks.styled(Style::Warning, &format!("Warning: {}", err));
+        callbacks.println(&warning);
+    }
+}
+
+/// Run the hooks of all events which happened since the given time.
+pub fn run_hooks<T>(state: &State, since: chrono::DateTime<Local>, callbacks: &mut dyn CliCallbacks<T>) {
+    for call in state.doc.hook_calls(since) {
+        run_hook(state, &call, callbacks);
+    }
+}
+
+pub fn resolve_selector(state: &State, selector: &str) -> CliResult<Vec<Uuid>> {
+    state.uuids_for_selector(selector)
+        .ok_or_else(|| CliError::ParseError { msg: format!("Couldn't resolve selector: {}", selector) })
+}
@@ -0,0 +1,176 @@
+//! Shell commands which run when something happens, like clocking in.
+//!
+//! The commands get details about the event in `SORS_*` environment
+//! variables.
+
+use crate::doc::Doc;
+use crate::activity::ActivityEvent;
+use crate::tasks::Progress;
+use crate::error::*;
+use chrono::prelude::*;
+use serde::{Serialize, Deserialize};
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    TaskDone,
+    ClockIn,
+    ClockOut,
+    Save,
+}
+
+impl HookEvent {
+    pub fn all() -> &'static [HookEvent] {
+        &[HookEvent::TaskDone, HookEvent::ClockIn, HookEvent::ClockOut, HookEvent::Save]
+    }
+
+    /// Name of the event in the hooks file and in `SORS_EVENT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::TaskDone => "task-done",
+            HookEvent::ClockIn => "clock-in",
+            HookEvent::ClockOut => "clock-out",
+            HookEvent::Save => "save",
+        }
+    }
+}
+
+impl std::str::FromStr for HookEvent {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<HookEvent> {
+        HookEvent::all().iter()
+            .find(|event| event.name() == name)
+            .cloned()
+            .ok_or_else(|| Error::UnknownHookEvent { name: name.to_string() })
+    }
+}
+
+/// An event which happened with the variables passed to the hooks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookCall {
+    pub event: HookEvent,
+    pub env: Vec<(String, String)>,
+}
+
+impl HookCall {
+    pub fn new(event: HookEvent) -> HookCall {
+        HookCall {
+            event,
+            env: vec![("SORS_EVENT".to_string(), event.name().to_string())],
+        }
+    }
+
+    pub fn var(mut self, name: impl ToString, value: impl ToString) -> HookCall {
+        self.env.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Shell commands per event name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Hooks {
+    pub commands: BTreeMap<String, Vec<String>>,
+}
+
+impl Hooks {
+    pub fn load(path: impl AsRef<Path>) -> Result<Hooks> {
+        let reader = BufReader::new(File::open(path).context(IO)?);
+        Ok(serde_json::from_reader(reader).context(SerdeSerializationError)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).context(IO)?;
+        }
+        let writer = BufWriter::new(File::create(path).context(IO)?);
+        serde_json::to_writer_pretty(writer, self).context(SerdeSerializationError)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, event: HookEvent, command: impl ToString) {
+        self.commands.entry(event.name().to_string()).or_default().push(command.to_string());
+    }
+
+    /// Remove the command at the index and return it.
+    pub fn remove(&mut self, event: HookEvent, index: usize) -> Option<String> {
+        let commands = self.commands.get_mut(event.name())?;
+        if index >= commands.len() {
+            return None;
+        }
+        let command = commands.remove(index);
+        if commands.is_empty() {
+            self.commands.remove(event.name());
+        }
+        Some(command)
+    }
+
+    pub fn for_event(&self, event: HookEvent) -> &[String] {
+        self.commands.get(event.name()).map(|commands| commands.as_slice()).unwrap_or_default()
+    }
+
+    /// Run all commands of the event with `sh` and wait for them.
+    ///
+    /// Returns the errors of the commands which couldn't be started or
+    /// failed.
+    pub fn run(&self, call: &HookCall) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for command in self.for_event(call.event) {
+            let exec = call.env.iter()
+                .fold(subprocess::Exec::shell(command), |exec, (name, value)| exec.env(name, value));
+            match exec.join() {
+                Ok(status) if status.success() => {},
+                Ok(status) => errors.push(Error::HookFailed { command: command.clone(), msg: format!("{:?}", status) }),
+                Err(err) => errors.push(Error::HookFailed { command: command.clone(), msg: err.to_string() }),
+            }
+        }
+        errors
+    }
+}
+
+impl Doc {
+    /// Events of the activity which was logged since the given time.
+    pub fn hook_calls(&self, since: DateTime<Local>) -> Vec<HookCall> {
+        let start = self.activity.iter().rposition(|entry| entry.time < since).map_or(0, |i| i + 1);
+        self.activity[start..].iter().filter_map(|entry| {
+            match &entry.event {
+                ActivityEvent::ProgressChanged { to: Some(Progress::Done), .. } =>
+                    Some(self.task_vars(HookCall::new(HookEvent::TaskDone), entry.task_id)),
+                ActivityEvent::ClockStarted { clock_id } => Some(self.clock_vars(HookCall::new(HookEvent::ClockIn), clock_id)),
+                ActivityEvent::ClockStopped { clock_id } | ActivityEvent::ClockAutoStopped { clock_id } => Some(self.clock_vars(HookCall::new(HookEvent::ClockOut), clock_id)),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    fn task_vars(&self, call: HookCall, task_id: Option<Uuid>) -> HookCall {
+        match task_id.and_then(|task_id| self.get(&task_id).ok()) {
+            Some(task) => call
+                .var("SORS_TASK_ID", task.id)
+                .var("SORS_TASK_TITLE", &task.title)
+                .var("SORS_TASK_PATH", self.path_titles(&task.id).join("/")),
+            None => call,
+        }
+    }
+
+    fn clock_vars(&self, call: HookCall, clock_id: &Uuid) -> HookCall {
+        match self.clock(clock_id) {
+            Ok(clock) => {
+                let call = call
+                    .var("SORS_CLOCK_ID", clock.id)
+                    .var("SORS_CLOCK_COMMENT", clock.comment.as_deref().unwrap_or_default())
+                    .var("SORS_CLOCK_CATEGORY", clock.group().unwrap_or_default())
+                    .var("SORS_CLOCK_TAGS", clock.tags.join(","))
+                    .var("SORS_CLOCK_CHANNEL", clock.channel.as_deref().unwrap_or_default());
+                self.task_vars(call, clock.task_id)
+            },
+            Err(_) => call,
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! iCalendar feed of due tasks and clocks, served over HTTP.
+//!
+//! Calendar apps subscribe to the feed and poll it, so the server answers
+//! each request with the state of the document after the last command.
+//! Only `GET` is supported and every path returns the same feed.
+
+use crate::doc::Doc;
+use crate::error::*;
+use crate::tasks::*;
+use chrono::prelude::*;
+use snafu::ResultExt;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Port of `serve-ics` if none is given.
+pub const DEFAULT_PORT: u16 = 8090;
+
+/// Time a client gets to send its request and to read the answer.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time between checks if the server should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Escape text values, see RFC 5545 section 3.3.11.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Reverse `escape`.
+pub(crate) fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Fold content lines after 75 octets and end them with CRLF.
+pub(crate) fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+pub(crate) fn utc_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+impl Doc {
+    /// Property with the time, as date if it's midnight in the time zone
+    /// of the document.
+    pub(crate) fn time_property(&self, name: &str, time: DateTime<Utc>) -> String {
+        let local = self.timezone.localize(time).naive_local();
+        if local.time() == NaiveTime::from_hms(0, 0, 0) {
+            format!("{};VALUE=DATE:{}", name, local.date().format("%Y%m%d"))
+        } else {
+            format!("{}:{}", name, utc_time(time))
+        }
+    }
+
+    fn push_task_event(&self, lines: &mut Vec<String>, task: &Task, due: DateTime<Local>, stamp: &str) {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@sors", task.id));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(self.time_property("DTSTART", due.with_timezone(&Utc)));
+        lines.push(format!("SUMMARY:{}", escape(&task.title)));
+        let path = self.path_titles(&task.id).into_iter().skip(1).collect::<Vec<_>>().join(" -> ");
+        lines.push(format!("DESCRIPTION:{}", escape(&path)));
+        if !task.tags.is_empty() {
+            lines.push(format!("CATEGORIES:{}", task.tags.iter().map(|tag| escape(tag)).collect::<Vec<_>>().join(",")));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    /// Calendar with the open tasks below the given task which have a due
+    /// date, and with `with_clocks` the clocks of these tasks.
+    ///
+    /// Due dates at midnight become all-day events.
+    pub fn to_ics(&self, main_task: &Uuid, with_clocks: bool) -> String {
+        let stamp = utc_time(Utc::now());
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//sors//sors//EN".to_string(),
+            "X-WR-CALNAME:sors".to_string(),
+        ];
+        let tasks: Vec<_> = self.iter_subtree(main_task).map(|(_, task)| task).collect();
+        for task in tasks.iter().filter(|task| task.habit.is_none() && task.progress.is_none_or(|progress| !progress.done() && progress.counts())) {
+            if let Some(due) = task.due {
+                self.push_task_event(&mut lines, task, due, &stamp);
+            }
+        }
+        if with_clocks {
+            let task_ids: HashSet<Uuid> = tasks.iter().map(|task| task.id).collect();
+            let mut clocks: Vec<_> = self.clocks.values()
+                .filter(|clock| clock.task_id.is_some_and(|task_id| task_ids.contains(&task_id)))
+                .collect();
+            clocks.sort_by_key(|clock| clock.start);
+            for clock in clocks {
+                let title = clock.task_id.and_then(|task_id| self.get(&task_id).ok()).map(|task| task.title.clone()).unwrap_or_default();
+                lines.push("BEGIN:VEVENT".to_string());
+                lines.push(format!("UID:{}@sors", clock.id));
+                lines.push(format!("DTSTAMP:{}", stamp));
+                lines.push(format!("DTSTART:{}", utc_time(clock.start)));
+                lines.push(format!("DTEND:{}", utc_time(clock.end.unwrap_or_else(Utc::now))));
+                lines.push(format!("SUMMARY:{}", escape(&title)));
+                if let Some(comment) = &clock.comment {
+                    lines.push(format!("DESCRIPTION:{}", escape(comment)));
+                }
+                let categories: Vec<String> = clock.category.iter().chain(clock.tags.iter())
+                    .map(|category| escape(category))
+                    .collect();
+                if !categories.is_empty() {
+                    lines.push(format!("CATEGORIES:{}", categories.join(",")));
+                }
+                lines.push("END:VEVENT".to_string());
+            }
+        }
+        lines.push("END:VCALENDAR".to_string());
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+/// Answer one HTTP request on the stream with the calendar of `feed`.
+///
+/// Returns the request line.
+pub fn handle_connection(mut stream: impl Read + Write, feed: impl FnOnce() -> String) -> Result<String> {
+    let mut request_line = String::new();
+    {
+        let mut reader = BufReader::new(&mut stream);
+        reader.read_line(&mut request_line).context(IO)?;
+        let mut header = String::new();
+        while reader.read_line(&mut header).context(IO)? > 0 && !header.trim().is_empty() {
+            header.clear();
+        }
+    }
+    let request_line = request_line.trim().to_string();
+    let (status, content_type, body) = match request_line.split(' ').next() {
+        Some("GET") => ("200 OK", "text/calendar; charset=utf-8", feed()),
+        _ => ("405 Method Not Allowed", "text/plain; charset=utf-8", "Only GET is supported\n".to_string()),
+    };
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body).context(IO)?;
+    stream.flush().context(IO)?;
+    Ok(request_line)
+}
+
+/// Answer the request and log it before the connection is closed.
+fn answer(mut stream: TcpStream, feed: &Mutex<String>, log: &Mutex<Vec<String>>) {
+    let result = (|| -> Result<String> {
+        stream.set_nonblocking(false).context(IO)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT)).context(IO)?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT)).context(IO)?;
+        handle_connection(&mut stream, || feed.lock().unwrap_or_else(PoisonError::into_inner).clone())
+    })();
+    log.lock().unwrap_or_else(PoisonError::into_inner).push(result.unwrap_or_else(|err| err.to_string()));
+}
+
+/// Server of the feed on a thread of its own, see `serve-ics`.
+///
+/// Documents can't be shared with the thread, so it answers with the feed
+/// given by `update`.  Dropping the server stops it.
+#[derive(Debug)]
+pub struct IcsServer {
+    pub port: u16,
+    pub with_clocks: bool,
+    feed: Arc<Mutex<String>>,
+    log: Arc<Mutex<Vec<String>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl IcsServer {
+    /// Answer requests on the port of the local machine, 0 picks a free one.
+    pub fn start(port: u16, with_clocks: bool, feed: String) -> Result<IcsServer> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).context(IO)?;
+        listener.set_nonblocking(true).context(IO)?;
+        let port = listener.local_addr().context(IO)?.port();
+        let feed = Arc::new(Mutex::new(feed));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (feed, log, stop) = (feed.clone(), log.clone(), stop.clone());
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => answer(stream, &feed, &log),
+                        Err(err) => {
+                            if err.kind() != ErrorKind::WouldBlock {
+                                log.lock().unwrap_or_else(PoisonError::into_inner).push(err.to_string());
+                            }
+                            std::thread::sleep(POLL_INTERVAL);
+                        },
+                    }
+                }
+            })
+        };
+        Ok(IcsServer { port, with_clocks, feed, log, stop, thread: Some(thread) })
+    }
+
+    /// Answer the following requests with the feed.
+    pub fn update(&self, feed: String) {
+        *self.feed.lock().unwrap_or_else(PoisonError::into_inner) = feed;
+    }
+
+    /// Request lines and errors since the last call.
+    pub fn take_log(&self) -> Vec<String> {
+        std::mem::take(&mut *self.log.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl Drop for IcsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Import tasks from other applications.
+//!
+//! Each supported format is an `ImportAdapter` which turns the exported
+//! data into a tree of `ImportedTask`s.  The tree is then added to a
+//! document with `Doc::import_tasks`.
+
+use crate::curl::SecretConfig;
+use crate::doc::Doc;
+use crate::tasks::*;
+use crate::error::*;
+use serde_json::Value;
+use snafu::ResultExt;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// A task read by an adapter which is not part of a document yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedTask {
+    pub title: String,
+    pub body: String,
+    pub progress: Option<Progress>,
+    pub tags: Vec<String>,
+    pub external_ref: Option<String>,
+    pub children: Vec<ImportedTask>,
+}
+
+impl ImportedTask {
+    pub fn new(title: impl ToString) -> ImportedTask {
+        ImportedTask {
+            title: title.to_string(),
+            ..ImportedTask::default()
+        }
+    }
+}
+
+pub trait ImportAdapter {
+    /// Name used to select the adapter in the `import` command.
+    fn name(&self) -> &'static str;
+
+    /// Convert the exported data into tasks.
+    fn import(&self, input: &str) -> Result<Vec<ImportedTask>>;
+}
+
+/// All available adapters.
+pub fn adapters() -> Vec<Box<dyn ImportAdapter>> {
+    vec![Box::new(TodoistAdapter), Box::new(TrelloAdapter), Box::new(GithubAdapter)]
+}
+
+/// Find the adapter with the given name.
+pub fn adapter(name: &str) -> Option<Box<dyn ImportAdapter>> {
+    adapters().into_iter().find(|adapter| adapter.name() == name)
+}
+
+impl Doc {
+    /// Add the imported tasks and their children below the given parent.
+    ///
+    /// Returns the number of created tasks.
+    pub fn import_tasks(&mut self, tasks: &[ImportedTask], parent_ref: &Uuid) -> Result<usize> {
+        let mut count = 0;
+        for imported in tasks.iter() {
+            let mut task = Rc::new(Task::new());
+            task.set_title(&imported.title).set_body(&imported.body);
+            if let Some(progress) = imported.progress {
+                task.set_progress(progress);
+            }
+            for tag in imported.tags.iter() {
+                task.add_tag(tag);
+            }
+            task.set_external_ref(imported.external_ref.clone());
+            let task_id = task.id;
+            self.add_subtask(task, parent_ref)?;
+            count += 1 + self.import_tasks(&imported.children, &task_id)?;
+        }
+        Ok(count)
+    }
+}
+
+fn format_error(msg: impl ToString) -> Error {
+    Error::ImportError { msg: msg.to_string() }
+}
+
+/// Ids are strings in newer and numbers in older exports.
+fn id_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn str_field(value: &Value, field: &str) -> String {
+    value[field].as_str().unwrap_or_default().to_string()
+}
+
+fn truthy(value: &Value) -> bool {
+    value.as_bool().unwrap_or(false) || value.as_i64().unwrap_or(0) != 0
+}
+
+fn done_progress(done: bool) -> Option<Progress> {
+    Some(if done { Progress::Done } else { Progress::Todo })
+}
+
+/// Todoist project data as returned by the sync API (`sections` and `items`)
+/// or a plain list of tasks as returned by the REST API.
+///
+/// Sections become intermediate tasks, sub-tasks keep their hierarchy.
+pub struct TodoistAdapter;
+
+impl TodoistAdapter {
+    fn children_of(items: &[Value], parent_id: Option<&str>, section_id: Option<&str>) -> Vec<ImportedTask> {
+        items.iter()
+            .filter(|item| id_string(&item["parent_id"]).as_deref() == parent_id)
+            .filter(|item| parent_id.is_some() || id_string(&item["section_id"]).as_deref() == section_id)
+            .map(|item| {
+                let id = id_string(&item["id"]);
+                ImportedTask {
+                    title: str_field(item, "content"),
+                    body: str_field(item, "description"),
+                    progress: done_progress(truthy(&item["checked"]) || truthy(&item["is_completed"])),
+                    tags: item["labels"].as_array().map(|labels| labels.iter()
+                        .filter_map(|label| label.as_str().map(|label| label.to_string()))
+                        .collect()).unwrap_or_default(),
+                    external_ref: None,
+                    children: match id {
+                        Some(id) => Self::children_of(items, Some(&id), None),
+                        None => Vec::new(),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+impl ImportAdapter for TodoistAdapter {
+    fn name(&self) -> &'static str {
+        "todoist"
+    }
+
+    fn import(&self, input: &str) -> Result<Vec<ImportedTask>> {
+        let json: Value = serde_json::from_str(input).context(SerdeSerializationError)?;
+        let (items, sections) = match &json {
+            Value::Array(items) => (items.clone(), Vec::new()),
+            Value::Object(_) => (
+                json["items"].as_array().cloned().ok_or_else(|| format_error("No items found"))?,
+                json["sections"].as_array().cloned().unwrap_or_default(),
+            ),
+            _ => return Err(format_error("Unexpected Todoist format")),
+        };
+        let mut tasks = Self::children_of(&items, None, None);
+        for section in sections.iter() {
+            let section_id = id_string(&section["id"]);
+            let mut section_task = ImportedTask::new(str_field(section, "name"));
+            section_task.children = Self::children_of(&items, None, section_id.as_deref());
+            tasks.push(section_task);
+        }
+        Ok(tasks)
+    }
+}
+
+/// Trello board export.
+///
+/// Lists become intermediate tasks containing their cards, checklist items
+/// become children of the cards.  Archived lists and cards are skipped.
+pub struct TrelloAdapter;
+
+impl ImportAdapter for TrelloAdapter {
+    fn name(&self) -> &'static str {
+        "trello"
+    }
+
+    fn import(&self, input: &str) -> Result<Vec<ImportedTask>> {
+        let json: Value = serde_json::from_str(input).context(SerdeSerializationError)?;
+        let lists = json["lists"].as_array().ok_or_else(|| format_error("No lists found"))?;
+        let empty = Vec::new();
+        let cards = json["cards"].as_array().unwrap_or(&empty);
+        let checklists = json["checklists"].as_array().unwrap_or(&empty);
+        let open = |value: &&Value| !truthy(&value["closed"]);
+
+        Ok(lists.iter().filter(open).map(|list| {
+            let mut list_task = ImportedTask::new(str_field(list, "name"));
+            list_task.children = cards.iter()
+                .filter(open)
+                .filter(|card| card["idList"] == list["id"])
+                .map(|card| {
+                    let children = checklists.iter()
+                        .filter(|checklist| checklist["idCard"] == card["id"])
+                        .flat_map(|checklist| checklist["checkItems"].as_array().cloned().unwrap_or_default())
+                        .map(|item| ImportedTask {
+                            title: str_field(&item, "name"),
+                            progress: done_progress(item["state"] == "complete"),
+                            ..ImportedTask::default()
+                        })
+                        .collect();
+                    ImportedTask {
+                        title: str_field(card, "name"),
+                        body: str_field(card, "desc"),
+                        progress: done_progress(truthy(&card["dueComplete"])),
+                        children,
+                        ..ImportedTask::default()
+                    }
+                })
+                .collect();
+            list_task
+        }).collect())
+    }
+}
+
+/// Issues as returned by the GitHub REST API.
+///
+/// Labels become tags and the issue URL is stored as external reference.
+/// Pull requests are skipped.
+pub struct GithubAdapter;
+
+impl ImportAdapter for GithubAdapter {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn import(&self, input: &str) -> Result<Vec<ImportedTask>> {
+        let json: Value = serde_json::from_str(input).context(SerdeSerializationError)?;
+        let issues = json.as_array().ok_or_else(|| format_error(str_field(&json, "message")))?;
+        Ok(issues.iter()
+            .filter(|issue| issue.get("pull_request").is_none())
+            .map(|issue| ImportedTask {
+                title: str_field(issue, "title"),
+                body: str_field(issue, "body"),
+                progress: done_progress(issue["state"] == "closed"),
+                tags: issue["labels"].as_array().map(|labels| labels.iter()
+                    .map(|label| str_field(label, "name"))
+                    .collect()).unwrap_or_default(),
+                external_ref: issue["html_url"].as_str().map(|url| url.to_string()),
+                children: Vec::new(),
+            })
+            .collect())
+    }
+}
+
+/// Fetch all open issues of a GitHub repository like `neosam/sors`.
+///
+/// Uses `curl`.  The token is optional, without it only public repositories
+/// are available and the rate limit is low.
+pub fn fetch_github_issues(repository: &str, token: Option<&str>) -> Result<Vec<ImportedTask>> {
+    let mut issues = Vec::new();
+    for page in 1.. {
+        let url = format!("https://api.github.com/repos/{}/issues?state=open&per_page=100&page={}", repository, page);
+        let mut exec = subprocess::Exec::cmd("curl")
+            .args(&["-sSfL", "-H", "Accept: application/vnd.github+json", "-H", "User-Agent: sors"]);
+        let header = token.map(|token| format!("Authorization: Bearer {}", token));
+        let config = header.map(|header| SecretConfig::new(&[("header", &header)]))
+            .transpose().map_err(format_error)?;
+        if let Some(config) = &config {
+            exec = exec.arg("-K").arg(config.path());
+        }
+        let capture = exec.arg(&url)
+            .stdout(subprocess::Redirection::Pipe)
+            .stderr(subprocess::Redirection::Pipe)
+            .capture()
+            .map_err(format_error)?;
+        if !capture.success() {
+            return Err(format_error(capture.stderr_str().trim()));
+        }
+        let page_issues = GithubAdapter.import(&capture.stdout_str())?;
+        // Pages can consist of pull requests only, so check the raw length.
+        let raw_len = serde_json::from_str::<Value>(&capture.stdout_str())
+            .context(SerdeSerializationError)?
+            .as_array().map_or(0, |issues| issues.len());
+        issues.extend(page_issues);
+        if raw_len < 100 {
+            break;
+        }
+    }
+    Ok(issues)
+}
@@ -0,0 +1,43 @@
+//! Inbox for tasks which are captured without changing the working task.
+//!
+//! The inbox is the task of the `inbox` bookmark.  Without the bookmark an
+//! `Inbox` task is added to the root and bookmarked.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use crate::error::*;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Bookmark of the inbox, so it's reachable as `@inbox`.
+pub const INBOX_BOOKMARK: &str = "inbox";
+
+/// Title of the inbox if it's created.
+pub const INBOX_TITLE: &str = "Inbox";
+
+impl Doc {
+    /// Get the inbox task and create it at the end of the root if the
+    /// bookmark doesn't point to an existing task.
+    pub fn inbox_task(&mut self) -> Result<Uuid> {
+        if let Some(inbox) = self.bookmarks.get(INBOX_BOOKMARK).filter(|inbox| self.map.contains_key(inbox)) {
+            return Ok(*inbox);
+        }
+        let mut task = Rc::new(Task::new());
+        task.set_title(INBOX_TITLE);
+        let inbox = task.id;
+        let root = self.root;
+        self.add_subtask(task, &root)?;
+        self.bookmarks.insert(INBOX_BOOKMARK.to_string(), inbox);
+        Ok(inbox)
+    }
+
+    /// Add a task with the title to the end of the inbox.
+    pub fn capture(&mut self, title: &str) -> Result<Uuid> {
+        let inbox = self.inbox_task()?;
+        let mut task = Rc::new(Task::new());
+        task.set_title(title);
+        let task_id = task.id;
+        self.add_subtask(task, &inbox)?;
+        Ok(task_id)
+    }
+}
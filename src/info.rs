@@ -0,0 +1,180 @@
+//! Size and health of the whole document, see the `info` and `doctor`
+//! commands.
+
+use crate::doc::{Doc, Format};
+use crate::invariants::Violation;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::fmt;
+use uuid::Uuid;
+
+/// Number of subtrees listed in `DocInfo::largest_subtrees`.
+pub const LARGEST_SUBTREES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocInfo {
+    pub tasks: usize,
+
+    /// Tasks in the trash, without the trash task itself.
+    pub trashed_tasks: usize,
+
+    /// Tasks which are neither below the root nor in the trash.
+    pub unreachable_tasks: usize,
+
+    pub clocks: usize,
+    pub running_clocks: usize,
+    pub clocks_without_task: usize,
+
+    /// Clocks of tasks which don't exist anymore.
+    pub unreferenced_clocks: usize,
+
+    /// Tasks with clocks and the start of the first and the last clock.
+    pub clocked_tasks: usize,
+    pub clock_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+
+    pub activity: usize,
+    pub notes: usize,
+    pub attachments: usize,
+
+    /// Deepest level below the root and the task at that level.
+    pub max_depth: usize,
+    pub deepest_task: Uuid,
+
+    /// Top-level tasks with the most subtasks and their number of tasks,
+    /// largest first.
+    pub largest_subtrees: Vec<(Uuid, usize)>,
+}
+
+/// Days after which `doctor` suggests a new snapshot.
+pub const SNAPSHOT_MAX_AGE_DAYS: i64 = 30;
+
+/// Size in bytes above which `doctor` suggests CBOR for JSON documents.
+pub const LARGE_DOCUMENT: u64 = 1_048_576;
+
+/// Maintenance the `doctor` command suggests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Suggestion {
+    /// Rules of the document are broken, no command repairs them.
+    Repair { violations: usize },
+    EmptyTrash { tasks: usize },
+
+    /// Done top-level tasks which aren't archived.
+    Archive { tasks: usize },
+
+    /// The journal next to the file is larger than the file itself, so
+    /// loading replays more than it reads.
+    CompactJournal { entries: usize },
+
+    /// JSON document larger than `LARGE_DOCUMENT`.
+    Compress { bytes: u64 },
+
+    /// There is no snapshot or none of the last `SNAPSHOT_MAX_AGE_DAYS`.
+    Snapshot,
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Suggestion::Repair { violations } => write!(f, "Repair the {} broken rules by hand or go back to an older copy of the file", violations),
+            Suggestion::EmptyTrash { tasks } => write!(f, "Remove the {} tasks in the trash with `trash empty`", tasks),
+            Suggestion::Archive { tasks } => write!(f, "Move the {} done top-level tasks to the archive with `archive <selector>`", tasks),
+            Suggestion::CompactJournal { entries } => write!(f, "Write the {} journal entries into the document with `save`", entries),
+            Suggestion::Compress { bytes } => write!(f, "Store the document of {} as smaller and faster CBOR with `export <file>.cbor` and open that", format_size(*bytes)),
+            Suggestion::Snapshot => write!(f, "Store a copy of the document with `snapshot`"),
+        }
+    }
+}
+
+/// Maintenance for the files of the document, its size and the number
+/// of entries and size of its journal.
+pub fn file_suggestions(format: Format, size: u64, journal: Option<(usize, u64)>) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+    if let Some((entries, journal_size)) = journal {
+        if entries > 0 && journal_size > size {
+            suggestions.push(Suggestion::CompactJournal { entries });
+        }
+    }
+    if format == Format::Json && size > LARGE_DOCUMENT {
+        suggestions.push(Suggestion::Compress { bytes: size });
+    }
+    suggestions
+}
+
+/// Size like `512 B`, `3.4 KiB` or `1.2 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+impl Doc {
+    pub fn info(&self) -> DocInfo {
+        let mut reachable = HashSet::new();
+        let (mut max_depth, mut deepest_task) = (0, self.root);
+        for (depth, task) in self.iter_subtree(&self.root) {
+            if depth > max_depth {
+                max_depth = depth;
+                deepest_task = task.id;
+            }
+            reachable.insert(task.id);
+        }
+        let mut trashed_tasks = 0;
+        if let Some(trash) = self.trash {
+            for (depth, task) in self.iter_subtree(&trash) {
+                trashed_tasks += if depth > 0 { 1 } else { 0 };
+                reachable.insert(task.id);
+            }
+        }
+        let mut largest_subtrees: Vec<(Uuid, usize)> = self.get(&self.root)
+            .map(|root| root.children.iter().map(|child| (*child, self.iter_subtree(child).count())).collect())
+            .unwrap_or_default();
+        largest_subtrees.sort_by(|(_, a), (_, b)| b.cmp(a));
+        largest_subtrees.truncate(LARGEST_SUBTREES);
+        let clocked_tasks: HashSet<Uuid> = self.clocks.values().filter_map(|clock| clock.task_id).collect();
+        let clock_range = self.clocks.values().map(|clock| clock.start).min()
+            .zip(self.clocks.values().map(|clock| clock.start).max());
+        DocInfo {
+            tasks: self.map.len(),
+            trashed_tasks,
+            unreachable_tasks: self.map.keys().filter(|task_id| !reachable.contains(task_id)).count(),
+            clocks: self.clocks.len(),
+            running_clocks: self.running_clocks().len(),
+            clocks_without_task: self.clocks.values().filter(|clock| clock.task_id.is_none()).count(),
+            unreferenced_clocks: self.clocks.values()
+                .filter(|clock| clock.task_id.is_some_and(|task_id| !self.map.contains_key(&task_id)))
+                .count(),
+            clocked_tasks: clocked_tasks.len(),
+            clock_range,
+            activity: self.activity.len(),
+            notes: self.map.values().map(|task| task.notes.len()).sum(),
+            attachments: self.map.values().map(|task| task.attachments.len()).sum(),
+            max_depth,
+            deepest_task,
+            largest_subtrees,
+        }
+    }
+
+    /// Maintenance for the document with the given info and violations,
+    /// without `Suggestion::Snapshot` which depends on the files.
+    pub fn suggestions(&self, info: &DocInfo, violations: &[Violation]) -> Vec<Suggestion> {
+        let mut suggestions = Vec::new();
+        if !violations.is_empty() {
+            suggestions.push(Suggestion::Repair { violations: violations.len() });
+        }
+        if info.trashed_tasks > 0 {
+            suggestions.push(Suggestion::EmptyTrash { tasks: info.trashed_tasks });
+        }
+        let done_tasks = self.get(&self.root)
+            .map(|root| root.children.iter()
+                .filter(|child| !self.is_archived(child))
+                .filter(|child| self.get(child).is_ok_and(|task| task.progress.is_some_and(|progress| progress.done())))
+                .count())
+            .unwrap_or(0);
+        if done_tasks > 0 {
+            suggestions.push(Suggestion::Archive { tasks: done_tasks });
+        }
+        suggestions
+    }
+}
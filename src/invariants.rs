@@ -0,0 +1,96 @@
+//! Rules every document has to follow.
+//!
+//! In debug builds each command is checked by `commands::transaction`, a
+//! command which breaks one of them fails and is rolled back.  Documents
+//! which were broken before aren't rejected, only new violations count.
+
+use crate::doc::Doc;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Violation {
+    MissingRoot,
+    RootHasParent { parent: Uuid },
+    MissingChild { parent: Uuid, child: Uuid },
+
+    /// The task is listed more than once as child, in one or several
+    /// parents.
+    SeveralParents { task: Uuid },
+
+    /// The task is neither below the root nor in the trash, which includes
+    /// tasks in a cycle.
+    Unreachable { task: Uuid },
+    MissingClockTask { clock: Uuid, task: Uuid },
+
+    /// The running clock of the channel, `None` for the default channel,
+    /// doesn't exist.
+    MissingRunningClock { channel: Option<String>, clock: Uuid },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::MissingRoot => write!(f, "The root task doesn't exist"),
+            Violation::RootHasParent { parent } => write!(f, "The root is a child of {}", parent),
+            Violation::MissingChild { parent, child } => write!(f, "Task {} has the child {} which doesn't exist", parent, child),
+            Violation::SeveralParents { task } => write!(f, "Task {} is a child more than once", task),
+            Violation::Unreachable { task } => write!(f, "Task {} can't be reached from the root", task),
+            Violation::MissingClockTask { clock, task } => write!(f, "Clock {} belongs to task {} which doesn't exist", clock, task),
+            Violation::MissingRunningClock { channel: Some(channel), clock } => write!(f, "The running clock {} of channel {} doesn't exist", clock, channel),
+            Violation::MissingRunningClock { channel: None, clock } => write!(f, "The running clock {} doesn't exist", clock),
+        }
+    }
+}
+
+impl Doc {
+    /// All violations of the rules, empty for a healthy document.
+    pub fn check_invariants(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if !self.map.contains_key(&self.root) {
+            violations.push(Violation::MissingRoot);
+        }
+
+        let mut parents: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for task in self.map.values() {
+            for child in task.children.iter() {
+                if !self.map.contains_key(child) {
+                    violations.push(Violation::MissingChild { parent: task.id, child: *child });
+                }
+                parents.entry(*child).or_default().push(task.id);
+            }
+        }
+        if let Some(parent) = parents.get(&self.root).and_then(|parents| parents.first()) {
+            violations.push(Violation::RootHasParent { parent: *parent });
+        }
+        violations.extend(parents.iter()
+            .filter(|(task, parents)| parents.len() > 1 && self.map.contains_key(task))
+            .map(|(task, _)| Violation::SeveralParents { task: *task }));
+
+        // Walked by hand, so cycles and shared children don't loop forever.
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<Uuid> = std::iter::once(self.root).chain(self.trash).collect();
+        while let Some(task_id) = pending.pop() {
+            if let Some(task) = self.map.get(&task_id) {
+                if reachable.insert(task_id) {
+                    pending.extend(task.children.iter());
+                }
+            }
+        }
+        violations.extend(self.map.keys()
+            .filter(|task_id| !reachable.contains(task_id))
+            .map(|task_id| Violation::Unreachable { task: *task_id }));
+
+        violations.extend(self.clocks.values()
+            .filter_map(|clock| clock.task_id.map(|task| (clock.id, task)))
+            .filter(|(_, task)| !self.map.contains_key(task))
+            .map(|(clock, task)| Violation::MissingClockTask { clock, task }));
+        let running = self.current_clock.map(|clock| (None, clock)).into_iter()
+            .chain(self.channel_clocks.iter().map(|(channel, clock)| (Some(channel.clone()), *clock)));
+        violations.extend(running
+            .filter(|(_, clock)| !self.clocks.contains_key(clock))
+            .map(|(channel, clock)| Violation::MissingRunningClock { channel, clock }));
+        violations
+    }
+}
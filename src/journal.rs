@@ -0,0 +1,214 @@
+//! Append-only journal of document changes.
+//!
+//! Instead of writing the whole document on every autosave, changed tasks,
+//! clocks and activity entries are appended to a journal file next to the
+//! document file.  `Doc::load` replays the journal and `Doc::save` writes
+//! the whole document and removes the journal again.  Once the journal grows
+//! beyond `COMPACT_AFTER` entries, `Doc::save_journal` compacts it into the
+//! document file.
+
+use crate::doc::{Doc, Format};
+use crate::tasks::Task;
+use crate::clock::Clock;
+use crate::activity::Activity;
+use crate::error::*;
+use crate::storage::is_remote;
+#[cfg(feature = "sqlite")]
+use crate::sqlite::SqliteDb;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use snafu::ResultExt;
+use std::cell::{Cell, RefCell};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Number of journal entries after which the journal is compacted.
+pub const COMPACT_AFTER: usize = 1000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum JournalEntry {
+    Task(Rc<Task>),
+    TaskRemoved(Uuid),
+    Clock(Rc<Clock>),
+    Activity(Activity),
+    /// All fields of the document except tasks, clocks and activity.
+    Meta(Value),
+}
+
+/// Changes of a document which are not written to disk yet.
+#[derive(Clone, Debug, Default)]
+pub struct Journal {
+    pending: RefCell<Vec<JournalEntry>>,
+    meta: RefCell<Option<Value>>,
+    written: Cell<usize>,
+
+    /// Length of the journal file without a half-written last line, which
+    /// is cut off before the next append.
+    torn: Cell<Option<u64>>,
+}
+
+impl Journal {
+    /// Remember a change which needs to be written.
+    pub fn record(&self, entry: JournalEntry) {
+        self.pending.borrow_mut().push(entry);
+    }
+
+    /// Check if there are changes of tasks, clocks or activity which are not
+    /// written yet.
+    pub fn is_empty(&self) -> bool {
+        self.pending.borrow().is_empty()
+    }
+
+    /// Forget all changes, called after the whole document was written.
+    pub fn reset(&self) {
+        self.pending.borrow_mut().clear();
+        *self.meta.borrow_mut() = None;
+        self.written.set(0);
+        self.torn.set(None);
+    }
+}
+
+/// Path of the journal which belongs to the document file.
+pub fn journal_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    file_name.push(".journal");
+    path.with_file_name(file_name)
+}
+
+impl Doc {
+    /// Serialize everything except tasks, clocks and activity.
+    ///
+    /// Comparing it before and after a change shows if any of these fields
+    /// changed.
+    pub fn meta(&mut self) -> Result<Value> {
+        let map = std::mem::take(&mut self.map);
+        let clocks = std::mem::take(&mut self.clocks);
+        let activity = std::mem::take(&mut self.activity);
+        let meta = serde_json::to_value(&*self);
+        self.map = map;
+        self.clocks = clocks;
+        self.activity = activity;
+        Ok(meta.context(SerdeSerializationError)?)
+    }
+
+    fn apply_meta(&mut self, meta: Value) -> Result<()> {
+        let mut doc: Doc = serde_json::from_value(meta).context(SerdeSerializationError)?;
+        doc.map = std::mem::take(&mut self.map);
+        doc.clocks = std::mem::take(&mut self.clocks);
+        doc.activity = std::mem::take(&mut self.activity);
+        doc.journal = std::mem::take(&mut self.journal);
+        *self = doc;
+        Ok(())
+    }
+
+    /// Append all changes since the last write to the journal of the
+    /// document file.
+    ///
+    /// Writes the whole document instead if the document file is remote or
+    /// doesn't exist yet, or if the journal became too long.  SQLite
+    /// documents get the changed rows instead of a journal.
+    pub fn save_journal(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly {});
+        }
+        let path = path.as_ref();
+        let meta = self.meta()?;
+        if self.journal.meta.borrow().as_ref() != Some(&meta) {
+            self.journal.record(JournalEntry::Meta(meta.clone()));
+            *self.journal.meta.borrow_mut() = Some(meta);
+        }
+        if self.journal.is_empty() {
+            return Ok(());
+        }
+        if Format::from_path(path) == Format::Sqlite && path.exists() {
+            #[cfg(not(feature = "sqlite"))]
+            return Err(crate::doc::sqlite_disabled());
+            #[cfg(feature = "sqlite")]
+            {
+                let pending = self.journal.pending.replace(Vec::new());
+                return SqliteDb::open(path)?.save_changes(&pending);
+            }
+        }
+        let written = self.journal.written.get();
+        if is_remote(path) || !path.exists() || written + self.journal.pending.borrow().len() > COMPACT_AFTER {
+            return self.save(path);
+        }
+        if let Some(len) = self.journal.torn.take() {
+            OpenOptions::new().write(true).open(journal_path(path)).context(IO)?
+                .set_len(len).context(IO)?;
+        }
+        let file = OpenOptions::new().create(true).append(true)
+            .open(journal_path(path)).context(IO)?;
+        let mut writer = BufWriter::new(file);
+        let pending = self.journal.pending.replace(Vec::new());
+        for entry in pending.iter() {
+            serde_json::to_writer(&mut writer, entry).context(SerdeSerializationError)?;
+            writer.write_all(b"\n").context(IO)?;
+        }
+        writer.flush().context(IO)?;
+        self.journal.written.set(written + pending.len());
+        Ok(())
+    }
+
+    /// Apply the journal of the document file if there is one.
+    ///
+    /// A crash while appending leaves a half-written last line.  It's
+    /// ignored, see `journal_was_cut`, and cut off before the next append.
+    /// Broken lines before the last one fail the replay.
+    pub fn replay_journal(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let content = match std::fs::read(journal_path(path.as_ref())) {
+            Ok(content) => content,
+            Err(_) => return Ok(()),
+        };
+        let mut start = 0;
+        let lines: Vec<(usize, &[u8])> = content.split(|byte| *byte == b'\n')
+            .map(|line| {
+                let line_start = start;
+                start += line.len() + 1;
+                (line_start, line)
+            })
+            .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+            .collect();
+        let mut count = 0;
+        let mut sort_activity = false;
+        let mut torn = None;
+        for (i, (line_start, line)) in lines.iter().enumerate() {
+            let entry = match serde_json::from_slice(line) {
+                Ok(entry) => entry,
+                Err(_) if i + 1 == lines.len() => {
+                    torn = Some(*line_start as u64);
+                    break;
+                },
+                Err(err) => Err(err).context(SerdeSerializationError)?,
+            };
+            match entry {
+                JournalEntry::Task(task) => { self.map.insert(task.id, task); },
+                JournalEntry::TaskRemoved(task_id) => { self.map.remove(&task_id); },
+                JournalEntry::Clock(clock) => { self.clocks.insert(clock.id, clock); },
+                JournalEntry::Activity(entry) => {
+                    self.activity.push(entry);
+                    sort_activity = true;
+                },
+                JournalEntry::Meta(meta) => self.apply_meta(meta)?,
+            }
+            count += 1;
+        }
+        self.invalidate_cache();
+        if sort_activity {
+            self.activity.sort_by_key(|entry| entry.time);
+        }
+        self.journal.reset();
+        self.journal.written.set(count);
+        self.journal.torn.set(torn);
+        Ok(())
+    }
+
+    /// Check if the last line of the journal was half written and ignored
+    /// by `replay_journal`.
+    pub fn journal_was_cut(&self) -> bool {
+        self.journal.torn.get().is_some()
+    }
+}
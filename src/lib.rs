@@ -1,3 +1,24 @@
+//! Task engine of sors, a standalone organization system.
+//!
+//! The library layer is `doc` with `tasks`, `clock` and `clockedit` and the
+//! modules built on them like `diff` or `trash`.  None of them depends on the
+//! command line, so the engine can be embedded in other applications:
+//!
+//! ```
+//! use sors::*;
+//!
+//! let mut doc = Doc::new();
+//! let mut task = Rc::new(Task::new());
+//! task.set_title("Write report");
+//! doc.add_subtask(task.clone(), &doc.root.clone()).unwrap();
+//! doc.modify_task(&task.id, |task| { task.set_progress(Progress::Done); Ok(()) }).unwrap();
+//! assert_eq!(doc.get(&task.id).unwrap().progress, Some(Progress::Done));
+//! ```
+//!
+//! The interactive command line (`cli`, `commands`, `helper` and
+//! `clockeditcli`) is only built with the default `cli` feature, the full
+//! screen view in `tui` with the `tui` feature.
+
 #[macro_use]
 extern crate lazy_static;
 
@@ -6,17 +27,87 @@ pub mod error;
 pub mod tasks;
 pub mod clock;
 pub mod doc;
+pub mod cache;
 pub mod state;
+#[cfg(feature = "cli")]
 pub mod cli;
+pub mod workflow;
+pub mod stats;
+pub mod activity;
+pub mod import;
+pub mod external;
+pub mod journal;
+pub mod budget;
+pub mod timesheet;
+pub mod worktime;
+pub mod trash;
+pub mod output;
+pub mod hooks;
+pub mod config;
+pub mod mount;
+pub mod snapshot;
+pub mod diff;
+pub mod tree;
+pub mod query;
+pub mod context;
+pub mod timezone;
+pub mod calendar;
+pub mod duration;
+pub mod sort;
+pub mod planner;
+pub mod habit;
+pub mod review;
+pub mod aging;
+pub mod agenda;
+pub mod inbox;
+pub mod ics;
+pub mod caldav;
+pub mod storage;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod billing;
+pub mod anonymize;
+pub mod info;
+pub mod generate;
+pub mod invariants;
+pub mod clockexport;
+pub mod text;
+pub mod render;
+pub mod links;
+pub mod split;
+pub mod curl;
+pub mod clockedit;
+#[cfg(feature = "cli")]
+pub mod clockeditcli;
+#[cfg(feature = "cli")]
+pub mod helper;
+#[cfg(feature = "cli")]
+pub mod commands;
+#[cfg(feature = "tui")]
+pub mod tui;
 
 pub use std::env::var;
 pub use uuid::Uuid;
 pub use std::io::Write;
 pub use std::path::Path;
-pub use chrono::Local;
+pub use chrono::{Local, Utc};
 pub use std::rc::Rc;
 
 pub use error::*;
 pub use tasks::*;
 pub use doc::*;
-pub use state::*;
\ No newline at end of file
+pub use state::*;
+pub use workflow::*;
+pub use stats::*;
+pub use activity::*;
+pub use budget::*;
+pub use timesheet::*;
+pub use worktime::*;
+pub use trash::*;
+pub use tree::*;
+pub use context::*;
+pub use timezone::DisplayZone;
+pub use duration::*;
+pub use sort::SortKey;
+pub use habit::*;
+pub use hooks::*;
\ No newline at end of file
@@ -0,0 +1,172 @@
+//! Wiki links like `[[3f2a9c1e]]` or `[[Release notes]]` between tasks in
+//! bodies and notes, and URLs to open with `open`.
+//!
+//! A link names the task by the start of its id or by its title, never by
+//! its place in the tree, so links stay valid when tasks are moved.  Titles
+//! which are used more than once don't resolve, the short id always does.
+
+use crate::doc::Doc;
+use crate::external::is_url;
+use crate::tasks::Task;
+use uuid::Uuid;
+
+/// Length of short ids like `3f2a9c1e`, the start of the UUID.
+pub const SHORT_ID_LEN: usize = 8;
+
+pub fn short_id(id: &Uuid) -> String {
+    id.to_string()[..SHORT_ID_LEN].to_string()
+}
+
+/// `[[target]]` in a text, `range` covers the brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink {
+    pub range: std::ops::Range<usize>,
+    pub target: String,
+}
+
+/// All wiki links in the text, targets are trimmed and never empty.
+pub fn wiki_links(text: &str) -> Vec<WikiLink> {
+    let mut links = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("[[").map(|start| offset + start) {
+        let inner = start + 2;
+        let end = match text[inner..].find("]]") {
+            Some(end) => inner + end,
+            None => break,
+        };
+        let target = &text[inner..end];
+        // `[[[x]]` starts the link at the last two brackets.
+        if let Some(bracket) = target.rfind('[') {
+            offset = inner + bracket - 1;
+            continue;
+        }
+        if !target.trim().is_empty() && !target.contains(']') && !target.contains('\n') {
+            links.push(WikiLink { range: start..end + 2, target: target.trim().to_string() });
+        }
+        offset = end + 2;
+    }
+    links
+}
+
+/// URLs starting with `http://` or `https://` in the text.  Punctuation at
+/// the end like the `.` of a sentence or the `)` of a Markdown link isn't
+/// part of the URL.
+pub fn detect_urls(text: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    for word in text.split_whitespace() {
+        let start = match [word.find("https://"), word.find("http://")].iter().flatten().min() {
+            Some(start) => *start,
+            None => continue,
+        };
+        let url = &word[start..];
+        let mut url = &url[..url.find(|c| "<>\"'`".contains(c)).unwrap_or(url.len())];
+        loop {
+            let trimmed = url.trim_end_matches(|c| ".,;:!?*_".contains(c));
+            let trimmed = match trimmed.chars().last() {
+                Some(')') if trimmed.matches('(').count() < trimmed.matches(')').count() => &trimmed[..trimmed.len() - 1],
+                Some(']') if trimmed.matches('[').count() < trimmed.matches(']').count() => &trimmed[..trimmed.len() - 1],
+                _ => trimmed,
+            };
+            if trimmed == url {
+                break;
+            }
+            url = trimmed;
+        }
+        if is_url(url) && url.len() > "https://".len() {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+impl Task {
+    /// URLs in the title, external reference, body and notes, then the
+    /// attachments, each once.  `open` numbers them from 1.
+    pub fn links(&self) -> Vec<String> {
+        let external_ref = self.external_ref.iter().filter(|external_ref| is_url(external_ref)).cloned();
+        let texts = std::iter::once(&self.body).chain(self.notes.iter().map(|note| &note.text));
+        let candidates = detect_urls(&self.title).into_iter()
+            .chain(external_ref)
+            .chain(texts.flat_map(|text| detect_urls(text)))
+            .chain(self.attachments.iter().cloned());
+        let mut links: Vec<String> = Vec::new();
+        for link in candidates {
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+        links
+    }
+}
+
+fn is_id_prefix(target: &str) -> bool {
+    target.len() >= SHORT_ID_LEN && target.len() <= 36
+        && target.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+impl Doc {
+    /// Task the link target names, by id prefix or else by title ignoring
+    /// case.  None if no task or several match.
+    pub fn resolve_link(&self, target: &str) -> Option<Uuid> {
+        let unique = |mut matches: Box<dyn Iterator<Item = Uuid> + '_>| {
+            let first = matches.next()?;
+            if matches.next().is_some() {
+                None
+            } else {
+                Some(first)
+            }
+        };
+        let target = target.trim();
+        if is_id_prefix(target) {
+            let prefix = target.to_lowercase();
+            let by_id = unique(Box::new(self.map.keys().filter(|id| id.to_string().starts_with(&prefix)).cloned()));
+            if by_id.is_some() {
+                return by_id;
+            }
+        }
+        let title = target.to_lowercase();
+        unique(Box::new(self.map.values().filter(|task| task.title.to_lowercase() == title).map(|task| task.id)))
+    }
+
+    /// The text with resolved wiki links replaced by Markdown links with
+    /// the current title of the task and `href` of its id.  Other links
+    /// stay as they are.
+    pub fn expand_links(&self, text: &str, href: impl Fn(&Uuid) -> String) -> String {
+        let mut result = String::new();
+        let mut last = 0;
+        for link in wiki_links(text) {
+            let task = match self.resolve_link(&link.target).and_then(|task_id| self.get(&task_id).ok()) {
+                Some(task) => task,
+                None => continue,
+            };
+            result.push_str(&text[last..link.range.start]);
+            let title: String = task.title.chars().filter(|c| *c != '[' && *c != ']').collect();
+            result.push_str(&format!("[{}]({})", title, href(&task.id)));
+            last = link.range.end;
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+
+    /// Tasks below the root with a link to the task in their body or notes,
+    /// in tree order.
+    pub fn backlinks(&self, task_ref: &Uuid) -> Vec<Uuid> {
+        let task = match self.get(task_ref) {
+            Ok(task) => task,
+            Err(_) => return Vec::new(),
+        };
+        let id = task.id.to_string();
+        let title = task.title.to_lowercase();
+        // Only links which could name the task are resolved.
+        let links_to_task = |text: &str| wiki_links(text).iter().any(|link| {
+            let target = link.target.to_lowercase();
+            (id.starts_with(&target) || target == title) && self.resolve_link(&link.target) == Some(task.id)
+        });
+        self.iter_subtree(&self.root)
+            .map(|(_, linking)| linking)
+            .filter(|linking| linking.id != task.id)
+            .filter(|linking| links_to_task(&linking.body) || linking.notes.iter().any(|note| links_to_task(&note.text)))
+            .map(|linking| linking.id)
+            .collect()
+    }
+}
@@ -6,77 +6,180 @@ pub mod error;
 pub mod tasks;
 pub mod clock;
 pub mod doc;
+pub mod cache;
 pub mod state;
 pub mod cli;
+pub mod workflow;
+pub mod stats;
+pub mod activity;
+pub mod import;
+pub mod external;
+pub mod journal;
+pub mod budget;
+pub mod timesheet;
+pub mod worktime;
+pub mod trash;
+pub mod output;
+pub mod hooks;
+pub mod config;
+pub mod mount;
+pub mod snapshot;
+pub mod diff;
+pub mod tree;
+pub mod query;
+pub mod context;
+pub mod timezone;
+pub mod calendar;
+pub mod duration;
+pub mod sort;
+pub mod planner;
+pub mod habit;
+pub mod review;
+pub mod aging;
+pub mod agenda;
+pub mod inbox;
+pub mod ics;
+pub mod caldav;
+pub mod storage;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod billing;
+pub mod anonymize;
+pub mod info;
+pub mod generate;
+pub mod invariants;
+pub mod clockexport;
+pub mod text;
+pub mod render;
+pub mod links;
+pub mod split;
+pub mod curl;
 pub mod clockedit;
 pub mod clockeditcli;
 pub mod helper;
+pub mod commands;
+#[cfg(feature = "tui")]
+pub mod tui;
 
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
 use std::env::var;
-use std::io::Write;
-use std::path::Path;
-use chrono::Local;
-use std::rc::Rc;
+use std::io::{IsTerminal, Write};
 
-use error::*;
-use tasks::*;
 use doc::*;
 use state::*;
-use clockeditcli::*;
+use output::Theme;
+use config::{Config, expand_home, find_project_file};
+use hooks::Hooks;
 use helper::*;
 use cli::*;
 use std::fs::File;
 use std::io::Read;
+use snafu::ResultExt;
 use crate::statics::*;
 
-
-
-trait DurationPrint {
-    fn print(&self) -> String;
-}
-
-impl DurationPrint for chrono::Duration {
-    fn print(&self) -> String {
-        format!("{}d {}h {}m {}s",
-            self.num_days(),
-            self.num_hours() % 24,
-            self.num_minutes() % 60,
-            self.num_seconds() % 60
-        )
-    }
-}
-
 struct TerminalCallback {
-    rl: Editor<()>,
+    /// None without line editing, see `line_editing_supported`.
+    rl: Option<Editor<()>>,
     exit: bool,
     main_save_path: String,
+    config: Config,
+    theme: Theme,
+
+    /// Command history of the document, see `Config::history_file_for`.
+    history_file: String,
+
+    /// When the current command started, hooks run for events since then.
+    command_start: chrono::DateTime<chrono::Local>,
+
+    /// Colors are disabled by `NO_COLOR` or if stdout is no terminal.
+    colors_supported: bool,
 }
 impl TerminalCallback {
-    pub fn new(main_save_path: String) -> Self {
-        let mut rl = Editor::<()>::new();
-        if rl.load_history(&*statics::HISTORY_FILE).is_err() {
-            println!("No previous history.");
-        }
+    pub fn new(main_save_path: String, config: Config, line_editing: bool) -> Self {
+        let history_file = config.history_file_for(&main_save_path);
+        let rl = Some(Editor::<()>::new()).filter(|_| line_editing).map(|mut rl| {
+            if rl.load_history(&history_file).is_err() {
+                println!("No previous history.");
+            }
+            rl
+        });
         TerminalCallback {
             rl,
+            history_file,
             main_save_path,
+            config,
             exit: false,
+            command_start: chrono::Local::now(),
+            theme: Theme::load(&*THEME_FILE).unwrap_or_default(),
+            colors_supported: var("NO_COLOR").is_err() && std::io::stdout().is_terminal(),
         }
     }
 }
 
 impl CliStateCallback<State> for TerminalCallback {
-    //fn pre_exec(&mut self, state: &mut T) {}
+    fn pre_exec(&mut self, state: &mut State, _command: &str) {
+        self.command_start = chrono::Local::now();
+        if state.changed_on_disk() {
+            self.handle_disk_change(state);
+        }
+        state.update_dirty();
+    }
     fn post_exec(&mut self, state: &mut State, command: &str) {
-        if Autosave::OnCommand == state.autosave {
-            if let Err(err) = state.doc.save(&self.main_save_path) {
+        run_hooks(state, self.command_start, self);
+        if Autosave::OnCommand == state.autosave && state.update_dirty() {
+            let path = self.main_save_path.clone();
+            if let Err(err) = save_doc(state, &path, true, self) {
                 self.println(&format!("Couldn't save the file, sorry: {}", err));
             }
         }
-        self.rl.add_history_entry(command);
+        state.update_dirty();
+        state.update_ics_feed();
+        if let Some(rl) = self.rl.as_mut() {
+            rl.add_history_entry(command);
+        }
+    }
+    fn prompt(&mut self, state: &State, default: &str) -> String {
+        let default = if state.dirty { format!("*{}", default) } else { default.to_string() };
+        let default = if state.read_only { format!("[ro] {}", default) } else { default };
+        let default = match &state.context {
+            Some(context) => format!("{} {}", context, default),
+            None => default,
+        };
+        let default = match state.focus.and_then(|focus| state.doc.get(&focus).ok()) {
+            Some(focus) => format!("[focus: {}] {}", focus.title, default),
+            None => default,
+        };
+        match clock_indicator(&state.doc) {
+            Some(indicator) => format!("{} {}", indicator, default),
+            None => default,
+        }
+    }
+}
+
+impl TerminalCallback {
+    fn handle_disk_change(&mut self, state: &mut State) {
+        loop {
+            let answer = match self.read_line("The file changed on disk. [r]eload, [m]erge or [k]eep local version? ") {
+                CliInputResult::Value(answer) => answer,
+                CliInputResult::Termination => "k".to_string(),
+            };
+            let result = match answer.trim() {
+                "r" => state.load_doc().map(|doc| {
+                    state.replace_doc(doc);
+                    state.mark_saved();
+                }),
+                "m" => state.load_doc().map(|doc| state.doc.merge(&doc)),
+                "k" => Ok(()),
+                _ => continue,
+            };
+            if let Err(err) = result {
+                self.println(&format!("Couldn't load the file: {}", err));
+            }
+            break;
+        }
+        state.refresh_disk_state();
     }
 }
 
@@ -89,7 +192,11 @@ impl CliCallbacks<State> for TerminalCallback {
     }
 
     fn read_line(&mut self, prompt: &str) -> CliInputResult {
-        match self.rl.readline(prompt) {
+        let rl = match self.rl.as_mut() {
+            Some(rl) => rl,
+            None => return read_plain_line(&mut std::io::stdin().lock(), &mut std::io::stdout(), prompt),
+        };
+        match rl.readline(prompt) {
             Ok(input) => CliInputResult::Value(input),
             Err(ReadlineError::Eof) => CliInputResult::Termination,
             Err(ReadlineError::Interrupted) => CliInputResult::Termination,
@@ -99,328 +206,228 @@ impl CliCallbacks<State> for TerminalCallback {
             }
         }
     }
-    fn edit_string(&mut self, text: String) -> String {
-        {   
-            let mut out = File::create(&*TASK_FILE).expect("Could not create .task file");
-            out.write_all(text.as_bytes()).expect("Couldn't write title to .task file");
+    fn edit_string(&mut self, text: String) -> error::Result<String> {
+        let edit_file = self.config.edit_file_path();
+        let path = edit_file.clone();
+        if let Some(dir) = std::path::Path::new(&edit_file).parent() {
+            std::fs::create_dir_all(dir).context(error::EditFile { path: path.clone() })?;
+        }
+        {
+            let mut out = File::create(&edit_file).context(error::EditFile { path: path.clone() })?;
+            out.write_all(text.as_bytes()).context(error::EditFile { path: path.clone() })?;
+        }
+        let mut editor = self.config.editor.split_whitespace();
+        let program = editor.next().unwrap_or("vi");
+        match subprocess::Exec::cmd(program).args(&editor.collect::<Vec<_>>()).arg(&edit_file).join() {
+            Ok(status) if status.success() => {},
+            Ok(status) => return Err(error::Error::EditorFailed { program: program.to_string(), msg: format!("{:?}", status) }),
+            Err(err) => return Err(error::Error::EditorFailed { program: program.to_string(), msg: err.to_string() }),
         }
-        subprocess::Exec::cmd("vi").arg(&*TASK_FILE).join().unwrap();
         let mut content = String::new();
         {
-            let mut input = File::open(&*TASK_FILE).expect("Could not open .task file");
-            input.read_to_string(&mut content).expect("Couldn't read .task file");
+            let mut input = File::open(&edit_file).context(error::EditFile { path: path.clone() })?;
+            input.read_to_string(&mut content).context(error::EditFile { path })?;
         }
-        content
+        Ok(content)
     }
 
     fn exit(&mut self) {
         self.exit = true;
-        if let Err(err) = self.rl.save_history(&*statics::HISTORY_FILE) {
-            println!("Failed to save history: {}", err);
+        if let Some(rl) = self.rl.as_ref() {
+            if let Some(dir) = std::path::Path::new(&self.history_file).parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            if let Err(err) = rl.save_history(&self.history_file) {
+                println!("Failed to save history: {}", err);
+            }
         }
     }
 
     fn is_exit(&self) -> bool {
         self.exit
     }
+
+    fn theme(&self) -> Option<&Theme> {
+        Some(&self.theme).filter(|_| self.colors_supported)
+    }
+    fn theme_mut(&mut self) -> Option<&mut Theme> {
+        Some(&mut self.theme)
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+    fn config_mut(&mut self) -> Option<&mut Config> {
+        Some(&mut self.config)
+    }
 }
 
 
-fn main() {
-    let main_file_path = format!("{}/.tasks.json", var("HOME").unwrap());
-    let doc = Doc::load(&main_file_path).unwrap_or_default();
-    let state = State {
-        wt: doc.root,
-        doc,
-        parents: Vec::new(),
-        path: main_file_path.clone(),
-        autosave: Autosave::ManualOnly,
-    };
-    let mut terminal = cli::Cli::new(state, TerminalCallback::new(main_file_path));
-    terminal.register_command("exit", Box::new(|_, _, response| {
-        response.exit();
-        Ok(())
-    }));
-    terminal.register_command("debug", Box::new(|state, _, response| { 
-        response.println(&format!("{:?}", state));
-        Ok(())
-    }));
-    terminal.register_command("ls", Box::new(|state: &mut State, _, response| {
-        let task = state.doc.get(&state.wt)?;
-        let mut breadcrumb_item_opn = Some(state.wt);
-        let mut breadcrumb_data = Vec::new();
-        while let Some(breadcrumb_item) = breadcrumb_item_opn {
-            breadcrumb_data.push(breadcrumb_item);
-            breadcrumb_item_opn = state.doc.find_parent(&breadcrumb_item);
-        }
-        breadcrumb_data.iter().rev().zip(1..).for_each(|(breadcrumb_ref, i)| {
-            if let Ok(task) = state.doc.get(breadcrumb_ref) {
-                if i > 1 {
-                    response.print(" -> ");
-                }
-                response.print(&task.title);
-            }
-        });
-        let (done, all_subtasks) = state.doc.progress_summary(&task.id)?;
-        response.println(&format!("  [{}/{}]", done, all_subtasks));
-        response.println("");
-        response.println(&task.body);
-        response.println("--- Children: ");
-        for (child_id, i) in task.children.iter().zip(1..) {
-            let child = state.doc.get(child_id)?;
-            let progress_str = if let Some(progress) = &child.progress {
-                progress.to_string()
-            } else {
-                String::new()
-            };
-            response.println(&format!("{}: {} {}", i, progress_str, child.title));
-        }
-        Ok(())
-    }));
-    terminal.register_command("ed", Box::new(|state: &mut State, _, callbacks| {
-        let task = vim_edit_task(state.doc.get(&state.wt)?, callbacks)?;
-        state.doc.upsert(task);
-        Ok(())
-    }));
-    terminal.register_command("add", Box::new(|state: &mut State, _, callbacks| {
-        let task = vim_edit_task(Rc::new(Task::new()), callbacks)?;
-        state.doc.add_subtask(task, &state.wt)?;
-        Ok(())
-    }));
-    terminal.register_command("save", Box::new(|state: &mut State, cmd: &str, _| {
-        let mut split = cmd.split(' ');
-        split.next();
-        let filename = if let Some(filename) = split.next() {
-            filename
-        } else {
-            &state.path
-        };
-        state.doc.save(filename).expect("Couldn't save the file");
-        Ok(())
-    }));
-    terminal.register_command("load", Box::new(|state: &mut State, cmd: &str, _| {
-        let mut split = cmd.split(' ');
-        split.next();
-        let filename = if let Some(filename) = split.next() {
-            filename
-        } else {
-            &state.path
-        };
-        let doc = Doc::load(filename).expect("Couldn't save the file");
-        let new_root = doc.root;
-        state.doc = doc;
-        state.wt = new_root;
-        Ok(())
-    }));
-    terminal.register_command("cd", Box::new(|state: &mut State, cmd: &str, _| {
-        let mut split = cmd.split(' ');
-        split.next();
-        if let Some(path) = split.next() {
-            state.wt = state.uuid_for_path(path)
-                .ok_or(CliError::ParseError { msg: "Couldn't resolve path".to_string() })?
-        } else {
-            state.wt = state.doc.root;
-            state.parents = Vec::new();
-        }
-        Ok(())
-    }));
-    terminal.register_command("todo", Box::new(|state: &mut State, _, _| {
-        let mut task = state.doc.get(&state.wt)?;
-        task.set_progress(Progress::Todo);
-        state.doc.upsert(task);
-        Ok(())
-    }));
-    terminal.register_command("work", Box::new(|state: &mut State, _, _| {
-        let mut task = state.doc.get(&state.wt)?;
-        task.set_progress(Progress::Work);
-        state.doc.upsert(task);
-        Ok(())
-    }));
-    terminal.register_command("done", Box::new(|state: &mut State, _, _| {
-        let mut task = state.doc.get(&state.wt)?;
-        task.set_progress(Progress::Done);
-        state.doc.upsert(task);
-        Ok(())
-    }));
-    terminal.register_command("id", Box::new(|state: &mut State, _, response| {
-        let task = state.doc.get(&state.wt)?;
-        response.println(&format!("Task ID: {}", task.id));
-        Ok(())
-    }));
-    terminal.register_command("parent", Box::new(|state: &mut State, _, response| {
-        let task = state.doc.get(&state.wt)?;
-        if let Some(parent)  = state.doc.find_parent(&task.id) {
-            response.println(&format!("Parent Task ID: {}", parent));
-        }
-        Ok(())
-    }));
-    terminal.register_command("rm", Box::new(|state: &mut State, cmd: &str, _| {
-        let mut split = cmd.split(' ');
-        split.next();
-        if let Some(path) = split.next() {
-            if let Some(child_id) = state.uuid_for_path(path) {
-                if let Some(parent) = state.doc.find_parent(&child_id) {
-                    let mut task = state.doc.get(&parent)?;
-                    task.remove_child(&child_id);
-                    state.doc.upsert(task);
-                }
-            }
-        }
-        Ok(())
-    }));
-    terminal.register_command("mv", Box::new(|state: &mut State, cmd: &str, _response| {
-        let mut split = cmd.split(' ');
-        split.next();
-        let dest_id = {
-            let path = split.next().ok_or(CliError::ParseError{ msg: "First path contains errors".to_string() })?;
-            state.uuid_for_path(path).ok_or_else(|| Box::new(CliError::ParseError{ msg: "First path contains errors".to_string() }))?
-        };
-        let to_id = {
-            let path = split.next().ok_or(CliError::ParseError{ msg: "First path contains errors".to_string() })?;
-            state.uuid_for_path(path).ok_or_else(|| Box::new(CliError::ParseError{ msg: "First path contains errors".to_string() }))?
-        };
-        let parent_id = state.doc.find_parent(&dest_id)
-            .ok_or(CliError::OtherError { msg: "Couldn't find parent".to_string()} )?;
+const USAGE: &str = "Usage: sors-cli [--file <path>... | --global] [--read-only] [--plain] [--tui]
+                [--capture <text>] [--no-rc]
 
-        let mut parent = state.doc.get(&parent_id)?;
-        parent.remove_child(&dest_id);
-        state.doc.upsert(parent);
-        let mut task = state.doc.get(&to_id)?;
-        task.add_child(dest_id);
-        state.doc.upsert(task);
-        Ok(())
-    }));
-    terminal.register_command("outline", Box::new(|state: &mut State, cmd: &str, response| {
-        let mut split = cmd.split(' ');
-        split.next();
-        let max_depth = if let Some(depth_str) = split.next() {
-            if let Ok(max_depth) = depth_str.parse() {
-                max_depth
-            } else {
-                1000
-            }
-        } else {
-            1000
-        };
-        rec_print(&mut state.doc, &state.wt, 0, max_depth, response)?;
-        Ok(())
-    }));
-    terminal.register_command("html", Box::new(|state: &mut State, _, response| {
-        dump_html(&state.doc, Path::new("html"), &state.wt, response)?;
-        Ok(())
-    }));
-    terminal.register_command("reorder", Box::new(|state: &mut State, cmd: &str, _| {
-        let mut split = cmd.split(' ');
-        split.next();
-        let idx_string: &str = split.next().ok_or(Error::UnsufficientInput {})?;
-        let idx_from: usize = idx_string.parse()?;
-        let idx_string: &str = split.next().ok_or(Error::UnsufficientInput {})?;
-        let idx_to: usize = idx_string.parse()?;
-        let mut task = state.doc.get(&state.wt)?;
-        if idx_from > task.children.len() {
-            return Err(Box::new(Error::ChildOutOfIndex {}));
-        }
-        if idx_to > task.children.len() {
-            return Err(Box::new(Error::ChildOutOfIndex {}));
+Without options, the project document .sors/tasks.json in the current
+directory or one of its parents is opened, otherwise the global document.
+Several --file options open the documents below a common root.
+Paths like ssh://host/path/tasks.json are read and written with ssh,
+s3://bucket/tasks.json with the s3_* settings of the config.
+Documents ending in .db are SQLite databases which are saved incrementally.
+With --read-only, all commands which change the document fail.
+With --plain, lines are read without line editing and history, which is
+the default if stdin is no terminal, TERM is dumb or inside Emacs.
+With --tui, the full screen tree view is shown before the prompt.
+With --capture, the text is added to the inbox and the document is saved
+without starting the prompt.
+Before the prompt starts, the commands in ~/.config/sors/sorsrc are run
+like `context @work` or `cd @inbox`, unless --no-rc is given.";
+
+struct Args {
+    doc: DocArg,
+    read_only: bool,
+    plain: bool,
+    tui: bool,
+    capture: Option<String>,
+    no_rc: bool,
+}
+
+/// Document selected on the command line.
+enum DocArg {
+    Discover,
+    Files(Vec<String>),
+    Global,
+}
+
+/// Print the message with the usage and exit with an error.
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
+fn parse_args() -> Args {
+    let mut doc_arg = DocArg::Discover;
+    let mut read_only = false;
+    let mut plain = false;
+    let mut tui = false;
+    let mut capture = None;
+    let mut no_rc = false;
+    let mut files = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" | "-f" => files.push(args.next().unwrap_or_else(|| usage_error("Missing path"))),
+            "--global" | "-g" => doc_arg = DocArg::Global,
+            "--read-only" | "-r" => read_only = true,
+            "--plain" => plain = true,
+            "--tui" => tui = true,
+            "--no-rc" => no_rc = true,
+            "--capture" | "-c" => capture = Some(args.next().unwrap_or_else(|| usage_error("Missing text"))),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            },
+            _ => match arg.strip_prefix("--file=") {
+                Some(path) => files.push(path.to_string()),
+                None => usage_error(&format!("Unknown argument: {}", arg)),
+            },
         }
-        let from_id = task.children[idx_from - 1];
-        task.remove_child(&from_id);
-        task.insert_child(from_id, idx_to - 1);
-        state.doc.upsert(task);
-        Ok(())
-    }));
-    terminal.register_command("cli", Box::new(|state: &mut State, _, _| {
-        state.doc.clock_new()?;
-        state.doc.clock_assign(state.wt)?;
-        Ok(())
-    }));
-    terminal.register_command("cln", Box::new(|state: &mut State, _, _| {
-        state.doc.clock_new()?;
-        Ok(())
-    }));
-    terminal.register_command("cla", Box::new(|state: &mut State, _, _| {
-        state.doc.clock_assign(state.wt)?;
-        Ok(())
-    }));
-    terminal.register_command("clo", Box::new(|state: &mut State, _, _| {
-        state.doc.clock_out()?;
-        Ok(())
-    }));
-    terminal.register_command("clc", Box::new(|state: &mut State, _, _response| {
-        let mut comment = String::new();
-        print!("Clock comment> ");
-        std::io::stdout().flush()?;
-        std::io::stdin().read_line(&mut comment)?;
-        state.doc.clock_comment(comment.trim())?;
-        Ok(())
-    }));
+    }
+    if !files.is_empty() {
+        doc_arg = DocArg::Files(files);
+    }
+    Args { doc: doc_arg, read_only, plain, tui, capture, no_rc }
+}
+
+/// Check if the file exists but can't be written.
+fn is_write_protected(path: &str) -> bool {
+    std::fs::metadata(path).map(|metadata| metadata.permissions().readonly()).unwrap_or(false)
+}
 
-    terminal.register_command("taskclock", Box::new(|state: &mut State, _, response| {
-        let mut clocks = state.doc.task_clock(&state.wt);
-        clocks.sort();
-        display_clocks(&clocks, &state.doc, response);
-        Ok(())
-    }));
-    terminal.register_command("dayclock", Box::new(|state: &mut State, cmd: &str, response| {
-        let mut cmd_split = cmd.split(' ');
-        cmd_split.next();
-        let date = if let Some(param) = cmd_split.next() {
-            parse_date(param)?
-        } else {
-            Local::today()
+fn main() {
+    let args = parse_args();
+    let config = Config::load(&*CONFIG_FILE).unwrap_or_else(|err| {
+        println!("Couldn't read the config, using the defaults: {}", err);
+        Config::default()
+    });
+    storage::set_s3_config(config.s3_config());
+    if let Err(err) = std::fs::create_dir_all(&*DATA_DIR) {
+        println!("Couldn't create {}: {}", *DATA_DIR, err);
+    }
+    match config.migrate_legacy_files() {
+        Ok(moved) => for (from, to) in moved {
+            println!("Moved {} to {}", from, to);
+        },
+        Err(err) => println!("Couldn't move the files to the new location: {}", err),
+    }
+    let project_file = std::env::current_dir().ok().and_then(|dir| find_project_file(&dir));
+    let files = match (args.doc, project_file) {
+        (DocArg::Files(files), _) => files.iter().map(|file| expand_home(file)).collect(),
+        (DocArg::Discover, Some(project_file)) => {
+            println!("Using the project document {}", project_file.display());
+            vec![project_file.to_string_lossy().to_string()]
+        },
+        _ => vec![config.file_path()],
+    };
+    let main_file_path = files[0].clone();
+    let mut state = if files.len() > 1 {
+        State::new_mounted(&files).unwrap_or_else(|err| {
+            eprintln!("Couldn't load the documents: {}", err);
+            std::process::exit(1);
+        })
+    } else {
+        let doc = match Doc::load(&main_file_path) {
+            Ok(doc) => doc,
+            // An empty document would replace the existing one on the next save.
+            Err(err) if storage::storage(&main_file_path).exists() => {
+                eprintln!("Couldn't load the document: {}", err);
+                std::process::exit(1);
+            },
+            Err(_) => Doc::default(),
         };
-        let mut clocks = state.doc.day_clock(date, state.wt);
-        clocks.sort();
-        display_clocks(&clocks, &state.doc, response);
-        Ok(())
-    }));
-    terminal.register_command("autosave", Box::new(|state: &mut State, _, _| {
+        if doc.journal_was_cut() {
+            println!("The last change in the journal was only half written and is ignored");
+        }
+        State::new(doc, &main_file_path)
+    };
+    state.hooks = Hooks::load(&*HOOKS_FILE).unwrap_or_default();
+    if !args.read_only && files.iter().any(|file| is_write_protected(file)) {
+        println!("The document is write protected, opening it read-only");
+    }
+    state.set_read_only(args.read_only || files.iter().any(|file| is_write_protected(file)));
+    if config.autosave {
         state.autosave = Autosave::OnCommand;
-        Ok(())
-    }));
-    terminal.register_command("noautosave", Box::new(|state: &mut State, _, _| {
-        state.autosave = Autosave::ManualOnly;
-        Ok(())
-    }));
-    terminal.register_command("cle", Box::new(|state: &mut State, cmd: &str, callbacks| {
-        let mut cmd_split = cmd.split(' ');
-        cmd_split.next();
-        let date = if let Some(param) = cmd_split.next() {
-            parse_date(param)?
-        } else {
-            Local::today()
-        };
-        let clockedit_state = {
-            let clockedit_state = ClockEditCli {
-                clockedit: state.doc.create_clock_edit(date),
-                apply_result: ExitAction::Cancel,
-                doc: &state.doc,
-            };
-            let mut clockedit_cli = new_cli_with_callbacks(callbacks, clockedit_state, ClockCallbacks);
-            ClockEditCli::apply_commands(&mut clockedit_cli);
-            clockedit_cli.run_loop("clockedit> ");
-            clockedit_cli.state
-        };
-        if clockedit_state.apply_result == ExitAction::Apply {
-            for clock in clockedit_state.clockedit.clocks.iter().cloned() {
-                state.doc.upsert_clock(clock);
+    }
+    let mut terminal = cli::Cli::new(state, TerminalCallback::new(main_file_path, config, !args.plain && line_editing_supported()));
+    commands::register_default_commands(&mut terminal);
+    if let Some(rl) = terminal.callbacks.rl.as_ref() {
+        terminal.history = rl.history().iter().cloned().collect();
+    }
+    if let Some(text) = args.capture {
+        for cmd in &[format!("capture {}", text), "save".to_string()] {
+            terminal.callbacks.pre_exec(&mut terminal.state, cmd);
+            if let Err(err) = terminal.run_command(cmd) {
+                eprintln!("Couldn't capture the task: {}", err);
+                std::process::exit(1);
             }
+            terminal.callbacks.post_exec(&mut terminal.state, cmd);
         }
-        Ok(())
-    }));
-    terminal.register_command("rangeclock", Box::new(|state: &mut State, cmd: &str, response| {
-        let mut split_cmd = cmd.split(' ');
-        split_cmd.next();
-        if let Some(index_str) = split_cmd.next() {
-            if let Ok(i) = index_str.parse() {
-                let end = Local::today();
-                let duration = chrono::Duration::days(i);
-                let start = end - duration;
-                let clocks = state.doc.range_clock(start, end, state.wt);
-                display_clocks(&clocks, &state.doc, response);
-            }
+        return;
+    }
+    if !terminal.state.read_only {
+        apply_auto_clock_out(&mut terminal.state.doc, &mut terminal.callbacks);
+        terminal.state.update_dirty();
+    }
+    if !args.no_rc {
+        if let Ok(script) = std::fs::read_to_string(&*RC_FILE) {
+            terminal.run_script(&script);
         }
-        Ok(())
-    }));
+    }
+    display_reminders(&terminal.state, &mut terminal.callbacks);
+    if args.tui {
+        terminal.callbacks.pre_exec(&mut terminal.state, "tui");
+        if let Err(err) = terminal.run_command("tui") {
+            println!("Couldn't start the full screen view: {}", err);
+        }
+        terminal.callbacks.post_exec(&mut terminal.state, "tui");
+    }
     terminal.run_loop("> ");
 }
@@ -0,0 +1,174 @@
+//! Several documents shown as children of one virtual root.
+//!
+//! The documents are combined into one `Doc` since task and clock ids are
+//! unique across documents.  Saving splits it again, every document gets the
+//! subtree below its root together with its clocks, activity and trash.
+
+use crate::doc::Doc;
+use crate::activity::ActivityEvent;
+use crate::tasks::*;
+use crate::error::*;
+use crate::storage::storage;
+use std::collections::HashSet;
+use std::path::Path;
+use uuid::Uuid;
+
+/// A document which is part of a combined document.
+#[derive(Clone, Debug)]
+pub struct Mount {
+    pub path: String,
+
+    /// Root of the document, a child of the virtual root.
+    pub root: Uuid,
+
+    /// The document as it was loaded without tasks, clocks and activity.
+    /// Settings like the workflow are taken from here when it's saved.
+    pub settings: Doc,
+}
+
+impl Mount {
+    /// Name shown for the document, the file name without extension.
+    pub fn name(&self) -> String {
+        Path::new(&self.path).file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.clone())
+    }
+}
+
+impl Doc {
+    /// Combine the documents as children of a new virtual root.
+    ///
+    /// Settings like the workflow are taken from the first document.  Roots
+    /// without title get the file name as title.
+    pub fn combine(docs: Vec<(String, Doc)>) -> (Doc, Vec<Mount>) {
+        let mut combined = Doc::new();
+        let mut virtual_root = combined.get(&combined.root).expect("new document has a root");
+        let mut mounts = Vec::new();
+        for (i, (path, mut doc)) in docs.into_iter().enumerate() {
+            if i == 0 {
+                combined.current_clock = doc.current_clock;
+                combined.channel_clocks = doc.channel_clocks.clone();
+                combined.progress_rollup = doc.progress_rollup;
+                combined.workflow = doc.workflow.clone();
+                combined.timesheet_projects = doc.timesheet_projects.clone();
+                combined.work_hours = doc.work_hours.clone();
+                combined.breaks = doc.breaks.clone();
+                combined.timezone = doc.timezone;
+                combined.caldav = doc.caldav.clone();
+            }
+            let trash = doc.trash.and_then(|trash| doc.map.remove(&trash));
+            match (trash, combined.trash.and_then(|trash| combined.get(&trash).ok())) {
+                (Some(trash), Some(mut combined_trash)) => {
+                    for child in trash.children.iter() {
+                        combined_trash.add_child(*child);
+                    }
+                    combined.map.insert(combined_trash.id, combined_trash);
+                },
+                (Some(trash), None) => {
+                    combined.trash = Some(trash.id);
+                    combined.map.insert(trash.id, trash);
+                },
+                (None, _) => {},
+            }
+            combined.map.extend(doc.map.drain());
+            combined.clocks.extend(doc.clocks.drain());
+            combined.activity.append(&mut doc.activity);
+            combined.bookmarks.append(&mut doc.bookmarks);
+            combined.trash_entries.append(&mut doc.trash_entries);
+            let mount = Mount { path, root: doc.root, settings: doc };
+            if let Ok(mut root) = combined.get(&mount.root) {
+                if root.title.is_empty() {
+                    root.set_title(mount.name());
+                    combined.map.insert(root.id, root);
+                }
+            }
+            virtual_root.add_child(mount.root);
+            mounts.push(mount);
+        }
+        combined.activity.sort_by_key(|entry| entry.time);
+        combined.trash_entries.sort_by_key(|entry| entry.deleted);
+        combined.map.insert(virtual_root.id, virtual_root);
+        combined.journal.reset();
+        combined.invalidate_cache();
+        (combined, mounts)
+    }
+
+    /// The part of a combined document which belongs to the mount.
+    ///
+    /// The primary document also gets the clocks and activity without task
+    /// and the settings of the combined document.
+    pub fn extract(&self, mount: &Mount, primary: bool) -> Doc {
+        let mut tasks: HashSet<Uuid> = self.iter_subtree(&mount.root).map(|(_, task)| task.id).collect();
+        let trash_entries: Vec<_> = self.trash_entries.iter()
+            .filter(|entry| tasks.contains(&entry.parent_id))
+            .cloned().collect();
+        for entry in trash_entries.iter() {
+            tasks.extend(self.iter_subtree(&entry.task_id).map(|(_, task)| task.id));
+        }
+        let belongs = |task_id: Option<Uuid>| match task_id {
+            Some(task_id) => tasks.contains(&task_id),
+            None => primary,
+        };
+
+        let mut doc = if primary { self.clone() } else { mount.settings.clone() };
+        doc.root = mount.root;
+        doc.journal.reset();
+        doc.map = self.map.iter()
+            .filter(|(task_id, _)| tasks.contains(task_id))
+            .map(|(task_id, task)| (*task_id, task.clone()))
+            .collect();
+        doc.clocks = self.clocks.iter()
+            .filter(|(_, clock)| belongs(clock.task_id))
+            .map(|(clock_id, clock)| (*clock_id, clock.clone()))
+            .collect();
+        doc.current_clock = self.current_clock.filter(|clock_id| doc.clocks.contains_key(clock_id));
+        doc.channel_clocks = self.channel_clocks.iter()
+            .filter(|(_, clock_id)| doc.clocks.contains_key(clock_id))
+            .map(|(channel, clock_id)| (channel.clone(), *clock_id))
+            .collect();
+        doc.activity = self.activity.iter()
+            .filter(|entry| match &entry.event {
+                ActivityEvent::ClockStarted { clock_id } | ActivityEvent::ClockStopped { clock_id }
+                        | ActivityEvent::ClockAutoStopped { clock_id } =>
+                    doc.clocks.contains_key(clock_id),
+                _ => belongs(entry.task_id),
+            })
+            .cloned().collect();
+        doc.bookmarks = self.bookmarks.iter()
+            .filter(|(_, task_id)| tasks.contains(task_id))
+            .map(|(name, task_id)| (name.clone(), *task_id))
+            .collect();
+        doc.trash = None;
+        doc.trash_entries = Vec::new();
+        if let (Some(mut trash), false) = (self.trash.and_then(|trash| self.get(&trash).ok()), trash_entries.is_empty()) {
+            let children = trash.children.iter().filter(|child| tasks.contains(child)).cloned().collect();
+            trash.set_children(children);
+            doc.trash = Some(trash.id);
+            doc.map.insert(trash.id, trash);
+            doc.trash_entries = trash_entries;
+        }
+        doc
+    }
+
+    /// Save every mounted document to its own file.
+    pub fn save_mounts(&self, mounts: &[Mount]) -> Result<()> {
+        if self.read_only {
+            return Err(Error::ReadOnly {});
+        }
+        for (i, mount) in mounts.iter().enumerate() {
+            self.extract(mount, i == 0).save(&mount.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the documents and combine them, missing files are created empty.
+pub fn load_mounts(paths: &[String]) -> Result<(Doc, Vec<Mount>)> {
+    let docs = paths.iter()
+        .map(|path| {
+            let doc = if storage(path).exists() { Doc::load(path)? } else { Doc::new() };
+            Ok((path.clone(), doc))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Doc::combine(docs))
+}
@@ -0,0 +1,183 @@
+//! Formatting of the output: styles with ANSI colors and machine readable
+//! output of tasks, clocks and activity.
+
+use crate::doc::Doc;
+use crate::clock::Clock;
+use crate::activity::Activity;
+use crate::tasks::Progress;
+use crate::error::*;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Kind of text which can be highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Style {
+    Todo,
+    Work,
+    Done,
+    Waiting,
+    Blocked,
+    Cancelled,
+    Overdue,
+    Heading,
+    Warning,
+    Error,
+
+    /// Markdown of task bodies shown by `view`.
+    Emphasis,
+    Strong,
+    Code,
+    Link,
+}
+
+impl Style {
+    pub fn all() -> &'static [Style] {
+        &[Style::Todo, Style::Work, Style::Done, Style::Waiting, Style::Blocked,
+          Style::Cancelled, Style::Overdue, Style::Heading, Style::Warning, Style::Error,
+          Style::Emphasis, Style::Strong, Style::Code, Style::Link]
+    }
+
+    pub fn for_progress(progress: Progress) -> Style {
+        match progress {
+            Progress::Todo => Style::Todo,
+            Progress::Work => Style::Work,
+            Progress::Done => Style::Done,
+            Progress::Waiting => Style::Waiting,
+            Progress::Blocked => Style::Blocked,
+            Progress::Cancelled => Style::Cancelled,
+        }
+    }
+}
+
+impl std::str::FromStr for Style {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Style> {
+        Style::all().iter()
+            .find(|style| format!("{:?}", style).eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or_else(|| Error::UnknownStyle { name: name.to_string() })
+    }
+}
+
+/// ANSI color codes (SGR parameters like `1;31`) for each style.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Theme {
+    /// Use colors at all.
+    pub color: bool,
+    pub styles: BTreeMap<Style, String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let styles = [
+            (Style::Todo, "33"), (Style::Work, "36"), (Style::Done, "32"),
+            (Style::Waiting, "35"), (Style::Blocked, "1;31"), (Style::Cancelled, "90"),
+            (Style::Overdue, "31"), (Style::Heading, "1"), (Style::Warning, "33"),
+            (Style::Error, "31"), (Style::Emphasis, "3"), (Style::Strong, "1"),
+            (Style::Code, "36"), (Style::Link, "4"),
+        ].iter().map(|(style, code)| (*style, code.to_string())).collect();
+        Theme { color: true, styles }
+    }
+}
+
+impl Theme {
+    /// Theme which doesn't change the text.
+    pub fn no_color() -> Theme {
+        Theme { color: false, ..Theme::default() }
+    }
+
+    /// Wrap the text into the ANSI codes of the style.
+    pub fn paint(&self, style: Style, text: &str) -> String {
+        match self.styles.get(&style) {
+            Some(code) if self.color && !code.is_empty() && !text.is_empty() => format!("\x1b[{}m{}\x1b[0m", code, text),
+            _ => text.to_string(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Theme> {
+        let reader = BufReader::new(File::open(path).context(IO)?);
+        Ok(serde_json::from_reader(reader).context(SerdeSerializationError)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).context(IO)?;
+        }
+        let writer = BufWriter::new(File::create(path).context(IO)?);
+        serde_json::to_writer_pretty(writer, self).context(SerdeSerializationError)?;
+        Ok(())
+    }
+}
+
+impl Doc {
+    /// Titles from the root down to the task.
+    pub fn path_titles(&self, task_ref: &Uuid) -> Vec<String> {
+        self.path(task_ref).iter().rev()
+            .filter_map(|task_id| self.get(task_id).ok())
+            .map(|task| task.title.clone())
+            .collect()
+    }
+
+    /// The task with its path, progress summary and children as they are
+    /// listed by `ls`.
+    pub fn task_json(&self, task_ref: &Uuid) -> Result<Value> {
+        let task = self.get(task_ref)?;
+        let (done, all) = self.progress_summary(task_ref)?;
+        let (subtree_done, subtree_all) = self.progress_summary_recursive(task_ref, None)?;
+        let mut value = serde_json::to_value(&*task).context(SerdeSerializationError)?;
+        value["label"] = json!(task.progress_label());
+        value["path"] = json!(self.path_titles(task_ref));
+        value["summary"] = json!({ "done": done, "all": all });
+        value["subtree_summary"] = json!({ "done": subtree_done, "all": subtree_all });
+        value["children"] = Value::Array(task.children.iter().zip(1..)
+            .filter_map(|(child_id, i)| self.get(child_id).ok().map(|child| json!({
+                "index": i,
+                "id": child.id,
+                "title": child.title,
+                "progress": child.progress,
+                "label": child.progress_label(),
+            })))
+            .collect());
+        Ok(value)
+    }
+
+    /// The task and its subtasks as nested objects.
+    pub fn outline_json(&self, task_ref: &Uuid, max_depth: usize) -> Result<Value> {
+        let task = self.get(task_ref)?;
+        let children = if max_depth > 1 {
+            task.children.iter()
+                .map(|child_id| self.outline_json(child_id, max_depth - 1))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+        Ok(json!({
+            "id": task.id,
+            "title": task.title,
+            "progress": task.progress,
+            "label": task.progress_label(),
+            "children": children,
+        }))
+    }
+
+    /// The clock with the path of its task and its duration in seconds.
+    pub fn clock_json(&self, clock: &Clock) -> Result<Value> {
+        let mut value = serde_json::to_value(clock).context(SerdeSerializationError)?;
+        value["task_path"] = json!(clock.task_id.map(|task_id| self.path_titles(&task_id)));
+        value["duration_seconds"] = json!(clock.duration().num_seconds());
+        Ok(value)
+    }
+
+    pub fn activity_json(&self, entry: &Activity) -> Result<Value> {
+        let mut value = serde_json::to_value(entry).context(SerdeSerializationError)?;
+        value["task_path"] = json!(entry.task_id.map(|task_id| self.path_titles(&task_id)));
+        Ok(value)
+    }
+}
@@ -0,0 +1,142 @@
+//! Printable week plan with due tasks, tasks in work and empty time slots.
+//!
+//! The plan is made to be printed or pasted into notes, as plain text or
+//! Markdown with checkboxes.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use chrono::prelude::*;
+use uuid::Uuid;
+
+/// Hours which get an empty slot for time blocking on each day.
+pub const SLOT_HOURS: std::ops::Range<u32> = 8..18;
+
+/// A task in the plan with its path below the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannerItem {
+    pub task_id: Uuid,
+    pub path: String,
+    pub due: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Planner {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+
+    /// Tasks which are due on each day, sorted by time.
+    pub days: Vec<(NaiveDate, Vec<PlannerItem>)>,
+
+    /// Tasks which are in work and carried over into the week.
+    pub in_work: Vec<PlannerItem>,
+
+    /// Open tasks which were due before the week.
+    pub overdue: Vec<PlannerItem>,
+}
+
+fn underline(title: &str, c: char) -> String {
+    format!("{}\n{}\n", title, c.to_string().repeat(title.chars().count()))
+}
+
+impl Planner {
+    fn sections(&self) -> Vec<(&str, &[PlannerItem])> {
+        vec![("Carried over", &self.in_work[..]), ("Overdue", &self.overdue[..])]
+    }
+
+    fn item_line(item: &PlannerItem, date_format: &str, with_date: bool) -> String {
+        match item.due {
+            Some(due) if with_date => format!("{} (due {})", item.path, due.format(date_format)),
+            Some(due) if due.time() != NaiveTime::from_hms(0, 0, 0) => format!("{} {}", due.format("%H:%M"), item.path),
+            _ => item.path.clone(),
+        }
+    }
+
+    /// Plain text with underlined headings.
+    pub fn to_text(&self, date_format: &str) -> String {
+        let mut text = underline(&format!("Week of {} - {}", self.start.format(date_format), self.end.format(date_format)), '=');
+        for (title, items) in self.sections().into_iter().filter(|(_, items)| !items.is_empty()) {
+            text.push('\n');
+            text.push_str(&underline(title, '-'));
+            for item in items {
+                text.push_str(&format!("[ ] {}\n", Self::item_line(item, date_format, true)));
+            }
+        }
+        for (day, items) in self.days.iter() {
+            text.push('\n');
+            text.push_str(&underline(&day.format(&format!("%A {}", date_format)).to_string(), '-'));
+            for item in items {
+                text.push_str(&format!("[ ] {}\n", Self::item_line(item, date_format, false)));
+            }
+            for hour in SLOT_HOURS {
+                text.push_str(&format!("{:02}:00 ________________________________\n", hour));
+            }
+        }
+        text
+    }
+
+    /// Markdown with checkboxes and a table of time slots per day.
+    pub fn to_markdown(&self, date_format: &str) -> String {
+        let mut text = format!("# Week of {} - {}\n", self.start.format(date_format), self.end.format(date_format));
+        for (title, items) in self.sections().into_iter().filter(|(_, items)| !items.is_empty()) {
+            text.push_str(&format!("\n## {}\n\n", title));
+            for item in items {
+                text.push_str(&format!("- [ ] {}\n", Self::item_line(item, date_format, true)));
+            }
+        }
+        for (day, items) in self.days.iter() {
+            text.push_str(&format!("\n## {}\n\n", day.format(&format!("%A {}", date_format))));
+            for item in items {
+                text.push_str(&format!("- [ ] {}\n", Self::item_line(item, date_format, false)));
+            }
+            if !items.is_empty() {
+                text.push('\n');
+            }
+            text.push_str("| Time | Plan |\n| ---- | ---- |\n");
+            for hour in SLOT_HOURS {
+                text.push_str(&format!("| {:02}:00 | |\n", hour));
+            }
+        }
+        text
+    }
+}
+
+impl Doc {
+    fn planner_item(&self, task: &Task) -> PlannerItem {
+        PlannerItem {
+            task_id: task.id,
+            path: self.path_titles(&task.id).into_iter().skip(1).collect::<Vec<_>>().join(" -> "),
+            due: task.due.map(|due| self.timezone.localize(due.with_timezone(&Utc)).naive_local()),
+        }
+    }
+
+    /// Plan for the days between both dates (inclusive) with the open tasks
+    /// of the given task and its subtasks, habits are left out.
+    pub fn planner(&self, start: NaiveDate, end: NaiveDate, main_task: &Uuid) -> Planner {
+        let open = |task: &Task| task.habit.is_none() && task.progress.is_none_or(|progress| !progress.done() && progress.counts());
+        let tasks: Vec<_> = self.iter_subtree(main_task)
+            .map(|(_, task)| task)
+            .filter(|task| open(task))
+            .collect();
+        let mut due: Vec<PlannerItem> = tasks.iter()
+            .filter(|task| task.due.is_some())
+            .map(|task| self.planner_item(task))
+            .collect();
+        due.sort_by_key(|item| item.due);
+        let mut days = Vec::new();
+        let mut day = start;
+        while day <= end {
+            days.push((day, due.iter().filter(|item| item.due.map(|due| due.date()) == Some(day)).cloned().collect()));
+            day = day.succ();
+        }
+        Planner {
+            start,
+            end,
+            days,
+            in_work: tasks.iter()
+                .filter(|task| task.progress == Some(Progress::Work))
+                .map(|task| self.planner_item(task))
+                .collect(),
+            overdue: due.into_iter().filter(|item| item.due.is_some_and(|due| due.date() < start)).collect(),
+        }
+    }
+}
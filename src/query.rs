@@ -0,0 +1,359 @@
+//! Small query language to filter tasks.
+//!
+//! A query compares task fields with values, like
+//! `progress=todo and tag=urgent and due<2024-07-01`.  Comparisons are
+//! combined with `and`, `or`, `not` and parentheses, `and` binds stronger
+//! than `or`.
+//!
+//! | Field                           | Values                                  |
+//! |---------------------------------|-----------------------------------------|
+//! | `title`, `body`, `state`        | text, `~` checks if it's contained      |
+//! | `progress`                      | `todo`, `work`, ..., or `none`          |
+//! | `tag`                           | `=` if the task has the tag             |
+//! | `due`, `created`, `finished`    | `YYYY-MM-DD`, `today`, `tomorrow`, `yesterday` or `none` |
+//! | `pinned`                        | `true` or `false`                       |
+//! | `prop.<key>`                    | text like `title`, empty if it's missing |
+//!
+//! Text is compared case insensitive.  Values with spaces are quoted with
+//! `"`.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use crate::error::*;
+use chrono::prelude::*;
+use std::collections::HashSet;
+use std::rc::Rc;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Body,
+    State,
+    Progress,
+    Tag,
+    Due,
+    Created,
+    Finished,
+    Pinned,
+    Property(String),
+}
+
+impl std::str::FromStr for Field {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Field> {
+        Ok(match name {
+            "title" => Field::Title,
+            "body" => Field::Body,
+            "state" => Field::State,
+            "progress" => Field::Progress,
+            "tag" => Field::Tag,
+            "due" => Field::Due,
+            "created" => Field::Created,
+            "finished" => Field::Finished,
+            "pinned" => Field::Pinned,
+            _ if name.starts_with("prop.") && name.len() > 5 => Field::Property(name[5..].to_string()),
+            _ => return Err(query_error(format!("Unknown field: {}", name))),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl Op {
+    /// Compare values which are only checked for equality.
+    fn equals<T: PartialEq>(self, left: T, right: T) -> bool {
+        match self {
+            Op::Ne => left != right,
+            _ => left == right,
+        }
+    }
+
+    fn compare<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Op::Eq | Op::Contains => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+}
+
+/// Value of a comparison, checked against the field when it's parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Text(String),
+    Progress(Option<Progress>),
+    Date(Option<NaiveDate>),
+    Bool(bool),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    Compare { field: Field, op: Op, value: Value },
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+fn query_error(msg: impl ToString) -> Error {
+    Error::QueryError { msg: msg.to_string() }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {},
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '~' => tokens.push(Token::Op(Op::Contains)),
+            '=' => tokens.push(Token::Op(Op::Eq)),
+            '!' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::Op(Op::Ne)),
+            '<' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::Op(Op::Le)),
+            '<' => tokens.push(Token::Op(Op::Lt)),
+            '>' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::Op(Op::Ge)),
+            '>' => tokens.push(Token::Op(Op::Gt)),
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(query_error("Missing closing quote")),
+                    }
+                }
+                tokens.push(Token::Word(word));
+            },
+            _ => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !" \t()~=!<>\"".contains(*c)) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            },
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_date(value: &str) -> Result<Option<NaiveDate>> {
+    let today = Local::today().naive_local();
+    Ok(Some(match value {
+        "none" => return Ok(None),
+        "today" => today,
+        "tomorrow" => today.succ(),
+        "yesterday" => today.pred(),
+        _ => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| query_error(format!("Invalid date: {}", value)))?,
+    }))
+}
+
+fn parse_value(field: &Field, op: Op, value: &str) -> Result<Value> {
+    let ordered = matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge);
+    let value = match field {
+        Field::Title | Field::Body | Field::State | Field::Tag | Field::Property(_) => Value::Text(value.to_lowercase()),
+        Field::Progress if value == "none" => Value::Progress(None),
+        Field::Progress => Value::Progress(Some(value.parse()?)),
+        Field::Due | Field::Created | Field::Finished => Value::Date(parse_date(value)?),
+        Field::Pinned => Value::Bool(value.parse().map_err(|_| query_error(format!("Not a boolean: {}", value)))?),
+    };
+    let valid = match (&value, op) {
+        (Value::Text(_), Op::Contains) => true,
+        (_, Op::Contains) => false,
+        (Value::Date(_), _) => !ordered || value != Value::Date(None),
+        (_, Op::Eq) | (_, Op::Ne) => true,
+        _ => false,
+    };
+    if !valid {
+        return Err(query_error(format!("Operator {:?} can't be used for {:?}", op, field)));
+    }
+    Ok(value)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(&Token::Word(keyword.to_string())) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Query> {
+        let mut query = self.and()?;
+        while self.keyword("or") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query> {
+        let mut query = self.unary()?;
+        while self.keyword("and") {
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+        Ok(query)
+    }
+
+    fn unary(&mut self) -> Result<Query> {
+        if self.keyword("not") {
+            return Ok(Query::Not(Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(query_error("Missing )")),
+                }
+            },
+            Some(Token::Word(field)) => {
+                let field: Field = field.parse()?;
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(query_error(format!("Expected an operator after {:?}", field))),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value)) => value,
+                    _ => return Err(query_error(format!("Expected a value after {:?}", field))),
+                };
+                let value = parse_value(&field, op, &value)?;
+                Ok(Query::Compare { field, op, value })
+            },
+            Some(token) => Err(query_error(format!("Unexpected {:?}", token))),
+            None => Err(query_error("Unexpected end of the query")),
+        }
+    }
+}
+
+fn compare_text(op: Op, text: &str, value: &str) -> bool {
+    let text = text.to_lowercase();
+    match op {
+        Op::Contains => text.contains(value),
+        _ => op.compare(text.as_str(), value),
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query> {
+        let mut parser = Parser { tokens: tokenize(query)?, pos: 0 };
+        let result = parser.or()?;
+        match parser.next() {
+            None => Ok(result),
+            Some(token) => Err(query_error(format!("Unexpected {:?}", token))),
+        }
+    }
+
+    /// Text which the title or body of all matching tasks contain.
+    pub fn required_text(&self) -> Option<&str> {
+        match self {
+            Query::And(left, right) => left.required_text().or_else(|| right.required_text()),
+            Query::Compare { field: Field::Title, op: Op::Eq, value: Value::Text(text) }
+            | Query::Compare { field: Field::Title, op: Op::Contains, value: Value::Text(text) }
+            | Query::Compare { field: Field::Body, op: Op::Eq, value: Value::Text(text) }
+            | Query::Compare { field: Field::Body, op: Op::Contains, value: Value::Text(text) } => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Query::And(left, right) => left.matches(task) && right.matches(task),
+            Query::Or(left, right) => left.matches(task) || right.matches(task),
+            Query::Not(query) => !query.matches(task),
+            Query::Compare { field, op, value } => match (field, value) {
+                (Field::Title, Value::Text(value)) => compare_text(*op, &task.title, value),
+                (Field::Body, Value::Text(value)) => compare_text(*op, &task.body, value),
+                (Field::State, Value::Text(value)) => compare_text(*op, task.state.as_deref().unwrap_or_default(), value),
+                (Field::Tag, Value::Text(value)) => {
+                    let found = task.tags.iter().any(|tag| compare_text(if *op == Op::Ne { Op::Eq } else { *op }, tag, value));
+                    found != (*op == Op::Ne)
+                },
+                (Field::Progress, Value::Progress(value)) => op.equals(task.progress, *value),
+                (Field::Due, Value::Date(value)) => compare_date(*op, task.due, *value),
+                (Field::Created, Value::Date(value)) => compare_date(*op, task.created, *value),
+                (Field::Finished, Value::Date(value)) => compare_date(*op, task.finished, *value),
+                (Field::Pinned, Value::Bool(value)) => op.equals(task.pinned, *value),
+                (Field::Property(key), Value::Text(value)) =>
+                    compare_text(*op, task.properties.get(key).map(|value| value.as_str()).unwrap_or_default(), value),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Ordered comparisons never match tasks without the date.
+fn compare_date(op: Op, date: Option<DateTime<Local>>, value: Option<NaiveDate>) -> bool {
+    let date = date.map(|date| date.naive_local().date());
+    match (op, date, value) {
+        (Op::Eq, _, _) | (Op::Ne, _, _) => op.compare(date, value),
+        (_, Some(date), Some(value)) => op.compare(date, value),
+        _ => false,
+    }
+}
+
+impl Doc {
+    /// Tasks below the root which match the query, see the `query` module.
+    pub fn query(&self, query: &str) -> Result<Vec<Rc<Task>>> {
+        self.query_subtree(&self.root, query)
+    }
+
+    /// Tasks below the given task which match the query, depth first.
+    ///
+    /// Only tasks with the required text are checked if the document is in a
+    /// database, see `Doc::database`.
+    pub fn query_subtree(&self, task_ref: &Uuid, query: &str) -> Result<Vec<Rc<Task>>> {
+        let query = Query::parse(query)?;
+        #[cfg(feature = "sqlite")]
+        let candidates: Option<HashSet<Uuid>> = match (&self.database, query.required_text()) {
+            (Some(database), Some(text)) if self.journal.is_empty() =>
+                database.search(text).ok().map(|task_ids| task_ids.into_iter().collect()),
+            _ => None,
+        };
+        #[cfg(not(feature = "sqlite"))]
+        let candidates: Option<HashSet<Uuid>> = None;
+        Ok(self.iter_subtree(task_ref).skip(1)
+            .map(|(_, task)| task)
+            .filter(|task| candidates.as_ref().is_none_or(|candidates| candidates.contains(&task.id)))
+            .filter(|task| query.matches(task))
+            .collect())
+    }
+}
@@ -0,0 +1,79 @@
+//! Markdown of task bodies and notes, as HTML for the export and with
+//! styles for the terminal.
+//!
+//! Both parse the text with the same `markdown::tokenize` after the same
+//! clean up, so the terminal shows what the export shows.
+
+use crate::output::Style;
+use crate::text::display_width;
+use markdown::{Block, ListItem, Span};
+
+/// Width of horizontal rules in the terminal.
+const RULE_WIDTH: usize = 40;
+
+/// Blocks of the text, Windows line endings are accepted.
+pub fn parse(text: &str) -> Vec<Block> {
+    markdown::tokenize(&text.replace("\r\n", "\n"))
+}
+
+pub fn to_html(text: &str) -> String {
+    markdown::to_html(&text.replace("\r\n", "\n"))
+}
+
+/// The text with headings, emphasis and code painted by `paint`, usually
+/// `Theme::paint`, and with lists, quotes and code blocks indented.
+pub fn to_terminal(text: &str, paint: &dyn Fn(Style, &str) -> String) -> String {
+    block_lines(&parse(text), paint).join("\n")
+}
+
+fn block_lines(blocks: &[Block], paint: &dyn Fn(Style, &str) -> String) -> Vec<String> {
+    let mut lines = Vec::new();
+    for block in blocks {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        match block {
+            Block::Header(spans, level) => {
+                lines.push(paint(Style::Heading, &span_text(spans, paint)));
+                let underline = match level {
+                    1 => "=",
+                    2 => "-",
+                    _ => continue,
+                };
+                lines.push(underline.repeat(display_width(&span_text(spans, &|_, text| text.to_string()))));
+            },
+            Block::Paragraph(spans) => lines.extend(span_text(spans, paint).lines().map(str::to_string)),
+            Block::Blockquote(blocks) => lines.extend(block_lines(blocks, paint).into_iter()
+                .map(|line| format!("│ {}", line).trim_end().to_string())),
+            Block::CodeBlock(code) => lines.extend(code.lines().map(|line| format!("    {}", paint(Style::Code, line)))),
+            Block::UnorderedList(items) => {
+                for item in items {
+                    let item_lines = match item {
+                        ListItem::Simple(spans) => span_text(spans, paint).lines().map(str::to_string).collect(),
+                        ListItem::Paragraph(blocks) => block_lines(blocks, paint),
+                    };
+                    for (line, i) in item_lines.iter().zip(0..) {
+                        let bullet = if i == 0 { "• " } else { "  " };
+                        lines.push(format!("{}{}", bullet, line).trim_end().to_string());
+                    }
+                }
+            },
+            Block::Raw(text) => lines.extend(text.lines().map(str::to_string)),
+            Block::Hr => lines.push("─".repeat(RULE_WIDTH)),
+        }
+    }
+    lines
+}
+
+fn span_text(spans: &[Span], paint: &dyn Fn(Style, &str) -> String) -> String {
+    spans.iter().map(|span| match span {
+        Span::Break => "\n".to_string(),
+        Span::Text(text) => text.clone(),
+        Span::Code(code) => paint(Style::Code, code),
+        Span::Link(text, url, _) if text == url => paint(Style::Link, url),
+        Span::Link(text, url, _) => format!("{} ({})", paint(Style::Link, text), url),
+        Span::Image(text, url, _) => format!("[{}] ({})", text, url),
+        Span::Emphasis(spans) => paint(Style::Emphasis, &span_text(spans, paint)),
+        Span::Strong(spans) => paint(Style::Strong, &span_text(spans, paint)),
+    }).collect()
+}
@@ -0,0 +1,69 @@
+//! Weekly review of tasks which weren't touched for a while.
+//!
+//! Archived tasks are moved below the `Archive` task of the root.  Unlike the
+//! trash it stays part of the tree, but it's left out of reviews.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use crate::error::*;
+use chrono::prelude::*;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Title of the child of the root which holds the archived tasks.
+pub const ARCHIVE_TITLE: &str = "Archive";
+
+impl Doc {
+    /// Get the archive task and create it at the end of the root if it
+    /// doesn't exist yet.
+    pub fn archive_task(&mut self) -> Result<Uuid> {
+        if let Some(archive) = self.find_archive() {
+            return Ok(archive);
+        }
+        let mut task = Rc::new(Task::new());
+        task.set_title(ARCHIVE_TITLE);
+        let archive = task.id;
+        let root = self.root;
+        self.add_subtask(task, &root)?;
+        Ok(archive)
+    }
+
+    fn find_archive(&self) -> Option<Uuid> {
+        self.get(&self.root).ok()?.children.iter()
+            .find(|child| self.get(child).is_ok_and(|task| task.title == ARCHIVE_TITLE))
+            .cloned()
+    }
+
+    /// Check if the task is the archive or inside of it.
+    pub fn is_archived(&self, task_ref: &Uuid) -> bool {
+        self.find_archive().is_some_and(|archive| self.is_in_hierarchy_of(task_ref, &archive))
+    }
+
+    /// Move the task and its subtasks to the end of the archive.
+    pub fn archive(&mut self, task_ref: &Uuid) -> Result<()> {
+        let archive = self.archive_task()?;
+        self.move_task(task_ref, &archive, None)
+    }
+
+    /// When the task was changed last, or created if it wasn't changed.
+    pub fn last_modified(&self, task_ref: &Uuid) -> Option<DateTime<Local>> {
+        self.get(task_ref).ok().and_then(|task| task.modified.or(task.created))
+    }
+
+    /// Open tasks below the given task which weren't changed since the time,
+    /// the least recently changed first.
+    ///
+    /// Habits and the archive are left out.
+    pub fn stale_tasks(&self, task_ref: &Uuid, since: DateTime<Local>) -> Vec<Uuid> {
+        let archive = self.find_archive();
+        let mut tasks: Vec<_> = self.iter_subtree_filtered(task_ref, move |task| Some(task.id) != archive)
+            .skip(1)
+            .map(|(_, task)| task)
+            .filter(|task| task.habit.is_none())
+            .filter(|task| task.progress.is_some_and(|progress| !progress.done() && progress.counts()))
+            .filter_map(|task| self.last_modified(&task.id).filter(|modified| *modified < since).map(|modified| (modified, task.id)))
+            .collect();
+        tasks.sort_by_key(|(modified, _)| *modified);
+        tasks.into_iter().map(|(_, task_id)| task_id).collect()
+    }
+}
@@ -0,0 +1,86 @@
+//! Named copies of a document to compare it with later versions.
+//!
+//! Snapshots are stored next to the document in a `<file>.snapshots`
+//! directory, one JSON file per snapshot.  `Doc::diff` compares them with
+//! the current document.
+
+use crate::doc::Doc;
+use crate::error::*;
+use chrono::{DateTime, Local};
+use snafu::ResultExt;
+use std::path::{Path, PathBuf};
+
+/// Directory which contains the snapshots of the document.
+pub fn snapshot_dir(doc_path: impl AsRef<Path>) -> PathBuf {
+    let mut dir = doc_path.as_ref().as_os_str().to_owned();
+    dir.push(".snapshots");
+    PathBuf::from(dir)
+}
+
+fn snapshot_path(doc_path: impl AsRef<Path>, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') || name.contains('\\') {
+        return Err(Error::InvalidSnapshotName { name: name.to_string() });
+    }
+    Ok(snapshot_dir(doc_path).join(format!("{}.json", name)))
+}
+
+/// Names of the snapshots of the document, sorted.
+pub fn list_snapshots(doc_path: impl AsRef<Path>) -> Result<Vec<String>> {
+    let dir = snapshot_dir(doc_path);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir).context(IO)? {
+        let path = entry.context(IO)?.path();
+        if path.extension().and_then(|extension| extension.to_str()) == Some("json") {
+            if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Name and time of the snapshot which was stored last, None if there is
+/// none.
+pub fn last_snapshot(doc_path: impl AsRef<Path>) -> Result<Option<(String, DateTime<Local>)>> {
+    let mut last = None;
+    for name in list_snapshots(&doc_path)? {
+        let modified = std::fs::metadata(snapshot_path(&doc_path, &name)?).and_then(|metadata| metadata.modified()).context(IO)?;
+        let modified = DateTime::<Local>::from(modified);
+        if last.as_ref().is_none_or(|(_, last)| modified > *last) {
+            last = Some((name, modified));
+        }
+    }
+    Ok(last)
+}
+
+/// Load the snapshot of the document with the given name.
+pub fn load_snapshot(doc_path: impl AsRef<Path>, name: &str) -> Result<Doc> {
+    let path = snapshot_path(doc_path, name)?;
+    if !path.is_file() {
+        return Err(Error::UnknownSnapshot { name: name.to_string() });
+    }
+    Doc::load(path)
+}
+
+impl Doc {
+    /// Store a copy of the document as snapshot of the document at
+    /// `doc_path`, an existing snapshot with the name is replaced.
+    ///
+    /// This also works for read-only documents since the document itself
+    /// isn't written.
+    pub fn save_snapshot(&self, doc_path: impl AsRef<Path>, name: &str) -> Result<PathBuf> {
+        let path = snapshot_path(doc_path, name)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context(IO)?;
+        }
+        let mut snapshot = self.clone();
+        snapshot.read_only = false;
+        snapshot.journal.reset();
+        snapshot.save(&path)?;
+        Ok(path)
+    }
+}
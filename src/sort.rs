@@ -0,0 +1,80 @@
+//! Permanent ordering of the children of a task.
+//!
+//! Sorting is stable, children with the same key keep their order.
+
+use crate::doc::Doc;
+use crate::error::*;
+use crate::tasks::*;
+use uuid::Uuid;
+
+/// Property with the priority of a task, numbers sort before text.
+pub const PRIORITY_PROPERTY: &str = "priority";
+
+/// What the children are sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// Title, ignoring the case.
+    Title,
+    /// Earliest due date first, tasks without one last.
+    Due,
+    /// Lowest `priority` property first, tasks without one last.
+    Priority,
+    /// Open tasks first, then tasks without progress, then finished tasks.
+    Progress,
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(string: &str) -> Result<SortKey> {
+        match string.to_lowercase().as_str() {
+            "title" => Ok(SortKey::Title),
+            "due" => Ok(SortKey::Due),
+            "prio" | "priority" => Ok(SortKey::Priority),
+            "progress" => Ok(SortKey::Progress),
+            _ => Err(Error::UnknownSortKey { name: string.to_string() }),
+        }
+    }
+}
+
+fn progress_rank(progress: Option<Progress>) -> u8 {
+    match progress {
+        Some(Progress::Work) => 0,
+        Some(Progress::Todo) => 1,
+        Some(Progress::Waiting) => 2,
+        Some(Progress::Blocked) => 3,
+        None => 4,
+        Some(Progress::Done) => 5,
+        Some(Progress::Cancelled) => 6,
+    }
+}
+
+/// Missing priorities last, then numbers before text.
+fn priority_key(task: &Task) -> (bool, Option<i64>, String) {
+    match task.properties.get(PRIORITY_PROPERTY) {
+        Some(value) => (false, Some(value.trim().parse().unwrap_or(i64::MAX)), value.to_lowercase()),
+        None => (true, None, String::new()),
+    }
+}
+
+impl Doc {
+    /// Sort the children of the task by the key.
+    ///
+    /// Children which don't exist in the document are kept at the end.
+    pub fn sort_children(&mut self, task_ref: &Uuid, key: SortKey) -> Result<()> {
+        let task = self.get(task_ref)?;
+        let mut children: Vec<_> = task.children.iter()
+            .filter_map(|child| self.get(child).ok())
+            .collect();
+        match key {
+            SortKey::Title => children.sort_by_key(|task| task.title.to_lowercase()),
+            SortKey::Due => children.sort_by_key(|task| (task.due.is_none(), task.due)),
+            SortKey::Priority => children.sort_by_key(|task| priority_key(task)),
+            SortKey::Progress => children.sort_by_key(|task| progress_rank(task.progress)),
+        }
+        let children: Vec<Uuid> = children.iter().map(|child| child.id)
+            .chain(task.children.iter().filter(|child| !self.map.contains_key(child)).cloned())
+            .collect();
+        self.modify_task(task_ref, move |task| { task.set_children(children.clone()); Ok(()) })
+    }
+}
@@ -0,0 +1,170 @@
+//! Breaking a big task apart, see the `split` command, and turning small
+//! tasks into checklist lines and back with `demote` and `promote`.
+//!
+//! Checklist lines of the body become subtasks and children move up to the
+//! level of the task, behind it.  Both keep the order they had.
+
+use crate::clock::Clock;
+use crate::doc::Doc;
+use crate::error::*;
+use crate::tasks::*;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Line of a checklist like `- [ ] Write tests` or `* [x] Review`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecklistItem {
+    /// Line in the body, starting at 0.
+    pub line: usize,
+    pub title: String,
+    pub done: bool,
+}
+
+fn parse_checklist_line(line: &str) -> Option<(String, bool)> {
+    let rest = line.trim_start().strip_prefix(['-', '*', '+'])?;
+    let rest = rest.strip_prefix(' ')?.trim_start();
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let title = rest[3..].trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some((title.to_string(), done))
+}
+
+/// Checklist items of the body, nested ones included.
+pub fn checklist_items(body: &str) -> Vec<ChecklistItem> {
+    body.lines().enumerate()
+        .filter_map(|(line, text)| parse_checklist_line(text).map(|(title, done)| ChecklistItem { line, title, done }))
+        .collect()
+}
+
+/// Checklist line of the task like `- [x] Review API`, done tasks are
+/// checked.
+pub fn checklist_line(task: &Task) -> String {
+    let checked = task.progress.is_some_and(|progress| progress.done());
+    format!("- [{}] {}", if checked { "x" } else { " " }, task.title)
+}
+
+/// Items selected by an answer like `1,3-5`, `all` or `none`, the first
+/// item is 1.  Returns the indices from 0 in ascending order, None if an
+/// item doesn't exist.
+pub fn parse_item_list(text: &str, count: usize) -> Option<Vec<usize>> {
+    match text.trim() {
+        "all" => return Some((0..count).collect()),
+        "" | "none" => return Some(Vec::new()),
+        _ => {},
+    }
+    let mut items = Vec::new();
+    for part in text.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (from, to) = part.split_once('-').unwrap_or((part, part));
+        let from: usize = from.trim().parse().ok()?;
+        let to: usize = to.trim().parse().ok()?;
+        if from == 0 || to > count || from > to {
+            return None;
+        }
+        items.extend(from - 1..to);
+    }
+    items.sort_unstable();
+    items.dedup();
+    Some(items)
+}
+
+impl Doc {
+    /// Turn the checklist items with the given indices into subtasks behind
+    /// the existing children and remove their lines from the body.
+    ///
+    /// Checked items are done, the others todo.  Returns the new tasks in
+    /// the order of the checklist.
+    pub fn checklist_to_subtasks(&mut self, task_ref: &Uuid, items: &[usize]) -> Result<Vec<Uuid>> {
+        let task = self.get(task_ref)?;
+        let checklist = checklist_items(&task.body);
+        if items.iter().any(|i| *i >= checklist.len()) {
+            return Err(Error::ChildOutOfIndex {});
+        }
+        let selected: Vec<&ChecklistItem> = checklist.iter().enumerate()
+            .filter(|(i, _)| items.contains(i))
+            .map(|(_, item)| item)
+            .collect();
+        let mut task_ids = Vec::new();
+        for item in selected.iter() {
+            let mut subtask = Rc::new(Task::new());
+            subtask.set_title(&item.title)
+                .set_progress(if item.done { Progress::Done } else { Progress::Todo });
+            task_ids.push(subtask.id);
+            self.add_subtask(subtask, task_ref)?;
+        }
+        let lines: Vec<usize> = selected.iter().map(|item| item.line).collect();
+        // The other lines keep their line endings.
+        let body: String = task.body.split_inclusive('\n').enumerate()
+            .filter(|(line, _)| !lines.contains(line))
+            .map(|(_, text)| text)
+            .collect();
+        let body = body.trim_start_matches(['\r', '\n']).trim_end();
+        self.modify_task(task_ref, |task| { task.set_body(body); Ok(()) })?;
+        Ok(task_ids)
+    }
+
+    /// Turn the task into a checklist line at the end of the body of its
+    /// parent.
+    ///
+    /// Its clocks, a running one too, belong to the parent afterwards.  The
+    /// task goes to the trash, so its body and notes can be restored.
+    ///
+    /// # Error
+    /// Fails without changes if the task has no parent or has subtasks.
+    pub fn task_to_checklist(&mut self, task_ref: &Uuid) -> Result<()> {
+        let task = self.get(task_ref)?;
+        let parent_id = self.find_parent(task_ref)
+            .ok_or_else(|| Error::NoParent { title: task.title.clone() })?;
+        if !task.children.is_empty() {
+            return Err(Error::HasSubtasks { title: task.title.clone() });
+        }
+        let clocks: Vec<Rc<Clock>> = self.clocks.values()
+            .filter(|clock| clock.task_id == Some(task.id))
+            .cloned().collect();
+        for mut clock in clocks {
+            Rc::make_mut(&mut clock).task_id = Some(parent_id);
+            self.upsert_clock(clock);
+        }
+        let line = checklist_line(&task);
+        self.modify_task(&parent_id, |parent| {
+            let body = parent.body.trim_end();
+            let newline = if body.contains("\r\n") { "\r\n" } else { "\n" };
+            // A new checklist is separated from the text before it.
+            let separator = match body.lines().last() {
+                None => String::new(),
+                Some(last) if !checklist_items(last).is_empty() => newline.to_string(),
+                Some(_) => newline.repeat(2),
+            };
+            let body = format!("{}{}{}", body, separator, line);
+            parent.set_body(body);
+            Ok(())
+        })?;
+        self.move_to_trash(task_ref)
+    }
+
+    /// Move the children to the parent of the task, right behind it in the
+    /// order they had as children.
+    ///
+    /// # Error
+    /// Fails without changes if the task has no parent or if one of the
+    /// tasks isn't a child of it.
+    pub fn promote_children(&mut self, task_ref: &Uuid, children: &[Uuid]) -> Result<()> {
+        let task = self.get(task_ref)?;
+        let (parent_id, index) = self.position(task_ref)
+            .ok_or_else(|| Error::NoParent { title: task.title.clone() })?;
+        if let Some(child) = children.iter().find(|child| !task.children.contains(child)) {
+            let title = self.get(child).map(|child| child.title.clone()).unwrap_or_else(|_| child.to_string());
+            return Err(Error::NotAChild { title });
+        }
+        let promoted = task.children.iter().filter(|child| children.contains(child));
+        for (child, offset) in promoted.zip(1..) {
+            self.move_task(child, &parent_id, Some(index + offset))?;
+        }
+        Ok(())
+    }
+}
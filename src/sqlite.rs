@@ -0,0 +1,179 @@
+//! SQLite database as document file for very large histories.
+//!
+//! Documents with the extension `.db`, `.sqlite` or `.sqlite3`, see
+//! `Format`, are stored in tables for tasks, clocks and activity, with
+//! everything else of the document as JSON in `meta`.  Each row keeps the
+//! serialized entry plus indexed columns, so `SqliteDb::clocks_between` and
+//! `SqliteDb::search` don't need to load the document.  `Doc::save_journal`
+//! only writes the changed rows instead of a journal.
+//!
+//! A loaded document remembers its database in `Doc::database`, and
+//! `Doc::range_clock` and `Doc::query_subtree` ask it while there are no
+//! unsaved changes.
+
+use crate::doc::Doc;
+use crate::clock::Clock;
+use crate::journal::JournalEntry;
+use crate::error::*;
+use crate::storage::is_remote;
+use chrono::prelude::*;
+use rusqlite::{params, Connection, OpenFlags};
+use rusqlite::functions::FunctionFlags;
+use serde_json::Value;
+use snafu::ResultExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use uuid::Uuid;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (id INTEGER PRIMARY KEY CHECK (id = 0), json TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS tasks (id TEXT PRIMARY KEY, title TEXT NOT NULL, body TEXT NOT NULL, json TEXT NOT NULL);
+CREATE INDEX IF NOT EXISTS tasks_title ON tasks (title);
+CREATE TABLE IF NOT EXISTS clocks (id TEXT PRIMARY KEY, task_id TEXT, start TEXT NOT NULL, end TEXT, json TEXT NOT NULL);
+CREATE INDEX IF NOT EXISTS clocks_start ON clocks (start);
+CREATE INDEX IF NOT EXISTS clocks_task ON clocks (task_id);
+CREATE TABLE IF NOT EXISTS activity (time TEXT NOT NULL, task_id TEXT, json TEXT NOT NULL);
+CREATE INDEX IF NOT EXISTS activity_time ON activity (time);
+";
+
+fn sqlite_error(msg: impl ToString) -> Error {
+    Error::StorageError { msg: msg.to_string() }
+}
+
+/// Time as text which sorts like the time.
+fn time_key(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+}
+
+fn json<T: serde::Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(value).context(SerdeSerializationError)?)
+}
+
+/// Document in a SQLite database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SqliteDb {
+    pub path: PathBuf,
+}
+
+impl SqliteDb {
+    /// Database at the path, which needs to be on this machine.
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteDb> {
+        if is_remote(path.as_ref()) {
+            return Err(sqlite_error(format!("SQLite documents need to be local files: {}", path.as_ref().display())));
+        }
+        Ok(SqliteDb { path: path.as_ref().to_path_buf() })
+    }
+
+    fn error(&self, err: rusqlite::Error) -> Error {
+        sqlite_error(format!("{}: {}", self.path.display(), err))
+    }
+
+    /// Connection which can only read, the file has to exist.
+    fn read(&self) -> Result<Connection> {
+        let connection = Connection::open_with_flags(&self.path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|err| self.error(err))?;
+        // Case is ignored like in queries, not only for ASCII like `LIKE`.
+        connection.create_scalar_function("lowercase", 1, FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |context| Ok(context.get::<String>(0)?.to_lowercase()))
+            .map_err(|err| self.error(err))?;
+        Ok(connection)
+    }
+
+    /// Write the entries in one transaction and create the tables first if
+    /// needed.  `clear` removes all rows before.
+    fn write(&self, clear: bool, entries: &[JournalEntry]) -> Result<()> {
+        let mut connection = Connection::open(&self.path).map_err(|err| self.error(err))?;
+        connection.execute_batch(SCHEMA).map_err(|err| self.error(err))?;
+        let transaction = connection.transaction().map_err(|err| self.error(err))?;
+        if clear {
+            transaction.execute_batch("DELETE FROM tasks; DELETE FROM clocks; DELETE FROM activity;")
+                .map_err(|err| self.error(err))?;
+        }
+        for entry in entries {
+            let result = match entry {
+                JournalEntry::Task(task) => transaction.execute("INSERT OR REPLACE INTO tasks VALUES (?1, ?2, ?3, ?4)",
+                    params![task.id.to_string(), task.title, task.body, json(task)?]),
+                JournalEntry::TaskRemoved(task_id) => transaction.execute("DELETE FROM tasks WHERE id = ?1",
+                    params![task_id.to_string()]),
+                JournalEntry::Clock(clock) => transaction.execute("INSERT OR REPLACE INTO clocks VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![clock.id.to_string(), clock.task_id.map(|task_id| task_id.to_string()),
+                        time_key(clock.start), clock.end.map(time_key), json(clock)?]),
+                JournalEntry::Activity(entry) => transaction.execute("INSERT INTO activity VALUES (?1, ?2, ?3)",
+                    params![time_key(entry.time.with_timezone(&Utc)), entry.task_id.map(|task_id| task_id.to_string()), json(entry)?]),
+                JournalEntry::Meta(meta) => transaction.execute("INSERT OR REPLACE INTO meta VALUES (0, ?1)",
+                    params![meta.to_string()]),
+            };
+            result.map_err(|err| self.error(err))?;
+        }
+        transaction.commit().map_err(|err| self.error(err))
+    }
+
+    /// `json` column of the rows, parsed.
+    fn entries<T: serde::de::DeserializeOwned>(&self, connection: &Connection, sql: &str, params: impl rusqlite::Params) -> Result<Vec<T>> {
+        let mut statement = connection.prepare(sql).map_err(|err| self.error(err))?;
+        let rows = statement.query_map(params, |row| row.get::<_, String>("json"))
+            .map_err(|err| self.error(err))?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let row = row.map_err(|err| self.error(err))?;
+            entries.push(serde_json::from_str(&row).context(SerdeSerializationError)?);
+        }
+        Ok(entries)
+    }
+
+    /// Read the whole document.
+    pub fn load(&self) -> Result<Doc> {
+        let connection = self.read()?;
+        let meta: Vec<Value> = self.entries(&connection, "SELECT json FROM meta", [])?;
+        let meta = meta.into_iter().next().ok_or_else(|| sqlite_error(format!("{}: no document", self.path.display())))?;
+        let mut doc: Doc = serde_json::from_value(meta).context(SerdeSerializationError)?;
+        doc.map = self.entries(&connection, "SELECT json FROM tasks", [])?.into_iter()
+            .map(|task: Rc<crate::tasks::Task>| (task.id, task))
+            .collect();
+        doc.clocks = self.entries(&connection, "SELECT json FROM clocks", [])?.into_iter()
+            .map(|clock: Rc<Clock>| (clock.id, clock))
+            .collect();
+        doc.activity = self.entries(&connection, "SELECT json FROM activity ORDER BY time, rowid", [])?;
+        doc.database = Some(self.clone());
+        doc.invalidate_cache();
+        Ok(doc)
+    }
+
+    /// Replace the content of the database by the document.
+    pub fn save(&self, doc: &Doc) -> Result<()> {
+        let entries = std::iter::once(JournalEntry::Meta(doc.clone().meta()?))
+            .chain(doc.map.values().cloned().map(JournalEntry::Task))
+            .chain(doc.clocks.values().cloned().map(JournalEntry::Clock))
+            .chain(doc.activity.iter().cloned().map(JournalEntry::Activity))
+            .collect::<Vec<_>>();
+        self.write(true, &entries)
+    }
+
+    /// Write only the changed rows.
+    pub fn save_changes(&self, entries: &[JournalEntry]) -> Result<()> {
+        self.write(false, entries)
+    }
+
+    /// Clocks which started in the time range, oldest first.
+    pub fn clocks_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Rc<Clock>>> {
+        self.entries(&self.read()?, "SELECT json FROM clocks WHERE start >= ?1 AND start < ?2 ORDER BY start",
+            params![time_key(start), time_key(end)])
+    }
+
+    /// Tasks with the text in the title or body, ignoring case.
+    pub fn search(&self, text: &str) -> Result<Vec<Uuid>> {
+        let connection = self.read()?;
+        let mut statement = connection.prepare(
+            "SELECT id FROM tasks WHERE instr(lowercase(title), ?1) > 0 OR instr(lowercase(body), ?1) > 0 ORDER BY title")
+            .map_err(|err| self.error(err))?;
+        let rows = statement.query_map(params![text.to_lowercase()], |row| row.get::<_, String>(0))
+            .map_err(|err| self.error(err))?;
+        let mut task_ids = Vec::new();
+        for row in rows {
+            if let Ok(task_id) = Uuid::parse_str(&row.map_err(|err| self.error(err))?) {
+                task_ids.push(task_id);
+            }
+        }
+        Ok(task_ids)
+    }
+}
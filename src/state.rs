@@ -1,5 +1,16 @@
 use super::doc::*;
+use super::ics::IcsServer;
+use super::hooks::Hooks;
+use super::mount::*;
+use super::error::Result;
+use super::storage::storage;
+use super::journal::journal_path;
 use uuid::Uuid;
+use std::path::Path;
+use std::time::SystemTime;
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Autosave {
@@ -7,43 +18,400 @@ pub enum Autosave {
     OnCommand
 }
 
+/// Format of the output of read commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
 #[derive(Debug)]
 pub struct State {
     pub doc: Doc,
     pub wt: Uuid,
-    pub parents: Vec<Uuid>,
+
+    /// Working tasks saved by `pushd`, the most recent last.
+    pub dir_stack: Vec<Uuid>,
+
+    /// Working task before the last change, used by `cd -`.
+    pub previous_wt: Option<Uuid>,
     pub path: String,
-    pub autosave: Autosave
+    pub autosave: Autosave,
+    pub output: OutputFormat,
+
+    /// Commands which run on events, see `hooks`.
+    pub hooks: Hooks,
+
+    /// Block all changes of the document, see `commands::transaction`.
+    pub read_only: bool,
+
+    /// Listings only show tasks of this context like `@home`, see `context`.
+    pub context: Option<String>,
+
+    /// Task which acts as root for paths, listings and reports, see
+    /// `State::focus`.
+    pub focus: Option<Uuid>,
+
+    /// Tasks marked with a letter for this session, used as `'a` in paths.
+    pub marks: BTreeMap<char, Uuid>,
+
+    /// Documents which are combined in `doc`, empty if only the document
+    /// at `path` is open.
+    pub mounts: Vec<Mount>,
+
+    /// Feed started by `serve-ics`, see `State::update_ics_feed`.
+    pub ics_server: Option<IcsServer>,
+
+    /// Modification time of the file at `path` when it was loaded or saved.
+    pub disk_modified: Option<SystemTime>,
+
+    /// Content hash of the file at `path` when it was loaded or saved.
+    pub disk_hash: Option<u64>,
+
+    /// The document changed since it was loaded or saved.
+    pub dirty: bool,
+
+    /// Document fields besides tasks, clocks and activity when it was
+    /// loaded or saved, see `Doc::meta`.
+    pub saved_meta: Option<serde_json::Value>,
+}
+
+/// Modification time of the file or its journal, whichever is later, or
+/// None if the file doesn't exist.
+pub fn file_modified(path: &str) -> Option<SystemTime> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let file = modified(Path::new(path))?;
+    Some(modified(&journal_path(Path::new(path))).map_or(file, |journal| journal.max(file)))
+}
+
+/// Hash of the file content and its journal or None if the file can't be
+/// read.
+///
+/// Remote files are read from the other machine, so saving still detects
+/// changes made there.
+pub fn file_hash(path: &str) -> Option<u64> {
+    let storage = storage(path);
+    let content = storage.read().ok()?;
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    if let Some(journal) = storage.local_path().and_then(|path| std::fs::read(journal_path(path)).ok()) {
+        journal.hash(&mut hasher);
+    }
+    Some(hasher.finish())
 }
 
 impl State {
+    /// State for the document which is saved at the given path.
+    ///
+    /// The document counts as saved and the root is the working task.
+    pub fn new(doc: Doc, path: impl ToString) -> State {
+        let path = path.to_string();
+        let mut state = State {
+            wt: doc.root,
+            doc,
+            dir_stack: Vec::new(),
+            previous_wt: None,
+            disk_modified: file_modified(&path),
+            disk_hash: file_hash(&path),
+            dirty: false,
+            saved_meta: None,
+            path,
+            autosave: Autosave::ManualOnly,
+            output: OutputFormat::Text,
+            hooks: Hooks::default(),
+            read_only: false,
+            context: None,
+            focus: None,
+            marks: BTreeMap::new(),
+            mounts: Vec::new(),
+            ics_server: None,
+        };
+        state.mark_saved();
+        state
+    }
+
+    /// Remember the current modification time and hash of the document file.
+    ///
+    /// Call this after the file was loaded or saved.
+    pub fn refresh_disk_state(&mut self) {
+        self.disk_modified = file_modified(&self.path);
+        self.disk_hash = file_hash(&self.path);
+    }
+
+    /// Check if the content of the document file differs from the content
+    /// when it was loaded or saved.
+    pub fn content_changed_on_disk(&self) -> bool {
+        let hash = file_hash(&self.path);
+        hash.is_some() && hash != self.disk_hash
+    }
+
+    /// Check if the document file was changed by someone else since it
+    /// was loaded or saved.
+    pub fn changed_on_disk(&self) -> bool {
+        let modified = file_modified(&self.path);
+        modified.is_some() && modified != self.disk_modified
+    }
+
+    /// Block or allow changes and saving of the document.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+        self.doc.read_only = read_only;
+    }
+
+    /// Check if the task is shown in the active context.
+    pub fn in_context(&self, task_ref: &Uuid) -> bool {
+        self.context.as_ref().is_none_or(|context| self.doc.in_context(task_ref, context))
+    }
+
+    /// The focused task or the root of the document.
+    pub fn root(&self) -> Uuid {
+        self.focus.unwrap_or(self.doc.root)
+    }
+
+    /// Serve the current document by the feed of `serve-ics`, called after
+    /// each command.
+    pub fn update_ics_feed(&self) {
+        if let Some(server) = &self.ics_server {
+            server.update(self.doc.to_ics(&self.root(), server.with_clocks));
+        }
+    }
+
+    /// Check if the task is the focused task or below it.
+    pub fn in_focus(&self, task_ref: &Uuid) -> bool {
+        self.focus.is_none_or(|focus| self.doc.is_in_hierarchy_of(task_ref, &focus))
+    }
+
+    /// Let the task act as root or go back to the root of the document
+    /// with None.
+    ///
+    /// The working task and the remembered tasks are moved into the focus.
+    pub fn focus(&mut self, focus: Option<Uuid>) {
+        self.focus = focus;
+        if !self.in_focus(&self.wt) {
+            self.wt = self.root();
+        }
+        self.previous_wt = self.previous_wt.filter(|task_id| self.in_focus(task_id));
+        let dir_stack = std::mem::take(&mut self.dir_stack);
+        self.dir_stack = dir_stack.into_iter().filter(|task_id| self.in_focus(task_id)).collect();
+    }
+
+    /// Remember that the document is saved.
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+        self.saved_meta = self.doc.meta().ok();
+    }
+
+    /// Check if the document changed since it was loaded or saved and
+    /// update the dirty flag.
+    pub fn update_dirty(&mut self) -> bool {
+        self.dirty = self.doc.has_unsaved_changes() || self.doc.meta().ok() != self.saved_meta;
+        self.dirty
+    }
+
+    /// State for several documents below a virtual root, the first one is
+    /// the primary document.
+    pub fn new_mounted(paths: &[String]) -> Result<State> {
+        let (doc, mounts) = load_mounts(paths)?;
+        let mut state = State::new(doc, &paths[0]);
+        state.mounts = mounts;
+        state.mark_saved();
+        Ok(state)
+    }
+
+    /// Load the document from disk again, or all mounted documents.
+    pub fn load_doc(&mut self) -> Result<Doc> {
+        if self.mounts.is_empty() {
+            return if storage(&self.path).exists() { Doc::load(&self.path) } else { Ok(Doc::new()) };
+        }
+        let paths: Vec<String> = self.mounts.iter().map(|mount| mount.path.clone()).collect();
+        let (doc, mounts) = load_mounts(&paths)?;
+        self.mounts = mounts;
+        Ok(doc)
+    }
+
+    /// Add another document below the virtual root.
+    ///
+    /// If only one document is open, it becomes the primary document.
+    pub fn mount(&mut self, path: &str) -> Result<()> {
+        let mut docs = Vec::new();
+        if self.mounts.is_empty() {
+            docs.push((self.path.clone(), self.doc.clone()));
+        } else {
+            for (i, mount) in self.mounts.iter().enumerate() {
+                docs.push((mount.path.clone(), self.doc.extract(mount, i == 0)));
+            }
+        }
+        let doc = if storage(path).exists() { Doc::load(path)? } else { Doc::new() };
+        docs.push((path.to_string(), doc));
+        let (doc, mounts) = Doc::combine(docs);
+        self.mounts = mounts;
+        self.replace_doc(doc);
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// Replace the document and make sure the working task still exists.
+    pub fn replace_doc(&mut self, doc: Doc) {
+        if doc.get(&self.wt).is_err() {
+            self.wt = doc.root;
+        }
+        self.dir_stack.retain(|task_id| doc.get(task_id).is_ok());
+        self.previous_wt = self.previous_wt.filter(|task_id| doc.get(task_id).is_ok());
+        self.focus = self.focus.filter(|task_id| doc.get(task_id).is_ok());
+        self.marks.retain(|_, task_id| doc.get(task_id).is_ok());
+        self.doc = doc;
+        self.doc.read_only = self.read_only;
+    }
+
+    /// Change the working task and remember the previous one.
+    pub fn change_wt(&mut self, task_id: Uuid) {
+        if task_id != self.wt {
+            self.previous_wt = Some(self.wt);
+            self.wt = task_id;
+        }
+    }
+
+    /// Save the working task on the stack and change to the given task.
+    pub fn pushd(&mut self, task_id: Uuid) {
+        self.dir_stack.push(self.wt);
+        self.change_wt(task_id);
+    }
+
+    /// Change to the task on top of the stack.
+    ///
+    /// Returns false if the stack is empty.
+    pub fn popd(&mut self) -> bool {
+        match self.dir_stack.pop() {
+            Some(task_id) => {
+                self.change_wt(task_id);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Resolve a path like `project/api`, `../3`, `.`, `/1/2` or `@inbox/2`
+    /// to a task.
+    ///
+    /// Relative paths start at the working task, paths starting with `@name`
+    /// at the bookmark with that name and paths starting with `'a` at the
+    /// task marked with that letter.  Numbers select the n-th child
+    /// (starting at 1), other parts match a child by title prefix.  With a
+    /// focus, `/` is the focused task and `..` doesn't leave it.
+    ///
+    /// Returns None if the path doesn't point to a task in the focus.
     pub fn uuid_for_path(&self, path: &str) -> Option<Uuid> {
-        let mut current_task = if path.starts_with('/') {
-            Some(self.doc.root)
+        let (mut current_task, path) = if path.starts_with('/') {
+            (Some(self.root()), path)
+        } else if let Some(bookmark_path) = path.strip_prefix('@') {
+            let (name, rest) = bookmark_path.split_once('/').unwrap_or((bookmark_path, ""));
+            (Some(*self.doc.bookmarks.get(name).filter(|task_id| self.doc.get(task_id).is_ok())?), rest)
+        } else if let Some(mark_path) = path.strip_prefix('\'') {
+            let mut chars = mark_path.chars();
+            let mark = chars.next()?;
+            let rest = chars.as_str();
+            if !rest.is_empty() && !rest.starts_with('/') {
+                return None;
+            }
+            (Some(*self.marks.get(&mark).filter(|task_id| self.doc.get(task_id).is_ok())?), rest)
         } else {
-            Some(self.wt)
+            (Some(self.wt), path)
         };
         let splitted_path = path.split('/');
         
         for part in splitted_path {
             if let Ok(i) = part.parse::<usize>() {
-                if let Some(task) = current_task {
-                    current_task = self.doc.task_child(&task, i - 1);
+                if let (Some(task), Some(i)) = (current_task, i.checked_sub(1)) {
+                    current_task = self.doc.task_child(&task, i);
                 } else {
                     current_task = None;
                 }
             } else if let Ok(id) = part.parse::<Uuid>() {
                 current_task = Some(id)
             } else if part == ".." {
-                if let Some(task) = current_task {
+                if let Some(task) = current_task.filter(|task| *task != self.root()) {
                     current_task = self.doc.find_parent(&task);
                 }
-            } else if part == "" {
-                // Empty - Do nothing
+            } else if part == "" || part == "." {
+                // Empty or current task - Do nothing
             } else if let Some(task) = current_task {
                     current_task = self.doc.task_child_prefix(&task, part);
             }
         }
-        current_task
+        current_task.filter(|task| self.in_focus(task))
+    }
+
+    /// Resolve a selector to all tasks it refers to.
+    ///
+    /// A selector is a comma separated list.  Each entry is either a range of
+    /// child indices like `1-3` or `3-1`, a glob pattern like `*done*` which is matched
+    /// case insensitive against the children of the working task as they are
+    /// listed by `ls` (progress and title), or a path like `uuid_for_path`
+    /// accepts it.
+    ///
+    /// Returns None if one of the entries couldn't be resolved.
+    pub fn uuids_for_selector(&self, selector: &str) -> Option<Vec<Uuid>> {
+        let mut result: Vec<Uuid> = Vec::new();
+        for part in selector.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()) {
+            let selected = if let Some(range) = parse_range(part) {
+                range.map(|i| self.doc.task_child(&self.wt, i.checked_sub(1)?))
+                    .collect::<Option<Vec<_>>>()?
+            } else if part.contains('*') || part.contains('?') {
+                let task = self.doc.get(&self.wt).ok()?;
+                let pattern = part.to_lowercase();
+                task.children.iter()
+                    .filter_map(|child_id| self.doc.get(child_id).ok())
+                    .filter(|child| {
+                        let listing = format!("{} {}", child.progress_label(), child.title).trim().to_lowercase();
+                        glob_match(&pattern, &listing) || glob_match(&pattern, &child.title.to_lowercase())
+                    })
+                    .map(|child| child.id)
+                    .collect()
+            } else {
+                vec![self.uuid_for_path(part)?]
+            };
+            for id in selected {
+                if !result.contains(&id) {
+                    result.push(id);
+                }
+            }
+        }
+        Some(result)
+    }
+}
+
+/// Parse a range like `2-5` into the numbers it covers, `5-2` counts down.
+///
+/// The numbers are produced lazily, so a huge range only costs as much as
+/// the caller consumes before it stops at the first missing child.
+fn parse_range(part: &str) -> Option<Box<dyn Iterator<Item = usize>>> {
+    let mut split = part.splitn(2, '-');
+    let from = split.next()?.parse::<usize>().ok()?;
+    let to = split.next()?.parse::<usize>().ok()?;
+    Some(if from <= to { Box::new(from..=to) } else { Box::new((to..=from).rev()) })
+}
+
+/// Check if the text matches the pattern which may contain `*` and `?`
+/// wildcards.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
     }
+    pattern[p..].iter().all(|c| *c == '*')
 }
\ No newline at end of file
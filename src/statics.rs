@@ -1,7 +1,25 @@
 use std::env::var;
 
+/// Warn about a running clock after this many hours.
+pub const LONG_CLOCK_HOURS: i64 = 10;
+
+/// Directory `sors` in the XDG base directory of the environment variable,
+/// or in the default relative to the home directory.
+fn xdg_dir(variable: &str, default: &str) -> String {
+    match var(variable) {
+        Ok(dir) if dir.starts_with('/') => format!("{}/sors", dir),
+        // Without a home the directories are relative to the working
+        // directory, instead of failing before the first prompt.
+        _ => format!("{}/{}/sors", var("HOME").unwrap_or_else(|_| ".".to_string()), default),
+    }
+}
+
 lazy_static! {
-    pub static ref TASK_FILE: String = format!("{}/.task.md", var("HOME").unwrap());
-    pub static ref HISTORY_FILE: String = format!("{}/.taskhistory", var("HOME").unwrap());
-    pub static ref CLOCK_HISTORY_FILE: String = format!("{}/.taskclockhistory", var("HOME").unwrap());
-}
\ No newline at end of file
+    pub static ref CONFIG_DIR: String = xdg_dir("XDG_CONFIG_HOME", ".config");
+    pub static ref DATA_DIR: String = xdg_dir("XDG_DATA_HOME", ".local/share");
+    pub static ref CONFIG_FILE: String = format!("{}/config.toml", *CONFIG_DIR);
+    pub static ref CLOCK_HISTORY_FILE: String = format!("{}/clockhistory", *DATA_DIR);
+    pub static ref HOOKS_FILE: String = format!("{}/hooks.json", *CONFIG_DIR);
+    pub static ref THEME_FILE: String = format!("{}/theme.json", *CONFIG_DIR);
+    pub static ref RC_FILE: String = format!("{}/sorsrc", *CONFIG_DIR);
+}
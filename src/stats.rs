@@ -0,0 +1,130 @@
+//! Size and throughput statistics of a task's subtree.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use crate::error::*;
+use crate::calendar::start_of_week;
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Size of the subtree below a task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtreeStats {
+    /// Number of subtasks on all levels, without the task itself.
+    pub descendants: usize,
+
+    /// Subtasks per progress label like `TODO` or a workflow state.
+    pub by_progress: BTreeMap<String, usize>,
+
+    /// Subtasks without progress.
+    pub without_progress: usize,
+
+    /// Deepest level below the task, 0 without subtasks.
+    pub depth: usize,
+
+    /// Time of all clocks of the task and its subtasks.
+    pub tracked: chrono::Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThroughputStats {
+    /// Finished tasks per week as `(first day, count)`, oldest first.
+    pub completed_per_week: Vec<(NaiveDate, usize)>,
+
+    /// Average time from the first clock (or creation) until done.
+    pub average_cycle_time: Option<chrono::Duration>,
+
+    /// Number of tasks which are currently in work.
+    pub wip: usize,
+
+    /// Open tasks at the end of each day, oldest first.
+    pub burndown: Vec<(Date<Local>, usize)>,
+}
+
+impl Doc {
+    /// Count the subtasks of the task by their progress.
+    pub fn subtree_stats(&self, task_ref: &Uuid) -> Result<SubtreeStats> {
+        self.get(task_ref)?;
+        let mut stats = SubtreeStats {
+            descendants: 0,
+            by_progress: BTreeMap::new(),
+            without_progress: 0,
+            depth: 0,
+            tracked: self.subtree_duration(task_ref),
+        };
+        for (depth, task) in self.iter_subtree(task_ref).skip(1) {
+            stats.descendants += 1;
+            stats.depth = stats.depth.max(depth);
+            match task.progress_label().as_str() {
+                "" => stats.without_progress += 1,
+                label => *stats.by_progress.entry(label.to_string()).or_insert(0) += 1,
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Calculate the throughput of the subtree below the given task for the
+    /// last days.
+    ///
+    /// Only tasks with a progress are taken into account, cancelled tasks
+    /// and habits are ignored.
+    pub fn throughput_stats(&self, task_ref: &Uuid, days: i64, week_start: Weekday) -> Result<ThroughputStats> {
+        self.get(task_ref)?;
+        let mut tasks: Vec<_> = self.iter_subtree(task_ref).skip(1).map(|(_, task)| task).collect();
+        tasks.retain(|task| task.habit.is_none() && task.progress.is_some_and(|progress| progress.counts()));
+
+        let today = Local::today();
+        let start = today - chrono::Duration::days(days.max(1) - 1);
+        let mut completed_per_week: Vec<(NaiveDate, usize)> = Vec::new();
+        let mut burndown = Vec::new();
+        let mut day = start;
+        while day <= today {
+            let week = start_of_week(day.naive_local(), week_start);
+            if completed_per_week.last().map(|(last_week, _)| *last_week) != Some(week) {
+                completed_per_week.push((week, 0));
+            }
+            let finished_today = tasks.iter()
+                .filter(|task| task.finished.map(|finished| finished.date()) == Some(day))
+                .count();
+            if let Some((_, count)) = completed_per_week.last_mut() {
+                *count += finished_today;
+            }
+            let open = tasks.iter()
+                .filter(|task| task.created.is_none_or(|created| created.date() <= day))
+                .filter(|task| task.finished.is_none_or(|finished| finished.date() > day))
+                .count();
+            burndown.push((day, open));
+            day = day.succ();
+        }
+
+        let cycle_times: Vec<chrono::Duration> = tasks.iter()
+            .filter_map(|task| {
+                let finished = task.finished?;
+                if finished.date() < start {
+                    return None;
+                }
+                let first_clock = self.task_clock(&task.id).iter().map(|clock| clock.start.with_timezone(&Local)).min();
+                let started = first_clock.or(task.created)?;
+                Some(finished - started)
+            })
+            .collect();
+        let average_cycle_time = if cycle_times.is_empty() {
+            None
+        } else {
+            let sum = cycle_times.iter().fold(chrono::Duration::zero(), |acc, new| acc + *new);
+            Some(sum / cycle_times.len() as i32)
+        };
+
+        let wip = tasks.iter()
+            .filter(|task| task.progress == Some(Progress::Work))
+            .count();
+
+        Ok(ThroughputStats {
+            completed_per_week,
+            average_cycle_time,
+            wip,
+            burndown,
+        })
+    }
+}
@@ -0,0 +1,318 @@
+//! Storage backends for document files.
+//!
+//! The backend is selected by the path of the document, so every workspace
+//! can keep its document somewhere else:
+//!
+//! * `ssh://host/path/tasks.json` is read and written by running `ssh` with
+//!   a small shell command on the remote machine, so one document can be
+//!   edited from several machines.  `ssh://host/~/tasks.json` is relative to
+//!   the remote home directory and `ssh://user@host:2222/...` selects the
+//!   user and port.
+//! * `s3://bucket/key.json` is an object in an S3 compatible object store,
+//!   requests are signed by `curl` with the credentials of `S3Config`.
+//! * `memory://name` only lives as long as the thread, for tests.
+//! * All other paths are local files.
+//!
+//! Journals only exist next to local files, remote documents are always
+//! written as a whole.
+
+use crate::curl::SecretConfig;
+use crate::error::*;
+use snafu::ResultExt;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Prefix of paths on a remote machine.
+pub const SSH_PREFIX: &str = "ssh://";
+
+/// Prefix of objects in an object store.
+pub const S3_PREFIX: &str = "s3://";
+
+/// Prefix of documents in memory.
+pub const MEMORY_PREFIX: &str = "memory://";
+
+thread_local! {
+    static MEMORY_FILES: RefCell<HashMap<String, Vec<u8>>> = RefCell::new(HashMap::new());
+    static S3_CONFIG: RefCell<S3Config> = RefCell::new(S3Config::default());
+}
+
+/// Place where the content of a document file is kept.
+pub trait Storage {
+    /// Read the whole content.
+    fn read(&self) -> Result<Vec<u8>>;
+
+    /// Replace the content, the file is created if it doesn't exist.
+    fn write(&self, content: &[u8]) -> Result<()>;
+
+    fn exists(&self) -> bool;
+
+    /// Path on this machine, None for remote files.
+    fn local_path(&self) -> Option<&Path>;
+}
+
+/// File on this machine.
+pub struct FileStorage {
+    pub path: PathBuf,
+}
+
+impl Storage for FileStorage {
+    fn read(&self) -> Result<Vec<u8>> {
+        Ok(std::fs::read(&self.path).context(IO)?)
+    }
+
+    fn write(&self, content: &[u8]) -> Result<()> {
+        Ok(std::fs::write(&self.path, content).context(IO)?)
+    }
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// File on another machine which is reachable with `ssh`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshStorage {
+    /// Destination for `ssh`, optionally with the user like `user@host`.
+    pub host: String,
+    pub port: Option<u16>,
+    /// Absolute path or a path starting with `~/` for the home directory.
+    pub path: String,
+}
+
+/// Quote the text for a POSIX shell.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn storage_error(msg: impl ToString) -> Error {
+    Error::StorageError { msg: msg.to_string() }
+}
+
+impl SshStorage {
+    /// Parse `ssh://[user@]host[:port]/path`, None for other paths.
+    pub fn parse(url: &str) -> Option<SshStorage> {
+        let rest = url.strip_prefix(SSH_PREFIX)?;
+        let slash = rest.find('/')?;
+        let (authority, path) = rest.split_at(slash);
+        let (host, port) = match authority.rfind(':') {
+            Some(colon) => (&authority[..colon], Some(authority[colon + 1..].parse().ok()?)),
+            None => (authority, None),
+        };
+        let path = match path.strip_prefix("/~/") {
+            Some(home_path) => format!("~/{}", home_path),
+            None => path.to_string(),
+        };
+        if host.is_empty() || path.ends_with('/') {
+            return None;
+        }
+        Some(SshStorage { host: host.to_string(), port, path })
+    }
+
+    /// Path of the file quoted for the remote shell.
+    ///
+    /// The `~/` prefix stays unquoted so the shell expands it.
+    pub fn quoted_path(&self, suffix: &str) -> String {
+        match self.path.strip_prefix("~/") {
+            Some(home_path) => format!("~/{}", shell_quote(&format!("{}{}", home_path, suffix))),
+            None => shell_quote(&format!("{}{}", self.path, suffix)),
+        }
+    }
+
+    fn exec(&self, script: &str) -> subprocess::Exec {
+        let mut exec = subprocess::Exec::cmd("ssh");
+        if let Some(port) = self.port {
+            exec = exec.arg("-p").arg(port.to_string());
+        }
+        exec.arg("--").arg(&self.host).arg(script)
+            .stdout(subprocess::Redirection::Pipe)
+            .stderr(subprocess::Redirection::Pipe)
+    }
+
+    fn run(&self, exec: subprocess::Exec) -> Result<Vec<u8>> {
+        let capture = exec.capture().map_err(storage_error)?;
+        if !capture.success() {
+            return Err(storage_error(format!("{}: {}", self.host, capture.stderr_str().trim())));
+        }
+        Ok(capture.stdout)
+    }
+}
+
+impl Storage for SshStorage {
+    fn read(&self) -> Result<Vec<u8>> {
+        self.run(self.exec(&format!("cat -- {}", self.quoted_path(""))))
+    }
+
+    /// Write to a temporary file first and rename it, so a broken
+    /// connection doesn't leave half a document behind.
+    fn write(&self, content: &[u8]) -> Result<()> {
+        let script = format!("cat > {temp} && mv -f -- {temp} {path}",
+            temp = self.quoted_path(".tmp"), path = self.quoted_path(""));
+        self.run(self.exec(&script).stdin(content.to_vec()))?;
+        Ok(())
+    }
+
+    fn exists(&self) -> bool {
+        self.exec(&format!("test -e {}", self.quoted_path("")))
+            .capture()
+            .map(|capture| capture.success())
+            .unwrap_or(false)
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Document which only exists in memory of the current thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryStorage {
+    pub name: String,
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self) -> Result<Vec<u8>> {
+        MEMORY_FILES.with(|files| files.borrow().get(&self.name).cloned())
+            .ok_or_else(|| storage_error(format!("No document in memory: {}", self.name)))
+    }
+
+    fn write(&self, content: &[u8]) -> Result<()> {
+        MEMORY_FILES.with(|files| files.borrow_mut().insert(self.name.clone(), content.to_vec()));
+        Ok(())
+    }
+
+    fn exists(&self) -> bool {
+        MEMORY_FILES.with(|files| files.borrow().contains_key(&self.name))
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Endpoint and credentials of the object store, see `Config::s3_config`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct S3Config {
+    /// Base URL like `https://minio.example.com`, AWS of the region if empty.
+    pub endpoint: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// Use the endpoint and credentials for `s3://` documents of this thread.
+pub fn set_s3_config(config: S3Config) {
+    S3_CONFIG.with(|s3_config| *s3_config.borrow_mut() = config);
+}
+
+/// Percent encode the object key, except for the slashes.
+fn encode_key(key: &str) -> String {
+    key.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+/// Object in an S3 compatible object store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Storage {
+    pub bucket: String,
+    pub key: String,
+    pub config: S3Config,
+}
+
+impl S3Storage {
+    /// Parse `s3://bucket/key`, None for other paths.
+    pub fn parse(url: &str, config: S3Config) -> Option<S3Storage> {
+        let rest = url.strip_prefix(S3_PREFIX)?;
+        let slash = rest.find('/')?;
+        let (bucket, key) = (&rest[..slash], &rest[slash + 1..]);
+        if bucket.is_empty() || key.is_empty() || key.ends_with('/') {
+            return None;
+        }
+        Some(S3Storage { bucket: bucket.to_string(), key: key.to_string(), config })
+    }
+
+    /// URL of the object with the bucket in the path, which works with all
+    /// bucket names and most object stores.
+    pub fn url(&self) -> String {
+        let endpoint = if self.config.endpoint.is_empty() {
+            format!("https://s3.{}.amazonaws.com", self.config.region)
+        } else {
+            self.config.endpoint.trim_end_matches('/').to_string()
+        };
+        format!("{}/{}/{}", endpoint, self.bucket, encode_key(&self.key))
+    }
+
+    fn curl(&self, args: &[&str], body: Option<&[u8]>) -> Result<subprocess::CaptureData> {
+        if self.config.access_key.is_empty() {
+            return Err(storage_error("No S3 credentials configured, set s3_access_key and s3_secret_key"));
+        }
+        let credentials = format!("{}:{}", self.config.access_key, self.config.secret_key);
+        let config = SecretConfig::new(&[("user", &credentials)]).map_err(storage_error)?;
+        let mut exec = subprocess::Exec::cmd("curl").arg("-sSf")
+            .arg("--aws-sigv4").arg(format!("aws:amz:{}:s3", self.config.region))
+            .arg("-K").arg(config.path())
+            .args(args);
+        if let Some(body) = body {
+            exec = exec.args(&["-X", "PUT", "-H", "Content-Type: application/octet-stream", "--data-binary", "@-"])
+                .stdin(body.to_vec());
+        }
+        exec.arg(self.url())
+            .stdout(subprocess::Redirection::Pipe)
+            .stderr(subprocess::Redirection::Pipe)
+            .capture()
+            .map_err(storage_error)
+    }
+}
+
+impl Storage for S3Storage {
+    fn read(&self) -> Result<Vec<u8>> {
+        let capture = self.curl(&[], None)?;
+        if !capture.success() {
+            return Err(storage_error(format!("{}: {}", self.url(), capture.stderr_str().trim())));
+        }
+        Ok(capture.stdout)
+    }
+
+    fn write(&self, content: &[u8]) -> Result<()> {
+        let capture = self.curl(&[], Some(content))?;
+        if !capture.success() {
+            return Err(storage_error(format!("{}: {}", self.url(), capture.stderr_str().trim())));
+        }
+        Ok(())
+    }
+
+    fn exists(&self) -> bool {
+        self.curl(&["--head"], None).map(|capture| capture.success()).unwrap_or(false)
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Storage for the path of a document.
+pub fn storage(path: impl AsRef<Path>) -> Box<dyn Storage> {
+    let path = path.as_ref();
+    let url = path.to_str().unwrap_or_default();
+    if let Some(name) = url.strip_prefix(MEMORY_PREFIX) {
+        return Box::new(MemoryStorage { name: name.to_string() });
+    }
+    if let Some(s3) = S3_CONFIG.with(|config| S3Storage::parse(url, config.borrow().clone())) {
+        return Box::new(s3);
+    }
+    match SshStorage::parse(url) {
+        Some(ssh) => Box::new(ssh),
+        None => Box::new(FileStorage { path: path.to_path_buf() }),
+    }
+}
+
+/// Check if the path is on another machine.
+pub fn is_remote(path: impl AsRef<Path>) -> bool {
+    storage(path).local_path().is_none()
+}
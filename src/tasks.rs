@@ -1,17 +1,44 @@
 use uuid::Uuid;
 use serde::{Serialize, Deserialize};
 use std::rc::Rc;
+use std::collections::BTreeMap;
+use chrono::prelude::*;
+use crate::error::*;
+use crate::habit::Habit;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Progress {
-    Todo, Work, Done
+    Todo, Work, Done, Waiting, Blocked, Cancelled
 }
 impl Progress {
     pub fn done(self) -> bool {
         match self {
             Progress::Todo => false,
             Progress::Work => false,
-            Progress::Done => true
+            Progress::Done => true,
+            Progress::Waiting => false,
+            Progress::Blocked => false,
+            Progress::Cancelled => false,
+        }
+    }
+
+    /// Cancelled tasks are not counted in progress summaries.
+    pub fn counts(self) -> bool {
+        self != Progress::Cancelled
+    }
+}
+
+impl std::str::FromStr for Progress {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Progress> {
+        match s.to_lowercase().as_str() {
+            "todo" => Ok(Progress::Todo),
+            "work" => Ok(Progress::Work),
+            "done" => Ok(Progress::Done),
+            "waiting" => Ok(Progress::Waiting),
+            "blocked" => Ok(Progress::Blocked),
+            "cancelled" => Ok(Progress::Cancelled),
+            _ => Err(Error::UnknownProgress { name: s.to_string() }),
         }
     }
 }
@@ -21,18 +48,83 @@ impl ToString for Progress {
         match self {
             Progress::Todo => "TODO".to_string(),
             Progress::Work => "WORK".to_string(),
-            Progress::Done => "DONE".to_string()
+            Progress::Done => "DONE".to_string(),
+            Progress::Waiting => "WAITING".to_string(),
+            Progress::Blocked => "BLOCKED".to_string(),
+            Progress::Cancelled => "CANCELLED".to_string(),
         }
     }
 }
 
+/// Timestamped note of a task.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub time: DateTime<Local>,
+    pub text: String,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: Uuid,
     pub title: String,
     pub body: String,
     pub children: Vec<Uuid>,
-    pub progress: Option<Progress>
+    pub progress: Option<Progress>,
+
+    /// Keep the progress of this task untouched by the progress roll-up.
+    #[serde(default)]
+    pub no_rollup: bool,
+
+    /// Name of the workflow state if the document defines a workflow.
+    #[serde(default)]
+    pub state: Option<String>,
+
+    #[serde(default)]
+    pub created: Option<DateTime<Local>>,
+
+    /// When the task was changed last, None if it wasn't changed since it
+    /// was created.
+    #[serde(default)]
+    pub modified: Option<DateTime<Local>>,
+
+    /// When the task was marked as done.
+    #[serde(default)]
+    pub finished: Option<DateTime<Local>>,
+
+    /// When the task is due.  A reminder is shown once this time passed.
+    #[serde(default)]
+    pub due: Option<DateTime<Local>>,
+
+    /// Issue key or URL in an external tracker like Jira or GitHub.
+    #[serde(default)]
+    pub external_ref: Option<String>,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Time budget of the task and its subtasks in seconds.
+    #[serde(default)]
+    pub budget: Option<i64>,
+
+    /// Notes in the order they were added.
+    #[serde(default)]
+    pub notes: Vec<Note>,
+
+    /// URLs or absolute paths of related files.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+
+    /// Listed by the `pinned` command.
+    #[serde(default)]
+    pub pinned: bool,
+
+    /// Custom fields defined by the user like `client`.
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+
+    /// Recurring task which is open again right after it's done.
+    #[serde(default)]
+    pub habit: Option<Habit>,
 }
 
 impl Default for Task {
@@ -49,8 +141,64 @@ impl Task {
             title: String::new(),
             body: String::new(),
             children: Vec::new(),
-            progress: None
+            progress: None,
+            no_rollup: false,
+            state: None,
+            created: Some(Local::now()),
+            modified: None,
+            finished: None,
+            due: None,
+            external_ref: None,
+            tags: Vec::new(),
+            budget: None,
+            notes: Vec::new(),
+            attachments: Vec::new(),
+            pinned: false,
+            properties: BTreeMap::new(),
+            habit: None,
+        }
+    }
+
+    /// Label of the progress as shown in listings.
+    ///
+    /// It's the workflow state if there is one, otherwise the progress.
+    pub fn progress_label(&self) -> String {
+        match (&self.state, self.progress) {
+            (Some(state), _) => state.to_uppercase(),
+            (None, Some(progress)) => progress.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Check if the task is due and still open.
+    pub fn is_overdue(&self, now: DateTime<Local>) -> bool {
+        self.due.is_some_and(|due| due <= now)
+            && self.progress.is_none_or(|progress| !progress.done() && progress.counts())
+    }
+
+    /// Title and body as text to edit them, separated by an empty line.
+    pub fn edit_text(&self) -> String {
+        format!("{}\n\n{}", self.title, self.body)
+    }
+
+    /// Split edited text into title and body, the first line is the title.
+    ///
+    /// Blank lines between title and body and whitespace at the end are
+    /// dropped, the body keeps its line endings and indentation.  Empty text
+    /// gives an empty title and body.
+    pub fn parse_edit_text(content: &str) -> (String, String) {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let (title, mut body) = content.split_once('\n').unwrap_or((content, ""));
+        while let Some((line, rest)) = body.split_once('\n') {
+            if !line.trim().is_empty() {
+                break;
+            }
+            body = rest;
+        }
+        if body.trim().is_empty() {
+            body = "";
         }
+        (title.trim().to_string(), body.trim_end().to_string())
     }
 }
 
@@ -62,6 +210,20 @@ pub trait TaskMod {
     fn insert_child(&mut self, child: Uuid, index: usize) -> &mut Self;
     fn remove_child(&mut self, child: &Uuid) -> &mut Self;
     fn set_progress(&mut self, progress: Progress) -> &mut Self;
+    fn set_no_rollup(&mut self, no_rollup: bool) -> &mut Self;
+    fn set_state(&mut self, state: impl ToString, progress: Progress) -> &mut Self;
+    fn set_due(&mut self, due: Option<DateTime<Local>>) -> &mut Self;
+    fn set_external_ref(&mut self, external_ref: Option<String>) -> &mut Self;
+    fn add_tag(&mut self, tag: impl ToString) -> &mut Self;
+    fn remove_tag(&mut self, tag: &str) -> &mut Self;
+    fn set_budget(&mut self, budget: Option<chrono::Duration>) -> &mut Self;
+    fn add_note(&mut self, text: impl ToString) -> &mut Self;
+    fn add_attachment(&mut self, attachment: impl ToString) -> &mut Self;
+    fn remove_attachment(&mut self, index: usize) -> &mut Self;
+    fn set_pinned(&mut self, pinned: bool) -> &mut Self;
+    fn set_property(&mut self, key: impl ToString, value: impl ToString) -> &mut Self;
+    fn remove_property(&mut self, key: &str) -> &mut Self;
+    fn set_habit(&mut self, habit: Option<Habit>) -> &mut Self;
 }
 impl TaskMod for Rc<Task> {
     fn set_title(&mut self, title: impl ToString) -> &mut Self {
@@ -100,7 +262,84 @@ impl TaskMod for Rc<Task> {
         self
     }
     fn set_progress(&mut self, progress: Progress) -> &mut Self {
-        Rc::make_mut(self).progress = Some(progress);
+        let task = Rc::make_mut(self);
+        if !progress.done() {
+            task.finished = None;
+        } else if task.finished.is_none() || !task.progress.is_some_and(|old| old.done()) {
+            task.finished = Some(Local::now());
+        }
+        // The workflow state only stays if the progress doesn't change.
+        if task.progress != Some(progress) {
+            task.state = None;
+        }
+        task.progress = Some(progress);
+        self
+    }
+    fn set_no_rollup(&mut self, no_rollup: bool) -> &mut Self {
+        Rc::make_mut(self).no_rollup = no_rollup;
+        self
+    }
+    fn set_state(&mut self, state: impl ToString, progress: Progress) -> &mut Self {
+        self.set_progress(progress);
+        Rc::make_mut(self).state = Some(state.to_string());
+        self
+    }
+    fn set_due(&mut self, due: Option<DateTime<Local>>) -> &mut Self {
+        Rc::make_mut(self).due = due;
+        self
+    }
+    fn set_external_ref(&mut self, external_ref: Option<String>) -> &mut Self {
+        Rc::make_mut(self).external_ref = external_ref;
+        self
+    }
+    fn add_tag(&mut self, tag: impl ToString) -> &mut Self {
+        let tag = tag.to_string();
+        if !self.tags.contains(&tag) {
+            Rc::make_mut(self).tags.push(tag);
+        }
+        self
+    }
+    fn remove_tag(&mut self, tag: &str) -> &mut Self {
+        Rc::make_mut(self).tags.retain(|existing| existing != tag);
+        self
+    }
+    fn set_budget(&mut self, budget: Option<chrono::Duration>) -> &mut Self {
+        Rc::make_mut(self).budget = budget.map(|budget| budget.num_seconds());
+        self
+    }
+    fn add_note(&mut self, text: impl ToString) -> &mut Self {
+        Rc::make_mut(self).notes.push(Note { time: Local::now(), text: text.to_string() });
+        self
+    }
+    fn add_attachment(&mut self, attachment: impl ToString) -> &mut Self {
+        let attachment = attachment.to_string();
+        if !self.attachments.contains(&attachment) {
+            Rc::make_mut(self).attachments.push(attachment);
+        }
+        self
+    }
+    fn remove_attachment(&mut self, index: usize) -> &mut Self {
+        if index < self.attachments.len() {
+            Rc::make_mut(self).attachments.remove(index);
+        }
+        self
+    }
+    fn set_pinned(&mut self, pinned: bool) -> &mut Self {
+        Rc::make_mut(self).pinned = pinned;
+        self
+    }
+    fn set_property(&mut self, key: impl ToString, value: impl ToString) -> &mut Self {
+        Rc::make_mut(self).properties.insert(key.to_string(), value.to_string());
+        self
+    }
+    fn remove_property(&mut self, key: &str) -> &mut Self {
+        if self.properties.contains_key(key) {
+            Rc::make_mut(self).properties.remove(key);
+        }
+        self
+    }
+    fn set_habit(&mut self, habit: Option<Habit>) -> &mut Self {
+        Rc::make_mut(self).habit = habit;
         self
     }
 }
\ No newline at end of file
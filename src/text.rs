@@ -0,0 +1,55 @@
+//! Width of text in terminal columns, for listings with titles in columns.
+//!
+//! Emoji and CJK characters take two columns and combining marks none, so
+//! `chars().count()` and `{:width$}` don't line up titles which use them.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Widest title column, longer titles are truncated.
+pub const TITLE_COLUMN_WIDTH: usize = 50;
+
+/// Columns the text takes in a terminal.
+pub fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// The text cut to at most `width` columns, ending with `…` when something
+/// was cut.  Wide characters are never split.
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    if display_width(text) <= width {
+        return text.to_string();
+    }
+    let mut result = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        // One column is left for the ellipsis.
+        if used + char_width + 1 > width {
+            break;
+        }
+        used += char_width;
+        result.push(c);
+    }
+    if width > 0 {
+        result.push('…');
+    }
+    result
+}
+
+/// The text filled up with spaces to `width` columns, longer text is
+/// returned unchanged.
+pub fn pad_to_width(text: &str, width: usize) -> String {
+    let mut result = text.to_string();
+    result.push_str(&" ".repeat(width.saturating_sub(display_width(text))));
+    result
+}
+
+/// The text truncated or padded to exactly `width` columns.
+pub fn fit_to_width(text: &str, width: usize) -> String {
+    pad_to_width(&truncate_to_width(text, width), width)
+}
+
+/// Width of a column for the texts, at most `max`.
+pub fn column_width<'a>(texts: impl IntoIterator<Item = &'a str>, max: usize) -> usize {
+    texts.into_iter().map(display_width).max().unwrap_or(0).min(max)
+}
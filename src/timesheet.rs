@@ -0,0 +1,112 @@
+//! Hours per day and project for timesheets.
+//!
+//! A project is a top-level task below the root.  The document can map the
+//! titles of these tasks to the project names of a timesheet tool, projects
+//! mapped to the same name are summed up.
+
+use crate::doc::Doc;
+use crate::text::{display_width, pad_to_width};
+use chrono::prelude::*;
+use uuid::Uuid;
+
+/// Name of the row for clocks without a project.
+pub const NO_PROJECT: &str = "(none)";
+
+#[derive(Debug, Clone)]
+pub struct Timesheet {
+    pub days: Vec<NaiveDate>,
+
+    /// Project name and the tracked time for each day.
+    pub rows: Vec<(String, Vec<chrono::Duration>)>,
+}
+
+fn hours(duration: chrono::Duration) -> String {
+    format!("{:.2}", duration.num_minutes() as f64 / 60.0)
+}
+
+impl Timesheet {
+    /// Tracked time per day of all projects.
+    pub fn day_totals(&self) -> Vec<chrono::Duration> {
+        (0..self.days.len())
+            .map(|i| self.rows.iter().fold(chrono::Duration::zero(), |acc, (_, durations)| acc + durations[i]))
+            .collect()
+    }
+
+    /// Comma separated values with decimal hours, one line per project.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("project");
+        for day in self.days.iter() {
+            csv.push_str(&format!(",{}", day.format("%Y-%m-%d")));
+        }
+        csv.push('\n');
+        for (project, durations) in self.rows.iter() {
+            csv.push_str(&format!("\"{}\"", project.replace('"', "\"\"")));
+            for duration in durations.iter() {
+                csv.push_str(&format!(",{}", hours(*duration)));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Text table with decimal hours and the daily totals.
+    pub fn to_table(&self) -> String {
+        let width = self.rows.iter().map(|(project, _)| display_width(project))
+            .chain(std::iter::once(5))
+            .max().unwrap_or(0);
+        let mut table = format!("{:width$}", "", width = width);
+        for day in self.days.iter() {
+            table.push_str(&format!(" {:>6}", day.format("%a %d")));
+        }
+        table.push('\n');
+        let total_row = ("Total".to_string(), self.day_totals());
+        for (project, durations) in self.rows.iter().chain(std::iter::once(&total_row)) {
+            table.push_str(&pad_to_width(project, width));
+            for duration in durations.iter() {
+                table.push_str(&format!(" {:>6}", hours(*duration)));
+            }
+            table.push('\n');
+        }
+        table
+    }
+}
+
+impl Doc {
+    /// Name of the timesheet project for the given top-level task title.
+    pub fn timesheet_project(&self, title: &str) -> String {
+        self.timesheet_projects.get(title).cloned().unwrap_or_else(|| title.to_string())
+    }
+
+    /// Collect the tracked time per day and project between both dates
+    /// (inclusive), only of the given task and its subtasks if there is one.
+    pub fn timesheet(&self, start: NaiveDate, end: NaiveDate, main_task: impl Into<Option<Uuid>>) -> Timesheet {
+        let mut days = Vec::new();
+        let mut day = start;
+        while day <= end {
+            days.push(day);
+            day = day.succ();
+        }
+        let mut rows: Vec<(String, Vec<chrono::Duration>)> = Vec::new();
+        for clock in self.range_clock(start, end, main_task) {
+            let project = clock.task_id
+                .map(|task_id| self.path(&task_id))
+                .filter(|path| path.len() >= 2)
+                .and_then(|path| self.get(&path[path.len() - 2]).ok())
+                .map(|task| self.timesheet_project(&task.title))
+                .unwrap_or_else(|| NO_PROJECT.to_string());
+            let index = match rows.iter().position(|(name, _)| *name == project) {
+                Some(index) => index,
+                None => {
+                    rows.push((project, vec![chrono::Duration::zero(); days.len()]));
+                    rows.len() - 1
+                },
+            };
+            if let Some(day_index) = days.iter().position(|day| *day == self.clock_date(&clock)) {
+                let durations = &mut rows[index].1;
+                durations[day_index] = durations[day_index] + clock.duration();
+            }
+        }
+        rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Timesheet { days, rows }
+    }
+}
@@ -0,0 +1,113 @@
+//! Time zone in which clocks are shown and grouped into days.
+//!
+//! Clocks are stored in UTC, so they stay correct when the time zone of the
+//! computer changes.  Older documents stored clocks in local time with
+//! their offset, they are converted to UTC when they're loaded and written
+//! as UTC with the next save.
+
+use crate::error::*;
+use chrono::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::convert::TryFrom;
+
+/// Time zone of the document for clock listings and reports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum DisplayZone {
+    /// Time zone of the computer.
+    #[default]
+    Local,
+    Utc,
+    /// Fixed offset like `+02:00`.
+    Offset(FixedOffset),
+}
+
+impl std::fmt::Display for DisplayZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DisplayZone::Local => f.write_str("local"),
+            DisplayZone::Utc => f.write_str("UTC"),
+            DisplayZone::Offset(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+fn parse_offset(string: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match string.chars().next()? {
+        '+' => (1, &string[1..]),
+        '-' => (-1, &string[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+impl std::str::FromStr for DisplayZone {
+    type Err = Error;
+
+    /// Parse `local`, `utc` or an offset like `+02:00` or `-5`.
+    fn from_str(string: &str) -> Result<DisplayZone> {
+        match string.to_lowercase().as_str() {
+            "local" => Ok(DisplayZone::Local),
+            "utc" | "z" => Ok(DisplayZone::Utc),
+            _ => parse_offset(string)
+                .map(DisplayZone::Offset)
+                .ok_or_else(|| Error::InvalidTimeZone { name: string.to_string() }),
+        }
+    }
+}
+
+impl From<DisplayZone> for String {
+    fn from(zone: DisplayZone) -> String {
+        zone.to_string()
+    }
+}
+
+impl TryFrom<String> for DisplayZone {
+    type Error = Error;
+
+    fn try_from(string: String) -> Result<DisplayZone> {
+        string.parse()
+    }
+}
+
+impl DisplayZone {
+    /// Offset of the zone at the given time, which only differs for
+    /// `Local` during daylight saving time.
+    pub fn offset_at(&self, time: DateTime<Utc>) -> FixedOffset {
+        match self {
+            DisplayZone::Local => Local.offset_from_utc_datetime(&time.naive_utc()).fix(),
+            DisplayZone::Utc => FixedOffset::east(0),
+            DisplayZone::Offset(offset) => *offset,
+        }
+    }
+
+    /// The time as it is shown in this zone.
+    pub fn localize(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        time.with_timezone(&self.offset_at(time))
+    }
+
+    /// Day of the time in this zone.
+    pub fn date(&self, time: DateTime<Utc>) -> NaiveDate {
+        self.localize(time).naive_local().date()
+    }
+
+    /// The current day in this zone.
+    pub fn today(&self) -> NaiveDate {
+        self.date(Utc::now())
+    }
+
+    /// Convert a time of this zone to UTC, for ambiguous local times the
+    /// earlier one is used.
+    pub fn to_utc(&self, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            DisplayZone::Local => Local.from_local_datetime(&time).earliest().map(|time| time.with_timezone(&Utc)),
+            DisplayZone::Utc => Some(Utc.from_utc_datetime(&time)),
+            DisplayZone::Offset(offset) => offset.from_local_datetime(&time).single().map(|time| time.with_timezone(&Utc)),
+        }
+    }
+}
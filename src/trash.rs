@@ -0,0 +1,100 @@
+//! Trash for removed tasks.
+//!
+//! Removed subtrees become children of a hidden trash task which is not part
+//! of the tree below the root.  They can be restored to their old parent
+//! until the trash is emptied.
+
+use crate::doc::Doc;
+use crate::tasks::*;
+use crate::error::*;
+use serde::{Serialize, Deserialize};
+use chrono::prelude::*;
+use uuid::Uuid;
+use std::rc::Rc;
+
+/// A task which was moved to the trash.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub task_id: Uuid,
+    pub parent_id: Uuid,
+
+    /// Index in the children of the parent, to restore it there.
+    #[serde(default)]
+    pub position: Option<usize>,
+    pub deleted: DateTime<Local>,
+}
+
+impl Doc {
+    /// Get the trash task and create it if it doesn't exist yet.
+    pub fn trash_task(&mut self) -> Uuid {
+        if let Some(trash) = self.trash.filter(|trash| self.map.contains_key(trash)) {
+            return trash;
+        }
+        let mut task = Rc::new(Task::new());
+        task.set_title("Trash");
+        let trash = task.id;
+        self.upsert(task);
+        self.trash = Some(trash);
+        trash
+    }
+
+    /// Move the task and its subtasks from its parent into the trash.
+    ///
+    /// # Error
+    /// Returns an error if the task has no parent, like the root.
+    pub fn move_to_trash(&mut self, task_ref: &Uuid) -> Result<()> {
+        let (parent_id, position) = self.position(task_ref).ok_or(Error::TaskUuidNotFound {})?;
+        let trash = self.trash_task();
+        if parent_id == trash {
+            return Ok(());
+        }
+        self.move_task(task_ref, &trash, None)?;
+        self.trash_entries.push(TrashEntry { task_id: *task_ref, parent_id, position: Some(position), deleted: Local::now() });
+        Ok(())
+    }
+
+    /// Move the trash entry with the given index back to its parent.
+    ///
+    /// It gets its old position if possible.  If the parent doesn't exist
+    /// anymore, it's moved to the end of the root.
+    pub fn restore(&mut self, index: usize) -> Result<Uuid> {
+        if index >= self.trash_entries.len() {
+            return Err(Error::ChildOutOfIndex {});
+        }
+        let entry = self.trash_entries.remove(index);
+        let (parent_id, position) = match self.get(&entry.parent_id) {
+            Ok(parent) if self.is_in_hierarchy_of(&parent.id, &self.root) =>
+                (parent.id, entry.position.map(|position| position.min(parent.children.len()))),
+            _ => (self.root, None),
+        };
+        self.move_task(&entry.task_id, &parent_id, position)?;
+        Ok(entry.task_id)
+    }
+
+    /// Get the trash entries which are older than the given duration or all
+    /// entries.
+    pub fn expired_trash_entries(&self, older_than: Option<chrono::Duration>) -> Vec<TrashEntry> {
+        let now = Local::now();
+        self.trash_entries.iter()
+            .filter(|entry| older_than.is_none_or(|older_than| now - entry.deleted > older_than))
+            .cloned().collect()
+    }
+
+    /// Delete the trash entries which are older than the given duration or
+    /// all entries.  The tasks are removed from the document.
+    ///
+    /// Returns the number of deleted entries.
+    pub fn empty_trash(&mut self, older_than: Option<chrono::Duration>) -> Result<usize> {
+        let expired = self.expired_trash_entries(older_than);
+        let kept = self.trash_entries.drain(..)
+            .filter(|entry| !expired.contains(entry))
+            .collect();
+        self.trash_entries = kept;
+        for entry in expired.iter() {
+            if self.map.contains_key(&entry.task_id) {
+                self.remove_task(&entry.task_id)?;
+            }
+        }
+        Ok(expired.len())
+    }
+}
@@ -0,0 +1,113 @@
+//! Iterators over the task tree.
+//!
+//! Tasks are returned with their depth relative to the task where the
+//! iteration started, which has the depth 0.
+
+use crate::doc::Doc;
+use crate::tasks::Task;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Decides if a task and its subtasks are returned.
+type TaskFilter<'a> = Box<dyn Fn(&Task) -> bool + 'a>;
+
+/// Iterator over a task and its subtasks, see `Doc::iter_subtree`.
+pub struct Subtree<'a> {
+    doc: &'a Doc,
+    pending: VecDeque<(usize, Uuid)>,
+    breadth_first: bool,
+    max_depth: Option<usize>,
+    filter: Option<TaskFilter<'a>>,
+}
+
+impl<'a> Subtree<'a> {
+    fn new(doc: &'a Doc, task_ref: &Uuid, breadth_first: bool) -> Subtree<'a> {
+        Subtree {
+            doc,
+            pending: vec![(0, *task_ref)].into(),
+            breadth_first,
+            max_depth: None,
+            filter: None,
+        }
+    }
+
+    /// Only return tasks with a depth below `max_depth`, so 1 only returns
+    /// the start task.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Skip the tasks for which `keep` returns false together with their
+    /// subtasks.
+    pub fn with_filter(mut self, keep: impl Fn(&Task) -> bool + 'a) -> Self {
+        self.filter = Some(Box::new(keep));
+        self
+    }
+}
+
+impl<'a> Iterator for Subtree<'a> {
+    type Item = (usize, Rc<Task>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (depth, task_id) = if self.breadth_first {
+                self.pending.pop_front()?
+            } else {
+                self.pending.pop_back()?
+            };
+            if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                continue;
+            }
+            let task = match self.doc.get(&task_id) {
+                Ok(task) => task,
+                Err(_) => continue,
+            };
+            if self.filter.as_ref().is_some_and(|keep| !keep(&task)) {
+                continue;
+            }
+            let children = task.children.iter().map(|child| (depth + 1, *child));
+            if self.breadth_first {
+                self.pending.extend(children);
+            } else {
+                self.pending.extend(children.rev());
+            }
+            return Some((depth, task));
+        }
+    }
+}
+
+impl Doc {
+    /// The task and its subtasks, depth first with parents before their
+    /// children.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sors::*;
+    ///
+    /// let mut doc = Doc::new();
+    /// let mut task = Rc::new(Task::new());
+    /// task.set_title("child");
+    /// doc.add_subtask(task, &doc.root.clone()).unwrap();
+    /// let titles: Vec<_> = doc.iter_subtree(&doc.root)
+    ///     .map(|(depth, task)| format!("{} {}", depth, task.title))
+    ///     .collect();
+    /// assert_eq!(titles, vec!["0 ", "1 child"]);
+    /// ```
+    pub fn iter_subtree(&self, task_ref: &Uuid) -> Subtree<'_> {
+        Subtree::new(self, task_ref, false)
+    }
+
+    /// The task and its subtasks level by level.
+    pub fn iter_subtree_breadth_first(&self, task_ref: &Uuid) -> Subtree<'_> {
+        Subtree::new(self, task_ref, true)
+    }
+
+    /// Like `iter_subtree` but tasks for which `keep` returns false are
+    /// skipped together with their subtasks.
+    pub fn iter_subtree_filtered<'a>(&'a self, task_ref: &Uuid, keep: impl Fn(&Task) -> bool + 'a) -> Subtree<'a> {
+        self.iter_subtree(task_ref).with_filter(keep)
+    }
+}
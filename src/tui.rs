@@ -0,0 +1,261 @@
+//! Full screen view of the task tree for arrow key navigation.
+//!
+//! The view works on the same `State` as the commands, so the prompt
+//! continues with the changes and the working task when the view is closed.
+//! `TreeView` doesn't depend on the terminal, `run` connects it to it.
+
+use crate::error::*;
+use crate::duration::format_duration;
+use crate::helper::{clock_indicator, task_path_string};
+use crate::state::State;
+use crate::tasks::*;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+use snafu::ResultExt;
+use std::collections::HashSet;
+use std::rc::Rc;
+use uuid::Uuid;
+
+/// Keys the view reacts to, independent of the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Esc,
+    Char(char),
+}
+
+/// Visible line of the tree, the children of the root have the depth 0.
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub depth: usize,
+    pub task: Rc<Task>,
+    pub expanded: bool,
+}
+
+/// Expanded tasks and the selection of the tree.
+#[derive(Clone, Debug)]
+pub struct TreeView {
+    expanded: HashSet<Uuid>,
+    pub selected: Option<Uuid>,
+
+    /// Result of the last action, shown in the status bar.
+    pub message: Option<String>,
+    pub quit: bool,
+}
+
+const KEY_HELP: &str = "↑↓ move  ←→ collapse/expand  enter cd  space done  c clock in  o clock out  q quit";
+
+impl TreeView {
+    /// Show the working task with its parents expanded.
+    pub fn new(state: &State) -> TreeView {
+        let mut expanded: HashSet<Uuid> = state.doc.path(&state.wt).into_iter().collect();
+        expanded.remove(&state.wt);
+        let selected = Some(state.wt).filter(|task_id| *task_id != state.root());
+        let mut view = TreeView { expanded, selected, message: None, quit: false };
+        if view.selected.is_none() {
+            view.selected = view.rows(state).first().map(|row| row.task.id);
+        }
+        view
+    }
+
+    /// Tasks below the root or focus whose parents are all expanded, in the
+    /// active context.
+    pub fn rows(&self, state: &State) -> Vec<Row> {
+        let mut rows = Vec::new();
+        self.push_rows(state, &state.root(), 0, &mut rows);
+        rows
+    }
+
+    fn push_rows(&self, state: &State, task_ref: &Uuid, depth: usize, rows: &mut Vec<Row>) {
+        let children = state.doc.get(task_ref).map(|task| task.children.clone()).unwrap_or_default();
+        for child in children.iter().filter(|child| state.in_context(child)) {
+            if let Ok(task) = state.doc.get(child) {
+                let expanded = self.expanded.contains(child);
+                rows.push(Row { depth, task, expanded });
+                if expanded {
+                    self.push_rows(state, child, depth + 1, rows);
+                }
+            }
+        }
+    }
+
+    fn select_offset(&mut self, rows: &[Row], offset: isize) {
+        let index = self.selected
+            .and_then(|selected| rows.iter().position(|row| row.task.id == selected))
+            .map_or(0, |index| (index as isize + offset).clamp(0, rows.len() as isize - 1) as usize);
+        self.selected = rows.get(index).map(|row| row.task.id);
+    }
+
+    /// Change the document unless it's read-only.
+    fn modify(&mut self, state: &mut State, change: impl FnOnce(&mut State, Uuid) -> Result<String>) {
+        let task_id = match self.selected {
+            Some(task_id) => task_id,
+            None => return,
+        };
+        if state.read_only {
+            self.message = Some(Error::ReadOnly {}.to_string());
+            return;
+        }
+        self.message = Some(change(state, task_id).unwrap_or_else(|err| err.to_string()));
+    }
+
+    pub fn handle_key(&mut self, state: &mut State, key: Key) {
+        let rows = self.rows(state);
+        let selected = self.selected.and_then(|selected| rows.iter().find(|row| row.task.id == selected));
+        self.message = None;
+        match key {
+            Key::Up | Key::Char('k') => self.select_offset(&rows, -1),
+            Key::Down | Key::Char('j') => self.select_offset(&rows, 1),
+            Key::Right | Key::Char('l') => if let Some(row) = selected {
+                if row.expanded {
+                    self.selected = row.task.children.first().cloned().or(self.selected);
+                } else if !row.task.children.is_empty() {
+                    self.expanded.insert(row.task.id);
+                }
+            },
+            Key::Left | Key::Char('h') => if let Some(row) = selected {
+                if row.expanded {
+                    self.expanded.remove(&row.task.id);
+                } else if let Some(parent) = state.doc.find_parent(&row.task.id).filter(|parent| *parent != state.root()) {
+                    self.selected = Some(parent);
+                }
+            },
+            Key::Enter => if let Some(row) = selected {
+                state.change_wt(row.task.id);
+                self.message = Some(format!("Working task: {}", task_path_string(&state.doc, &row.task.id)));
+            },
+            Key::Char(' ') => self.modify(state, |state, task_id| {
+                let task = state.doc.get(&task_id)?;
+                let progress = if task.progress.is_some_and(|progress| progress.done()) { Progress::Todo } else { Progress::Done };
+                state.doc.set_progress(&task_id, progress)?;
+                Ok(format!("{}: {}", task.title, state.doc.get(&task_id)?.progress_label()))
+            }),
+            Key::Char('c') => self.modify(state, |state, task_id| {
+                state.doc.clock_new()?;
+                state.doc.clock_assign(task_id)?;
+                Ok(format!("Clocked in: {}", task_path_string(&state.doc, &task_id)))
+            }),
+            Key::Char('o') => self.modify(state, |state, _| {
+                Ok(if state.doc.clock_out()? { "Clocked out" } else { "No clock running" }.to_string())
+            }),
+            Key::Char('q') | Key::Esc => self.quit = true,
+            _ => {},
+        }
+    }
+
+    fn detail(&self, state: &State) -> Vec<Line<'static>> {
+        let task = match self.selected.and_then(|task_id| state.doc.get(&task_id).ok()) {
+            Some(task) => task,
+            None => return vec![Line::from("No tasks")],
+        };
+        let mut lines = vec![
+            Line::styled(task.title.clone(), Style::default().add_modifier(Modifier::BOLD)),
+            Line::from(task_path_string(&state.doc, &task.id)),
+            Line::from(""),
+        ];
+        if task.progress.is_some() || task.state.is_some() {
+            lines.push(Line::from(format!("Progress: {}", task.progress_label())));
+        }
+        if !task.tags.is_empty() {
+            lines.push(Line::from(format!("Tags: {}", task.tags.join(", "))));
+        }
+        if let Some(due) = task.due {
+            lines.push(Line::from(format!("Due: {}", due.format("%Y-%m-%d %H:%M"))));
+        }
+        for (key, value) in task.properties.iter() {
+            lines.push(Line::from(format!("{}: {}", key, value)));
+        }
+        let tracked = state.doc.subtree_duration(&task.id);
+        if tracked > chrono::Duration::zero() {
+            lines.push(Line::from(format!("Tracked: {}", format_duration(tracked))));
+        }
+        if !task.body.is_empty() {
+            lines.push(Line::from(""));
+            lines.extend(task.body.lines().map(|line| Line::from(line.to_string())));
+        }
+        lines
+    }
+
+    fn status(&self, state: &State) -> String {
+        let clock = clock_indicator(&state.doc).unwrap_or_else(|| "No clock running".to_string());
+        let flags = match (state.dirty, state.read_only) {
+            (_, true) => " [ro]",
+            (true, _) => " *",
+            _ => "",
+        };
+        format!("{}{} | {}", clock, flags, self.message.as_deref().unwrap_or(KEY_HELP))
+    }
+
+    /// Tree on the left, details of the selected task on the right and the
+    /// clock in the status bar.
+    pub fn draw(&self, frame: &mut Frame, state: &State) {
+        let [main, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [tree, detail] = Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+
+        let rows = self.rows(state);
+        let items: Vec<ListItem> = rows.iter().map(|row| {
+            let marker = match (row.task.children.is_empty(), row.expanded) {
+                (true, _) => " ",
+                (false, true) => "▾",
+                (false, false) => "▸",
+            };
+            let label = row.task.progress_label();
+            let title = if label.is_empty() { row.task.title.clone() } else { format!("{} {}", label, row.task.title) };
+            ListItem::new(format!("{}{} {}", "  ".repeat(row.depth), marker, title))
+        }).collect();
+        let mut list_state = ListState::default()
+            .with_selected(self.selected.and_then(|selected| rows.iter().position(|row| row.task.id == selected)));
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Tasks"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, tree, &mut list_state);
+
+        let paragraph = Paragraph::new(self.detail(state))
+            .block(Block::default().borders(Borders::ALL).title("Details"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, detail);
+        frame.render_widget(Paragraph::new(self.status(state)), status);
+    }
+}
+
+fn key(code: KeyCode) -> Option<Key> {
+    Some(match code {
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    })
+}
+
+/// Show the view until it's closed with `q`.
+pub fn run(state: &mut State) -> Result<()> {
+    let mut terminal = ratatui::try_init().context(IO)?;
+    let mut view = TreeView::new(state);
+    let result = (|| -> std::io::Result<()> {
+        while !view.quit {
+            terminal.draw(|frame| view.draw(frame, state))?;
+            if let Event::Key(event) = event::read()? {
+                if let (KeyEventKind::Press, Some(key)) = (event.kind, key(event.code)) {
+                    view.handle_key(state, key);
+                    state.update_dirty();
+                }
+            }
+        }
+        Ok(())
+    })();
+    ratatui::try_restore().context(IO)?;
+    result.context(IO)?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! Document specific progress states and the transitions between them.
+
+use serde::{Serialize, Deserialize};
+use crate::tasks::Progress;
+
+/// A named state of a workflow.
+///
+/// Each state maps to one of the built-in progress values which is used
+/// for summaries and reports.  A state counts as done if it maps to
+/// `Progress::Done`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowState {
+    pub name: String,
+    pub progress: Progress,
+}
+
+/// Custom state machine of a document.
+///
+/// If no transitions are defined, every transition is allowed.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Workflow {
+    pub states: Vec<WorkflowState>,
+    #[serde(default)]
+    pub transitions: Vec<(String, String)>,
+}
+
+impl Workflow {
+    /// Find the state with the given name, ignoring the case.
+    pub fn state(&self, name: &str) -> Option<&WorkflowState> {
+        self.states.iter().find(|state| state.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Find the first state which maps to the given progress.
+    pub fn state_for_progress(&self, progress: Progress) -> Option<&WorkflowState> {
+        self.states.iter().find(|state| state.progress == progress)
+    }
+
+    /// Check if a task may change from one state to another.
+    ///
+    /// Tasks which are not in any state yet may enter every state.
+    pub fn allows(&self, from: Option<&str>, to: &str) -> bool {
+        match from {
+            Some(from) if !self.transitions.is_empty() => self.allows_explicitly(from, to),
+            _ => true,
+        }
+    }
+
+    /// Add a state or replace the progress of an existing one.
+    pub fn add_state(&mut self, name: impl ToString, progress: Progress) {
+        let name = name.to_string();
+        if let Some(state) = self.states.iter_mut().find(|state| state.name.eq_ignore_ascii_case(&name)) {
+            state.progress = progress;
+        } else {
+            self.states.push(WorkflowState { name, progress });
+        }
+    }
+
+    /// Remove a state and all transitions from or to it.
+    pub fn remove_state(&mut self, name: &str) {
+        self.states.retain(|state| !state.name.eq_ignore_ascii_case(name));
+        self.transitions.retain(|(from, to)| !from.eq_ignore_ascii_case(name) && !to.eq_ignore_ascii_case(name));
+    }
+
+    /// Allow the transition between two states.
+    pub fn allow(&mut self, from: impl ToString, to: impl ToString) {
+        let (from, to) = (from.to_string(), to.to_string());
+        if !self.allows_explicitly(&from, &to) {
+            self.transitions.push((from, to));
+        }
+    }
+
+    /// Remove the transition between two states.
+    pub fn disallow(&mut self, from: &str, to: &str) {
+        self.transitions.retain(|(t_from, t_to)| !(t_from.eq_ignore_ascii_case(from) && t_to.eq_ignore_ascii_case(to)));
+    }
+
+    fn allows_explicitly(&self, from: &str, to: &str) -> bool {
+        self.transitions.iter()
+            .any(|(t_from, t_to)| t_from.eq_ignore_ascii_case(from) && t_to.eq_ignore_ascii_case(to))
+    }
+}
@@ -0,0 +1,154 @@
+//! Expected working hours, breaks and the overtime balance.
+
+use crate::doc::Doc;
+use crate::duration::parse_duration;
+use crate::error::*;
+use serde::{Serialize, Deserialize};
+use chrono::prelude::*;
+
+/// Expected working time per weekday in seconds, starting with Monday.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WorkHours {
+    pub targets: [i64; 7],
+}
+
+impl WorkHours {
+    pub fn target(&self, weekday: Weekday) -> chrono::Duration {
+        chrono::Duration::seconds(self.targets[weekday.num_days_from_monday() as usize])
+    }
+
+    pub fn set_target(&mut self, weekday: Weekday, target: chrono::Duration) {
+        self.targets[weekday.num_days_from_monday() as usize] = target.num_seconds();
+    }
+}
+
+/// Pause between clocks, which doesn't count as worked time.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Break {
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl Break {
+    pub fn duration(&self) -> chrono::Duration {
+        self.end.unwrap_or_else(Utc::now) - self.start
+    }
+}
+
+/// Break which is required if more than `worked` time is worked on a day.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BreakRule {
+    pub worked: chrono::Duration,
+    pub required: chrono::Duration,
+}
+
+/// Parse rules like `6h=30m, 9h=45m`.
+pub fn parse_break_rules(text: &str) -> Result<Vec<BreakRule>> {
+    text.split(',')
+        .map(|rule| rule.trim())
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let (worked, required) = rule.split_once('=')
+                .ok_or_else(|| Error::InvalidDuration { text: rule.to_string() })?;
+            Ok(BreakRule { worked: parse_duration(worked)?, required: parse_duration(required)? })
+        })
+        .collect()
+}
+
+/// Break time the rules require for the worked time.
+pub fn required_break(rules: &[BreakRule], worked: chrono::Duration) -> chrono::Duration {
+    rules.iter()
+        .filter(|rule| worked > rule.worked)
+        .map(|rule| rule.required)
+        .max()
+        .unwrap_or_else(chrono::Duration::zero)
+}
+
+/// Worked and expected time of a day.
+#[derive(Clone, Debug)]
+pub struct WorkDay {
+    pub date: NaiveDate,
+    pub worked: chrono::Duration,
+    pub target: chrono::Duration,
+
+    /// Time of the breaks which started this day.
+    pub breaks: chrono::Duration,
+
+    /// Sum of the deltas from the first day of the range up to this day.
+    pub balance: chrono::Duration,
+}
+
+impl WorkDay {
+    pub fn delta(&self) -> chrono::Duration {
+        self.worked - self.target
+    }
+
+    /// Worked time including the breaks.
+    pub fn gross(&self) -> chrono::Duration {
+        self.worked + self.breaks
+    }
+}
+
+impl Doc {
+    /// Expected working time of the day, zero if no work hours are set.
+    pub fn target_time(&self, date: NaiveDate) -> chrono::Duration {
+        self.work_hours.as_ref()
+            .map(|work_hours| work_hours.target(date.weekday()))
+            .unwrap_or_else(chrono::Duration::zero)
+    }
+
+    /// Time of all clocks of the day.
+    pub fn worked_time(&self, date: NaiveDate) -> chrono::Duration {
+        self.day_clock(date, None).iter()
+            .fold(chrono::Duration::zero(), |acc, clock| acc + clock.duration())
+    }
+
+    /// Worked and expected time of each day between both dates (inclusive).
+    pub fn work_days(&self, start: NaiveDate, end: NaiveDate) -> Vec<WorkDay> {
+        let mut days = Vec::new();
+        let mut balance = chrono::Duration::zero();
+        let mut date = start;
+        while date <= end {
+            let worked = self.worked_time(date);
+            let target = self.target_time(date);
+            let breaks = self.break_time(date);
+            balance = balance + worked - target;
+            days.push(WorkDay { date, worked, target, breaks, balance });
+            date = date.succ();
+        }
+        days
+    }
+
+    /// Time of the breaks which started on the day.
+    pub fn break_time(&self, date: NaiveDate) -> chrono::Duration {
+        self.breaks.iter()
+            .filter(|pause| self.timezone.date(pause.start) == date)
+            .fold(chrono::Duration::zero(), |acc, pause| acc + pause.duration())
+    }
+
+    /// The break which isn't over yet.
+    pub fn running_break(&self) -> Option<&Break> {
+        self.breaks.last().filter(|pause| pause.end.is_none())
+    }
+
+    /// Clock out of the default channel and start a break, if none is
+    /// running yet.
+    pub fn start_break(&mut self, now: DateTime<Utc>) -> Result<()> {
+        self.clock_out()?;
+        if self.running_break().is_none() {
+            self.breaks.push(Break { start: now, end: None });
+        }
+        Ok(())
+    }
+
+    /// End the running break, returns false if there is none.
+    pub fn end_break(&mut self, now: DateTime<Utc>) -> bool {
+        match self.breaks.last_mut().filter(|pause| pause.end.is_none()) {
+            Some(pause) => {
+                pause.end = Some(now);
+                true
+            },
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use sors::*;
+use sors::caldav::*;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+/// Calendar objects by href with their ETags.
+#[derive(Default)]
+struct FakeServer {
+    objects: RefCell<BTreeMap<String, (String, String)>>,
+    version: Cell<usize>,
+}
+
+impl FakeServer {
+    fn store(&self, href: &str, body: &str) -> String {
+        self.version.set(self.version.get() + 1);
+        let etag = format!("\"{}\"", self.version.get());
+        self.objects.borrow_mut().insert(href.to_string(), (etag.clone(), body.to_string()));
+        etag
+    }
+
+    fn body(&self, href: &str) -> String {
+        self.objects.borrow()[href].1.clone()
+    }
+}
+
+impl CaldavTransport for FakeServer {
+    fn list(&self) -> Result<Vec<(String, String)>> {
+        Ok(self.objects.borrow().iter().map(|(href, (etag, _))| (href.clone(), etag.clone())).collect())
+    }
+
+    fn get(&self, href: &str) -> Result<String> {
+        Ok(self.body(href))
+    }
+
+    fn put(&self, href: &str, body: &str, etag: Option<&str>) -> Result<Option<String>> {
+        let current = self.objects.borrow().get(href).map(|(etag, _)| etag.clone());
+        if current.as_deref() != etag {
+            return Err(Error::CaldavError { msg: format!("Precondition failed: {}", href) });
+        }
+        Ok(Some(self.store(href, body)))
+    }
+}
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str, progress: Option<Progress>) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title.to_string());
+    if let Some(progress) = progress {
+        task.set_progress(progress);
+    }
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+fn doc() -> (Doc, Uuid, Uuid) {
+    let mut doc = Doc::new();
+    doc.timezone = DisplayZone::Utc;
+    let root = doc.root;
+    let milk = add(&mut doc, &root, "Buy milk", Some(Progress::Todo));
+    add(&mut doc, &root, "Notes", None);
+    add(&mut doc, &root, "Old", Some(Progress::Done));
+    let report = add(&mut doc, &root, "Report", Some(Progress::Work));
+    doc.caldav = Some(CaldavSync { url: "https://dav.example.com/cal/tasks/".to_string(), user: None, items: BTreeMap::new() });
+    (doc, milk, report)
+}
+
+#[test]
+fn push_open_tasks_and_pull_changes() {
+    let (mut doc, milk, report) = doc();
+    let root = doc.root;
+    let server = FakeServer::default();
+    assert_eq!(doc.caldav_sync(&root, &server).unwrap(), SyncReport { pushed: 2, pulled: 0, created: 0 });
+    let href = format!("/cal/tasks/{}.ics", milk);
+    let body = server.body(&href);
+    assert!(body.contains(&format!("UID:{}\r\n", milk)));
+    assert!(body.contains("SUMMARY:Buy milk\r\nSTATUS:NEEDS-ACTION\r\n"));
+    assert!(server.body(&format!("/cal/tasks/{}.ics", report)).contains("STATUS:IN-PROCESS"));
+    assert_eq!(doc.caldav_sync(&root, &server).unwrap(), SyncReport::default());
+
+    // Completed on the phone.
+    server.store(&href, &body.replace("STATUS:NEEDS-ACTION", "STATUS:COMPLETED").replace("Buy milk", "Buy oat milk"));
+    assert_eq!(doc.caldav_sync(&root, &server).unwrap().pulled, 1);
+    let task = doc.get(&milk).unwrap();
+    assert_eq!((task.title.as_str(), task.progress), ("Buy oat milk", Some(Progress::Done)));
+
+    // Changed locally.
+    doc.modify_task(&report, |task| { task.set_title("Final report"); Ok(()) }).unwrap();
+    assert_eq!(doc.caldav_sync(&root, &server).unwrap().pushed, 1);
+    assert!(server.body(&format!("/cal/tasks/{}.ics", report)).contains("SUMMARY:Final report"));
+}
+
+#[test]
+fn remote_todos_go_to_the_inbox() {
+    let (mut doc, milk, _) = doc();
+    let root = doc.root;
+    let server = FakeServer::default();
+    doc.caldav_sync(&root, &server).unwrap();
+    server.store("/cal/tasks/phone.ics", "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:phone-1\r\nSUMMARY:Call \r\n the dentist\\, soon\r\nDUE;VALUE=DATE:20300501\r\nEND:VTODO\r\nEND:VCALENDAR\r\n");
+    server.objects.borrow_mut().remove(&format!("/cal/tasks/{}.ics", milk));
+
+    assert_eq!(doc.caldav_sync(&root, &server).unwrap().created, 1);
+    let inbox = doc.bookmarks["inbox"];
+    let task = doc.get(&doc.get(&inbox).unwrap().children[0]).unwrap();
+    assert_eq!(task.title, "Call the dentist, soon");
+    assert_eq!(task.progress, Some(Progress::Todo));
+    assert_eq!(task.due.map(|due| due.with_timezone(&Utc).to_rfc3339()).as_deref(), Some("2030-05-01T00:00:00+00:00"));
+    assert!(doc.caldav.as_ref().unwrap().items[&milk].removed);
+
+    doc.modify_task(&milk, |task| { task.set_title("Milk"); Ok(()) }).unwrap();
+    assert_eq!(doc.caldav_sync(&root, &server).unwrap(), SyncReport::default());
+}
+
+#[test]
+fn propfind_responses() {
+    let xml = r#"<?xml version="1.0"?>
+        <D:multistatus xmlns:D="DAV:">
+          <D:response><D:href>/cal/tasks/</D:href><D:propstat><D:prop><D:getetag>"c"</D:getetag></D:prop></D:propstat></D:response>
+          <D:response><D:href>/cal/tasks/a.ics</D:href><D:propstat><D:prop><D:getetag>&quot;1&quot;</D:getetag></D:prop></D:propstat></D:response>
+        </D:multistatus>"#;
+    assert_eq!(parse_multistatus(xml), vec![("/cal/tasks/a.ics".to_string(), "\"1\"".to_string())]);
+    assert_eq!(resolve("https://dav.example.com/cal/tasks/", "/cal/tasks/a.ics"), "https://dav.example.com/cal/tasks/a.ics");
+    assert_eq!(resolve("https://dav.example.com/cal/tasks/", "a.ics"), "https://dav.example.com/cal/tasks/a.ics");
+}
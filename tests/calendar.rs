@@ -0,0 +1,17 @@
+use sors::calendar::*;
+use chrono::{NaiveDate, Weekday};
+
+#[test]
+fn weeks_start_on_the_configured_day() {
+    let wednesday = NaiveDate::from_ymd(2024, 6, 5);
+    assert_eq!(start_of_week(wednesday, Weekday::Mon), NaiveDate::from_ymd(2024, 6, 3));
+    assert_eq!(start_of_week(wednesday, Weekday::Sun), NaiveDate::from_ymd(2024, 6, 2));
+    assert_eq!(start_of_week(wednesday, Weekday::Wed), wednesday);
+    assert_eq!(week_of(wednesday, Weekday::Sat), (NaiveDate::from_ymd(2024, 6, 1), NaiveDate::from_ymd(2024, 6, 7)));
+
+    assert_eq!(parse_week("2024-W23", Weekday::Mon), Some((NaiveDate::from_ymd(2024, 6, 3), NaiveDate::from_ymd(2024, 6, 9))));
+    assert_eq!(parse_week("2024-W23", Weekday::Sun), Some((NaiveDate::from_ymd(2024, 6, 2), NaiveDate::from_ymd(2024, 6, 8))));
+    assert_eq!(parse_week("2024-W99", Weekday::Mon), None);
+    assert_eq!(parse_weekday("Tuesday"), Some(Weekday::Tue));
+    assert_eq!(parse_weekday("tue"), Some(Weekday::Tue));
+}
@@ -0,0 +1,1781 @@
+#![cfg(feature = "cli")]
+
+use sors::cli::*;
+use sors::commands::register_default_commands;
+use sors::*;
+use chrono::TimeZone;
+
+fn new_cli() -> Cli<State, ScriptedCallbacks> {
+    let path = std::env::temp_dir().join(format!("sors-test-{}.json", Uuid::new_v4()));
+    let mut cli = Cli::new(State::new(Doc::new(), path.to_str().unwrap()), ScriptedCallbacks::default());
+    register_default_commands(&mut cli);
+    cli
+}
+
+/// Run the command and return its output.
+fn run(cli: &mut Cli<State, ScriptedCallbacks>, line: &str) -> String {
+    cli.callbacks.output.clear();
+    cli.run_command(line).unwrap_or_else(|err| panic!("{} failed: {}", line, err));
+    cli.state.update_dirty();
+    cli.callbacks.output.clone()
+}
+
+fn child(cli: &Cli<State, ScriptedCallbacks>, parent: &Uuid, i: usize) -> std::rc::Rc<Task> {
+    let task_id = cli.state.doc.task_child(parent, i).expect("child exists");
+    cli.state.doc.get(&task_id).unwrap()
+}
+
+#[test]
+fn add_and_ls() {
+    let mut cli = new_cli();
+    run(&mut cli, "add first task");
+    run(&mut cli, "add second");
+    let output = run(&mut cli, "ls");
+    assert!(output.contains("1:  first task"));
+    assert!(output.contains("2:  second"));
+}
+
+#[test]
+fn unknown_command() {
+    let mut cli = new_cli();
+    assert!(cli.run_command("nonsense").is_err());
+}
+
+#[test]
+fn progress_commands() {
+    let mut cli = new_cli();
+    run(&mut cli, "add a");
+    run(&mut cli, "add b");
+    run(&mut cli, "done 1");
+    run(&mut cli, "work 2");
+    let root = cli.state.doc.root;
+    assert_eq!(child(&cli, &root, 0).progress, Some(Progress::Done));
+    assert_eq!(child(&cli, &root, 1).progress, Some(Progress::Work));
+    assert!(run(&mut cli, "ls").contains("1: DONE a"));
+}
+
+#[test]
+fn selectors() {
+    let mut cli = new_cli();
+    for title in &["write docs", "write tests", "review", "release"] {
+        run(&mut cli, &format!("add {}", title));
+    }
+    let root = cli.state.doc.root;
+    let ids: Vec<Uuid> = (0..4).map(|i| child(&cli, &root, i).id).collect();
+    assert_eq!(cli.state.uuids_for_selector("1-2,4"), Some(vec![ids[0], ids[1], ids[3]]));
+    assert_eq!(cli.state.uuids_for_selector("3-1"), Some(vec![ids[2], ids[1], ids[0]]));
+    assert_eq!(cli.state.uuids_for_selector("write*"), Some(vec![ids[0], ids[1]]));
+    assert_eq!(cli.state.uuids_for_selector("2,1-2"), Some(vec![ids[1], ids[0]]));
+    assert_eq!(cli.state.uuids_for_selector("1-5"), None);
+    assert_eq!(cli.state.uuids_for_selector("0"), None);
+    assert_eq!(cli.state.uuids_for_selector("1-9999999999999"), None);
+    assert_eq!(cli.state.uuids_for_selector("9999999999999-1"), None);
+
+    run(&mut cli, "done 3-1");
+    assert!((0..3).all(|i| child(&cli, &root, i).progress == Some(Progress::Done)));
+    assert_eq!(child(&cli, &root, 3).progress, None);
+}
+
+#[test]
+fn cd_and_back() {
+    let mut cli = new_cli();
+    run(&mut cli, "add project");
+    run(&mut cli, "cd 1");
+    let project = child(&cli, &cli.state.doc.root.clone(), 0);
+    assert_eq!(cli.state.wt, project.id);
+    run(&mut cli, "add subtask");
+    run(&mut cli, "cd ..");
+    assert_eq!(cli.state.wt, cli.state.doc.root);
+    run(&mut cli, "cd -");
+    assert_eq!(cli.state.wt, project.id);
+    assert!(cli.run_command("cd 5").is_err());
+}
+
+#[test]
+fn tag_and_untag() {
+    let mut cli = new_cli();
+    run(&mut cli, "tag home urgent");
+    assert!(run(&mut cli, "ls").contains("Tags: home, urgent"));
+    run(&mut cli, "untag home");
+    let root = cli.state.doc.get(&cli.state.doc.root).unwrap();
+    assert_eq!(root.tags, vec!["urgent".to_string()]);
+}
+
+#[test]
+fn rm_asks_and_restores() {
+    let mut cli = new_cli();
+    run(&mut cli, "add doomed");
+    cli.callbacks.input.push_back("n".to_string());
+    run(&mut cli, "rm 1");
+    assert_eq!(cli.state.doc.get(&cli.state.doc.root).unwrap().children.len(), 1);
+
+    assert!(run(&mut cli, "rm 1 --dry-run").contains("Would move to trash:"));
+    run(&mut cli, "rm 1 --yes");
+    assert!(cli.state.doc.get(&cli.state.doc.root).unwrap().children.is_empty());
+
+    assert!(run(&mut cli, "restore 1").contains("Restored to"));
+    assert_eq!(cli.state.doc.get(&cli.state.doc.root).unwrap().children.len(), 1);
+
+    run(&mut cli, "add last");
+    run(&mut cli, "rm 1 --yes");
+    run(&mut cli, "add new");
+    run(&mut cli, "restore 1");
+    assert!(run(&mut cli, "ls").contains("1:  doomed"));
+}
+
+#[test]
+fn archive_asks_first() {
+    let mut cli = new_cli();
+    for title in &["shipped", "open", "also shipped"] {
+        run(&mut cli, &format!("add {}", title));
+    }
+    run(&mut cli, "done 1,3");
+    assert_eq!(run(&mut cli, "archive *done* --dry-run"), "Would archive:  -> shipped\nWould archive:  -> also shipped\n");
+    assert!(cli.state.uuid_for_path("/Archive").is_none());
+    cli.callbacks.input.push_back("n".to_string());
+    run(&mut cli, "archive *done*");
+    assert!(cli.state.uuid_for_path("/Archive").is_none());
+
+    run(&mut cli, "archive *done* --yes");
+    let root = cli.state.doc.root;
+    assert_eq!(child(&cli, &root, 0).title, "open");
+    let archive = cli.state.uuid_for_path("/Archive").unwrap();
+    assert_eq!(child(&cli, &archive, 1).title, "also shipped");
+    assert_eq!(run(&mut cli, "archive 2 --dry-run"), "");
+    assert!(cli.run_command("archive").is_err());
+}
+
+#[test]
+fn trash_list_and_empty() {
+    let mut cli = new_cli();
+    run(&mut cli, "add project");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "add old");
+    run(&mut cli, "add recent");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "clo");
+    run(&mut cli, "rm 1-2 --yes");
+    assert_eq!(cli.state.doc.trash_entries.len(), 2);
+    cli.state.doc.trash_entries[0].deleted = Local::now() - chrono::Duration::days(40);
+    let output = run(&mut cli, "trash");
+    assert!(output.starts_with("1: ") && output.contains(" old (from  -> project)\n2: "));
+    assert!(output.contains(" recent (from  -> project)"));
+    assert!(cli.state.uuid_for_path("/project/old").is_none());
+
+    let output = run(&mut cli, "trash empty --older-than 30d --dry-run");
+    assert_eq!(output, "Would delete: old\n");
+    cli.callbacks.input.push_back("n".to_string());
+    run(&mut cli, "trash empty --older-than 30d");
+    assert_eq!(cli.state.doc.trash_entries.len(), 2);
+    assert!(run(&mut cli, "trash empty --older-than 30d --yes").contains("Deleted 1 tasks"));
+    assert_eq!(cli.state.doc.trash_entries.len(), 1);
+    assert_eq!(cli.state.doc.clocks.len(), 1);
+    assert!(cli.state.doc.clocks.values().all(|clock| clock.task_id.is_none()));
+
+    // A task whose parent is in the trash too is restored to the root.
+    run(&mut cli, "cd /");
+    run(&mut cli, "rm 1 --yes");
+    assert!(cli.run_command("restore 3").is_err());
+    assert_eq!(run(&mut cli, "restore 1"), "Restored to  -> recent\n");
+    assert_eq!(run(&mut cli, "restore 1"), "Restored to  -> project\n");
+    assert!(cli.state.uuid_for_path("/recent").is_some());
+    assert!(cli.state.uuid_for_path("/project").is_some());
+    assert!(cli.state.doc.trash_entries.is_empty());
+}
+
+#[test]
+fn mv_and_reorder_positions() {
+    let mut cli = new_cli();
+    for title in ["a", "b", "c", "target"].iter() {
+        run(&mut cli, &format!("add {}", title));
+    }
+    run(&mut cli, "reorder 3 1");
+    assert!(run(&mut cli, "ls").contains("1:  c"));
+    assert!(cli.run_command("reorder 0 1").is_err());
+    assert!(cli.run_command("reorder 1 5").is_err());
+    run(&mut cli, "cd target");
+    run(&mut cli, "add x");
+    run(&mut cli, "cd ..");
+    run(&mut cli, "mv 1 target 1");
+    assert!(cli.run_command("mv 1 target 9").is_err());
+    let target = cli.state.uuid_for_path("target").unwrap();
+    assert_eq!(child(&cli, &target, 0).title, "c");
+    assert_eq!(child(&cli, &target, 1).title, "x");
+}
+
+#[test]
+fn exit_confirms_unsaved_changes() {
+    let mut cli = new_cli();
+    run(&mut cli, "exit");
+    assert!(cli.callbacks.exit);
+
+    let mut cli = new_cli();
+    run(&mut cli, "add unsaved");
+    assert!(cli.state.dirty);
+    cli.callbacks.input.push_back("no".to_string());
+    run(&mut cli, "exit");
+    assert!(!cli.callbacks.exit);
+    run(&mut cli, "exit --yes");
+    assert!(cli.callbacks.exit);
+}
+
+#[test]
+fn dirty_tracking_and_discard() {
+    let mut cli = new_cli();
+    assert!(!cli.state.dirty);
+    run(&mut cli, "add kept");
+    assert!(cli.state.dirty);
+    run(&mut cli, "save");
+    assert!(!cli.state.dirty);
+    run(&mut cli, "ls");
+    run(&mut cli, "count");
+    assert!(cli.run_command("done 5").is_err());
+    cli.state.update_dirty();
+    assert!(!cli.state.dirty);
+    run(&mut cli, "bookmark set kept /1");
+    assert!(cli.state.dirty);
+    run(&mut cli, "save");
+
+    run(&mut cli, "add dropped");
+    run(&mut cli, "done 1");
+    cli.callbacks.input.push_back("n".to_string());
+    run(&mut cli, "discard");
+    assert!(cli.state.dirty);
+    run(&mut cli, "discard --yes");
+    assert!(!cli.state.dirty);
+    let root = cli.state.doc.get(&cli.state.doc.root).unwrap();
+    assert_eq!(root.children.len(), 1);
+    assert_eq!(child(&cli, &root.id, 0).progress, None);
+    assert!(cli.state.doc.bookmarks.contains_key("kept"));
+    std::fs::remove_file(&cli.state.path).unwrap();
+}
+
+#[test]
+fn save_and_load() {
+    let mut cli = new_cli();
+    run(&mut cli, "add persisted");
+    run(&mut cli, "save");
+    assert!(!cli.state.dirty);
+    let doc = Doc::load(&cli.state.path).unwrap();
+    let root = doc.get(&doc.root).unwrap();
+    assert_eq!(doc.get(&root.children[0]).unwrap().title, "persisted");
+    std::fs::remove_file(&cli.state.path).unwrap();
+}
+
+#[test]
+fn clock_comment_from_callbacks() {
+    let mut cli = new_cli();
+    run(&mut cli, "cli");
+    cli.callbacks.input.push_back("meeting".to_string());
+    run(&mut cli, "clc");
+    let clock = cli.state.doc.running_clock().expect("clock is running");
+    assert_eq!(clock.comment.as_deref(), Some("meeting"));
+    assert_eq!(clock.task_id, Some(cli.state.doc.root));
+    run(&mut cli, "clo");
+    assert!(cli.state.doc.running_clock().is_none());
+}
+
+#[test]
+fn ls_json() {
+    let mut cli = new_cli();
+    run(&mut cli, "add child");
+    run(&mut cli, "set output json");
+    let value: serde_json::Value = serde_json::from_str(&run(&mut cli, "ls")).unwrap();
+    assert_eq!(value["children"][0]["title"], "child");
+}
+
+#[test]
+fn help() {
+    let mut cli = new_cli();
+    let output = run(&mut cli, "help");
+    assert!(output.contains("Tasks:\n"));
+    assert!(output.contains("  add "));
+    let output = run(&mut cli, "help rm");
+    assert!(output.starts_with("Usage: rm <selector>"));
+    assert!(output.contains("Examples:"));
+    assert!(cli.run_command("help nonsense").is_err());
+    assert!(cli.help.keys().all(|command| cli.commands.contains_key(command)));
+    assert!(cli.commands.keys().all(|command| cli.help.contains_key(command)));
+}
+
+#[test]
+fn hooks_run_on_events() {
+    let mut cli = new_cli();
+    let since = Local::now();
+    run(&mut cli, "add project");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "cli");
+    run(&mut cli, "clo");
+    run(&mut cli, "done");
+    let events: Vec<_> = cli.state.doc.hook_calls(since).iter().map(|call| call.event).collect();
+    assert_eq!(events, vec![HookEvent::ClockIn, HookEvent::ClockOut, HookEvent::TaskDone]);
+
+    let log = std::env::temp_dir().join(format!("sors-hook-{}.log", Uuid::new_v4()));
+    cli.state.hooks.add(HookEvent::TaskDone, format!("echo \"$SORS_EVENT $SORS_TASK_TITLE\" >> {}", log.display()));
+    cli.state.hooks.add(HookEvent::ClockIn, "exit 1");
+    sors::helper::run_hooks::<State>(&cli.state, since, &mut cli.callbacks);
+    assert_eq!(std::fs::read_to_string(&log).unwrap(), "task-done project\n");
+    assert!(cli.callbacks.output.contains("Hook `exit 1` failed"));
+    std::fs::remove_file(&log).unwrap();
+}
+
+#[test]
+fn read_only_blocks_changes() {
+    let mut cli = new_cli();
+    run(&mut cli, "add existing");
+    run(&mut cli, "save");
+    cli.state.set_read_only(true);
+    assert!(cli.run_command("add new").is_err());
+    assert!(cli.run_command("done 1").is_err());
+    assert!(cli.run_command("save").is_err());
+    cli.state.update_dirty();
+    assert!(!cli.state.dirty);
+    let root = cli.state.doc.get(&cli.state.doc.root).unwrap();
+    assert_eq!(root.children.len(), 1);
+    assert_eq!(child(&cli, &cli.state.doc.root.clone(), 0).progress, None);
+    assert!(run(&mut cli, "ls").contains("1:  existing"));
+    run(&mut cli, "cd 1");
+    std::fs::remove_file(&cli.state.path).unwrap();
+}
+
+#[test]
+fn failed_commands_are_rolled_back() {
+    let path = std::env::temp_dir().join(format!("sors-test-{}.json", Uuid::new_v4()));
+    let mut cli = Cli::new(State::new(Doc::new(), path.to_str().unwrap()), ScriptedCallbacks::default());
+    cli.register_command("half", CommandHelp::new("Test", "half", "Change half and fail"), Box::new(|state: &mut State, _, _| {
+        let mut task = Rc::new(Task::new());
+        task.set_title("partial".to_string());
+        let task_id = task.id;
+        state.doc.add_subtask(task, &state.doc.root.clone())?;
+        state.change_wt(task_id);
+        Err(Box::new(Error::UnsufficientInput {}))
+    }));
+    cli.register_command("merge-older", CommandHelp::new("Test", "merge-older", "Merge older activity and fail"), Box::new(|state: &mut State, _, _| {
+        let mut other = state.doc.clone();
+        let root = other.root;
+        other.modify_task(&root, |task| { task.add_child(Uuid::new_v4()); Ok(()) })?;
+        other.activity.insert(0, Activity { time: Local.ymd(2001, 1, 1).and_hms(0, 0, 0), task_id: None, event: ActivityEvent::TaskCreated });
+        state.doc.merge(&other);
+        Err(Box::new(Error::UnsufficientInput {}))
+    }));
+    cli.register_command("replace", CommandHelp::new("Test", "replace", "Replace the document and fail"), Box::new(|state: &mut State, _, _| {
+        state.replace_doc(Doc::new());
+        Err(Box::new(Error::UnsufficientInput {}))
+    }));
+    register_default_commands(&mut cli);
+    run(&mut cli, "add kept");
+    let activity = cli.state.doc.activity.clone();
+    assert!(cli.run_command("half").is_err());
+    assert_eq!(cli.state.wt, cli.state.doc.root);
+    let root = cli.state.doc.get(&cli.state.doc.root).unwrap();
+    assert_eq!(root.children.len(), 1);
+    assert!(run(&mut cli, "ls").contains("1:  kept"));
+    assert_eq!(cli.state.doc.activity, activity);
+
+    assert!(cli.run_command("merge-older").is_err());
+    assert_eq!(cli.state.doc.activity, activity);
+    assert_eq!(cli.state.doc.get(&cli.state.doc.root).unwrap().children.len(), 1);
+
+    // A replaced document stays.
+    assert!(cli.run_command("replace").is_err());
+    assert!(cli.state.doc.activity.is_empty());
+}
+
+#[test]
+fn mv_refuses_cycles() {
+    let mut cli = new_cli();
+    run(&mut cli, "add parent");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "add child");
+    run(&mut cli, "cd ..");
+    assert!(cli.run_command("mv 1 parent/child").is_err());
+    assert!(run(&mut cli, "ls").contains("1:  parent"));
+    run(&mut cli, "add other");
+    run(&mut cli, "mv 2 parent/child");
+    assert!(!run(&mut cli, "ls").contains("other"));
+    assert!(run(&mut cli, "outline").contains("other"));
+}
+
+#[test]
+fn sort_children() {
+    let mut cli = new_cli();
+    for (title, priority, due) in [("Bravo", "2", "2024-03-01"), ("alpha", "high", ""), ("Charlie", "1", "2024-02-01"), ("delta", "", "")].iter() {
+        run(&mut cli, &format!("add {}", title));
+        run(&mut cli, &format!("cd {}", title));
+        if !priority.is_empty() {
+            run(&mut cli, &format!("set priority {}", priority));
+        }
+        if !due.is_empty() {
+            run(&mut cli, &format!("remind {}", due));
+        }
+        run(&mut cli, "cd ..");
+    }
+    run(&mut cli, "done delta");
+    let order = |cli: &mut Cli<State, ScriptedCallbacks>, key: &str| {
+        run(cli, &format!("sort {}", key));
+        let root = cli.state.doc.root;
+        (0..4).map(|i| child(cli, &root, i).title.clone()).collect::<Vec<_>>().join(" ")
+    };
+    assert_eq!(order(&mut cli, "title"), "alpha Bravo Charlie delta");
+    assert_eq!(order(&mut cli, "due"), "Charlie Bravo alpha delta");
+    assert_eq!(order(&mut cli, "prio"), "Charlie Bravo alpha delta");
+    assert_eq!(order(&mut cli, "title"), "alpha Bravo Charlie delta");
+    assert_eq!(order(&mut cli, "progress"), "alpha Bravo Charlie delta");
+    run(&mut cli, "todo alpha");
+    assert_eq!(order(&mut cli, "progress"), "alpha Bravo Charlie delta");
+    run(&mut cli, "done alpha");
+    assert_eq!(order(&mut cli, "progress"), "Bravo Charlie alpha delta");
+    assert!(cli.run_command("sort size").is_err());
+}
+
+#[test]
+fn count_subtasks() {
+    let mut cli = new_cli();
+    run(&mut cli, "add project");
+    run(&mut cli, "cd project");
+    run(&mut cli, "add first");
+    run(&mut cli, "add second");
+    run(&mut cli, "add note");
+    run(&mut cli, "done first");
+    run(&mut cli, "todo second");
+    run(&mut cli, "cd second");
+    run(&mut cli, "add nested");
+    run(&mut cli, "cd ../..");
+    let output = run(&mut cli, "count");
+    assert!(output.contains("Subtasks: 5 in 3 levels"));
+    assert!(output.contains("  DONE: 1\n  TODO: 1\n"));
+    assert!(output.contains("No progress: 3"));
+    assert!(output.contains("Tracked: 0s"));
+
+    let stats = cli.state.doc.subtree_stats(&cli.state.uuid_for_path("project/second").unwrap()).unwrap();
+    assert_eq!((stats.descendants, stats.depth, stats.without_progress), (1, 1, 1));
+}
+
+#[test]
+fn progress_rolls_up() {
+    let mut cli = new_cli();
+    run(&mut cli, "rollup on");
+    run(&mut cli, "add project");
+    run(&mut cli, "todo 1");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "add a");
+    run(&mut cli, "add b");
+    run(&mut cli, "todo 1-2");
+    let project = cli.state.wt;
+    let progress = |cli: &Cli<State, ScriptedCallbacks>| cli.state.doc.get(&project).unwrap().progress;
+    run(&mut cli, "work 1");
+    assert_eq!(progress(&cli), Some(Progress::Work));
+    run(&mut cli, "done 1-2");
+    assert_eq!(progress(&cli), Some(Progress::Done));
+    run(&mut cli, "todo 2");
+    assert_eq!(progress(&cli), Some(Progress::Todo));
+    run(&mut cli, "rollup skip");
+    run(&mut cli, "done 2");
+    assert_eq!(progress(&cli), Some(Progress::Todo));
+}
+
+#[test]
+fn rollup_keeps_workflow_states() {
+    let mut cli = new_cli();
+    run(&mut cli, "rollup on");
+    run(&mut cli, "states add open todo");
+    run(&mut cli, "states add doing work");
+    run(&mut cli, "states add review work");
+    run(&mut cli, "states add shipped done");
+    run(&mut cli, "add project");
+    run(&mut cli, "state review 1");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "add a");
+    run(&mut cli, "add b");
+    run(&mut cli, "state open 1-2");
+    let project = cli.state.wt;
+    let state = |cli: &Cli<State, ScriptedCallbacks>| cli.state.doc.get(&project).unwrap().state.clone();
+    assert_eq!(state(&cli).as_deref(), Some("review"));
+    run(&mut cli, "state doing 1");
+    assert_eq!(state(&cli).as_deref(), Some("review"));
+    run(&mut cli, "state shipped 1-2");
+    assert_eq!(state(&cli).as_deref(), Some("shipped"));
+    assert_eq!(cli.state.doc.get(&project).unwrap().progress, Some(Progress::Done));
+    run(&mut cli, "state open 2");
+    assert_eq!(state(&cli).as_deref(), Some("open"));
+}
+
+#[test]
+fn waiting_blocked_and_cancelled() {
+    let mut cli = new_cli();
+    run(&mut cli, "rollup on");
+    run(&mut cli, "add project");
+    run(&mut cli, "todo 1");
+    run(&mut cli, "cd 1");
+    for title in &["a", "b", "c"] {
+        run(&mut cli, &format!("add {}", title));
+    }
+    run(&mut cli, "waiting 1");
+    run(&mut cli, "blocked 2");
+    run(&mut cli, "cancel 3");
+    let output = run(&mut cli, "ls");
+    assert!(output.contains("project  [0/2]"));
+    assert!(output.contains("1: WAITING   a"));
+    assert!(output.contains("2: BLOCKED   b"));
+    assert!(output.contains("3: CANCELLED c"));
+
+    // Cancelled tasks don't keep the parent from being done.
+    run(&mut cli, "done 1-2");
+    assert_eq!(cli.state.doc.get(&cli.state.wt).unwrap().progress, Some(Progress::Done));
+    assert_eq!(cli.state.doc.progress_summary(&cli.state.wt).unwrap(), (2, 2));
+}
+
+#[test]
+fn workflow_transitions() {
+    let mut cli = new_cli();
+    assert!(run(&mut cli, "states").contains("No workflow defined"));
+    run(&mut cli, "states add open todo");
+    run(&mut cli, "states add review work");
+    run(&mut cli, "states add shipped done");
+    run(&mut cli, "add task");
+    run(&mut cli, "state open 1");
+    let task = child(&cli, &cli.state.doc.root.clone(), 0).id;
+    assert_eq!(cli.state.doc.get(&task).unwrap().progress, Some(Progress::Todo));
+    assert!(run(&mut cli, "ls").contains("1: OPEN task"));
+
+    run(&mut cli, "states allow open review");
+    run(&mut cli, "states allow review shipped");
+    let output = run(&mut cli, "states");
+    assert!(output.contains("review (WORK)"));
+    assert!(output.contains("open -> review"));
+    assert!(cli.run_command("state shipped 1").is_err());
+    assert!(cli.run_command("state unknown 1").is_err());
+    assert!(cli.run_command("states allow open unknown").is_err());
+    run(&mut cli, "work 1");
+    assert_eq!(cli.state.doc.get(&task).unwrap().state.as_deref(), Some("review"));
+    run(&mut cli, "done 1");
+    assert_eq!(cli.state.doc.get(&task).unwrap().state.as_deref(), Some("shipped"));
+    assert!(cli.run_command("waiting 1").is_err());
+
+    run(&mut cli, "states disallow review shipped");
+    run(&mut cli, "states rm shipped");
+    assert!(cli.run_command("done 1").is_err());
+    run(&mut cli, "states clear");
+    run(&mut cli, "todo 1");
+    assert_eq!(cli.state.doc.get(&task).unwrap().state, None);
+}
+
+#[test]
+fn throughput_stats() {
+    let mut cli = new_cli();
+    for title in &["shipped", "in work", "new", "dropped"] {
+        run(&mut cli, &format!("add {}", title));
+    }
+    run(&mut cli, "done 1");
+    run(&mut cli, "work 2");
+    run(&mut cli, "todo 3");
+    run(&mut cli, "cancel 4");
+    let root = cli.state.doc.root;
+    let now = Local::now();
+    let ago = |days: i64| now - chrono::Duration::days(days);
+    for (i, created, finished) in [(0, ago(10), Some(ago(2))), (1, ago(5), None), (2, now, None), (3, ago(20), None)] {
+        let mut task = child(&cli, &root, i);
+        let task_mut = std::rc::Rc::make_mut(&mut task);
+        task_mut.created = Some(created);
+        task_mut.finished = finished;
+        cli.state.doc.upsert(task);
+    }
+
+    let stats = cli.state.doc.throughput_stats(&root, 7, chrono::Weekday::Mon).unwrap();
+    assert_eq!(stats.completed_per_week.iter().map(|(_, count)| count).sum::<usize>(), 1);
+    assert_eq!(stats.average_cycle_time, Some(chrono::Duration::days(8)));
+    assert_eq!(stats.wip, 1);
+    assert_eq!(stats.burndown.len(), 7);
+    assert_eq!(stats.burndown.first().unwrap().1, 1);
+    assert_eq!(stats.burndown.last().unwrap().1, 2);
+
+    let output = run(&mut cli, "stats 7");
+    assert!(output.contains("Average cycle time: 8d"));
+    assert!(output.contains("Work in progress: 1"));
+    assert!(cli.run_command("stats many").is_err());
+}
+
+#[test]
+fn focus_hides_the_rest() {
+    let mut cli = new_cli();
+    run(&mut cli, "add work");
+    run(&mut cli, "add home");
+    run(&mut cli, "cd home");
+    run(&mut cli, "pin");
+    run(&mut cli, "cd /work");
+    run(&mut cli, "add api");
+    run(&mut cli, "cd api");
+    run(&mut cli, "pin");
+    let work = cli.state.uuid_for_path("/work").unwrap();
+
+    run(&mut cli, "focus /work");
+    assert_eq!(cli.state.focus, Some(work));
+    assert_eq!(cli.state.uuid_for_path("/1"), cli.state.uuid_for_path("/api"));
+    assert!(cli.state.uuid_for_path("/1").is_some());
+    assert!(cli.state.uuid_for_path("/home").is_none());
+    assert!(cli.run_command("cd ../../home").is_err());
+    run(&mut cli, "cd");
+    assert_eq!(cli.state.wt, work);
+    run(&mut cli, "cd ..");
+    assert_eq!(cli.state.wt, work);
+    let pinned = run(&mut cli, "pinned");
+    assert!(pinned.contains("api") && !pinned.contains("home"));
+
+    run(&mut cli, "unfocus");
+    assert!(cli.state.focus.is_none());
+    assert!(run(&mut cli, "pinned").contains("home"));
+    run(&mut cli, "cd /home");
+    run(&mut cli, "focus /work");
+    assert_eq!(cli.state.wt, work);
+}
+
+#[test]
+fn marks_in_paths() {
+    let mut cli = new_cli();
+    run(&mut cli, "add inbox");
+    run(&mut cli, "add project");
+    run(&mut cli, "cd project");
+    run(&mut cli, "add api");
+    run(&mut cli, "mark p");
+    run(&mut cli, "mark i /inbox");
+    assert!(cli.run_command("mark ab").is_err());
+    assert!(run(&mut cli, "marks").contains("'i: "));
+
+    run(&mut cli, "cd 'i");
+    assert_eq!(cli.state.wt, cli.state.uuid_for_path("/inbox").unwrap());
+    run(&mut cli, "mv 'p/api 'i");
+    assert!(cli.state.uuid_for_path("/inbox/api").is_some());
+    assert!(cli.state.uuid_for_path("'x").is_none());
+    assert!(cli.state.uuid_for_path("'ix").is_none());
+    assert!(cli.run_command("cd 'x").is_err());
+
+    run(&mut cli, "mark a /inbox/api");
+    run(&mut cli, "bookmark set api /inbox/api");
+    run(&mut cli, "rm /inbox/api --yes");
+    run(&mut cli, "trash empty --yes");
+    assert!(cli.state.uuid_for_path("'a").is_none());
+    assert!(cli.state.uuid_for_path("@api").is_none());
+    assert!(!cli.state.doc.bookmarks.contains_key("api"));
+    assert!(!run(&mut cli, "marks").contains("'a"));
+}
+
+#[test]
+fn planner_for_a_week() {
+    let mut cli = new_cli();
+    run(&mut cli, "add project");
+    run(&mut cli, "cd project");
+    run(&mut cli, "add call");
+    run(&mut cli, "add review");
+    run(&mut cli, "add old");
+    run(&mut cli, "add finished");
+    run(&mut cli, "work 1");
+    for (task, due) in [("call", "2024-06-05 14:00"), ("review", "2024-06-07"), ("old", "2024-05-01"), ("finished", "2024-06-04")].iter() {
+        run(&mut cli, &format!("cd {}", task));
+        run(&mut cli, &format!("remind {}", due));
+        run(&mut cli, "cd ..");
+    }
+    run(&mut cli, "done finished");
+    run(&mut cli, "cd /");
+    run(&mut cli, "timezone utc");
+
+    let text = run(&mut cli, "planner 2024-W23");
+    assert!(text.starts_with("Week of 2024-06-03 - 2024-06-09\n====="));
+    assert!(text.contains("Carried over\n------------\n[ ] project -> call (due 2024-06-05)\n"));
+    assert!(text.contains("Overdue\n-------\n[ ] project -> old (due 2024-05-01)\n"));
+    assert!(text.contains("Wednesday 2024-06-05\n--------------------\n[ ] 14:00 project -> call\n08:00 "));
+    assert!(text.contains("Friday 2024-06-07\n-----------------\n[ ] project -> review\n"));
+    assert!(!text.contains("finished"));
+    assert_eq!(text.matches("17:00").count(), 7);
+
+    let markdown = run(&mut cli, "planner 2024-06-05 --md");
+    assert!(markdown.starts_with("# Week of 2024-06-03 - 2024-06-09\n"));
+    assert!(markdown.contains("## Wednesday 2024-06-05\n\n- [ ] 14:00 project -> call\n\n| Time | Plan |\n"));
+}
+
+#[test]
+fn habits_recur_and_keep_streaks() {
+    let mut cli = new_cli();
+    run(&mut cli, "add routines");
+    run(&mut cli, "cd routines");
+    run(&mut cli, "add stretch");
+    run(&mut cli, "add report");
+    run(&mut cli, "cd stretch");
+    assert!(cli.run_command("habit 0/day").is_err());
+    run(&mut cli, "habit daily");
+    run(&mut cli, "cd ..");
+    run(&mut cli, "done 1");
+    run(&mut cli, "todo 2");
+    let stretch = cli.state.uuid_for_path("stretch").unwrap();
+    let task = cli.state.doc.get(&stretch).unwrap();
+    assert_eq!((task.progress, task.finished), (Some(Progress::Todo), None));
+    assert_eq!(cli.state.doc.progress_summary(&cli.state.wt).unwrap(), (0, 1));
+    assert!(run(&mut cli, "habits").contains("routines -> stretch (daily): 1/1 this day, streak 1"));
+
+    let today = cli.state.doc.timezone.today();
+    for days in &[1, 2, 4] {
+        let mut entry = Activity::new(Some(stretch), ActivityEvent::ProgressChanged { from: Some(Progress::Todo), to: Some(Progress::Done) });
+        entry.time = entry.time - chrono::Duration::days(*days);
+        cli.state.doc.activity.push(entry);
+    }
+    cli.state.doc.activity.sort_by_key(|entry| entry.time);
+    let stats = cli.state.doc.habit_stats(&stretch, today, chrono::Weekday::Mon).unwrap();
+    assert_eq!((stats.current, stats.streak), (1, 3));
+    let weekly = Habit { period: HabitPeriod::Week, target: 3 };
+    assert_eq!("3/week".parse::<Habit>().unwrap(), weekly);
+    assert_eq!(weekly.to_string(), "3/week");
+}
+
+#[test]
+fn review_stale_tasks() {
+    let mut cli = new_cli();
+    for title in &["first", "second", "third", "fourth", "notes"] {
+        run(&mut cli, &format!("add {}", title));
+    }
+    run(&mut cli, "todo 1-4");
+    assert!(run(&mut cli, "review").contains("No tasks unchanged for 7 days"));
+
+    cli.callbacks.input = vec!["x", "d", "r", "someday", "r", "2030-01-01", "a", ""]
+        .into_iter().map(|line| line.to_string()).collect();
+    let output = run(&mut cli, "review 0");
+    assert!(output.contains("[1/4] -> first (TODO)"));
+    assert!(output.contains("[4/4] -> fourth (TODO)"));
+    assert!(output.contains("Done: 1, rescheduled: 1, archived: 1"));
+    let root = cli.state.doc.root;
+    assert_eq!(child(&cli, &root, 0).progress, Some(Progress::Done));
+    assert!(child(&cli, &root, 1).due.is_some());
+    assert_eq!(child(&cli, &root, 2).title, "fourth");
+    let archive = child(&cli, &root, 4);
+    assert_eq!((archive.title.as_str(), archive.children.len()), ("Archive", 1));
+
+    cli.callbacks.input = vec!["s".to_string(), "q".to_string()].into_iter().collect();
+    let output = run(&mut cli, "review 0");
+    assert!(output.contains("[1/2] -> fourth (TODO)"));
+    assert!(output.contains("[2/2] -> second (TODO)"));
+}
+
+#[test]
+fn aging_report() {
+    let mut cli = new_cli();
+    for title in &["old", "fresh", "waiting", "clocked"] {
+        run(&mut cli, &format!("add {}", title));
+    }
+    run(&mut cli, "todo 1");
+    run(&mut cli, "work 2");
+    run(&mut cli, "waiting 3");
+    run(&mut cli, "todo 4");
+    let long_ago = Local::now() - chrono::Duration::days(40);
+    for title in &["old", "clocked"] {
+        let task_id = cli.state.uuid_for_path(title).unwrap();
+        let task = std::rc::Rc::make_mut(cli.state.doc.map.get_mut(&task_id).unwrap());
+        task.created = Some(long_ago);
+        task.modified = Some(long_ago);
+    }
+    run(&mut cli, "cli clocked");
+    run(&mut cli, "clo");
+
+    let output = run(&mut cli, "aging");
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("  40d TODO") && lines[0].ends_with("old"));
+    assert!(output.contains("   0d WORK"));
+    assert!(!output.contains("waiting"));
+
+    let old = cli.state.uuid_for_path("old").unwrap();
+    let age = cli.state.doc.task_age(&cli.state.doc.get(&old).unwrap(), Local::now()).unwrap();
+    assert_eq!(sors::aging::AgingThresholds::default().style(age), Some(sors::output::Style::Error));
+}
+
+#[test]
+fn capture_into_inbox() {
+    let mut cli = new_cli();
+    run(&mut cli, "add project");
+    run(&mut cli, "cd project");
+    assert!(cli.run_command("capture").is_err());
+    assert!(run(&mut cli, "capture Call the dentist").contains("Inbox -> Call the dentist"));
+    run(&mut cli, "capture Buy milk");
+    assert_eq!(cli.state.wt, cli.state.uuid_for_path("/project").unwrap());
+    let inbox = cli.state.uuid_for_path("@inbox").unwrap();
+    assert_eq!(child(&cli, &inbox, 1).title, "Buy milk");
+
+    run(&mut cli, "bookmark set inbox /project");
+    run(&mut cli, "capture Write report");
+    let project = cli.state.wt;
+    assert_eq!(child(&cli, &project, 0).title, "Write report");
+}
+
+#[test]
+fn clock_out_asks_for_comment() {
+    let mut cli = new_cli();
+    run(&mut cli, "cln");
+    run(&mut cli, "clo");
+    let comments = |cli: &Cli<State, ScriptedCallbacks>| {
+        let mut clocks: Vec<_> = cli.state.doc.clocks.values().cloned().collect();
+        clocks.sort();
+        clocks.iter().map(|clock| clock.comment.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(comments(&cli), vec![None]);
+
+    let mut config = sors::config::Config::default();
+    config.set("clock_out_comment", "true").unwrap();
+    cli.callbacks.config = Some(config);
+    cli.callbacks.input.push_back("Reviewed PRs".to_string());
+    run(&mut cli, "cln");
+    run(&mut cli, "clo");
+    assert_eq!(comments(&cli)[1].as_deref(), Some("Reviewed PRs"));
+
+    // A comment which is already set isn't asked for.
+    run(&mut cli, "cln");
+    cli.callbacks.input.push_back("Planning".to_string());
+    run(&mut cli, "clc");
+    cli.callbacks.input.push_back("Unused".to_string());
+    run(&mut cli, "clo");
+    assert_eq!(comments(&cli)[2].as_deref(), Some("Planning"));
+    assert_eq!(cli.callbacks.input.len(), 1);
+}
+
+#[test]
+fn snapshot_and_diff() {
+    let mut cli = new_cli();
+    run(&mut cli, "add kept");
+    run(&mut cli, "add renamed");
+    run(&mut cli, "add removed");
+    run(&mut cli, "snapshot sprint");
+    assert_eq!(run(&mut cli, "snapshots"), "sprint\n");
+
+    run(&mut cli, "done 1");
+    let renamed = child(&cli, &cli.state.doc.root.clone(), 1).id;
+    cli.state.doc.modify_task(&renamed, |task| { task.set_title("better name".to_string()); Ok(()) }).unwrap();
+    run(&mut cli, "rm 3 --yes");
+    run(&mut cli, "add new");
+    let output = run(&mut cli, "diff sprint");
+    assert!(output.contains("* kept: - -> DONE\n"));
+    assert!(output.contains("~ better name (was renamed)\n"));
+    assert!(output.contains("- removed\n"));
+    assert!(output.contains("+ new\n"));
+    assert!(output.contains("1 added, 1 removed, 1 retitled, 1 progress changes"));
+    assert!(cli.run_command("diff unknown").is_err());
+    assert!(cli.run_command("snapshot ../escape").is_err());
+    std::fs::remove_dir_all(sors::snapshot::snapshot_dir(&cli.state.path)).unwrap();
+}
+
+#[test]
+fn properties() {
+    let mut cli = new_cli();
+    run(&mut cli, "add project");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "set client ACME Corp");
+    run(&mut cli, "set rate 120");
+    assert_eq!(run(&mut cli, "props"), "client: ACME Corp\nrate: 120\n");
+    assert!(run(&mut cli, "ls").contains("Properties: client=ACME Corp, rate=120"));
+    run(&mut cli, "unset rate");
+    assert_eq!(run(&mut cli, "props"), "client: ACME Corp\n");
+    assert!(cli.run_command("set client").is_err());
+    run(&mut cli, "cd ..");
+    assert!(run(&mut cli, "find prop.client=\"acme corp\"").contains("project"));
+    assert!(run(&mut cli, "find prop.client~globex").is_empty());
+    run(&mut cli, "add Document the --json flag");
+    assert!(run(&mut cli, "find title~\"--json\"").contains("Document the --json flag"));
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "find title~\"--json\" --json")).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 1, "{}", json);
+    run(&mut cli, "set output json");
+    assert_eq!(cli.state.output, OutputFormat::Json);
+}
+
+#[test]
+fn contexts() {
+    let mut cli = new_cli();
+    run(&mut cli, "add garden");
+    run(&mut cli, "add report");
+    run(&mut cli, "add call mom");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "tag @home");
+    run(&mut cli, "add mow lawn");
+    run(&mut cli, "cd /");
+    run(&mut cli, "cd 2");
+    run(&mut cli, "tag @office");
+    run(&mut cli, "cd /");
+    assert_eq!(run(&mut cli, "context"), "No active context\n@home\n@office\n");
+
+    run(&mut cli, "context home");
+    assert_eq!(cli.state.context, Some("@home".to_string()));
+    let ls = run(&mut cli, "ls");
+    assert!(ls.contains("1: ") && ls.contains("garden"));
+    assert!(!ls.contains("report"));
+    assert!(ls.contains("3: ") && ls.contains("call mom"));
+    assert!(ls.contains("(1 hidden by the context)"));
+    assert!(run(&mut cli, "find title~mow").contains("mow lawn"));
+    assert!(!run(&mut cli, "outline").contains("report"));
+
+    run(&mut cli, "context @office");
+    assert!(run(&mut cli, "find title~mow").is_empty());
+
+    run(&mut cli, "context clear");
+    assert_eq!(cli.state.context, None);
+    assert!(run(&mut cli, "ls").contains("report"));
+}
+
+#[test]
+fn next_actions_and_agenda_in_context() {
+    let mut cli = new_cli();
+    run(&mut cli, "add garden");
+    run(&mut cli, "add report");
+    run(&mut cli, "add call mom");
+    run(&mut cli, "add notes");
+    run(&mut cli, "todo 1-3");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "tag @home");
+    run(&mut cli, "add mow lawn");
+    run(&mut cli, "add buy seeds");
+    run(&mut cli, "work 1");
+    run(&mut cli, "done 2");
+    run(&mut cli, "cd /report");
+    run(&mut cli, "tag @office");
+    run(&mut cli, "remind 2000-01-03 09:00");
+    run(&mut cli, "cd /call mom");
+    let tomorrow = (Local::today() + chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+    run(&mut cli, &format!("remind {} 18:00", tomorrow));
+    run(&mut cli, "cd /garden/mow lawn");
+    run(&mut cli, "remind 2999-01-01");
+    run(&mut cli, "cd /");
+
+    // Garden has an open subtask and notes no progress.
+    assert_eq!(run(&mut cli, "next"), "WORK  -> garden -> mow lawn\nTODO  -> report\nTODO  -> call mom\n");
+    let output = run(&mut cli, "agenda");
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("--- ") && lines[1] == "09:00 TODO  -> report");
+    assert!(lines[2].starts_with("--- ") && lines[3] == "18:00 TODO  -> call mom");
+    assert_eq!(run(&mut cli, "agenda 0").lines().count(), 2);
+
+    run(&mut cli, "context home");
+    assert_eq!(run(&mut cli, "next"), "WORK  -> garden -> mow lawn\nTODO  -> call mom\n");
+    assert!(!run(&mut cli, "agenda").contains("report"));
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "next --json")).unwrap();
+    assert_eq!(json[0]["title"], "mow lawn");
+    run(&mut cli, "context office");
+    run(&mut cli, "cd /garden");
+    assert_eq!(run(&mut cli, "next"), "No next actions\n");
+    assert_eq!(run(&mut cli, "agenda"), "Nothing due in the next 7 days\n");
+    assert!(cli.run_command("agenda soon").is_err());
+}
+
+#[test]
+fn clocks_in_display_zone() {
+    let mut cli = new_cli();
+    run(&mut cli, "add work");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "timezone +02:00");
+    assert_eq!(run(&mut cli, "timezone"), "+02:00\n");
+    run(&mut cli, "clockadd 2024-03-01T09:00 2024-03-01T10:00");
+    let clock = cli.state.doc.clocks.values().next().unwrap().clone();
+    assert_eq!(clock.start.to_rfc3339(), "2024-03-01T07:00:00+00:00");
+    assert!(run(&mut cli, "dayclock 2024-03-01").contains("2024-03-01 09:00:00 +02:00"));
+    run(&mut cli, "clockadd 2024-03-01T11:00 1h15m");
+    assert!(run(&mut cli, "dayclock 2024-03-01").contains("Day duration: 2h 15m"));
+    assert!(cli.run_command("timezone Mars/Olympus").is_err());
+}
+
+#[test]
+fn date_parsing() {
+    use chrono::Datelike;
+    use sors::helper::*;
+    let context = DateContext { timezone: DisplayZone::Utc, week_start: chrono::Weekday::Sun, date_format: "%d.%m.%Y".to_string() };
+    let today = context.today();
+    let parse = |date: &str| parse_date(date, &context).unwrap();
+    assert_eq!(parse("today"), today);
+    assert_eq!(parse("Yesterday"), today.pred());
+    assert_eq!(parse("tomorrow"), today.succ());
+    assert_eq!(parse("-3"), today - chrono::Duration::days(3));
+    assert_eq!(parse("2024-05-31"), chrono::NaiveDate::from_ymd(2024, 5, 31));
+    assert_eq!(parse("31.05.2024"), chrono::NaiveDate::from_ymd(2024, 5, 31));
+    assert_eq!(parse("2024-W23"), chrono::NaiveDate::from_ymd(2024, 6, 2));
+
+    let (start, end) = context.this_week();
+    assert_eq!(start.weekday(), chrono::Weekday::Sun);
+    let monday = parse("mon");
+    assert!(monday.weekday() == chrono::Weekday::Mon && start <= monday && monday <= end);
+    assert_eq!(parse("sun"), start);
+    assert_eq!(parse("last-mon"), monday - chrono::Duration::weeks(1));
+    assert_eq!(parse("next-sat"), end + chrono::Duration::weeks(1));
+    assert!(parse_date("someday", &context).is_err());
+    assert!(parse_date("last-", &context).is_err());
+
+    assert_eq!(parse_week_range("2024-06-05", &context).unwrap(),
+        (chrono::NaiveDate::from_ymd(2024, 6, 2), chrono::NaiveDate::from_ymd(2024, 6, 8)));
+    let point = parse_point_in_time("yesterday@14:30", &context).unwrap();
+    assert_eq!(point.naive_utc(), today.pred().and_hms(14, 30, 0));
+}
+
+#[test]
+fn plain_callbacks_run_scripts() {
+    let path = std::env::temp_dir().join(format!("sors-test-{}.json", Uuid::new_v4()));
+    let script: &[u8] = b"add Buy milk\r\nls\nunknown\nexit --yes\nls\n";
+    let mut cli = Cli::new(State::new(Doc::new(), path.to_str().unwrap()), PlainCallbacks::new(script, Vec::new()));
+    register_default_commands(&mut cli);
+    cli.run_loop("> ");
+    let output = String::from_utf8(cli.callbacks.into_output()).unwrap();
+    assert!(output.starts_with("> > "));
+    assert!(output.contains("Buy milk\n"));
+    assert!(output.contains("Error: "));
+    assert_eq!(output.matches("> ").count(), 4);
+
+    let mut output = Vec::new();
+    assert!(matches!(read_plain_line(&mut &b""[..], &mut output, "? "), CliInputResult::Termination));
+    assert_eq!(output, b"? ");
+}
+
+#[test]
+fn history_reruns_commands() {
+    let path = std::env::temp_dir().join(format!("sors-test-{}.json", Uuid::new_v4()));
+    let script: &[u8] = b"add Buy milk\nls\n!l\n!!\n!1 later\n!rm\nhistory 3\n";
+    let mut cli = Cli::new(State::new(Doc::new(), path.to_str().unwrap()), PlainCallbacks::new(script, Vec::new()));
+    register_default_commands(&mut cli);
+    cli.run_loop("> ");
+    assert_eq!(cli.history, vec!["add Buy milk", "ls", "ls", "ls", "add Buy milk later", "history 3"]);
+    let root = cli.state.doc.root;
+    assert_eq!(cli.state.doc.get(&root).unwrap().children.len(), 2);
+    let output = String::from_utf8(cli.callbacks.into_output()).unwrap();
+    assert!(output.contains("Error: Couldn't parse: No command in history: !rm"));
+    assert!(output.contains("add Buy milk later\n"));
+    assert!(output.contains("   4  ls\n   5  add Buy milk later\n   6  history 3\n"));
+}
+
+#[test]
+fn startup_script() {
+    let mut cli = new_cli();
+    run(&mut cli, "add Inbox");
+    run(&mut cli, "bookmark set inbox 1");
+    let failures = cli.run_script("# Start in the inbox\n\n  cd @inbox\nunknown\nadd Read mail\n");
+    assert_eq!(failures, 1);
+    assert!(cli.callbacks.output.contains("Error in line 4: "));
+    let inbox = cli.state.doc.bookmarks["inbox"];
+    assert_eq!(cli.state.wt, inbox);
+    assert_eq!(child(&cli, &inbox, 0).title, "Read mail");
+    assert!(cli.history.is_empty());
+}
+
+#[test]
+fn clock_tags() {
+    let mut cli = new_cli();
+    assert!(cli.run_command("clt travel").is_err());
+    run(&mut cli, "add Customer visit");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "clt travel on-call travel");
+    run(&mut cli, "clt rm on-call");
+    assert_eq!(run(&mut cli, "clt"), "travel\n");
+    run(&mut cli, "cln");
+    run(&mut cli, "clt on-call");
+    run(&mut cli, "clo");
+    let mut clocks: Vec<_> = cli.state.doc.clocks.values().cloned().collect();
+    clocks.sort();
+    assert_eq!(clocks[0].tags, vec!["travel".to_string()]);
+    assert_eq!(clocks[1].tags, vec!["on-call".to_string()]);
+
+    let output = run(&mut cli, "dayclock --tag=travel");
+    assert!(output.contains("Customer visit\n"), "{}", output);
+    assert!(output.contains(" Tags: travel\n"));
+    assert!(!output.contains("on-call"));
+    let output = run(&mut cli, "dayclock --chart=tag");
+    assert!(output.contains("travel "));
+    assert!(output.contains("on-call "));
+}
+
+#[test]
+fn clock_channels() {
+    let mut cli = new_cli();
+    run(&mut cli, "add Feature");
+    run(&mut cli, "add Pager");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "cli 2 --channel oncall");
+    run(&mut cli, "clt --channel oncall night");
+    assert_eq!(cli.state.doc.running_clocks().len(), 2);
+    let output = run(&mut cli, "status");
+    assert!(output.contains("Clock running since"));
+    assert!(output.contains("Clock running in channel oncall since"));
+
+    // Clocking in or out only affects the own channel.
+    run(&mut cli, "cli 1");
+    run(&mut cli, "clo");
+    let oncall = cli.state.doc.running_clock_in(Some("oncall")).expect("on-call clock still runs");
+    assert_eq!(oncall.tags, vec!["night".to_string()]);
+    assert!(cli.state.doc.running_clock().is_none());
+    assert_eq!(cli.state.doc.clocks.len(), 3);
+
+    let output = run(&mut cli, "dayclock --channel oncall");
+    assert!(output.contains("Pager"));
+    assert!(!output.contains("Feature"));
+    let output = run(&mut cli, "dayclock --chart=channel");
+    assert!(output.contains("default "));
+    assert!(output.contains("oncall "));
+
+    run(&mut cli, "clo --channel=oncall");
+    assert!(cli.state.doc.running_clocks().is_empty());
+    assert_eq!(run(&mut cli, "status"), "No clock running\n");
+}
+
+#[test]
+fn reports_stop_forgotten_clocks() {
+    let mut cli = new_cli();
+    run(&mut cli, "add Report");
+    run(&mut cli, "cli 1");
+    let clock_id = cli.state.doc.current_clock.unwrap();
+    let mut clock = cli.state.doc.clock(&clock_id).unwrap();
+    std::rc::Rc::make_mut(&mut clock).start = chrono::Utc::now() - chrono::Duration::days(2);
+    cli.state.doc.upsert_clock(clock);
+
+    // Without the setting, clocks run as long as they like.
+    run(&mut cli, "dayclock");
+    assert!(cli.state.doc.running_clock().is_some());
+
+    let mut config = sors::config::Config::default();
+    assert!(config.set("auto_clock_out", "late").is_err());
+    config.set("auto_clock_out", "23:59").unwrap();
+    cli.callbacks.config = Some(config);
+    run(&mut cli, "ls");
+    assert!(cli.state.doc.running_clock().is_some());
+    let output = run(&mut cli, "dayclock");
+    assert!(output.starts_with("Clock stopped at the end of the day "), "{}", output);
+    assert!(cli.state.doc.running_clock().is_none());
+    assert!(run(&mut cli, "log").contains("Clock stopped at the end of the day 23:59"));
+}
+
+#[test]
+fn breaks_and_required_break_time() {
+    use chrono::TimeZone;
+    let mut cli = new_cli();
+    run(&mut cli, "add Report");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "break");
+    assert!(cli.state.doc.running_clock().is_none());
+    assert!(cli.state.doc.running_break().is_some());
+    assert!(run(&mut cli, "status").contains("Break since"));
+    run(&mut cli, "cli 1");
+    assert!(cli.state.doc.running_break().is_none());
+    assert_eq!(run(&mut cli, "break end"), "No break running\n");
+
+    run(&mut cli, "timezone utc");
+    let report = child(&cli, &cli.state.doc.root.clone(), 0).id;
+    cli.state.doc.breaks = vec![sors::worktime::Break {
+        start: chrono::Utc.ymd(2030, 5, 1).and_hms(12, 0, 0),
+        end: Some(chrono::Utc.ymd(2030, 5, 1).and_hms(12, 15, 0)),
+    }];
+    cli.state.doc.upsert_clock(std::rc::Rc::new(sors::clock::Clock {
+        id: Uuid::new_v4(),
+        start: chrono::Utc.ymd(2030, 5, 1).and_hms(8, 0, 0),
+        end: Some(chrono::Utc.ymd(2030, 5, 1).and_hms(15, 0, 0)),
+        comment: None,
+        task_id: Some(report),
+        category: None,
+        tags: Vec::new(),
+        channel: None,
+    }));
+    let output = run(&mut cli, "dayclock 2030-05-01");
+    assert!(output.contains("gross   7h 15m breaks      15m net    7h 0m"), "{}", output);
+    assert!(!output.contains("short of"), "{}", output);
+
+    let mut config = sors::config::Config::default();
+    assert!(config.set("break_rules", "6h").is_err());
+    config.set("break_rules", "6h=30m, 9h=45m").unwrap();
+    cli.callbacks.config = Some(config);
+    let output = run(&mut cli, "dayclock 2030-05-01");
+    assert!(output.contains("Breaks are 15m short of the required 30m"), "{}", output);
+}
+
+#[test]
+fn clock_export_and_import() {
+    use chrono::TimeZone;
+    let path = std::env::temp_dir().join(format!("sors-clocks-{}.json", Uuid::new_v4()));
+    let path_str = path.to_str().unwrap().to_string();
+    let clock = |task_id, day| std::rc::Rc::new(sors::clock::Clock {
+        id: Uuid::new_v4(),
+        start: chrono::Utc.ymd(2030, 5, day).and_hms(9, 0, 0),
+        end: Some(chrono::Utc.ymd(2030, 5, day).and_hms(11, 0, 0)),
+        comment: Some("Layout".to_string()),
+        task_id: Some(task_id),
+        category: None,
+        tags: Vec::new(),
+        channel: None,
+    });
+
+    let mut cli = new_cli();
+    run(&mut cli, "timezone utc");
+    run(&mut cli, "add ACME");
+    run(&mut cli, "add Internal");
+    let root = cli.state.doc.root;
+    let acme = child(&cli, &root, 0).id;
+    let internal = child(&cli, &root, 1).id;
+    run(&mut cli, "cd 1");
+    run(&mut cli, "add Website");
+    let website = child(&cli, &acme, 0).id;
+    cli.state.doc.upsert_clock(clock(website, 1));
+    cli.state.doc.upsert_clock(clock(website, 20));
+    cli.state.doc.upsert_clock(clock(internal, 1));
+    assert_eq!(run(&mut cli, &format!("clockexport {} 2030-05-01 2030-05-10", path_str)), "Exported 1 clocks\n");
+    assert_eq!(run(&mut cli, &format!("clockexport {}", path_str)), "Exported 2 clocks\n");
+
+    // Another document with other task UUIDs.
+    let mut other = new_cli();
+    run(&mut other, "add ACME");
+    run(&mut other, "cd 1");
+    run(&mut other, "add Website");
+    let other_acme = child(&other, &other.state.doc.root.clone(), 0).id;
+    let other_website = child(&other, &other_acme, 0).id;
+    assert_eq!(run(&mut other, &format!("clockimport {}", path_str)), "Imported 2 clocks, skipped 0 existing\n");
+    assert!(other.state.doc.clocks.values().all(|clock| clock.task_id == Some(other_website)));
+    assert_eq!(run(&mut other, &format!("clockimport {}", path_str)), "Imported 0 clocks, skipped 2 existing\n");
+    assert_eq!(other.state.doc.clocks.len(), 2);
+
+    let mut empty = new_cli();
+    let output = run(&mut empty, &format!("clockimport {}", path_str));
+    assert!(output.contains("2 clocks have no task"), "{}", output);
+    assert!(empty.state.doc.clocks.values().all(|clock| clock.task_id.is_none()));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn anonymized_export() {
+    let path = std::env::temp_dir().join(format!("sors-anonymized-{}.json", Uuid::new_v4()));
+    let path_str = path.to_str().unwrap().to_string();
+    let mut cli = new_cli();
+    run(&mut cli, "add ACME Corp");
+    run(&mut cli, "add Globex");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "set rate 85 EUR");
+    run(&mut cli, "set contact Jane Doe");
+    run(&mut cli, "tag secret-project");
+    run(&mut cli, "add Review");
+    run(&mut cli, "cd /2");
+    run(&mut cli, "add Review");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "clt travel");
+    run(&mut cli, "clo");
+    let root = cli.state.doc.root;
+    let clock_id = *cli.state.doc.clocks.keys().next().unwrap();
+    let mut clock = cli.state.doc.clock(&clock_id).unwrap();
+    std::rc::Rc::make_mut(&mut clock).comment = Some("Call with ACME".to_string());
+    cli.state.doc.upsert_clock(clock);
+
+    assert_eq!(run(&mut cli, &format!("export {} --anonymize", path_str)), format!("Exported 5 tasks and 1 clocks to {}\n", path_str));
+    let content = std::fs::read_to_string(&path).unwrap();
+    for secret in ["ACME", "Globex", "Review", "Jane", "secret-project", "travel"].iter() {
+        assert!(!content.contains(secret), "{} in {}", secret, content);
+    }
+    let exported = Doc::load(&path).unwrap();
+    let acme = child(&cli, &root, 0);
+    let exported_acme = exported.get(&acme.id).unwrap();
+    assert_eq!(exported_acme.children, acme.children);
+    assert_eq!(exported_acme.properties["rate"], "85 EUR");
+    assert!(exported_acme.title.starts_with("task-"));
+    // Equal titles get equal placeholders.
+    let review = |doc: &Doc, parent: &Uuid| doc.get(&doc.get(parent).unwrap().children[0]).unwrap().title.clone();
+    assert_eq!(review(&exported, &acme.id), review(&exported, &child(&cli, &root, 1).id));
+    let exported_clock = exported.clock(&clock_id).unwrap();
+    assert_eq!(exported_clock.duration(), cli.state.doc.clock(&clock_id).unwrap().duration());
+    assert!(exported_clock.comment.as_ref().unwrap().starts_with("comment-"));
+
+    // The document itself is unchanged.
+    assert_eq!(child(&cli, &root, 0).title, "ACME Corp");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn document_info() {
+    let mut cli = new_cli();
+    run(&mut cli, "add Small");
+    run(&mut cli, "add Large");
+    run(&mut cli, "cd 2");
+    run(&mut cli, "add Api");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "add Endpoints");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "clo");
+    run(&mut cli, "add Obsolete");
+    run(&mut cli, "cli 2");
+    run(&mut cli, "clo");
+    let root = cli.state.doc.root;
+    let obsolete = cli.state.doc.get(&child(&cli, &root, 1).children[0]).unwrap().children[1];
+    cli.state.doc.map.remove(&obsolete);
+    cli.state.doc.invalidate_cache();
+    run(&mut cli, "save");
+
+    let output = run(&mut cli, "info");
+    assert!(output.starts_with(&format!("File: {} (load ", cli.state.path)), "{}", output);
+    assert!(output.contains("Tasks: 5, in the trash: 0, unreachable: 0"), "{}", output);
+    assert!(output.contains("Clocks: 2, running: 0, without task: 0, unreferenced: 1"), "{}", output);
+    assert!(output.contains("Deepest nesting: 3 ("), "{}", output);
+    assert!(output.contains("--- Largest subtrees: \n     3 "), "{}", output);
+    assert!(output.contains("clocks of deleted tasks"), "{}", output);
+
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "info --json")).unwrap();
+    assert_eq!(json["largest_subtrees"][0]["title"], "Large");
+    assert_eq!(json["largest_subtrees"][1]["tasks"], 1);
+    assert_eq!(json["unreferenced_clocks"], 1);
+    std::fs::remove_file(&cli.state.path).unwrap();
+}
+
+#[test]
+fn doctor_suggests_maintenance() {
+    let mut cli = new_cli();
+    run(&mut cli, "add Released");
+    run(&mut cli, "add Next");
+    run(&mut cli, "done 1");
+    run(&mut cli, "cd 2");
+    run(&mut cli, "add Api");
+    run(&mut cli, "add Obsolete");
+    run(&mut cli, "rm 2 --yes");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "clo");
+    run(&mut cli, "save");
+
+    let output = run(&mut cli, "doctor");
+    assert!(output.starts_with(&format!("File: {} (", cli.state.path)), "{}", output);
+    assert!(output.contains("Orphans: 0 unreachable tasks, 0 clocks of deleted tasks, 0 clocks without task\n"), "{}", output);
+    assert!(output.contains("\nTrash: 1 tasks\nLast snapshot: none\n"), "{}", output);
+    assert!(output.contains("--- Largest subtrees: \n     2 "), "{}", output);
+    assert!(output.contains(" - Remove the 1 tasks in the trash with `trash empty`\n"), "{}", output);
+    assert!(output.contains(" - Move the 1 done top-level tasks to the archive with `archive <selector>`\n"), "{}", output);
+    assert!(output.contains(" - Store a copy of the document with `snapshot`\n"), "{}", output);
+    assert!(!output.contains("--- Broken rules"), "{}", output);
+    assert!(output.contains("\nClocks: 1 on 1 tasks, running: 0, from "), "{}", output);
+    assert!(output.contains("\nJournal: none\n"), "{}", output);
+
+    // A journal larger than the document should be written into it, the
+    // notes and the settings are journaled.
+    for i in 0..30 {
+        run(&mut cli, &format!("note Step {}", i));
+    }
+    let path = cli.state.path.clone();
+    sors::helper::save_doc::<State>(&mut cli.state, &path, true, &mut cli.callbacks).unwrap();
+    let output = run(&mut cli, "doctor");
+    assert!(output.contains("\nJournal: 31 entries ("), "{}", output);
+    assert!(output.contains(" - Write the 31 journal entries into the document with `save`\n"), "{}", output);
+    run(&mut cli, "save");
+    assert!(!run(&mut cli, "doctor").contains("journal entries"));
+    assert_eq!(sors::info::file_suggestions(Format::Json, 2 * sors::info::LARGE_DOCUMENT, None),
+        vec![sors::info::Suggestion::Compress { bytes: 2 * sors::info::LARGE_DOCUMENT }]);
+    assert!(sors::info::file_suggestions(Format::Cbor, 2 * sors::info::LARGE_DOCUMENT, None).is_empty());
+
+    run(&mut cli, "snapshot before-cleanup");
+    run(&mut cli, "trash empty --yes");
+    run(&mut cli, "cd /");
+    run(&mut cli, "archive 1 --yes");
+    let output = run(&mut cli, "doctor");
+    assert!(output.contains("Last snapshot: before-cleanup ("), "{}", output);
+    assert!(output.ends_with("Nothing to do\n"), "{}", output);
+
+    let root = cli.state.doc.root;
+    let api = child(&cli, &root, 0).children[0];
+    cli.state.doc.map.remove(&api);
+    cli.state.doc.invalidate_cache();
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "doctor --json")).unwrap();
+    assert_eq!(json["unreferenced_clocks"], 1);
+    assert_eq!(json["last_snapshot"]["name"], "before-cleanup");
+    assert!(json["violations"].as_array().unwrap().len() >= 2, "{}", json);
+    assert!(json["suggestions"][0].as_str().unwrap().starts_with("Repair the "), "{}", json);
+    std::fs::remove_file(&cli.state.path).unwrap();
+    std::fs::remove_dir_all(sors::snapshot::snapshot_dir(&cli.state.path)).unwrap();
+}
+
+#[test]
+fn earnings_report() {
+    use chrono::TimeZone;
+    let mut cli = new_cli();
+    run(&mut cli, "add ACME");
+    run(&mut cli, "add Internal");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "set rate 85.50 EUR");
+    assert!(cli.run_command("set rate lots").is_err());
+    run(&mut cli, "add Website");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "add Hosting");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "set rate $120");
+    let root = cli.state.doc.root;
+    let acme = child(&cli, &root, 0).id;
+    let website = child(&cli, &acme, 0).id;
+    let hosting = child(&cli, &website, 0).id;
+    let internal = child(&cli, &root, 1).id;
+    assert_eq!(cli.state.doc.rate(&website).unwrap().unwrap().to_string(), "85.50 EUR");
+    let start = chrono::Utc.ymd(2030, 6, 3).and_hms(9, 0, 0);
+    for (task_id, minutes) in &[(website, 600), (website, 20), (hosting, 30), (internal, 45)] {
+        cli.state.doc.upsert_clock(std::rc::Rc::new(sors::clock::Clock {
+            id: Uuid::new_v4(),
+            start,
+            end: Some(start + chrono::Duration::minutes(*minutes)),
+            comment: None,
+            task_id: Some(*task_id),
+            category: None,
+            tags: Vec::new(),
+            channel: None,
+        }));
+    }
+    cli.state.wt = root;
+    let output = run(&mut cli, "earnings 2030-06-01 2030-06-30");
+    assert!(output.contains("ACME       10.33h       883.50 EUR\n"), "{}", output);
+    assert!(output.contains("ACME        0.50h        60.00 USD\n"));
+    assert!(output.contains("Total                   883.50 EUR\n"));
+    assert!(output.contains("Unbilled    0.75h\n"));
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "earnings 2030-06-01 2030-06-30 --json")).unwrap();
+    assert_eq!(json["totals"][1]["cents"], 6000);
+    assert_eq!(json["unbilled_seconds"], 2700);
+}
+
+#[test]
+fn invoice_markdown_and_html() {
+    use chrono::TimeZone;
+    let mut cli = new_cli();
+    let mut config = sors::config::Config::default();
+    config.set("invoice_footer", "Please pay {total} for {hours} hours.\\nThank you!").unwrap();
+    cli.callbacks.config = Some(config);
+    run(&mut cli, "add ACME");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "set rate 90 EUR");
+    run(&mut cli, "add Website");
+    run(&mut cli, "add Support");
+    let root = cli.state.doc.root;
+    let acme = child(&cli, &root, 0).id;
+    let website = child(&cli, &acme, 0).id;
+    let support = child(&cli, &acme, 1).id;
+    let start = chrono::Utc.ymd(2030, 6, 3).and_hms(9, 0, 0);
+    for (task_id, minutes) in &[(support, 20), (website, 90), (website, 11)] {
+        cli.state.doc.upsert_clock(std::rc::Rc::new(sors::clock::Clock {
+            id: Uuid::new_v4(),
+            start,
+            end: Some(start + chrono::Duration::minutes(*minutes)),
+            comment: None,
+            task_id: Some(*task_id),
+            category: None,
+            tags: Vec::new(),
+            channel: None,
+        }));
+    }
+    cli.state.wt = root;
+    let output = run(&mut cli, "invoice ACME 2030-06-01 2030-06-30");
+    assert!(output.starts_with("# Invoice\n\n**ACME**\n\nPeriod: 2030-06-01 - 2030-06-30"), "{}", output);
+    assert!(output.contains("| Website | 1.68 | 90.00 EUR | 151.20 EUR |\n| Support | 0.33 | 90.00 EUR | 29.70 EUR |\n"));
+    assert!(output.contains("| **Total** | | | **180.90 EUR** |\n"));
+    assert!(output.contains("Please pay 180.90 EUR for 2.01 hours.\nThank you!"));
+    assert!(cli.run_command("invoice ACME 2030-07-01 2030-07-31").is_err());
+
+    let path = std::env::temp_dir().join(format!("sors-invoice-{}.html", Uuid::new_v4()));
+    run(&mut cli, &format!("invoice ACME 2030-06-01 2030-06-30 {}", path.display()));
+    let html = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(html.contains(">Invoice</h1>"), "{}", html);
+    assert!(html.contains("<td>Website</td><td class=\"number\">1.68</td>"));
+
+    run(&mut cli, "cd 1");
+    assert!(cli.run_command("set rounding soon").is_err());
+    run(&mut cli, "set rounding 15m");
+    cli.state.wt = root;
+    let output = run(&mut cli, "invoice ACME 2030-06-01 2030-06-30");
+    assert!(output.contains("| Website | 1.75 | 90.00 EUR | 157.50 EUR |\n| Support | 0.50 | 90.00 EUR | 45.00 EUR |\n"), "{}", output);
+    assert!(output.contains("| **Total** | | | **202.50 EUR** |\n"));
+    run(&mut cli, "cd 1");
+    run(&mut cli, "set rounding 6m nearest");
+    cli.state.wt = root;
+    let output = run(&mut cli, "invoice ACME 2030-06-01 2030-06-30");
+    assert!(output.contains("| Website | 1.70 | 90.00 EUR | 153.00 EUR |\n| Support | 0.30 | 90.00 EUR | 27.00 EUR |\n"), "{}", output);
+}
+
+#[test]
+fn html_export_into_directory() {
+    let mut cli = new_cli();
+    run(&mut cli, "add Website");
+    let dir = std::env::temp_dir().join(format!("sors-html-{}", Uuid::new_v4()));
+    let output = run(&mut cli, &format!("html {} --copy-attachments", dir.display()));
+    let website = child(&cli, &cli.state.doc.root.clone(), 0).id;
+    assert!(output.contains(&format!("{}.html", website)), "{}", output);
+    assert!(dir.join("index.html").is_file());
+    assert!(dir.join(format!("{}.html", website)).is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dialog_questions() {
+    let script: &[u8] = b"\nTrip\nmaybe\n\nx\nM\nfive\n5\n";
+    let mut callbacks = PlainCallbacks::new(script, Vec::new());
+    let mut dialog: Dialog<()> = Dialog::new(&mut callbacks);
+    assert_eq!(dialog.ask("Title", Some("Untitled")).as_deref(), Some("Untitled"));
+    assert_eq!(dialog.ask("Title", None).as_deref(), Some("Trip"));
+    assert_eq!(dialog.confirm("Continue?", true), Some(true));
+    let choices = [Choice::new('k', "keep", 1), Choice::new('m', "merge", 2).as_default(), Choice::new('x', "abort", 3)];
+    assert_eq!(dialog.choose("Conflict:", &choices), Some(3));
+    assert_eq!(dialog.choose("Conflict:", &choices), Some(2));
+    assert_eq!(dialog.ask_parsed("Days", None, |days| days.parse::<u32>()), Some(5));
+    assert_eq!(dialog.ask("Title", None), None);
+    let output = String::from_utf8(callbacks.into_output()).unwrap();
+    assert!(output.starts_with("Title [Untitled]: Title: Continue? [Y/n] Continue? [Y/n] "));
+    assert!(output.contains("Conflict: [k]eep, [M]erge or abort [x]? "));
+    assert!(output.contains("Days: invalid digit found in string\nDays: "));
+}
+
+/// Another session which only appended to the journal changed the file.
+#[test]
+fn journal_changes_on_disk() {
+    let mut cli = new_cli();
+    let path = cli.state.path.clone();
+    run(&mut cli, "add first");
+    run(&mut cli, "save");
+    assert!(!cli.state.content_changed_on_disk());
+
+    let mut other = Doc::load(&path).unwrap();
+    let root = other.root;
+    other.modify_task(&root, |task| { task.set_body("Changed elsewhere"); Ok(()) }).unwrap();
+    other.save_journal(&path).unwrap();
+    assert!(sors::journal::journal_path(std::path::Path::new(&path)).exists());
+    assert!(cli.state.content_changed_on_disk());
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(sors::journal::journal_path(std::path::Path::new(&path))).unwrap();
+}
+
+#[test]
+fn journal_replays_the_session() {
+    let mut cli = new_cli();
+    let path = cli.state.path.clone();
+    let journal = sors::journal::journal_path(std::path::Path::new(&path));
+    run(&mut cli, "add first");
+    run(&mut cli, "add second");
+    run(&mut cli, "save");
+    let saved = std::fs::read(&path).unwrap();
+
+    run(&mut cli, "done 1");
+    run(&mut cli, "cli 2");
+    run(&mut cli, "clo");
+    run(&mut cli, "bookmark set second /2");
+    run(&mut cli, "rm 1 --yes");
+    run(&mut cli, "add third");
+    sors::helper::save_doc::<State>(&mut cli.state, &path, true, &mut cli.callbacks).unwrap();
+    cli.state.update_dirty();
+    assert!(!cli.state.dirty);
+    assert_eq!(std::fs::read(&path).unwrap(), saved);
+    assert!(std::fs::metadata(&journal).unwrap().len() > 0);
+
+    let loaded = Doc::load(&path).unwrap();
+    assert_eq!(loaded.map, cli.state.doc.map);
+    assert_eq!(loaded.clocks, cli.state.doc.clocks);
+    assert_eq!(loaded.activity, cli.state.doc.activity);
+    assert_eq!(loaded.bookmarks, cli.state.doc.bookmarks);
+    assert_eq!(loaded.trash_entries, cli.state.doc.trash_entries);
+    assert!(!loaded.has_unsaved_changes());
+
+    run(&mut cli, "save");
+    assert!(!journal.exists());
+    assert_eq!(Doc::load(&path).unwrap().map, cli.state.doc.map);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn import_todoist_trello_and_github() {
+    let dir = std::env::temp_dir().join(format!("sors-import-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |name: &str, content: &str| {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let todoist = write("todoist.json", r#"{
+        "sections": [{"id": "s1", "name": "Later"}],
+        "items": [
+            {"id": "1", "content": "Write docs", "description": "All of them", "labels": ["docs"], "checked": false},
+            {"id": "2", "content": "Outline", "parent_id": "1", "checked": true},
+            {"id": 3, "content": "Release", "section_id": "s1", "is_completed": 0}
+        ]}"#);
+    let trello = write("trello.json", r#"{
+        "lists": [{"id": "l1", "name": "Doing"}, {"id": "l2", "name": "Old", "closed": true}],
+        "cards": [
+            {"id": "c1", "idList": "l1", "name": "Card", "desc": "Text", "dueComplete": true},
+            {"id": "c2", "idList": "l1", "name": "Archived", "closed": true}
+        ],
+        "checklists": [{"idCard": "c1", "checkItems": [{"name": "Item", "state": "complete"}]}]}"#);
+    let github = write("github.json", r#"[
+        {"title": "Bug", "body": "Crash", "state": "open", "labels": [{"name": "bug"}], "html_url": "https://github.com/o/r/issues/1"},
+        {"title": "Fix", "state": "open", "pull_request": {}}]"#);
+
+    let mut cli = new_cli();
+    run(&mut cli, "add todoist");
+    run(&mut cli, "cd 1");
+    assert_eq!(run(&mut cli, &format!("import todoist {}", todoist)), "Imported 4 tasks\n");
+    let docs = cli.state.uuid_for_path("Write docs").unwrap();
+    let task = cli.state.doc.get(&docs).unwrap();
+    assert_eq!((task.body.as_str(), task.progress, task.tags.clone()), ("All of them", Some(Progress::Todo), vec!["docs".to_string()]));
+    assert_eq!(child(&cli, &docs, 0).progress, Some(Progress::Done));
+    assert!(cli.state.uuid_for_path("Later/Release").is_some());
+
+    run(&mut cli, "cd /");
+    assert_eq!(run(&mut cli, &format!("import trello {}", trello)), "Imported 3 tasks\n");
+    let card = cli.state.uuid_for_path("/Doing/Card").unwrap();
+    assert_eq!(cli.state.doc.get(&card).unwrap().progress, Some(Progress::Done));
+    assert_eq!(child(&cli, &card, 0).title, "Item");
+    assert!(cli.state.uuid_for_path("/Old").is_none());
+
+    assert_eq!(run(&mut cli, &format!("import github {}", github)), "Imported 1 tasks\n");
+    let bug = cli.state.doc.get(&cli.state.uuid_for_path("/Bug").unwrap()).unwrap();
+    assert_eq!(bug.external_ref.as_deref(), Some("https://github.com/o/r/issues/1"));
+    assert_eq!(bug.tags, vec!["bug".to_string()]);
+
+    assert!(run(&mut cli, "import").contains("formats: todoist, trello, github"));
+    assert!(cli.run_command(&format!("import asana {}", github)).is_err());
+    assert!(cli.run_command(&format!("import trello {}", github)).is_err());
+    let tasks = cli.state.doc.map.len();
+    assert!(cli.run_command(&format!("import todoist {}", write("broken.json", "{\"items\": ["))).is_err());
+    assert_eq!(cli.state.doc.map.len(), tasks);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn addmany_adds_a_child_per_line() {
+    let mut cli = new_cli();
+    for line in &["first", "  second  ", "", "ignored"] {
+        cli.callbacks.input.push_back(line.to_string());
+    }
+    run(&mut cli, "addmany");
+    let root = cli.state.doc.root;
+    assert_eq!(cli.state.doc.get(&root).unwrap().children.len(), 2);
+    assert_eq!(child(&cli, &root, 1).title, "second");
+    run(&mut cli, "add  spaced   title ");
+    assert_eq!(child(&cli, &root, 2).title, "spaced   title");
+}
+
+#[test]
+fn activity_log() {
+    let mut cli = new_cli();
+    run(&mut cli, "add task");
+    run(&mut cli, "work 1");
+    run(&mut cli, "cli 1");
+    run(&mut cli, "clo");
+    let output = run(&mut cli, "log");
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].ends_with(" Created: task"));
+    assert!(lines[1].ends_with(" (none) -> WORK: task"));
+    assert!(lines[2].ends_with(" Clock started: task"));
+    assert!(lines[3].ends_with(" Clock stopped: task"));
+    assert_eq!(run(&mut cli, "log 1").lines().count(), 1);
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "log 2 --json")).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+    assert!(cli.run_command("log many").is_err());
+}
+
+#[test]
+fn clockadd_afterwards() {
+    let mut cli = new_cli();
+    run(&mut cli, "add meeting");
+    run(&mut cli, "clockadd 2019-06-03@09:00 2019-06-03@10:30 meeting Weekly sync");
+    run(&mut cli, "clockadd 2019-06-04@14:00 45m");
+    let meeting = cli.state.uuid_for_path("meeting").unwrap();
+    let mut clocks: Vec<_> = cli.state.doc.clocks.values().cloned().collect();
+    clocks.sort();
+    assert_eq!(clocks.len(), 2);
+    assert_eq!(clocks[0].task_id, Some(meeting));
+    assert_eq!(clocks[0].comment.as_deref(), Some("Weekly sync"));
+    assert_eq!(clocks[0].duration(), chrono::Duration::minutes(90));
+    assert_eq!(clocks[1].task_id, Some(cli.state.doc.root));
+    assert_eq!(clocks[1].duration(), chrono::Duration::minutes(45));
+    assert!(cli.run_command("clockadd 2019-06-03@09:00").is_err());
+    assert!(cli.run_command("clockadd 2019-06-03@09:00 1h missing").is_err());
+    assert!(cli.run_command("clockadd 2019-06-03@10:00 2019-06-03@09:00").is_err());
+    assert_eq!(cli.state.doc.clocks.len(), 2);
+}
+
+#[test]
+fn clock_in_by_path_and_resume() {
+    let mut cli = new_cli();
+    assert_eq!(run(&mut cli, "clr"), "No clock with a task found\n");
+    run(&mut cli, "add project");
+    run(&mut cli, "cd project");
+    run(&mut cli, "add api");
+    run(&mut cli, "cd /");
+    run(&mut cli, "cli project/api");
+    let api = cli.state.uuid_for_path("/project/api").unwrap();
+    assert_eq!(cli.state.doc.running_clock().unwrap().task_id, Some(api));
+    assert!(run(&mut cli, "status").contains("api"));
+    run(&mut cli, "clo");
+    assert!(cli.state.doc.running_clock().is_none());
+    assert!(cli.run_command("cli nowhere").is_err());
+
+    assert_eq!(run(&mut cli, "clr"), "Clocked in:  -> project -> api\n");
+    assert_eq!(cli.state.doc.running_clock().unwrap().task_id, Some(api));
+    run(&mut cli, "cd project");
+    run(&mut cli, "cla");
+    assert_eq!(cli.state.doc.running_clock().unwrap().task_id, Some(cli.state.wt));
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "status --json")).unwrap();
+    assert!(json["clock"].is_object());
+}
+
+#[test]
+fn budgets_warn_when_exceeded() {
+    let mut cli = new_cli();
+    run(&mut cli, "add project");
+    run(&mut cli, "budget project 2h");
+    run(&mut cli, "clockadd 2019-06-03@09:00 1h project");
+    assert_eq!(run(&mut cli, "budget"), " -> project: 1h 0m of 2h 0m\n");
+    run(&mut cli, "clockadd 2019-06-03@11:00 90m project");
+    assert_eq!(run(&mut cli, "budget"), " -> project: 2h 30m of 2h 0m (exceeded)\n");
+    run(&mut cli, "budget project clear");
+    assert_eq!(run(&mut cli, "budget"), "");
+    assert!(cli.run_command("budget project").is_err());
+    assert!(cli.run_command("budget project soon").is_err());
+}
+
+#[test]
+fn timestamped_notes() {
+    let mut cli = new_cli();
+    run(&mut cli, "add call");
+    run(&mut cli, "cd 1");
+    run(&mut cli, "note Left a message");
+    run(&mut cli, "note   Called back  ");
+    assert!(cli.run_command("note").is_err());
+    let output = run(&mut cli, "notes");
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with(" Left a message"));
+    assert!(lines[1].ends_with(" Called back"));
+    let json: serde_json::Value = serde_json::from_str(&run(&mut cli, "notes --json")).unwrap();
+    assert_eq!(json[1]["text"], "Called back");
+}
+
+#[test]
+fn attachments() {
+    let mut cli = new_cli();
+    let file = std::env::temp_dir().join(format!("sors-attachment-{}.txt", Uuid::new_v4()));
+    std::fs::write(&file, "content").unwrap();
+    run(&mut cli, "attach https://example.com/spec");
+    run(&mut cli, &format!("attach {}", file.display()));
+    assert!(cli.run_command("attach /does/not/exist").is_err());
+    let output = run(&mut cli, "attachments");
+    assert!(output.starts_with("1: https://example.com/spec\n2: "));
+    assert!(!output.contains("(missing)"));
+    std::fs::remove_file(&file).unwrap();
+    assert!(run(&mut cli, "attachments").contains("(missing)"));
+    run(&mut cli, "detach 2");
+    assert_eq!(run(&mut cli, "attachments"), "1: https://example.com/spec\n");
+    assert!(cli.run_command("detach 0").is_err());
+}
+
+#[test]
+fn directory_stack_and_bookmarks() {
+    let mut cli = new_cli();
+    run(&mut cli, "add inbox");
+    run(&mut cli, "add project");
+    let (inbox, project) = (cli.state.uuid_for_path("inbox").unwrap(), cli.state.uuid_for_path("project").unwrap());
+    run(&mut cli, "pushd inbox");
+    run(&mut cli, "pushd /project");
+    assert_eq!(run(&mut cli, "dirs"), " -> project\n -> inbox\n/\n");
+    run(&mut cli, "popd");
+    assert_eq!(cli.state.wt, inbox);
+    run(&mut cli, "popd");
+    assert_eq!(cli.state.wt, cli.state.doc.root);
+    assert!(cli.run_command("popd").is_err());
+
+    run(&mut cli, "bookmark set in /inbox");
+    run(&mut cli, "cd project");
+    run(&mut cli, "bookmark set here");
+    run(&mut cli, "cd @in");
+    assert_eq!(cli.state.wt, inbox);
+    assert_eq!(cli.state.uuid_for_path("@here"), Some(project));
+    assert_eq!(run(&mut cli, "bookmark"), "@here:  -> project\n@in:  -> inbox\n");
+    run(&mut cli, "bookmark rm here");
+    assert!(cli.state.uuid_for_path("@here").is_none());
+    assert!(cli.run_command("bookmark set x /missing").is_err());
+}
@@ -0,0 +1,70 @@
+use sors::config::*;
+
+#[test]
+fn set_save_and_load() {
+    let path = std::env::temp_dir().join(format!("sors-config-{}/config.toml", sors::Uuid::new_v4()));
+    assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+    let mut config = Config::default();
+    config.set("date_format", "%d.%m.%Y").unwrap();
+    config.set("autosave", "true").unwrap();
+    assert!(config.set("autosave", "maybe").is_err());
+    assert!(config.set("date_format", "%Q").is_err());
+    assert!(config.set("unknown", "x").is_err());
+    config.set("week_start", "sun").unwrap();
+    assert!(config.set("week_start", "someday").is_err());
+    assert_eq!(config.first_weekday(), chrono::Weekday::Sun);
+    config.set("aging_stale_days", "14").unwrap();
+    assert!(config.set("aging_warning_days", "-1").is_err());
+    assert_eq!(config.aging_thresholds(), sors::aging::AgingThresholds { warning: 7, stale: 14 });
+    config.set("s3_access_key", "key").unwrap();
+    config.set("s3_secret_key", "secret").unwrap();
+    assert_eq!(config.get("s3_secret_key").as_deref(), Some("********"));
+    assert_eq!(config.s3_config().region, "us-east-1");
+    assert_eq!(config.history_file_for(&config.file_path()), config.history_file_path());
+    assert!(config.history_file_for("/work/.sors/tasks.json").ends_with("/histories/%work%.sors%tasks.json"));
+    config.save(&path).unwrap();
+
+    let loaded = Config::load(&path).unwrap();
+    assert_eq!(loaded, config);
+    assert_eq!(loaded.get("date_format").as_deref(), Some("%d.%m.%Y"));
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn missing_settings_use_defaults() {
+    let path = std::env::temp_dir().join(format!("sors-config-{}.toml", sors::Uuid::new_v4()));
+    std::fs::write(&path, "editor = \"nano\"\n").unwrap();
+    let config = Config::load(&path).unwrap();
+    assert_eq!(config.editor, "nano");
+    assert_eq!(config.date_format, DEFAULT_DATE_FORMAT);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn project_file_in_parent() {
+    let root = std::env::temp_dir().join(format!("sors-project-{}", sors::Uuid::new_v4()));
+    let deep = root.join("src/deep");
+    std::fs::create_dir_all(&deep).unwrap();
+    assert_eq!(find_project_file(&deep), None);
+
+    std::fs::create_dir_all(root.join(".sors")).unwrap();
+    std::fs::write(root.join(PROJECT_FILE), "").unwrap();
+    assert_eq!(find_project_file(&deep), Some(root.join(PROJECT_FILE)));
+    assert_eq!(find_project_file(&root), Some(root.join(PROJECT_FILE)));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+/// The config directory doesn't exist after a fresh install.
+#[test]
+fn hooks_and_theme_create_the_directory() {
+    let dir = std::env::temp_dir().join(format!("sors-config-{}", sors::Uuid::new_v4()));
+    let mut hooks = sors::hooks::Hooks::default();
+    hooks.add(sors::hooks::HookEvent::Save, "git commit -a");
+    hooks.save(dir.join("hooks/hooks.json")).unwrap();
+    assert_eq!(sors::hooks::Hooks::load(dir.join("hooks/hooks.json")).unwrap(), hooks);
+    let theme = sors::output::Theme::default();
+    theme.save(dir.join("theme/theme.json")).unwrap();
+    assert_eq!(sors::output::Theme::load(dir.join("theme/theme.json")).unwrap(), theme);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,61 @@
+use sors::*;
+use sors::curl::SecretConfig;
+use sors::caldav::CaldavTransport;
+use sors::storage::*;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn secret_config_file() {
+    let config = SecretConfig::new(&[("user", "me:p\"a\\ss word"), ("header", "Authorization: Bearer token")]).unwrap();
+    let path = config.path().to_path_buf();
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(),
+        "user = \"me:p\\\"a\\\\ss word\"\nheader = \"Authorization: Bearer token\"\n");
+    drop(config);
+    assert!(!path.exists());
+}
+
+/// Fake `curl` which logs its arguments, then the mode and content of the
+/// config files, and answers `[]`.
+fn fake_curl() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sors-curl-test-{}", Uuid::new_v4()));
+    let bin = dir.join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let curl = bin.join("curl");
+    std::fs::write(&curl, format!("#!/bin/sh\nlog='{}'\necho \"args: $*\" >> \"$log\"\n\
+        while [ $# -gt 0 ]; do\n  if [ \"$1\" = -K ]; then stat -c %a \"$2\" >> \"$log\"; cat \"$2\" >> \"$log\"; fi\n  shift\ndone\necho '[]'\n",
+        dir.join("calls").display())).unwrap();
+    std::fs::set_permissions(&curl, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::env::set_var("PATH", format!("{}:{}", bin.display(), std::env::var("PATH").unwrap()));
+    dir
+}
+
+fn config_files(log: &str) -> Vec<&Path> {
+    log.lines().filter_map(|line| line.split(" -K ").nth(1))
+        .map(|rest| Path::new(rest.split(' ').next().unwrap()))
+        .collect()
+}
+
+/// Secrets are never on the command line where other users can see them.
+#[test]
+fn secrets_are_not_arguments() {
+    let dir = fake_curl();
+    let config = S3Config { region: "eu-central-1".to_string(), access_key: "AKIA".to_string(), secret_key: "s3cret".to_string(), ..S3Config::default() };
+    let s3 = S3Storage::parse("s3://tasks/a.json", config).unwrap();
+    assert_eq!(s3.read().unwrap(), b"[]\n");
+    let caldav = sors::caldav::CurlTransport { url: "https://dav.example.com/tasks/".to_string(), user: Some("me".to_string()), password: Some("dav-pass".to_string()) };
+    assert_eq!(caldav.get("1.ics").unwrap(), "");
+    assert!(sors::import::fetch_github_issues("neosam/sors", Some("ghp_token")).unwrap().is_empty());
+
+    let log = std::fs::read_to_string(dir.join("calls")).unwrap();
+    let args: Vec<&str> = log.lines().filter(|line| line.starts_with("args: ")).collect();
+    assert_eq!(args.len(), 3);
+    assert!(!args.iter().any(|args| args.contains("s3cret") || args.contains("dav-pass") || args.contains("ghp_token")));
+    assert!(log.contains("600\nuser = \"AKIA:s3cret\"\n"));
+    assert!(log.contains("600\nuser = \"me:dav-pass\"\n"));
+    assert!(log.contains("600\nheader = \"Authorization: Bearer ghp_token\"\n"));
+    assert_eq!(config_files(&log).len(), 3);
+    assert!(config_files(&log).iter().all(|path| !path.exists()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
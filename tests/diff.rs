@@ -0,0 +1,49 @@
+use sors::diff::*;
+use sors::*;
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title.to_string());
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+#[test]
+fn diff_lists_field_changes() {
+    let mut old = Doc::new();
+    let root = old.root;
+    let project = add(&mut old, &root, "project");
+    let task = add(&mut old, &root, "task");
+    let removed = add(&mut old, &root, "removed");
+    old.clock_new().unwrap();
+    old.clock_out().unwrap();
+
+    let mut doc = old.clone();
+    let added = add(&mut doc, &project, "added");
+    doc.modify_task(&task, |task| {
+        task.set_title("renamed".to_string());
+        task.set_progress(Progress::Done);
+        Ok(())
+    }).unwrap();
+    doc.move_to_trash(&removed).unwrap();
+    doc.modify_task(&root, |root| { root.remove_child(&task); Ok(()) }).unwrap();
+    doc.modify_task(&project, |project| { project.add_child(task); Ok(()) }).unwrap();
+    let clock = doc.clock_new().unwrap();
+
+    let changes = doc.diff(&old);
+    assert!(changes.contains(&Change::TaskAdded { task_id: added, path: doc.path_titles(&added) }));
+    assert!(changes.contains(&Change::TaskRemoved { task_id: removed, path: old.path_titles(&removed) }));
+    assert!(changes.contains(&Change::ClockAdded { clock_id: clock.id }));
+
+    let modified = changes.iter()
+        .find(|change| matches!(change, Change::TaskModified { task_id, .. } if *task_id == task))
+        .expect("task is modified");
+    let title = modified.field("title").unwrap();
+    assert_eq!((title.from.as_str(), title.to.as_str()), (Some("task"), Some("renamed")));
+    assert!(modified.field("progress").is_some());
+    assert_eq!(modified.field("parent").unwrap().to, serde_json::json!(project));
+    assert!(modified.field("children").is_none());
+
+    assert!(doc.diff(&doc).is_empty());
+}
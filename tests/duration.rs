@@ -0,0 +1,33 @@
+use sors::duration::*;
+use chrono::Duration;
+
+#[test]
+fn parse_durations() {
+    let minutes = |text: &str| parse_duration(text).unwrap().num_minutes();
+    assert_eq!(minutes("1h30m"), 90);
+    assert_eq!(minutes("90m"), 90);
+    assert_eq!(minutes("2d4h"), 52 * 60);
+    assert_eq!(minutes("2d 4h"), 52 * 60);
+    assert_eq!(minutes("1 hour 30 minutes"), 90);
+    assert_eq!(minutes("1w"), 7 * 24 * 60);
+    assert_eq!(minutes("1:30"), 90);
+    assert_eq!(minutes("-15m"), -15);
+    assert_eq!(parse_duration("45s").unwrap(), Duration::seconds(45));
+    for invalid in ["", "h", "5", "5x", "1h30", "1:xx", "99999999999999999w"] {
+        assert!(parse_duration(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn formatted_durations_parse_back() {
+    assert_eq!(format_duration(Duration::zero()), "0s");
+    assert_eq!(format_duration(Duration::minutes(90)), "1h 30m");
+    assert_eq!(format_duration(Duration::seconds(26 * 3600 + 5)), "1d 2h 5s");
+    assert_eq!(format_duration(-Duration::minutes(15)), "-15m");
+    for seconds in [0, 59, 3600, 90061, -5400] {
+        let duration = Duration::seconds(seconds);
+        assert_eq!(parse_duration(&format_duration(duration)).unwrap(), duration);
+    }
+    assert_eq!(short_duration(Duration::hours(26)), "26h 0m");
+    assert_eq!(signed_duration(-Duration::minutes(5)), "-5m");
+}
@@ -0,0 +1,51 @@
+use sors::*;
+use std::time::{Duration, Instant};
+
+/// Upper bound for operations on 10k tasks in debug builds.  Far above the
+/// real time, it only catches accidentally quadratic code.
+const LIMIT: Duration = Duration::from_secs(5);
+
+#[test]
+fn generated_documents_are_reproducible() {
+    let doc = Doc::generate_random(7, 1000);
+    let again = Doc::generate_random(7, 1000);
+    assert_eq!(doc.root, again.root);
+    assert_eq!(doc.map.len(), 1001);
+    assert_eq!(doc.clocks.len(), 500);
+    for (task_id, task) in doc.map.iter() {
+        assert_eq!(again.get(task_id).unwrap(), *task);
+    }
+    assert!(doc.clocks.keys().all(|clock_id| again.clocks.contains_key(clock_id)));
+    assert_eq!(doc.info().unreachable_tasks, 0);
+    assert_eq!(doc.iter_subtree(&doc.root).count(), 1001);
+
+    let other = Doc::generate_random(8, 1000);
+    assert!(other.get(&doc.root).is_err());
+}
+
+#[test]
+fn operations_on_large_documents_stay_fast() {
+    let doc = Doc::generate_random(42, 10_000);
+    let task_ids: Vec<Uuid> = doc.map.keys().cloned().collect();
+
+    let start = Instant::now();
+    assert_eq!(task_ids.iter().filter(|task_id| doc.find_parent(task_id).is_none()).count(), 1);
+    assert!(start.elapsed() < LIMIT, "find_parent took {:?}", start.elapsed());
+
+    let start = Instant::now();
+    assert_eq!(doc.iter_subtree(&doc.root).count(), 10_001);
+    assert!(start.elapsed() < LIMIT, "iter_subtree took {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let clocks: usize = (1..=28).map(|day| doc.day_clock(chrono::NaiveDate::from_ymd(2020, 2, day), doc.root).len()).sum();
+    assert!(clocks > 0);
+    assert!(start.elapsed() < LIMIT, "day_clock took {:?}", start.elapsed());
+
+    let path = std::env::temp_dir().join(format!("sors-generated-{}.json", Uuid::new_v4()));
+    let start = Instant::now();
+    doc.save(&path).unwrap();
+    let loaded = Doc::load(&path).unwrap();
+    assert!(start.elapsed() < LIMIT, "save and load took {:?}", start.elapsed());
+    assert_eq!(loaded.map.len(), doc.map.len());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,99 @@
+use sors::*;
+use sors::ics::handle_connection;
+use chrono::TimeZone;
+use std::io::{Cursor, Read};
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str, due: Option<chrono::DateTime<Local>>) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title.to_string());
+    task.set_due(due);
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+/// Request to read from and response written to memory.
+struct Stream {
+    request: Cursor<Vec<u8>>,
+    response: Vec<u8>,
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.request.read(buf)
+    }
+}
+
+impl std::io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.response.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn request(request: &str, feed: &str) -> String {
+    let mut stream = Stream { request: Cursor::new(request.as_bytes().to_vec()), response: Vec::new() };
+    handle_connection(&mut stream, || feed.to_string()).unwrap();
+    String::from_utf8(stream.response).unwrap()
+}
+
+#[test]
+fn due_tasks_as_events() {
+    let mut doc = Doc::new();
+    doc.timezone = DisplayZone::Utc;
+    let root = doc.root;
+    let meeting = add(&mut doc, &root, "Meeting; room 2", Some(Utc.ymd(2030, 5, 1).and_hms(14, 30, 0).with_timezone(&Local)));
+    add(&mut doc, &root, "Taxes", Some(Utc.ymd(2030, 4, 30).and_hms(0, 0, 0).with_timezone(&Local)));
+    let done = add(&mut doc, &root, "Done", Some(Utc.ymd(2030, 4, 1).and_hms(0, 0, 0).with_timezone(&Local)));
+    add(&mut doc, &root, &"long ".repeat(20), None);
+    doc.set_progress(&done, Progress::Done).unwrap();
+
+    let ics = doc.to_ics(&root, false);
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    assert!(ics.contains(&format!("UID:{}@sors\r\n", meeting)));
+    assert!(ics.contains("DTSTART:20300501T143000Z\r\nSUMMARY:Meeting\\; room 2\r\n"));
+    assert!(ics.contains("DTSTART;VALUE=DATE:20300430\r\n"));
+    assert!(!ics.contains("Done"));
+    assert!(ics.lines().all(|line| line.len() <= 75));
+
+    doc.clock_new().unwrap();
+    doc.clock_assign(meeting).unwrap();
+    doc.clock_out().unwrap();
+    assert_eq!(doc.to_ics(&root, true).matches("BEGIN:VEVENT").count(), 3);
+}
+
+#[test]
+fn serve_the_feed_on_get() {
+    let response = request("GET /sors.ics HTTP/1.1\r\nHost: localhost\r\n\r\n", "BEGIN:VCALENDAR\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/calendar"));
+    assert!(response.contains("Content-Length: 17\r\n"));
+    assert!(response.ends_with("\r\n\r\nBEGIN:VCALENDAR\r\n"));
+
+    assert!(request("POST / HTTP/1.1\r\n\r\n", "").starts_with("HTTP/1.1 405"));
+}
+
+fn fetch(port: u16) -> String {
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    std::io::Write::write_all(&mut stream, b"GET /sors.ics HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+/// The server runs in the background while commands change the feed.
+#[test]
+fn server_in_the_background() {
+    let server = sors::ics::IcsServer::start(0, false, "first".to_string()).unwrap();
+    let port = server.port;
+    assert!(fetch(port).ends_with("\r\n\r\nfirst"));
+    server.update("second".to_string());
+    assert!(fetch(port).ends_with("\r\n\r\nsecond"));
+    assert_eq!(server.take_log(), vec!["GET /sors.ics HTTP/1.1", "GET /sors.ics HTTP/1.1"]);
+    assert!(server.take_log().is_empty());
+    drop(server);
+    assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_err());
+}
@@ -0,0 +1,142 @@
+use sors::*;
+use sors::invariants::Violation;
+use proptest::prelude::*;
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title);
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+/// A task moved in one version stays where this version has it.
+#[test]
+fn merging_moved_tasks_keeps_one_parent() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let a = add(&mut doc, &root, "A");
+    let b = add(&mut doc, &root, "B");
+    let c = add(&mut doc, &a, "C");
+    let mut disk = doc.clone();
+    disk.move_task(&c, &b, None).unwrap();
+    let d = add(&mut disk, &b, "D");
+    let e = add(&mut disk, &d, "E");
+    add(&mut disk, &c, "F");
+
+    let entries = doc.activity.len();
+    doc.merge(&disk);
+    assert!(doc.check_invariants().is_empty());
+    assert_eq!(doc.get(&a).unwrap().children, vec![c]);
+    assert_eq!(doc.get(&b).unwrap().children, vec![d]);
+    assert_eq!(doc.get(&d).unwrap().children, vec![e]);
+    assert_eq!(doc.get(&c).unwrap().children.len(), 1);
+    assert_eq!(doc.activity.len(), entries + 3);
+    doc.merge(&disk);
+    assert_eq!(doc.activity.len(), entries + 3);
+}
+
+#[test]
+fn broken_documents_are_detected() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let a = add(&mut doc, &root, "A");
+    let b = add(&mut doc, &a, "B");
+    assert!(doc.check_invariants().is_empty());
+
+    let mut shared = doc.clone();
+    shared.modify_task(&root, |task| { task.add_child(b); Ok(()) }).unwrap();
+    assert_eq!(shared.check_invariants(), vec![Violation::SeveralParents { task: b }]);
+
+    let mut cycle = doc.clone();
+    cycle.modify_task(&root, |task| { task.remove_child(&a); Ok(()) }).unwrap();
+    cycle.modify_task(&b, |task| { task.add_child(a); Ok(()) }).unwrap();
+    let violations = cycle.check_invariants();
+    assert_eq!(violations.len(), 2);
+    assert!(violations.contains(&Violation::Unreachable { task: a }));
+    assert!(violations.contains(&Violation::Unreachable { task: b }));
+
+    let mut missing = doc.clone();
+    let ghost = Uuid::new_v4();
+    missing.modify_task(&a, |task| { task.add_child(ghost); Ok(()) }).unwrap();
+    missing.upsert_clock(Rc::new(sors::clock::Clock {
+        id: Uuid::new_v4(),
+        start: Utc::now(),
+        end: None,
+        comment: None,
+        task_id: Some(ghost),
+        category: None,
+        tags: Vec::new(),
+        channel: None,
+    }));
+    let violations = missing.check_invariants();
+    assert!(violations.contains(&Violation::MissingChild { parent: a, child: ghost }));
+    assert!(violations.iter().any(|violation| matches!(violation, Violation::MissingClockTask { task, .. } if *task == ghost)));
+}
+
+#[test]
+fn removed_tasks_keep_their_clocks() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let a = add(&mut doc, &root, "A");
+    let b = add(&mut doc, &a, "B");
+    let clock = doc.clock_new().unwrap();
+    doc.clock_assign(b).unwrap();
+    doc.clock_out().unwrap();
+    doc.remove_task(&a).unwrap();
+    assert!(doc.check_invariants().is_empty());
+    assert_eq!(doc.clock(&clock.id).unwrap().task_id, None);
+}
+
+#[cfg(feature = "cli")]
+mod commands {
+    use super::*;
+    use sors::cli::*;
+    use sors::commands::register_default_commands;
+
+    fn command() -> impl Strategy<Value = String> {
+        let index = 1..5usize;
+        prop_oneof![
+            3 => (0..100usize).prop_map(|i| format!("add Task {}", i)),
+            2 => index.clone().prop_map(|i| format!("cd {}", i)),
+            1 => Just("cd ..".to_string()),
+            1 => Just("cd /".to_string()),
+            2 => (index.clone(), prop::sample::select(vec!["..", "/", "1", "2", "../1", "'a"]))
+                .prop_map(|(i, path)| format!("mv {} {}", i, path)),
+            1 => index.clone().prop_map(|i| format!("rm {} --yes", i)),
+            1 => Just("trash empty --yes".to_string()),
+            1 => Just("restore 1".to_string()),
+            1 => index.clone().prop_map(|i| format!("cli {}", i)),
+            1 => Just("cli --channel oncall".to_string()),
+            1 => Just("clo".to_string()),
+            1 => Just("cln".to_string()),
+            1 => Just("break".to_string()),
+            1 => Just("clt travel".to_string()),
+            1 => index.clone().prop_map(|i| format!("done {}", i)),
+            1 => Just("mark a".to_string()),
+        ]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn random_commands_keep_the_document_intact(commands in prop::collection::vec(command(), 1..60)) {
+            let path = std::env::temp_dir().join(format!("sors-proptest-{}.json", Uuid::new_v4()));
+            let mut cli = Cli::new(State::new(Doc::new(), path.to_str().unwrap()), ScriptedCallbacks::new());
+            register_default_commands(&mut cli);
+            for command in commands.iter() {
+                if let Err(err) = cli.run_command(command) {
+                    prop_assert!(!err.to_string().contains("broke the document"), "{}: {}", command, err);
+                }
+                let violations = cli.state.doc.check_invariants();
+                prop_assert!(violations.is_empty(), "after {}: {:?}", command, violations);
+            }
+            cli.state.doc.save(&path).unwrap();
+            let loaded = Doc::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            prop_assert!(loaded.check_invariants().is_empty());
+            prop_assert_eq!(loaded.map.len(), cli.state.doc.map.len());
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use sors::*;
+use sors::links::*;
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str, body: &str) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title).set_body(body);
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+#[test]
+fn links_in_text() {
+    let targets = |text: &str| wiki_links(text).into_iter().map(|link| link.target).collect::<Vec<_>>();
+    assert_eq!(targets("See [[Release notes]] and [[ 3f2a9c1e ]]."), vec!["Release notes", "3f2a9c1e"]);
+    assert_eq!(targets("[[[Nested]]] [[]] [[open"), vec!["Nested"]);
+    assert_eq!(targets("[[line\nbreak]] [x](y)"), Vec::<String>::new());
+    let text = "a [[b]] c";
+    assert_eq!(wiki_links(text)[0].range, 2..7);
+}
+
+#[test]
+fn links_resolve_by_id_and_title() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let project = add(&mut doc, &root, "Project", "");
+    let notes = add(&mut doc, &project, "Release notes", "");
+    add(&mut doc, &root, "Twice", "");
+    add(&mut doc, &project, "Twice", "");
+    assert_eq!(doc.resolve_link("release NOTES"), Some(notes));
+    assert_eq!(doc.resolve_link(&short_id(&notes)), Some(notes));
+    assert_eq!(doc.resolve_link(&notes.to_string()), Some(notes));
+    assert_eq!(doc.resolve_link("Twice"), None);
+    assert_eq!(doc.resolve_link("Missing"), None);
+}
+
+#[test]
+fn backlinks_survive_moves() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let project = add(&mut doc, &root, "Project", "");
+    let target = add(&mut doc, &project, "Release notes", "");
+    let by_title = add(&mut doc, &root, "Announce", "Link to [[release notes]].");
+    let by_id = add(&mut doc, &root, "Blog", &format!("Based on [[{}]]", short_id(&target)));
+    let mut noted = Rc::new(Task::new());
+    noted.set_title("Review").add_note("Checked [[Release notes]]");
+    let noted_id = noted.id;
+    doc.add_subtask(noted, &root).unwrap();
+    add(&mut doc, &root, "Unrelated", "[[Project]]");
+    assert_eq!(doc.backlinks(&target), vec![by_title, by_id, noted_id]);
+
+    doc.move_task(&target, &by_id, None).unwrap();
+    assert_eq!(doc.backlinks(&target), vec![by_title, by_id, noted_id]);
+    let expanded = doc.expand_links("[[release notes]] and [[Nothing]]", |id| format!("{}.html", id));
+    assert_eq!(expanded, format!("[Release notes]({}.html) and [[Nothing]]", target));
+    assert!(doc.to_html(&by_title).unwrap().contains(&format!("<a href='{}.html'>Release notes</a>", target)));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn view_and_backlinks_commands() {
+    use sors::cli::*;
+    use sors::commands::register_default_commands;
+
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let target = add(&mut doc, &root, "Release notes", "");
+    add(&mut doc, &root, "Announce", "Link to [[Release notes]]");
+    let path = std::env::temp_dir().join(format!("sors-links-{}.json", Uuid::new_v4()));
+    let mut cli = Cli::new(State::new(doc, path.to_str().unwrap()), ScriptedCallbacks::new());
+    register_default_commands(&mut cli);
+    cli.run_command("view 2").unwrap();
+    assert!(cli.callbacks.take_output().contains(&format!("Link to Release notes ({})", short_id(&target))));
+    cli.run_command("cd 1").unwrap();
+    cli.run_command("backlinks").unwrap();
+    assert!(cli.callbacks.take_output().contains("Announce"));
+}
+
+#[test]
+fn urls_in_text() {
+    let text = "Ticket https://example.com/issues/42. See [docs](https://docs.rs/sors) or \
+        <http://intranet/wiki_(sors)>, and *https://example.com/x*; not ftp://host or https://";
+    assert_eq!(detect_urls(text), vec![
+        "https://example.com/issues/42",
+        "https://docs.rs/sors",
+        "http://intranet/wiki_(sors)",
+        "https://example.com/x",
+    ]);
+}
+
+#[test]
+fn links_of_a_task() {
+    let mut task = Rc::new(Task::new());
+    task.set_title("Fix https://example.com/issues/42")
+        .set_body("Spec: https://example.com/spec\nAgain https://example.com/issues/42")
+        .set_external_ref(Some("https://example.com/issues/42".to_string()))
+        .add_note("Call log https://example.com/log")
+        .add_attachment("/home/me/report.pdf");
+    assert_eq!(task.links(), vec![
+        "https://example.com/issues/42",
+        "https://example.com/spec",
+        "https://example.com/log",
+        "/home/me/report.pdf",
+    ]);
+    assert!(Task::new().links().is_empty());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn open_command() {
+    use sors::cli::*;
+    use sors::commands::register_default_commands;
+
+    let mut doc = Doc::new();
+    let root = doc.root;
+    add(&mut doc, &root, "Deploy", "Runbook https://example.com/runbook and https://example.com/dashboard");
+    let path = std::env::temp_dir().join(format!("sors-open-{}.json", Uuid::new_v4()));
+    let mut cli = Cli::new(State::new(doc, path.to_str().unwrap()), ScriptedCallbacks::new());
+    register_default_commands(&mut cli);
+    cli.run_command("cd 1").unwrap();
+    cli.run_command("open").unwrap();
+    cli.run_command("open 2").unwrap();
+    assert_eq!(cli.callbacks.opened, vec!["https://example.com/runbook", "https://example.com/dashboard"]);
+    let err = cli.run_command("open 3").unwrap_err();
+    assert!(err.to_string().contains("has 2 links"), "{}", err);
+    cli.callbacks.take_output();
+    cli.run_command("open --list").unwrap();
+    assert_eq!(cli.callbacks.take_output(), "1: https://example.com/runbook\n2: https://example.com/dashboard\n");
+}
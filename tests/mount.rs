@@ -0,0 +1,70 @@
+use sors::mount::*;
+use sors::*;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(format!("sors-mount-{}-{}.json", name, Uuid::new_v4())).to_string_lossy().to_string()
+}
+
+fn titles(doc: &Doc) -> Vec<String> {
+    let mut titles: Vec<String> = doc.map.values().map(|task| task.title.clone()).collect();
+    titles.sort();
+    titles
+}
+
+#[test]
+fn save_routes_subtrees_back() {
+    let (work_path, personal_path) = (temp_path("work"), temp_path("personal"));
+    let mut work = Doc::new();
+    let task = std::rc::Rc::new(Task::new());
+    work.add_subtask(task.clone(), &work.root.clone()).unwrap();
+    work.clock_new().unwrap();
+    work.clock_assign(task.id).unwrap();
+    work.save(&work_path).unwrap();
+    Doc::new().save(&personal_path).unwrap();
+
+    let (mut doc, mounts) = load_mounts(&[work_path.clone(), personal_path.clone()]).unwrap();
+    let root = doc.get(&doc.root).unwrap();
+    assert_eq!(root.children, vec![mounts[0].root, mounts[1].root]);
+
+    let mut personal_task = std::rc::Rc::new(Task::new());
+    personal_task.set_title("personal task");
+    doc.add_subtask(personal_task, &mounts[1].root).unwrap();
+    doc.save_mounts(&mounts).unwrap();
+
+    let work = Doc::load(&work_path).unwrap();
+    let personal = Doc::load(&personal_path).unwrap();
+    assert_eq!(work.map.len(), 2);
+    assert_eq!(work.clocks.len(), 1);
+    assert!(work.current_clock.is_some());
+    assert_eq!(titles(&personal), vec!["personal task".to_string(), mounts[1].name()]);
+    assert!(personal.clocks.is_empty());
+    assert!(personal.current_clock.is_none());
+
+    std::fs::remove_file(&work_path).unwrap();
+    std::fs::remove_file(&personal_path).unwrap();
+}
+
+#[test]
+fn trash_stays_with_its_document() {
+    let (first_path, second_path) = (temp_path("first"), temp_path("second"));
+    let (mut doc, mounts) = load_mounts(&[first_path.clone(), second_path.clone()]).unwrap();
+    let mut removed = std::rc::Rc::new(Task::new());
+    removed.set_title("removed");
+    let removed_id = removed.id;
+    doc.add_subtask(removed, &mounts[1].root).unwrap();
+    doc.move_to_trash(&removed_id).unwrap();
+
+    let first = doc.extract(&mounts[0], true);
+    let second = doc.extract(&mounts[1], false);
+    assert!(first.trash_entries.is_empty());
+    assert!(!first.map.contains_key(&removed_id));
+    assert_eq!(second.trash_entries.len(), 1);
+    assert!(second.map.contains_key(&removed_id));
+
+    doc.save_mounts(&mounts).unwrap();
+    let (mut doc, _) = load_mounts(&[first_path.clone(), second_path.clone()]).unwrap();
+    doc.restore(0).unwrap();
+    assert_eq!(doc.find_parent(&removed_id), Some(mounts[1].root));
+    std::fs::remove_file(&first_path).unwrap();
+    std::fs::remove_file(&second_path).unwrap();
+}
@@ -0,0 +1,56 @@
+use sors::query::*;
+use sors::*;
+use chrono::TimeZone;
+
+fn add(doc: &mut Doc, title: &str, modify: impl Fn(&mut Rc<Task>)) {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title.to_string());
+    modify(&mut task);
+    doc.add_subtask(task, &doc.root.clone()).unwrap();
+}
+
+fn titles(doc: &Doc, query: &str) -> Vec<String> {
+    let mut titles: Vec<String> = doc.query(query).unwrap().iter().map(|task| task.title.clone()).collect();
+    titles.sort();
+    titles
+}
+
+fn sample() -> Doc {
+    let mut doc = Doc::new();
+    let date = |date: &str| Some(Local.datetime_from_str(&format!("{} 12:00", date), "%Y-%m-%d %H:%M").unwrap());
+    add(&mut doc, "Write report", |task| { task.set_progress(Progress::Todo).add_tag("urgent").set_due(date("2024-06-15")); });
+    add(&mut doc, "Review code", |task| { task.set_progress(Progress::Todo).add_tag("urgent").set_due(date("2024-08-01")); });
+    add(&mut doc, "Plan sprint", |task| { task.set_progress(Progress::Done).set_pinned(true); });
+    add(&mut doc, "Idea", |_| {});
+    doc
+}
+
+#[test]
+fn comparisons() {
+    let doc = sample();
+    assert_eq!(titles(&doc, "progress=todo and tag=urgent and due<2024-07-01"), vec!["Write report"]);
+    assert_eq!(titles(&doc, "progress=none"), vec!["Idea"]);
+    assert_eq!(titles(&doc, "title~RE"), vec!["Review code", "Write report"]);
+    assert_eq!(titles(&doc, "due>=2024-06-15"), vec!["Review code", "Write report"]);
+    assert_eq!(titles(&doc, "due=2024-08-01"), vec!["Review code"]);
+    assert_eq!(titles(&doc, "tag!=urgent"), vec!["Idea", "Plan sprint"]);
+    assert_eq!(titles(&doc, "pinned=true"), vec!["Plan sprint"]);
+    assert_eq!(titles(&doc, "title=\"plan sprint\""), vec!["Plan sprint"]);
+}
+
+#[test]
+fn combinations() {
+    let doc = sample();
+    assert_eq!(titles(&doc, "progress=done or title=idea"), vec!["Idea", "Plan sprint"]);
+    assert_eq!(titles(&doc, "not tag=urgent and not progress=none"), vec!["Plan sprint"]);
+    assert_eq!(titles(&doc, "title~review or title~write and due<2024-07-01"), vec!["Review code", "Write report"]);
+    assert_eq!(titles(&doc, "(title~review or title~write) and due<2024-07-01"), vec!["Write report"]);
+}
+
+#[test]
+fn errors() {
+    for query in &["", "color=red", "title", "title=", "title<a", "progress=later", "due<none", "due<tomorow",
+                   "(title=a", "title=a)", "title=a and", "title=\"a", "pinned~true"] {
+        assert!(Query::parse(query).is_err(), "{} should fail", query);
+    }
+}
@@ -0,0 +1,40 @@
+use sors::output::Style;
+use sors::render;
+
+/// Styles as tags, so tests see what is painted.
+fn tags(style: Style, text: &str) -> String {
+    format!("<{:?}>{}</{:?}>", style, text, style)
+}
+
+#[test]
+fn headings_lists_and_emphasis() {
+    let body = "# Release\r\n\r\nShip *soon* and **well**, see [notes](https://example.com).\r\n\r\n* Build `cargo`\r\n* Test\r\n\r\n## Steps\r\n\r\n> Quoted\r\n\r\n---\r\n";
+    let rendered = render::to_terminal(body, &tags);
+    assert_eq!(rendered, "<Heading>Release</Heading>\n=======\n\n\
+        Ship <Emphasis>soon</Emphasis> and <Strong>well</Strong>, see <Link>notes</Link> (https://example.com).\n\n\
+        • Build <Code>cargo</Code>\n• Test\n\n\
+        <Heading>Steps</Heading>\n-----\n\n\
+        │ Quoted\n\n\
+        ────────────────────────────────────────");
+}
+
+#[test]
+fn code_blocks_are_indented() {
+    let rendered = render::to_terminal("Run it:\n\n    cargo test\n    cargo bench\n", &tags);
+    assert_eq!(rendered, "Run it:\n\n    <Code>cargo test</Code>\n    <Code>cargo bench</Code>");
+}
+
+#[test]
+fn plain_text_is_unchanged() {
+    let plain = |_: Style, text: &str| text.to_string();
+    assert_eq!(render::to_terminal("Just a line", &plain), "Just a line");
+    assert_eq!(render::to_terminal("", &plain), "");
+}
+
+#[test]
+fn export_and_terminal_read_the_same_blocks() {
+    let body = "# Title\r\n\r\n* one\r\n* two\r\n";
+    assert_eq!(render::parse(body), render::parse(&body.replace("\r\n", "\n")));
+    assert_eq!(render::to_html(body), render::to_html(&body.replace("\r\n", "\n")));
+    assert!(render::to_html(body).contains("<h1 id='title'>Title</h1>"), "{}", render::to_html(body));
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "cli")]
+
+use sors::cli::*;
+use sors::commands::register_default_commands;
+use sors::*;
+
+/// Commands which block, open a terminal UI or need the network.
+const SKIPPED: &[&str] = &["serve-ics", "tui", "import-github", "caldav-sync", "sync-status"];
+
+fn new_cli(doc: Doc, callbacks: ScriptedCallbacks) -> Cli<State, ScriptedCallbacks> {
+    let path = std::env::temp_dir().join(format!("sors-scripted-{}.json", Uuid::new_v4()));
+    let mut cli = Cli::new(State::new(doc, path.to_str().unwrap()), callbacks);
+    register_default_commands(&mut cli);
+    cli
+}
+
+#[test]
+fn editor_commands() {
+    let callbacks = ScriptedCallbacks::new()
+        .edit("Write report\n\nWith the numbers of Q2")
+        .edit("Draft report\n\nWith the numbers of Q3");
+    let mut cli = new_cli(Doc::new(), callbacks);
+    cli.run_command("add").unwrap();
+    cli.run_command("add -e Slides").unwrap();
+    let root = cli.state.doc.root;
+    let children = cli.state.doc.get(&root).unwrap().children.clone();
+    let report = cli.state.doc.get(&children[0]).unwrap();
+    assert_eq!(report.title, "Write report");
+    assert_eq!(report.body, "With the numbers of Q2");
+    assert_eq!(cli.state.doc.get(&children[1]).unwrap().title, "Draft report");
+    assert_eq!(cli.callbacks.edited, vec!["\n\n".to_string(), "Slides\n\n".to_string()]);
+
+    // Without queued edits the editor keeps the text.
+    cli.run_command("cd 1").unwrap();
+    cli.run_command("ed").unwrap();
+    assert_eq!(cli.state.doc.get(&children[0]).unwrap().body, "With the numbers of Q2");
+    assert_eq!(cli.callbacks.edited.last().unwrap(), "Write report\n\nWith the numbers of Q2");
+}
+
+#[test]
+fn prompts_are_answered_from_the_script() {
+    let mut cli = new_cli(Doc::new(), ScriptedCallbacks::new().input("n").input("y"));
+    cli.run_command("add Old").unwrap();
+    cli.run_command("rm 1").unwrap();
+    assert_eq!(cli.state.doc.get(&cli.state.doc.root).unwrap().children.len(), 1);
+    cli.run_command("rm 1").unwrap();
+    assert!(cli.state.doc.get(&cli.state.doc.root).unwrap().children.is_empty());
+    assert_eq!(cli.callbacks.prompts.len(), 2);
+    assert!(cli.callbacks.input.is_empty());
+}
+
+/// Every command runs without arguments and with each example of its help
+/// on a generated document, without panics and without breaking it.
+#[test]
+fn every_command_and_example() {
+    let dir = std::env::temp_dir().join(format!("sors-scripted-{}", Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    // Examples write files like `acme-june.json` to the working directory.
+    std::env::set_current_dir(&dir).unwrap();
+
+    let mut names: Vec<String> = new_cli(Doc::new(), ScriptedCallbacks::new()).help.keys().cloned().collect();
+    names.sort();
+    assert!(names.len() > 100);
+    for name in names.iter().filter(|name| !SKIPPED.contains(&name.as_str())) {
+        let mut cli = new_cli(Doc::generate_random(1, 30), ScriptedCallbacks::new());
+        let help = cli.help[name].clone();
+        assert!(help.usage.starts_with(name.as_str()), "usage of {}: {}", name, help.usage);
+        let lines = std::iter::once(name.clone())
+            .chain(help.examples.iter().filter(|example| !example.contains("/tmp/")).cloned());
+        for line in lines {
+            if let Err(err) = cli.run_command(&line) {
+                assert!(!err.to_string().contains("broke the document"), "{}: {}", line, err);
+            }
+            let violations = cli.state.doc.check_invariants();
+            assert!(violations.is_empty(), "after {}: {:?}", line, violations);
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_edits_are_reported() {
+    let mut cli = new_cli(Doc::new(), ScriptedCallbacks::new().failed_edit().failed_edit());
+    let err = cli.run_command("add").unwrap_err();
+    assert!(err.to_string().contains("editor"), "{}", err);
+    assert!(cli.state.doc.get(&cli.state.doc.root).unwrap().children.is_empty());
+
+    cli.run_command("add Slides").unwrap();
+    cli.run_command("cd 1").unwrap();
+    assert!(cli.run_command("ed").is_err());
+    let task = cli.state.doc.get(&cli.state.doc.get(&cli.state.doc.root).unwrap().children[0]).unwrap();
+    assert_eq!(task.title, "Slides");
+}
+
+#[test]
+fn empty_edits_are_cancelled() {
+    let mut cli = new_cli(Doc::new(), ScriptedCallbacks::new().edit("").edit("Report\n\nNotes"));
+    cli.run_command("add Report").unwrap();
+    cli.run_command("cd 1").unwrap();
+    let err = cli.run_command("ed").unwrap_err();
+    assert!(err.to_string().contains("empty"), "{}", err);
+    assert_eq!(cli.state.doc.get(&cli.state.wt).unwrap().title, "Report");
+    cli.run_command("ed").unwrap();
+    assert_eq!(cli.state.doc.get(&cli.state.wt).unwrap().body, "Notes");
+}
+
+#[test]
+fn wide_titles_line_up() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    for title in &["報告書", "Plan 🚀", "Slides"] {
+        let mut task = Rc::new(Task::new());
+        task.set_title(title);
+        let task_id = task.id;
+        doc.add_subtask(task, &root).unwrap();
+        doc.clock_new().unwrap();
+        doc.clock_assign(task_id).unwrap();
+        doc.clock_out().unwrap();
+    }
+    let mut cli = new_cli(doc, ScriptedCallbacks::new());
+    for command in &["ls --time", "outline --time"] {
+        cli.run_command(command).unwrap();
+        let output = cli.callbacks.take_output();
+        let columns: Vec<usize> = output.lines()
+            .filter_map(|line| line.rfind(" (").map(|index| sors::text::display_width(&line[..index])))
+            .collect();
+        assert!(columns.len() >= 3, "{}", output);
+        assert!(columns.iter().all(|column| *column == columns[0]), "{}", output);
+    }
+}
+
+#[test]
+fn view_renders_markdown() {
+    let mut cli = new_cli(Doc::new(), ScriptedCallbacks::new());
+    cli.run_command("add Release").unwrap();
+    cli.run_command("cd 1").unwrap();
+    let mut task = cli.state.doc.get(&cli.state.wt).unwrap();
+    task.set_body("## Steps\n\n* Build `cargo`\n* **Test**");
+    cli.state.doc.upsert(task);
+    cli.run_command("note Asked *Ana*").unwrap();
+    cli.run_command("view").unwrap();
+    let output = cli.callbacks.take_output();
+    assert!(output.starts_with("Release\n\nSteps\n-----\n\n• Build cargo\n• Test\n\n--- Notes: \n"), "{}", output);
+    assert!(output.trim_end().ends_with("Asked Ana"), "{}", output);
+
+    cli.run_command("cd ..").unwrap();
+    cli.run_command("view 1").unwrap();
+    assert!(cli.callbacks.take_output().starts_with("Release\n"));
+}
@@ -0,0 +1,163 @@
+use sors::*;
+use sors::split::*;
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title);
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+fn titles(doc: &Doc, task_id: &Uuid) -> Vec<String> {
+    doc.get(task_id).unwrap().children.iter()
+        .map(|child| doc.get(child).unwrap().title.clone())
+        .collect()
+}
+
+const BODY: &str = "Plan for the launch\r\n\r\n- [ ] Write docs\r\n  * [x] Review API\r\nNot an item: [ ] here\r\n+ [X] Tag release\r\n- [ ]\r\n";
+
+#[test]
+fn checklist_lines() {
+    let items = checklist_items(BODY);
+    let titles: Vec<_> = items.iter().map(|item| (item.line, item.title.as_str(), item.done)).collect();
+    assert_eq!(titles, vec![(2, "Write docs", false), (3, "Review API", true), (5, "Tag release", true)]);
+}
+
+#[test]
+fn item_lists() {
+    assert_eq!(parse_item_list("all", 3), Some(vec![0, 1, 2]));
+    assert_eq!(parse_item_list("none", 3), Some(vec![]));
+    assert_eq!(parse_item_list("", 3), Some(vec![]));
+    assert_eq!(parse_item_list("3, 1-2,2", 3), Some(vec![0, 1, 2]));
+    assert_eq!(parse_item_list("4", 3), None);
+    assert_eq!(parse_item_list("0", 3), None);
+    assert_eq!(parse_item_list("3-1", 3), None);
+    assert_eq!(parse_item_list("x", 3), None);
+}
+
+#[test]
+fn checklist_becomes_subtasks() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let launch = add(&mut doc, &root, "Launch");
+    add(&mut doc, &launch, "Existing");
+    doc.modify_task(&launch, |task| { task.set_body(BODY); Ok(()) }).unwrap();
+    let task_ids = doc.checklist_to_subtasks(&launch, &[0, 2]).unwrap();
+    assert_eq!(titles(&doc, &launch), vec!["Existing", "Write docs", "Tag release"]);
+    assert_eq!(doc.get(&task_ids[0]).unwrap().progress, Some(Progress::Todo));
+    assert_eq!(doc.get(&task_ids[1]).unwrap().progress, Some(Progress::Done));
+    assert_eq!(doc.get(&launch).unwrap().body, "Plan for the launch\r\n\r\n  * [x] Review API\r\nNot an item: [ ] here\r\n- [ ]");
+
+    assert!(doc.checklist_to_subtasks(&launch, &[1]).is_err());
+    assert_eq!(titles(&doc, &launch).len(), 3);
+}
+
+#[test]
+fn children_move_up_behind_the_task() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    add(&mut doc, &root, "Before");
+    let big = add(&mut doc, &root, "Big");
+    add(&mut doc, &root, "After");
+    let a = add(&mut doc, &big, "A");
+    let b = add(&mut doc, &big, "B");
+    let c = add(&mut doc, &big, "C");
+    doc.promote_children(&big, &[c, a]).unwrap();
+    assert_eq!(titles(&doc, &root), vec!["Before", "Big", "A", "C", "After"]);
+    assert_eq!(titles(&doc, &big), vec!["B"]);
+
+    let other = add(&mut doc, &root, "Other");
+    assert!(doc.promote_children(&big, &[b, other]).is_err());
+    assert_eq!(titles(&doc, &big), vec!["B"]);
+    assert!(doc.promote_children(&root, &[big]).is_err());
+    assert!(doc.check_invariants().is_empty());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn split_command() {
+    use sors::cli::*;
+    use sors::commands::register_default_commands;
+
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let launch = add(&mut doc, &root, "Launch");
+    add(&mut doc, &root, "Party");
+    add(&mut doc, &launch, "Existing");
+    doc.modify_task(&launch, |task| { task.set_body(BODY); Ok(()) }).unwrap();
+    let path = std::env::temp_dir().join(format!("sors-split-{}.json", Uuid::new_v4()));
+    let callbacks = ScriptedCallbacks::new().input("9").input("1,3").input("2-3");
+    let mut cli = Cli::new(State::new(doc, path.to_str().unwrap()), callbacks);
+    register_default_commands(&mut cli);
+    cli.run_command("cd 1").unwrap();
+    cli.run_command("split").unwrap();
+    assert!(cli.callbacks.take_output().ends_with("Added 2 subtasks, moved 2 children up\n"));
+    assert_eq!(titles(&cli.state.doc, &launch), vec!["Existing"]);
+    assert_eq!(titles(&cli.state.doc, &root), vec!["Launch", "Write docs", "Tag release", "Party"]);
+
+    cli.run_command("split checklist").unwrap();
+    assert_eq!(titles(&cli.state.doc, &launch), vec!["Existing", "Review API"]);
+    cli.run_command("split children 2").unwrap();
+    assert_eq!(titles(&cli.state.doc, &root), vec!["Launch", "Review API", "Write docs", "Tag release", "Party"]);
+    assert!(cli.run_command("split checklist 1").is_err());
+}
+
+#[test]
+fn tasks_become_checklist_lines_and_back() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let launch = add(&mut doc, &root, "Launch");
+    let docs = add(&mut doc, &launch, "Write docs");
+    let review = add(&mut doc, &launch, "Review API");
+    let nested = add(&mut doc, &review, "Nested");
+    doc.set_progress(&nested, Progress::Done).unwrap();
+    doc.modify_task(&launch, |task| { task.set_body("Plan for the launch\n"); Ok(()) }).unwrap();
+    let clock = doc.clock_new().unwrap();
+    doc.clock_assign(docs).unwrap();
+
+    assert!(doc.task_to_checklist(&review).is_err());
+    assert!(doc.task_to_checklist(&root).is_err());
+    doc.task_to_checklist(&nested).unwrap();
+    assert_eq!(doc.get(&review).unwrap().body, "- [x] Nested");
+    doc.task_to_checklist(&docs).unwrap();
+    doc.task_to_checklist(&review).unwrap();
+    let body = doc.get(&launch).unwrap().body.clone();
+    assert_eq!(body, "Plan for the launch\n\n- [ ] Write docs\n- [ ] Review API");
+    assert!(doc.get(&launch).unwrap().children.is_empty());
+    assert_eq!(doc.clock(&clock.id).unwrap().task_id, Some(launch));
+    assert_eq!(doc.trash_entries.len(), 3);
+    assert!(doc.check_invariants().is_empty());
+
+    let task_ids = doc.checklist_to_subtasks(&launch, &[0, 1]).unwrap();
+    assert_eq!(titles(&doc, &launch), vec!["Write docs", "Review API"]);
+    assert_eq!(doc.get(&task_ids[0]).unwrap().progress, Some(Progress::Todo));
+    assert_eq!(doc.get(&launch).unwrap().body, "Plan for the launch");
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn demote_and_promote_commands() {
+    use sors::cli::*;
+    use sors::commands::register_default_commands;
+
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let launch = add(&mut doc, &root, "Launch");
+    add(&mut doc, &launch, "Write docs");
+    add(&mut doc, &launch, "Review API");
+    let path = std::env::temp_dir().join(format!("sors-demote-{}.json", Uuid::new_v4()));
+    let mut cli = Cli::new(State::new(doc, path.to_str().unwrap()), ScriptedCallbacks::new());
+    register_default_commands(&mut cli);
+    cli.run_command("cd 1/2").unwrap();
+    cli.run_command("demote").unwrap();
+    assert_eq!(cli.state.wt, launch);
+    cli.run_command("demote 1").unwrap();
+    assert_eq!(cli.state.doc.get(&launch).unwrap().body, "- [ ] Review API\n- [ ] Write docs");
+    cli.callbacks.take_output();
+    cli.run_command("promote").unwrap();
+    assert_eq!(cli.callbacks.take_output(), "1: [ ] Review API\n2: [ ] Write docs\n");
+    cli.run_command("promote 2").unwrap();
+    assert_eq!(titles(&cli.state.doc, &launch), vec!["Write docs"]);
+    assert!(cli.run_command("promote 5").is_err());
+}
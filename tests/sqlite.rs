@@ -0,0 +1,72 @@
+#![cfg(feature = "sqlite")]
+use sors::*;
+use sors::sqlite::SqliteDb;
+use chrono::{NaiveDate, TimeZone};
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title);
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+#[test]
+fn save_and_load_incrementally() {
+    let path = std::env::temp_dir().join(format!("sors-test-{}.db", Uuid::new_v4()));
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let report = add(&mut doc, &root, "Quarterly report");
+    doc.upsert_clock(Rc::new(sors::clock::Clock {
+        id: Uuid::new_v4(),
+        start: Utc.ymd(2030, 5, 1).and_hms(9, 0, 0),
+        end: Some(Utc.ymd(2030, 5, 1).and_hms(11, 0, 0)),
+        comment: None,
+        task_id: Some(report),
+        category: None,
+        tags: Vec::new(),
+        channel: None,
+    }));
+    doc.bookmarks.insert("work".to_string(), report);
+    doc.save(&path).unwrap();
+
+    let mut loaded = Doc::load(&path).unwrap();
+    assert_eq!(loaded.get(&report).unwrap().title, "Quarterly report");
+    assert_eq!(loaded.clocks.len(), 1);
+    assert_eq!(loaded.bookmarks["work"], report);
+
+    // Only the changes are written, there is no journal.
+    let invoice = add(&mut loaded, &root, "Invoice 100%");
+    loaded.modify_task(&report, |task| { task.set_body("Numbers for Q2"); Ok(()) }).unwrap();
+    loaded.save_journal(&path).unwrap();
+    assert!(!sors::journal::journal_path(&path).exists());
+    assert!(!loaded.has_unsaved_changes());
+    let reloaded = Doc::load(&path).unwrap();
+    assert_eq!(reloaded.get(&root).unwrap().children, vec![report, invoice]);
+    assert_eq!(reloaded.get(&report).unwrap().body, "Numbers for Q2");
+    assert_eq!(reloaded.activity.len(), loaded.activity.len());
+
+    let db = SqliteDb::open(&path).unwrap();
+    assert_eq!(db.search("q2").unwrap(), vec![report]);
+    assert_eq!(db.search("100%").unwrap(), vec![invoice]);
+    assert_eq!(db.search("%").unwrap(), vec![invoice]);
+    assert_eq!(db.clocks_between(Utc.ymd(2030, 5, 1).and_hms(0, 0, 0), Utc.ymd(2030, 5, 2).and_hms(0, 0, 0)).unwrap().len(), 1);
+    assert!(db.clocks_between(Utc.ymd(2030, 5, 2).and_hms(0, 0, 0), Utc.ymd(2030, 5, 3).and_hms(0, 0, 0)).unwrap().is_empty());
+
+    // Reports and queries ask the database while everything is saved.
+    let mut saved = Doc::load(&path).unwrap();
+    assert_eq!(saved.database, Some(db));
+    saved.clocks.clear();
+    assert_eq!(saved.range_clock(NaiveDate::from_ymd(2030, 5, 1), NaiveDate::from_ymd(2030, 5, 1), None).len(), 1);
+    assert!(saved.day_clock(NaiveDate::from_ymd(2030, 5, 2), None).is_empty());
+    let found: Vec<Uuid> = saved.query("title~QUARTERLY and progress=none").unwrap().iter().map(|task| task.id).collect();
+    assert_eq!(found, vec![report]);
+    saved.modify_task(&invoice, |task| { task.set_title("Überweisung"); Ok(()) }).unwrap();
+    assert!(saved.range_clock(NaiveDate::from_ymd(2030, 5, 1), NaiveDate::from_ymd(2030, 5, 1), None).is_empty());
+    saved.save_journal(&path).unwrap();
+    assert_eq!(saved.query("title~überweisung").unwrap()[0].id, invoice);
+    assert_eq!(SqliteDb::open(&path).unwrap().search("ÜBER").unwrap(), vec![invoice]);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(SqliteDb::open("ssh://server/tasks.db").is_err());
+}
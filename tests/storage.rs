@@ -0,0 +1,112 @@
+use sors::*;
+use sors::storage::*;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn parse_ssh_paths() {
+    let ssh = SshStorage::parse("ssh://me@server:2222/~/it's tasks.json").unwrap();
+    assert_eq!(ssh, SshStorage { host: "me@server".to_string(), port: Some(2222), path: "~/it's tasks.json".to_string() });
+    assert_eq!(ssh.quoted_path(".tmp"), "~/'it'\\''s tasks.json.tmp'");
+    assert_eq!(SshStorage::parse("ssh://server/srv/tasks.json").unwrap().quoted_path(""), "'/srv/tasks.json'");
+    assert_eq!(SshStorage::parse("ssh://server/srv/"), None);
+    assert_eq!(SshStorage::parse("/srv/tasks.json"), None);
+    assert!(is_remote("ssh://server/tasks.json"));
+    assert!(!is_remote("tasks.json"));
+}
+
+/// Documents on a "remote" machine which is a fake `ssh` running the
+/// command locally with the directory as home.
+#[test]
+fn load_and_save_over_ssh() {
+    let dir = std::env::temp_dir().join(format!("sors-ssh-{}", Uuid::new_v4()));
+    let bin = dir.join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let ssh = bin.join("ssh");
+    std::fs::write(&ssh, "#!/bin/sh\nwhile [ \"$1\" != -- ]; do shift; done\nexec sh -c \"$3\"\n").unwrap();
+    std::fs::set_permissions(&ssh, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::env::set_var("PATH", format!("{}:{}", bin.display(), std::env::var("PATH").unwrap()));
+    std::env::set_var("HOME", &dir);
+
+    let path = "ssh://server:22/~/tasks.cbor";
+    assert!(!storage(path).exists());
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let mut task = Rc::new(Task::new());
+    task.set_title("Remote");
+    let task_id = task.id;
+    doc.add_subtask(task, &root).unwrap();
+    doc.save_journal(path).unwrap();
+    assert!(storage(path).exists());
+    assert!(dir.join("tasks.cbor").exists());
+    assert!(!dir.join("tasks.cbor.tmp").exists());
+    assert_eq!(Doc::load(path).unwrap().get(&task_id).unwrap().title, "Remote");
+
+    assert!(Doc::load("ssh://server/~/missing.json").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn documents_in_memory() {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    doc.modify_task(&root, |task| { task.set_title("Memory"); Ok(()) }).unwrap();
+    assert!(!storage("memory://doc.cbor").exists());
+    doc.save_journal("memory://doc.cbor").unwrap();
+    assert!(storage("memory://doc.cbor").exists());
+    assert!(is_remote("memory://doc.cbor"));
+    let loaded = Doc::load("memory://doc.cbor").unwrap();
+    assert_eq!(loaded.get(&loaded.root).unwrap().title, "Memory");
+    assert!(Doc::load("memory://missing.json").is_err());
+}
+
+/// A crash while appending to the journal leaves a half-written last line.
+#[test]
+fn half_written_journal_line() {
+    let dir = std::env::temp_dir().join(format!("sors-test-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tasks.json");
+    let journal = sors::journal::journal_path(&path);
+    let mut doc = Doc::new();
+    let root = doc.root;
+    doc.save(&path).unwrap();
+    doc.modify_task(&root, |task| { task.set_title("Kept"); Ok(()) }).unwrap();
+    doc.save_journal(&path).unwrap();
+    let complete = std::fs::read_to_string(&journal).unwrap();
+    doc.modify_task(&root, |task| { task.set_body("Lost"); Ok(()) }).unwrap();
+    doc.save_journal(&path).unwrap();
+    let written = std::fs::read_to_string(&journal).unwrap();
+    std::fs::write(&journal, &written[..written.len() - 10]).unwrap();
+
+    let mut loaded = Doc::load(&path).unwrap();
+    assert!(loaded.journal_was_cut());
+    assert_eq!(loaded.get(&root).unwrap().title, "Kept");
+    assert_eq!(loaded.get(&root).unwrap().body, "");
+    loaded.modify_task(&root, |task| { task.set_body("Again"); Ok(()) }).unwrap();
+    loaded.save_journal(&path).unwrap();
+    assert!(std::fs::read_to_string(&journal).unwrap().starts_with(&complete));
+    let reloaded = Doc::load(&path).unwrap();
+    assert!(!reloaded.journal_was_cut());
+    assert_eq!(reloaded.get(&root).unwrap().body, "Again");
+
+    // Only the last line may be broken.
+    std::fs::write(&journal, format!("{{\n{}", complete)).unwrap();
+    assert!(Doc::load(&path).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn objects_in_s3() {
+    let config = S3Config { region: "eu-central-1".to_string(), ..S3Config::default() };
+    let s3 = S3Storage::parse("s3://tasks/me/my tasks.json", config.clone()).unwrap();
+    assert_eq!((s3.bucket.as_str(), s3.key.as_str()), ("tasks", "me/my tasks.json"));
+    assert_eq!(s3.url(), "https://s3.eu-central-1.amazonaws.com/tasks/me/my%20tasks.json");
+    let minio = S3Config { endpoint: "http://localhost:9000/".to_string(), ..config.clone() };
+    assert_eq!(S3Storage::parse("s3://tasks/a.json", minio).unwrap().url(), "http://localhost:9000/tasks/a.json");
+    assert_eq!(S3Storage::parse("s3://tasks/", config.clone()), None);
+    assert_eq!(S3Storage::parse("ssh://tasks/a.json", config), None);
+
+    // Without credentials nothing is sent.
+    assert!(is_remote("s3://tasks/a.json"));
+    assert!(!storage("s3://tasks/a.json").exists());
+    assert!(Doc::new().save("s3://tasks/a.json").is_err());
+}
@@ -0,0 +1,49 @@
+use sors::text::*;
+use sors::Task;
+
+#[test]
+fn widths_of_wide_and_combining_characters() {
+    assert_eq!(display_width("Report"), 6);
+    assert_eq!(display_width("報告書"), 6);
+    assert_eq!(display_width("🚀 Launch"), 9);
+    assert_eq!(display_width("Cafe\u{301}"), 4);
+}
+
+#[test]
+fn truncate_and_pad() {
+    assert_eq!(truncate_to_width("Report", 10), "Report");
+    assert_eq!(truncate_to_width("Quarterly report", 10), "Quarterly…");
+    // The wide character doesn't fit next to the ellipsis and isn't split.
+    assert_eq!(truncate_to_width("報告書です", 6), "報告…");
+    assert_eq!(truncate_to_width("報告書です", 5), "報告…");
+    assert_eq!(truncate_to_width("Report", 0), "");
+    assert_eq!(pad_to_width("報告", 6), "報告  ");
+    assert_eq!(pad_to_width("Quarterly report", 6), "Quarterly report");
+    for title in &["Plan 🚀", "報告書です", "Cafe\u{301} menu", "x"] {
+        assert_eq!(display_width(&fit_to_width(title, 7)), 7, "{}", title);
+    }
+    assert_eq!(column_width(vec!["a", "報告書"], 50), 6);
+    assert_eq!(column_width(vec!["a", "報告書"], 4), 4);
+    assert_eq!(column_width(Vec::new(), 4), 0);
+}
+
+#[test]
+fn edit_text_round_trip() {
+    let mut task = Task::new();
+    task.title = "報告 🚀".to_string();
+    task.body = "  indented first line\r\nsecond line\r\n\r\nafter a blank line".to_string();
+    assert_eq!(Task::parse_edit_text(&task.edit_text()), (task.title.clone(), task.body.clone()));
+
+    task.body = String::new();
+    assert_eq!(Task::parse_edit_text(&task.edit_text()), (task.title.clone(), String::new()));
+}
+
+#[test]
+fn edit_text_splitting() {
+    assert_eq!(Task::parse_edit_text(""), (String::new(), String::new()));
+    assert_eq!(Task::parse_edit_text("\n\n"), (String::new(), String::new()));
+    assert_eq!(Task::parse_edit_text("Title"), ("Title".to_string(), String::new()));
+    assert_eq!(Task::parse_edit_text("\u{feff}Title\r\n\r\nBody\r\n"), ("Title".to_string(), "Body".to_string()));
+    assert_eq!(Task::parse_edit_text("Title\nBody right below\n  \n"), ("Title".to_string(), "Body right below".to_string()));
+    assert_eq!(Task::parse_edit_text("Title\n \n\t\n- item\n  - nested\n\n"), ("Title".to_string(), "- item\n  - nested".to_string()));
+}
@@ -0,0 +1,92 @@
+use sors::*;
+use chrono::{NaiveDate, TimeZone};
+
+/// Document in the old format with a clock in local time.
+fn old_doc_file(start: &str, end: &str) -> std::path::PathBuf {
+    let mut value = serde_json::to_value(Doc::new()).unwrap();
+    value.as_object_mut().unwrap().remove("timezone");
+    let clock_id = Uuid::new_v4();
+    value["clocks"] = serde_json::json!({
+        clock_id.to_string(): { "id": clock_id, "start": start, "end": end, "comment": null, "task_id": null }
+    });
+    let path = std::env::temp_dir().join(format!("sors-timezone-{}.json", Uuid::new_v4()));
+    std::fs::write(&path, value.to_string()).unwrap();
+    path
+}
+
+#[test]
+fn local_clocks_are_migrated_to_utc() {
+    let path = old_doc_file("2024-03-02T00:30:00+02:00", "2024-03-02T01:30:00+02:00");
+    let doc = Doc::load(&path).unwrap();
+    assert_eq!(doc.timezone, DisplayZone::Local);
+    let clock = doc.clocks.values().next().unwrap().clone();
+    assert_eq!(clock.start, Utc.ymd(2024, 3, 1).and_hms(22, 30, 0));
+    assert_eq!(clock.duration(), chrono::Duration::hours(1));
+
+    doc.save(&path).unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(content.contains("2024-03-01T22:30:00Z"));
+    assert!(!content.contains("+02:00"));
+}
+
+#[test]
+fn reports_use_the_display_zone() {
+    let path = old_doc_file("2024-03-02T00:30:00+02:00", "2024-03-02T01:30:00+02:00");
+    let mut doc = Doc::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let (first, second) = (NaiveDate::from_ymd(2024, 3, 1), NaiveDate::from_ymd(2024, 3, 2));
+
+    doc.timezone = "+02:00".parse().unwrap();
+    assert_eq!(doc.day_clock(second, None).len(), 1);
+    assert!(doc.day_clock(first, None).is_empty());
+    assert_eq!(doc.worked_time(second), chrono::Duration::hours(1));
+
+    doc.timezone = "utc".parse().unwrap();
+    assert_eq!(doc.day_clock(first, None).len(), 1);
+    assert_eq!(doc.timesheet(first, second, None).day_totals(), vec![chrono::Duration::hours(1), chrono::Duration::zero()]);
+    let edit = doc.create_clock_edit(first);
+    assert_eq!(edit.clocks.len(), 1);
+}
+
+#[test]
+fn display_zones_parse_and_print() {
+    for (input, output) in [("local", "local"), ("UTC", "UTC"), ("+02:00", "+02:00"), ("-5", "-05:00"), ("+5:30", "+05:30")] {
+        assert_eq!(input.parse::<DisplayZone>().unwrap().to_string(), output);
+    }
+    for input in ["", "Europe/Berlin", "+25:00", "2"] {
+        assert!(input.parse::<DisplayZone>().is_err());
+    }
+    let zone: DisplayZone = "-03:00".parse().unwrap();
+    let time = Utc.ymd(2024, 3, 1).and_hms(1, 0, 0);
+    assert_eq!(zone.date(time), NaiveDate::from_ymd(2024, 2, 29));
+    assert_eq!(zone.to_utc(zone.localize(time).naive_local()), Some(time));
+}
+
+#[test]
+fn forgotten_clocks_stop_at_the_end_of_the_day() {
+    let mut doc = Doc::new();
+    doc.timezone = "+02:00".parse().unwrap();
+    let overnight = doc.clock_new().unwrap().id;
+    let late = doc.clock_new_in(Some("oncall")).unwrap().id;
+    let start = |hour| Utc.ymd(2024, 3, 1).and_hms(hour, 0, 0);
+    for (clock_id, hour) in &[(overnight, 7), (late, 20)] {
+        let mut clock = doc.clock(clock_id).unwrap();
+        std::rc::Rc::make_mut(&mut clock).start = start(*hour);
+        doc.upsert_clock(clock);
+    }
+    let end_of_day = chrono::NaiveTime::from_hms(18, 0, 0);
+
+    // 18:00 local time is 16:00 UTC, the late clock stops the next day.
+    assert!(doc.auto_clock_out(end_of_day, start(15)).is_empty());
+    let stopped = doc.auto_clock_out(end_of_day, start(23));
+    assert_eq!(stopped.len(), 1);
+    assert_eq!(doc.clock(&overnight).unwrap().end, Some(start(16)));
+    assert!(doc.running_clock().is_none());
+    assert_eq!(doc.running_clock_in(Some("oncall")).unwrap().id, late);
+    assert_eq!(doc.activity.last().unwrap().event, ActivityEvent::ClockAutoStopped { clock_id: overnight });
+
+    doc.auto_clock_out(end_of_day, Utc.ymd(2024, 3, 3).and_hms(0, 0, 0));
+    assert_eq!(doc.clock(&late).unwrap().end, Some(Utc.ymd(2024, 3, 2).and_hms(16, 0, 0)));
+    assert!(doc.running_clocks().is_empty());
+}
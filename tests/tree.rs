@@ -0,0 +1,149 @@
+use sors::*;
+use sors::clock::Clock;
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title.to_string());
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+fn titles(tasks: Subtree) -> Vec<String> {
+    tasks.map(|(depth, task)| format!("{}{}", depth, task.title)).collect()
+}
+
+/// root
+///   a
+///     a1
+///   b
+///     b1
+fn sample() -> Doc {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    doc.modify_task(&root, |root| { root.set_title("root"); Ok(()) }).unwrap();
+    let a = add(&mut doc, &root, "a");
+    let b = add(&mut doc, &root, "b");
+    add(&mut doc, &a, "a1");
+    add(&mut doc, &b, "b1");
+    doc
+}
+
+#[test]
+fn depth_first() {
+    let doc = sample();
+    assert_eq!(titles(doc.iter_subtree(&doc.root)), vec!["0root", "1a", "2a1", "1b", "2b1"]);
+    assert_eq!(titles(doc.iter_subtree(&doc.root).max_depth(2)), vec!["0root", "1a", "1b"]);
+    assert!(doc.iter_subtree(&Uuid::new_v4()).next().is_none());
+}
+
+#[test]
+fn breadth_first() {
+    let doc = sample();
+    assert_eq!(titles(doc.iter_subtree_breadth_first(&doc.root)), vec!["0root", "1a", "1b", "2a1", "2b1"]);
+}
+
+#[test]
+fn filtered_skips_subtrees() {
+    let doc = sample();
+    assert_eq!(titles(doc.iter_subtree_filtered(&doc.root, |task| task.title != "a")), vec!["0root", "1b", "2b1"]);
+}
+
+#[test]
+fn progress_summary_of_subtree() {
+    let mut doc = sample();
+    let done: Vec<Uuid> = doc.iter_subtree(&doc.root).skip(1).map(|(_, task)| task.id).collect();
+    for (task_id, progress) in done.iter().zip(&[Progress::Todo, Progress::Done, Progress::Cancelled, Progress::Done]) {
+        doc.modify_task(task_id, |task| { task.set_progress(*progress); Ok(()) }).unwrap();
+    }
+    assert_eq!(doc.progress_summary(&doc.root).unwrap(), (0, 1));
+    assert_eq!(doc.progress_summary_recursive(&doc.root, Some(1)).unwrap(), (0, 1));
+    assert_eq!(doc.progress_summary_recursive(&doc.root, None).unwrap(), (2, 3));
+}
+
+#[test]
+fn subtree_durations_add_up() {
+    let mut doc = sample();
+    let ids: Vec<Uuid> = doc.iter_subtree(&doc.root).map(|(_, task)| task.id).collect();
+    let start = Utc::now() - chrono::Duration::hours(10);
+    for (task_id, minutes) in [(ids[1], 10), (ids[2], 20), (ids[2], 5), (ids[4], 30)] {
+        doc.upsert_clock(Rc::new(Clock {
+            id: Uuid::new_v4(),
+            start,
+            end: Some(start + chrono::Duration::minutes(minutes)),
+            comment: None,
+            task_id: Some(task_id),
+            category: None,
+            tags: Vec::new(),
+            channel: None,
+        }));
+    }
+    let durations = doc.subtree_durations(&doc.root);
+    let minutes = |i: usize| durations[&ids[i]].num_minutes();
+    assert_eq!((minutes(0), minutes(1), minutes(2), minutes(3), minutes(4)), (65, 35, 25, 30, 30));
+    assert_eq!(doc.subtree_duration(&ids[1]).num_minutes(), 35);
+    assert_eq!(doc.tracked_time(&ids[3]).num_minutes(), 30);
+}
+
+#[test]
+fn cached_results_follow_changes() {
+    let mut doc = sample();
+    let ids: Vec<Uuid> = doc.iter_subtree(&doc.root).map(|(_, task)| task.id).collect();
+    let (a, a1, b) = (ids[1], ids[2], ids[3]);
+    assert_eq!(doc.find_parent(&a1), Some(a));
+    assert_eq!(doc.progress_summary_recursive(&doc.root, None).unwrap(), (0, 0));
+
+    doc.modify_task(&a, |task| { task.remove_child(&a1); Ok(()) }).unwrap();
+    doc.modify_task(&b, |task| { task.add_child(a1); Ok(()) }).unwrap();
+    doc.modify_task(&a1, |task| { task.set_progress(Progress::Done); Ok(()) }).unwrap();
+    assert_eq!(doc.find_parent(&a1), Some(b));
+    assert!(doc.is_in_hierarchy_of(&a1, &b));
+    assert_eq!(doc.progress_summary_recursive(&doc.root, None).unwrap(), (1, 1));
+
+    assert_eq!(doc.subtree_duration(&b), chrono::Duration::zero());
+    let start = Utc::now() - chrono::Duration::hours(1);
+    doc.upsert_clock(Rc::new(Clock { id: Uuid::new_v4(), start, end: Some(start + chrono::Duration::minutes(15)), comment: None, task_id: Some(a1), category: None, tags: Vec::new(), channel: None }));
+    assert_eq!(doc.subtree_duration(&b).num_minutes(), 15);
+    doc.upsert_clock(Rc::new(Clock { id: Uuid::new_v4(), start, end: None, comment: None, task_id: Some(a1), category: None, tags: Vec::new(), channel: None }));
+    assert_eq!(doc.subtree_duration(&b).num_minutes(), 75);
+}
+
+#[test]
+fn move_task_rejects_cycles() {
+    let mut doc = sample();
+    let ids = |doc: &Doc, title: &str| doc.iter_subtree(&doc.root).find(|(_, task)| task.title == title).unwrap().1.id;
+    let (a, a1, b) = (ids(&doc, "a"), ids(&doc, "a1"), ids(&doc, "b"));
+
+    assert!(doc.move_task(&a, &a1, None).is_err());
+    assert!(doc.move_task(&a, &a, None).is_err());
+    assert!(doc.move_task(&doc.root.clone(), &b, None).is_err());
+    assert!(doc.move_task(&a, &Uuid::new_v4(), None).is_err());
+    assert_eq!(titles(doc.iter_subtree(&doc.root)), vec!["0root", "1a", "2a1", "1b", "2b1"]);
+
+    doc.move_task(&a, &b, None).unwrap();
+    assert_eq!(titles(doc.iter_subtree(&doc.root)), vec!["0root", "1b", "2b1", "2a", "3a1"]);
+    assert_eq!(doc.find_parent(&a), Some(b));
+}
+
+#[test]
+fn move_to_position_and_remove() {
+    let mut doc = sample();
+    let ids = |doc: &Doc, title: &str| doc.iter_subtree(&doc.root).find(|(_, task)| task.title == title).unwrap().1.id;
+    let (a, b, b1) = (ids(&doc, "a"), ids(&doc, "b"), ids(&doc, "b1"));
+
+    doc.move_task(&b, &doc.root.clone(), Some(0)).unwrap();
+    assert_eq!(doc.position(&b), Some((doc.root, 0)));
+    assert!(doc.move_task(&b, &doc.root.clone(), Some(2)).is_err());
+    doc.move_task(&a, &b, Some(0)).unwrap();
+    assert_eq!(titles(doc.iter_subtree(&doc.root)), vec!["0root", "1b", "2a", "3a1", "2b1"]);
+
+    let a1 = ids(&doc, "a1");
+    doc.bookmarks.insert("a1".to_string(), a1);
+    doc.bookmarks.insert("b1".to_string(), b1);
+    doc.remove_task(&a).unwrap();
+    assert_eq!(titles(doc.iter_subtree(&doc.root)), vec!["0root", "1b", "2b1"]);
+    assert_eq!(doc.bookmarks.keys().collect::<Vec<_>>(), vec!["b1"]);
+    assert_eq!(doc.map.len(), 3);
+    assert_eq!(doc.position(&b1), Some((b, 0)));
+    assert!(doc.remove_task(&doc.root.clone()).is_err());
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "tui")]
+
+use sors::*;
+use sors::tui::{Key, TreeView};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+
+fn add(doc: &mut Doc, parent: &Uuid, title: &str) -> Uuid {
+    let mut task = Rc::new(Task::new());
+    task.set_title(title.to_string());
+    let task_id = task.id;
+    doc.add_subtask(task, parent).unwrap();
+    task_id
+}
+
+/// root
+///   a
+///     a1
+///   b
+fn state() -> (State, Uuid, Uuid, Uuid) {
+    let mut doc = Doc::new();
+    let root = doc.root;
+    let a = add(&mut doc, &root, "a");
+    let a1 = add(&mut doc, &a, "a1");
+    let b = add(&mut doc, &root, "b");
+    (State::new(doc, "/tmp/sors-tui-test.json"), a, a1, b)
+}
+
+fn titles(view: &TreeView, state: &State) -> Vec<String> {
+    view.rows(state).iter().map(|row| format!("{}{}", row.depth, row.task.title)).collect()
+}
+
+#[test]
+fn navigate_the_tree() {
+    let (mut state, a, a1, b) = state();
+    let mut view = TreeView::new(&state);
+    assert_eq!(view.selected, Some(a));
+    assert_eq!(titles(&view, &state), vec!["0a", "0b"]);
+
+    view.handle_key(&mut state, Key::Right);
+    assert_eq!(titles(&view, &state), vec!["0a", "1a1", "0b"]);
+    view.handle_key(&mut state, Key::Right);
+    assert_eq!(view.selected, Some(a1));
+    view.handle_key(&mut state, Key::Down);
+    assert_eq!(view.selected, Some(b));
+    view.handle_key(&mut state, Key::Down);
+    assert_eq!(view.selected, Some(b));
+    view.handle_key(&mut state, Key::Char('k'));
+    view.handle_key(&mut state, Key::Left);
+    assert_eq!(view.selected, Some(a));
+    view.handle_key(&mut state, Key::Left);
+    assert_eq!(titles(&view, &state), vec!["0a", "0b"]);
+
+    view.handle_key(&mut state, Key::Enter);
+    assert_eq!(state.wt, a);
+    view.handle_key(&mut state, Key::Char('q'));
+    assert!(view.quit);
+
+    state.change_wt(a1);
+    assert_eq!(TreeView::new(&state).selected, Some(a1));
+}
+
+#[test]
+fn change_tasks_and_clocks() {
+    let (mut state, a, _, _) = state();
+    let mut view = TreeView::new(&state);
+    view.handle_key(&mut state, Key::Char(' '));
+    assert_eq!(state.doc.get(&a).unwrap().progress, Some(Progress::Done));
+    view.handle_key(&mut state, Key::Char(' '));
+    assert_eq!(state.doc.get(&a).unwrap().progress, Some(Progress::Todo));
+
+    view.handle_key(&mut state, Key::Char('c'));
+    assert_eq!(state.doc.running_clock().and_then(|clock| clock.task_id), Some(a));
+    view.handle_key(&mut state, Key::Char('o'));
+    assert!(state.doc.running_clock().is_none());
+
+    state.set_read_only(true);
+    view.handle_key(&mut state, Key::Char(' '));
+    assert_eq!(state.doc.get(&a).unwrap().progress, Some(Progress::Todo));
+    assert!(view.message.is_some());
+}
+
+#[test]
+fn draw_tree_and_details() {
+    let (state, _, _, _) = state();
+    let view = TreeView::new(&state);
+    let mut terminal = Terminal::new(TestBackend::new(80, 10)).unwrap();
+    terminal.draw(|frame| view.draw(frame, &state)).unwrap();
+    let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+    assert!(screen.contains("▸ a"));
+    assert!(screen.contains("Details"));
+    assert!(screen.contains("No clock running"));
+}